@@ -0,0 +1,2 @@
+-- Postgres can't remove a value from an enum, and leaving 'stadium' in the type is harmless
+SELECT 1;
@@ -0,0 +1 @@
+ALTER TYPE entity_type ADD VALUE 'stadium';
@@ -0,0 +1,2 @@
+DROP INDEX conflicts_index;
+DROP TABLE conflicts;
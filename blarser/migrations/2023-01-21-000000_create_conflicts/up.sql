@@ -0,0 +1,17 @@
+CREATE TABLE conflicts
+(
+    id           SERIAL PRIMARY KEY,
+    ingest_id    INT                      NOT NULL,
+
+    entity_type  entity_type              NOT NULL,
+    entity_id    UUID                     NOT NULL,
+    perceived_at TIMESTAMP WITH TIME ZONE NOT NULL,
+
+    property     TEXT                     NOT NULL,
+    message      TEXT                     NOT NULL,
+    resolved     BOOLEAN                  NOT NULL DEFAULT FALSE,
+
+    CONSTRAINT ingest_fk FOREIGN KEY (ingest_id) REFERENCES ingests (id) ON DELETE CASCADE
+);
+
+CREATE INDEX conflicts_index ON conflicts (entity_type, entity_id, perceived_at);
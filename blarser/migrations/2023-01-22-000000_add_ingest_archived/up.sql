@@ -0,0 +1,2 @@
+-- Archived ingests keep their data but are hidden from the default views
+ALTER TABLE ingests ADD COLUMN archived BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,2 @@
+ALTER TABLE ingests DROP COLUMN backfilled_to;
+ALTER TABLE ingests DROP COLUMN backfill_until;
@@ -0,0 +1,3 @@
+-- Only set for backfill ingests. backfilled_to is where a resumed backfill picks up from.
+ALTER TABLE ingests ADD COLUMN backfill_until TIMESTAMP WITH TIME ZONE;
+ALTER TABLE ingests ADD COLUMN backfilled_to TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1 @@
+ALTER TABLE conflicts DROP COLUMN low_severity;
@@ -0,0 +1,2 @@
+-- Low-severity conflicts are recorded for analysis but don't stop the observation from being accepted
+ALTER TABLE conflicts ADD COLUMN low_severity BOOLEAN NOT NULL DEFAULT FALSE;
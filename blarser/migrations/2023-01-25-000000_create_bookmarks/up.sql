@@ -0,0 +1,16 @@
+-- Operator notes on moments in an ingest that are worth coming back to
+CREATE TABLE bookmarks
+(
+    id          SERIAL PRIMARY KEY,
+    ingest_id   INT                      NOT NULL,
+
+    time        TIMESTAMP WITH TIME ZONE NOT NULL,
+    entity_type entity_type              NOT NULL,
+    entity_id   UUID                     NOT NULL,
+    note        TEXT                     NOT NULL,
+    created_at  TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+
+    CONSTRAINT ingest_fk FOREIGN KEY (ingest_id) REFERENCES ingests (id) ON DELETE CASCADE
+);
+
+CREATE INDEX bookmarks_index ON bookmarks (ingest_id, time);
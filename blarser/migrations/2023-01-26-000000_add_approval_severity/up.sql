@@ -0,0 +1,2 @@
+-- Matches partial_information::Severity::level. Existing approvals were all treated as errors.
+ALTER TABLE approvals ADD COLUMN severity INTEGER NOT NULL DEFAULT 2;
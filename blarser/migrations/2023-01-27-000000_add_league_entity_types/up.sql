@@ -0,0 +1,3 @@
+ALTER TYPE entity_type ADD VALUE 'league';
+ALTER TYPE entity_type ADD VALUE 'subleague';
+ALTER TYPE entity_type ADD VALUE 'division';
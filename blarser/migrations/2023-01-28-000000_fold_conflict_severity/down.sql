@@ -0,0 +1,3 @@
+ALTER TABLE conflicts ADD COLUMN low_severity BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE conflicts SET low_severity = TRUE WHERE severity < 2;
+ALTER TABLE conflicts DROP COLUMN severity;
@@ -0,0 +1,5 @@
+-- Matches partial_information::Severity::level, like approvals.severity. Conflicts that used to be
+-- low severity are Info, and everything else was treated as an error.
+ALTER TABLE conflicts ADD COLUMN severity INTEGER NOT NULL DEFAULT 2;
+UPDATE conflicts SET severity = 0 WHERE low_severity;
+ALTER TABLE conflicts DROP COLUMN low_severity;
@@ -0,0 +1,142 @@
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::state::EntityType;
+
+// The live Blaseball API, for eras where it's still reachable. The base URL comes from the
+// environment so it can point at the real thing, a mirror, or nothing at all.
+pub const LIVE_API_URL_VAR: &str = "BLARSER_LIVE_API_URL";
+
+#[derive(Debug, Error)]
+pub enum LiveApiError {
+    #[error("The live API doesn't serve {0} entities")]
+    UnsupportedEntityType(EntityType),
+
+    #[error("Live API request failed: {0}")]
+    RequestFailed(#[from] reqwest::Error),
+
+    #[error("Live API has no {ty} {id}")]
+    NotFound {
+        ty: EntityType,
+        id: Uuid,
+    },
+}
+
+pub struct LiveApi {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl LiveApi {
+    pub fn new(base_url: String) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    // None if the live API isn't configured
+    pub fn from_env() -> Option<Self> {
+        std::env::var(LIVE_API_URL_VAR).ok()
+            .filter(|url| !url.is_empty())
+            .map(Self::new)
+    }
+
+    pub async fn fetch_entity(&self, entity_type: EntityType, id: Uuid) -> Result<serde_json::Value, LiveApiError> {
+        let request = match entity_type {
+            EntityType::Sim => {
+                self.client.get(format!("{}/database/simulationData", self.base_url))
+            }
+            EntityType::Player => {
+                self.client.get(format!("{}/database/players", self.base_url))
+                    .query(&[("ids", id)])
+            }
+            EntityType::Team => {
+                self.client.get(format!("{}/database/team", self.base_url))
+                    .query(&[("id", id)])
+            }
+            EntityType::Game => {
+                self.client.get(format!("{}/database/gameById/{}", self.base_url, id))
+            }
+            other => { return Err(LiveApiError::UnsupportedEntityType(other)); }
+        };
+
+        let json: serde_json::Value = request.send().await?
+            .error_for_status()?
+            .json().await?;
+
+        // The players endpoint returns a list even when asking for one id
+        let json = match json {
+            serde_json::Value::Array(items) => { items.into_iter().next() }
+            serde_json::Value::Null => { None }
+            other => { Some(other) }
+        };
+
+        json.ok_or(LiveApiError::NotFound { ty: entity_type, id })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+    use serde_json::json;
+    use crate::entity::{test_player, AnyEntity, AnyEntityRaw};
+    use crate::state::EntityType;
+    use super::{LiveApi, LiveApiError};
+
+    // Serves a single canned response on a local port and hands back the request line it got
+    fn mock_live_api(body: serde_json::Value) -> (LiveApi, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Binding the mock live API failed");
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("Mock live API never got a request");
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            // The headers aren't interesting, but they have to be read before responding
+            let mut header = String::new();
+            while reader.read_line(&mut header).unwrap() > 2 {
+                header.clear();
+            }
+
+            let body = body.to_string();
+            write!(stream, "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                   body.len(), body).unwrap();
+            request_line
+        });
+        (LiveApi::new(url), server)
+    }
+
+    #[rocket::async_test]
+    async fn divergences_from_the_live_api_are_reported() {
+        let player = test_player(None);
+        let mut live_player = player.clone();
+        live_player.name = "Live Player".to_string();
+        let live_json = AnyEntity::Player(live_player).into_raw_approximation().to_json()
+            .expect("Test player should serialize");
+        // The live players endpoint returns a list, even for one id
+        let (live_api, server) = mock_live_api(json!([live_json]));
+
+        let fetched = live_api.fetch_entity(EntityType::Player, player.id).await
+            .expect("Mock live API should return the player");
+        let request_line = server.join().unwrap();
+        assert!(request_line.starts_with(&format!("GET /database/players?ids={} ", player.id)), "{request_line}");
+
+        let live_raw = AnyEntityRaw::from_json(EntityType::Player, fetched)
+            .expect("Live player should parse");
+        let divergences = AnyEntity::Player(player).observe_raw(&live_raw);
+        assert_eq!(divergences.iter().map(|conflict| conflict.property()).collect::<Vec<_>>(), vec!["name".to_string()]);
+    }
+
+    #[rocket::async_test]
+    async fn entities_missing_from_the_live_api_are_not_found() {
+        let player = test_player(None);
+        let (live_api, server) = mock_live_api(json!([]));
+
+        let result = live_api.fetch_entity(EntityType::Player, player.id).await;
+        server.join().unwrap();
+        assert!(matches!(result, Err(LiveApiError::NotFound { ty: EntityType::Player, id }) if id == player.id), "{result:?}");
+    }
+}
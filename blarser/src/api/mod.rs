@@ -1,5 +1,6 @@
 pub mod chronicler;
 mod chronicler_schema;
+pub mod live;
 // pub mod eventually;
 // mod eventually_schema;
 
@@ -1,5 +1,7 @@
 use std::cmp::Reverse;
 use std::ops::Deref;
+use std::sync::{Arc, Mutex as StdMutex};
+use chrono::{DateTime, Duration, Utc};
 use itertools::Itertools;
 use rocket::{get, Request, response, Route, State};
 use rocket::http::Status;
@@ -8,8 +10,12 @@ use rocket::serde::json::Json;
 use serde_json::{json, Value};
 use thiserror::Error;
 use uuid::Uuid;
-use blarser::ingest::{GraphDebugHistorySync, GraphDebugHistory, IngestTaskHolder};
-use blarser::state::EntityType;
+use partial_information::Severity;
+use blarser::ingest::{BackfillConfig, PauseError, EventLogExport, GraphDebugHistorySync, GraphDebugHistory, GraphInvariantViolation, IngestTask, IngestTaskHolder, StateGraph, TimedEventQueue};
+use blarser::db::{BlarserDbConn, get_conflicts_for_ingest};
+use blarser::api::live::{LiveApi, LiveApiError, LIVE_API_URL_VAR};
+use blarser::entity::{AnyEntity, AnyEntityRaw};
+use blarser::state::{EntityType, StateInterface};
 
 #[derive(Debug, Error)]
 pub enum DebugApiError {
@@ -28,12 +34,61 @@ pub enum DebugApiError {
         id: Uuid,
     },
 
+    #[error("Database error: {0}")]
+    DatabaseError(#[from] diesel::result::Error),
+
+    #[error("Invalid field {field} for entity type {ty}")]
+    InvalidField {
+        ty: EntityType,
+        field: String,
+    },
+
+    #[error("No events were applied for play {play} of game {game_id}")]
+    InvalidPlay {
+        game_id: Uuid,
+        play: i64,
+    },
+
+    #[error("Invalid time {0}")]
+    InvalidTime(String),
+
+    #[error("The live API isn't configured. Set {} to enable it.", LIVE_API_URL_VAR)]
+    LiveApiNotConfigured,
+
+    #[error(transparent)]
+    LiveApiError(#[from] LiveApiError),
+
+    #[error("Couldn't parse live {ty} {id}: {err}")]
+    InvalidLiveEntity {
+        ty: EntityType,
+        id: Uuid,
+        err: serde_json::Error,
+    },
+
     #[error("Invalid version {index} for entity {ty} {id}")]
     InvalidEntityVersion {
         ty: EntityType,
         id: Uuid,
         index: usize,
     },
+
+    #[error("Invalid version id {0}")]
+    InvalidVersionId(i32),
+
+    #[error("Couldn't parse event {index} of the event log: {err}")]
+    InvalidLoggedEvent {
+        index: usize,
+        err: serde_json::Error,
+    },
+
+    #[error("There's no unfinished backfill to resume")]
+    NoUnfinishedBackfill,
+
+    #[error("The event log has no initial state to replay it on top of")]
+    NoLoggedInitialState,
+
+    #[error(transparent)]
+    PauseError(#[from] PauseError),
 }
 
 impl<'r, 'o: 'r> Responder<'r, 'o> for DebugApiError {
@@ -41,10 +96,31 @@ impl<'r, 'o: 'r> Responder<'r, 'o> for DebugApiError {
         // log `self` to your favored error tracker, e.g.
         // sentry::capture_error(&self);
 
-        match self {
-            // in our simplistic example, we're happy to respond with the default 500 responder in all cases
-            _ => Status::InternalServerError.respond_to(req)
-        }
+        let status = match &self {
+            DebugApiError::NoActiveIngest |
+            DebugApiError::NoUnfinishedBackfill |
+            DebugApiError::InvalidEntity { .. } |
+            DebugApiError::InvalidEntityVersion { .. } |
+            DebugApiError::InvalidVersionId(_) |
+            DebugApiError::InvalidPlay { .. } => { Status::NotFound }
+            DebugApiError::InvalidEntityType(_) |
+            DebugApiError::InvalidField { .. } |
+            DebugApiError::InvalidTime(_) |
+            DebugApiError::InvalidLoggedEvent { .. } |
+            DebugApiError::NoLoggedInitialState => { Status::BadRequest }
+            DebugApiError::LiveApiNotConfigured => { Status::ServiceUnavailable }
+            DebugApiError::PauseError(e) => {
+                match e {
+                    PauseError::NoIngest => { Status::NotFound }
+                    PauseError::AlreadyPaused |
+                    PauseError::NotPaused => { Status::Conflict }
+                    PauseError::Stopped |
+                    PauseError::TooManyPendingPauses => { Status::ServiceUnavailable }
+                }
+            }
+            _ => { Status::InternalServerError }
+        };
+        (status, self.to_string()).respond_to(req)
     }
 }
 
@@ -58,13 +134,23 @@ pub async fn entities(task: &State<IngestTaskHolder>) -> Result<Json<serde_json:
 
 fn get_history_entities(history: &GraphDebugHistory) -> Value {
     let items = history.iter()
-        .sorted_by_key(|(_, item)| Reverse(item.versions.last().unwrap().time))
+        .sorted_by_key(|(_, item)| Reverse(item.latest_version().unwrap().time))
         .take(500)
-        .map(|((ty, id), item)| json!({
-            "name": item.entity_human_name,
-            "type": ty,
-            "id": id
-        }))
+        .map(|((ty, id), item)| {
+            // Report the least confident of the current leafs, since that's the worst case
+            let latest_tree = &item.latest_version().unwrap().tree;
+            let confidence = latest_tree.leafs.iter()
+                .filter_map(|idx| latest_tree.data.get(idx))
+                .map(|node| node.confidence)
+                .reduce(f32::min);
+
+            json!({
+                "name": item.entity_human_name,
+                "type": ty,
+                "id": id,
+                "confidence": confidence,
+            })
+        })
         .collect();
 
     Value::Array(items)
@@ -75,15 +161,7 @@ pub async fn entity(task: &State<IngestTaskHolder>, entity_type: String, id: Uui
     let history = get_history(task)?;
     let history = history.lock().await;
 
-    let entity_type = match entity_type.as_str() {
-        "Sim" => EntityType::Sim,
-        "Player" => EntityType::Player,
-        "Team" => EntityType::Team,
-        "Game" => EntityType::Game,
-        "Standings" => EntityType::Standings,
-        "Season" => EntityType::Season,
-        _ => return Err(DebugApiError::InvalidEntityType(entity_type))
-    };
+    let entity_type = parse_entity_type(entity_type)?;
 
     Ok(Json(get_history_entity(history.deref(), entity_type, id)?))
 }
@@ -91,8 +169,7 @@ pub async fn entity(task: &State<IngestTaskHolder>, entity_type: String, id: Uui
 fn get_history_entity(history: &GraphDebugHistory, entity_type: EntityType, id: Uuid) -> Result<Value, DebugApiError> {
     let items = history.get(&(entity_type, id))
         .ok_or_else(|| DebugApiError::InvalidEntity { ty: entity_type, id })?
-        .versions.iter()
-        .enumerate()
+        .iter_versions()
         .rev()
         .take(500)
         .map(|(i, v)| json!({
@@ -109,98 +186,417 @@ pub async fn version(task: &State<IngestTaskHolder>, entity_type: String, id: Uu
     let history = get_history(task)?;
     let history = history.lock().await;
 
-    let entity_type = match entity_type.as_str() {
-        "Sim" => EntityType::Sim,
-        "Player" => EntityType::Player,
-        "Team" => EntityType::Team,
-        "Game" => EntityType::Game,
-        "Standings" => EntityType::Standings,
-        "Season" => EntityType::Season,
-        _ => return Err(DebugApiError::InvalidEntityType(entity_type))
-    };
+    let entity_type = parse_entity_type(entity_type)?;
 
     Ok(Json(get_history_version(history.deref(), entity_type, id, index)?.clone()))
 }
 
+// Unlike /version/<entity_type>/<id>/<index>, this reads from the database, so `id` is the same
+// version id the graph visualization uses
+#[get("/version/<id>")]
+pub async fn version_by_id(task: &State<IngestTaskHolder>, conn: BlarserDbConn, id: i32) -> Result<Json<serde_json::Value>, DebugApiError> {
+    let ingest_id = task.latest_ingest_id().ok_or(DebugApiError::NoActiveIngest)?;
+    let version = conn.run(move |c| {
+        StateInterface::new(c, ingest_id).get_version_lineage_debug(id)
+    }).await?
+        .ok_or(DebugApiError::InvalidVersionId(id))?;
+
+    Ok(Json(serde_json::to_value(version).unwrap()))
+}
+
 fn get_history_version(history: &GraphDebugHistory, entity_type: EntityType, id: Uuid, index: usize) -> Result<Value, DebugApiError> {
     let version = history.get(&(entity_type, id))
         .ok_or_else(|| DebugApiError::InvalidEntity { ty: entity_type, id })?
-        .versions.get(index)
+        .get_version(index)
         .ok_or_else(|| DebugApiError::InvalidEntityVersion { ty: entity_type, id, index })?;
 
     Ok(serde_json::to_value(version).unwrap())
 }
 
+fn parse_entity_type(entity_type: String) -> Result<EntityType, DebugApiError> {
+    match entity_type.as_str() {
+        "Sim" => Ok(EntityType::Sim),
+        "Player" => Ok(EntityType::Player),
+        "Team" => Ok(EntityType::Team),
+        "Game" => Ok(EntityType::Game),
+        "Standings" => Ok(EntityType::Standings),
+        "Season" => Ok(EntityType::Season),
+        "Stadium" => Ok(EntityType::Stadium),
+        "League" => Ok(EntityType::League),
+        "Subleague" => Ok(EntityType::Subleague),
+        "Division" => Ok(EntityType::Division),
+        _ => Err(DebugApiError::InvalidEntityType(entity_type))
+    }
+}
+
+// `fields` is a comma-separated list of field names. If it's not provided, all fields are returned.
+#[get("/fields/<entity_type>/<id>?<fields>")]
+pub async fn entity_fields(task: &State<IngestTaskHolder>, entity_type: String, id: Uuid, fields: Option<String>) -> Result<Json<serde_json::Value>, DebugApiError> {
+    let entity_type = parse_entity_type(entity_type)?;
+
+    let valid_fields = AnyEntity::fields_for_type(entity_type);
+    let requested_fields = match &fields {
+        None => valid_fields.to_vec(),
+        Some(fields) => {
+            fields.split(',')
+                .map(|field| {
+                    valid_fields.iter()
+                        .find(|&&valid| valid == field)
+                        .copied()
+                        .ok_or_else(|| DebugApiError::InvalidField { ty: entity_type, field: field.to_string() })
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        }
+    };
+
+    let state = get_state(task)?;
+    let state = state.lock().map_err(|_| DebugApiError::LockPoisoned)?;
+    let graph = state.entity_graph(entity_type, id)
+        .ok_or_else(|| DebugApiError::InvalidEntity { ty: entity_type, id })?;
+
+    // One object per leaf, since the current value may be ambiguous
+    let items = graph.leafs().iter()
+        .filter_map(|&idx| graph.get_version(idx))
+        .map(|node| {
+            requested_fields.iter()
+                .map(|&field| {
+                    let value = node.entity.get_field(field)
+                        .expect("Field names from fields_for_type should always be gettable");
+                    (field.to_string(), value)
+                })
+                .collect()
+        })
+        .map(Value::Object)
+        .collect();
+
+    Ok(Json(Value::Array(items)))
+}
+
+#[get("/validate/<entity_type>/<id>")]
+pub async fn validate_entity(task: &State<IngestTaskHolder>, entity_type: String, id: Uuid) -> Result<Json<serde_json::Value>, DebugApiError> {
+    let entity_type = parse_entity_type(entity_type)?;
+
+    let state = get_state(task)?;
+    let state = state.lock().map_err(|_| DebugApiError::LockPoisoned)?;
+    let graph = state.entity_graph(entity_type, id)
+        .ok_or_else(|| DebugApiError::InvalidEntity { ty: entity_type, id })?;
+
+    Ok(Json(violations_json(graph.validate())))
+}
+
+// Only entities with at least one violation are included
+#[get("/validate")]
+pub async fn validate_all(task: &State<IngestTaskHolder>) -> Result<Json<serde_json::Value>, DebugApiError> {
+    let state = get_state(task)?;
+    let state = state.lock().map_err(|_| DebugApiError::LockPoisoned)?;
+
+    let items = state.entity_graphs()
+        .map(|((ty, id), graph)| (ty, id, graph.validate()))
+        .filter(|(_, _, violations)| !violations.is_empty())
+        .map(|(ty, id, violations)| json!({
+            "type": ty,
+            "id": id,
+            "violations": violations_json(violations),
+        }))
+        .collect();
+
+    Ok(Json(Value::Array(items)))
+}
+
+// Compares the live API's current version of an entity with every leaf of its reconstruction.
+// Only meaningful while the ingest is caught up to the present.
+#[get("/live-diff/<entity_type>/<id>")]
+pub async fn live_diff(task: &State<IngestTaskHolder>, entity_type: String, id: Uuid) -> Result<Json<serde_json::Value>, DebugApiError> {
+    let entity_type = parse_entity_type(entity_type)?;
+    let live_api = LiveApi::from_env().ok_or(DebugApiError::LiveApiNotConfigured)?;
+
+    // Fetch before locking so the state isn't held during the request
+    let live_json = live_api.fetch_entity(entity_type, id).await?;
+    let live_raw = AnyEntityRaw::from_json(entity_type, live_json.clone())
+        .map_err(|err| DebugApiError::InvalidLiveEntity { ty: entity_type, id, err })?;
+
+    let state = get_state(task)?;
+    let state = state.lock().map_err(|_| DebugApiError::LockPoisoned)?;
+    let graph = state.entity_graph(entity_type, id)
+        .ok_or_else(|| DebugApiError::InvalidEntity { ty: entity_type, id })?;
+
+    let leafs: Vec<_> = graph.leafs().iter()
+        .filter_map(|&idx| graph.get_version(idx))
+        .map(|node| node.entity.observe_raw(&live_raw))
+        .map(|conflicts| json!({
+            "divergences": conflicts.iter()
+                .map(|conflict| json!({
+                    "property": conflict.property(),
+                    "path": conflict.path(),
+                    "expected": conflict.expected(),
+                    "observed": conflict.observed(),
+                    "message": conflict.message(),
+                }))
+                .collect::<Vec<_>>(),
+        }))
+        .collect();
+
+    // The reconstruction is consistent with the live API if any of its leaves is
+    let consistent = leafs.iter()
+        .any(|leaf| leaf["divergences"].as_array().map_or(false, Vec::is_empty));
+
+    Ok(Json(json!({
+        "consistent": consistent,
+        "live": live_json,
+        "leafs": leafs,
+    })))
+}
+
+fn violations_json(violations: Vec<GraphInvariantViolation>) -> Value {
+    let items = violations.into_iter()
+        .map(|violation| json!({
+            "message": violation.to_string(),
+            "detail": violation,
+        }))
+        .collect();
+
+    Value::Array(items)
+}
+
 fn get_history(task: &State<IngestTaskHolder>) -> Result<GraphDebugHistorySync, DebugApiError> {
     let ingest = task.latest_ingest.lock().map_err(|_| DebugApiError::LockPoisoned)?;
     let ingest = ingest.as_ref().ok_or_else(|| DebugApiError::NoActiveIngest)?;
     Ok(ingest.debug_history.clone())
 }
 
-#[get("/pause_state")]
-pub async fn pause_state(task: &State<IngestTaskHolder>) -> Json<serde_json::Value> {
-    let is_paused = {
-        let ingest = task.latest_ingest.lock().unwrap();
-        ingest.as_ref().map_or(false, |ingest| ingest.resumer.is_some())
+fn get_state(task: &State<IngestTaskHolder>) -> Result<Arc<StdMutex<StateGraph>>, DebugApiError> {
+    let ingest = task.latest_ingest.lock().map_err(|_| DebugApiError::LockPoisoned)?;
+    let ingest = ingest.as_ref().ok_or_else(|| DebugApiError::NoActiveIngest)?;
+    Ok(ingest.state.clone())
+}
+
+#[get("/timed-events")]
+pub async fn timed_events(task: &State<IngestTaskHolder>) -> Result<Json<serde_json::Value>, DebugApiError> {
+    let timed_events = {
+        let ingest = task.latest_ingest.lock().map_err(|_| DebugApiError::LockPoisoned)?;
+        let ingest = ingest.as_ref().ok_or(DebugApiError::NoActiveIngest)?;
+        ingest.timed_events.clone()
     };
+    let timed_events = timed_events.lock().map_err(|_| DebugApiError::LockPoisoned)?;
+
+    Ok(Json(Value::Array(timed_event_items(&timed_events))))
+}
+
+fn timed_event_items(timed_events: &TimedEventQueue) -> Vec<Value> {
+    timed_events.sorted().into_iter()
+        .map(|event| json!({
+            "time": event.time(),
+            "description": event.to_string(),
+            "targets": event.targets().into_iter()
+                .map(|(ty, id)| json!({ "type": ty, "id": id }))
+                .collect::<Vec<_>>(),
+        }))
+        .collect()
+}
+
+// Returns the Feed event(s) that were applied to produce the given play. Ranked below /version
+// because the paths can collide.
+#[get("/<game_id>/play/<play>/event", rank = 2)]
+pub async fn play_event(task: &State<IngestTaskHolder>, game_id: Uuid, play: i64) -> Result<Json<serde_json::Value>, DebugApiError> {
+    let applied_events = {
+        let ingest = task.latest_ingest.lock().map_err(|_| DebugApiError::LockPoisoned)?;
+        let ingest = ingest.as_ref().ok_or(DebugApiError::NoActiveIngest)?;
+        ingest.applied_events.clone()
+    };
+    let applied_events = applied_events.lock().map_err(|_| DebugApiError::LockPoisoned)?;
+
+    let events = applied_events.get(&(game_id, play))
+        .ok_or(DebugApiError::InvalidPlay { game_id, play })?;
 
+    Ok(Json(Value::Array(events.clone())))
+}
+
+#[get("/stadium_report")]
+pub async fn stadium_report(task: &State<IngestTaskHolder>) -> Result<Json<serde_json::Value>, DebugApiError> {
+    let state = get_state(task)?;
+    let state = state.lock().map_err(|_| DebugApiError::LockPoisoned)?;
+
+    Ok(Json(serde_json::to_value(state.game_stadium_reports()).unwrap()))
+}
+
+#[get("/pause_state")]
+pub async fn pause_state(task: &State<IngestTaskHolder>) -> Json<serde_json::Value> {
     Json(json!({
-        "paused": is_paused,
+        "paused": task.is_paused(),
     }))
 }
 
 #[rocket::post("/pause")]
-pub async fn post_pause(task: &State<IngestTaskHolder>) -> Json<serde_json::Value> {
-    let requester = {
-        let mut ingest = task.latest_ingest.lock().unwrap();
-        if let Some(ingest) = ingest.as_mut() {
-            if ingest.resumer.is_some() {
-                return Json(json!({ "error": "Already paused" }))
-            }
+pub async fn post_pause(task: &State<IngestTaskHolder>) -> Result<Json<serde_json::Value>, DebugApiError> {
+    task.pause()?;
+    Ok(Json(json!({
+        "paused": true,
+    })))
+}
 
-            let (resumer, resume_reciever) = tokio::sync::oneshot::channel();
-            ingest.resumer = Some(resumer);
-            Some((ingest.pause_requester.clone(), resume_reciever))
-        } else {
-            None
-        }
-    };
+#[rocket::post("/resume")]
+pub async fn post_resume(task: &State<IngestTaskHolder>) -> Result<Json<serde_json::Value>, DebugApiError> {
+    task.resume()?;
+    Ok(Json(json!({
+        "paused": false,
+    })))
+}
 
-    if let Some((requester, resume_reciever)) = requester {
-        let requester = requester.lock().await;
-        requester.send(resume_reciever).await.unwrap();
+// Starts an ingest that only replays observations of the entities involved in the given approvals.
+// It runs alongside the live ingest, which carries on as normal. Check /focus_report for the
+// results.
+#[rocket::post("/focus?<approval_ids>")]
+pub async fn post_focus(task: &State<IngestTaskHolder>, conn: BlarserDbConn, approval_ids: Vec<i32>) -> Result<Json<serde_json::Value>, DebugApiError> {
+    let ingest_task = IngestTask::new_focused(conn, approval_ids).await?;
+    let ingest_id = ingest_task.ingest_id();
+    task.add_side_ingest(ingest_task);
 
-        Json(json!({
-            "paused": true,
-        }))
-    } else {
-        Json(json!({
-            "error": "No ingest",
-        }))
+    Ok(Json(json!({
+        "ingest_id": ingest_id,
+    })))
+}
+
+// Starts a backfill of the given range alongside the live ingest. Times are RFC 3339.
+#[rocket::post("/backfill?<from>&<until>&<chunk_minutes>&<debug_history>")]
+pub async fn post_backfill(task: &State<IngestTaskHolder>, conn: BlarserDbConn, from: String, until: String, chunk_minutes: Option<i64>, debug_history: Option<bool>) -> Result<Json<serde_json::Value>, DebugApiError> {
+    let from = parse_time(from)?;
+    let mut config = BackfillConfig::new(parse_time(until)?);
+    if let Some(chunk_minutes) = chunk_minutes {
+        config = config.with_chunk_size(Duration::minutes(chunk_minutes));
+    }
+    if let Some(debug_history) = debug_history {
+        config = config.with_debug_history(debug_history);
     }
+
+    let ingest_task = IngestTask::new_backfill(conn, from, config).await;
+    let ingest_id = ingest_task.ingest_id();
+    task.add_side_ingest(ingest_task);
+
+    Ok(Json(json!({
+        "ingest_id": ingest_id,
+    })))
 }
 
-#[rocket::post("/resume")]
-pub async fn post_resume(task: &State<IngestTaskHolder>) -> Json<serde_json::Value> {
-    let mut ingest = task.latest_ingest.lock().unwrap();
-    if let Some(ingest) = ingest.as_mut() {
-        if let Some(resumer) = ingest.resumer.take() {
-            resumer.send(()).unwrap();
-            Json(json!({
-                "paused": false,
-            }))
-        } else {
-            Json(json!({
-                "error": "Not paused",
-            }))
-        }
-    } else {
-        Json(json!({
-            "error": "No ingest",
-        }))
+#[rocket::post("/backfill/resume?<chunk_minutes>")]
+pub async fn post_resume_backfill(task: &State<IngestTaskHolder>, conn: BlarserDbConn, chunk_minutes: Option<i64>) -> Result<Json<serde_json::Value>, DebugApiError> {
+    let ingest_task = IngestTask::resume_backfill(conn, chunk_minutes.map(Duration::minutes)).await?
+        .ok_or(DebugApiError::NoUnfinishedBackfill)?;
+    let ingest_id = ingest_task.ingest_id();
+    task.add_side_ingest(ingest_task);
+
+    Ok(Json(json!({
+        "ingest_id": ingest_id,
+    })))
+}
+
+// Every event and observation the current ingest has ingested, in order, with the state it started
+// from. POST it back to /event_log/replay to reproduce the ingest without going to Chronicler or
+// the Feed.
+#[get("/event_log")]
+pub async fn event_log(task: &State<IngestTaskHolder>) -> Result<Json<EventLogExport>, DebugApiError> {
+    let latest_ingest = task.latest_ingest.lock().map_err(|_| DebugApiError::LockPoisoned)?;
+    let ingest = latest_ingest.as_ref().ok_or(DebugApiError::NoActiveIngest)?;
+
+    Ok(Json(ingest.export_event_log()))
+}
+
+// Replaces the current ingest with a replay of an exported event log
+#[rocket::post("/event_log/replay", data = "<log>")]
+pub async fn post_replay_event_log(task: &State<IngestTaskHolder>, conn: BlarserDbConn, log: Json<EventLogExport>) -> Result<Json<serde_json::Value>, DebugApiError> {
+    let EventLogExport { start_time, initial_state, entries, .. } = log.into_inner();
+    let initial_state = initial_state.ok_or(DebugApiError::NoLoggedInitialState)?;
+    let items = entries.into_iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            entry.into_replay_item()
+                .map_err(|err| DebugApiError::InvalidLoggedEvent { index, err })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let ingest_task = IngestTask::new_replay(conn, start_time, initial_state, items).await;
+    let ingest_id = ingest_task.ingest_id();
+    replace_ingest(task, ingest_task)?;
+
+    Ok(Json(json!({
+        "ingest_id": ingest_id,
+    })))
+}
+
+fn parse_time(time: String) -> Result<DateTime<Utc>, DebugApiError> {
+    DateTime::parse_from_rfc3339(&time)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|_| DebugApiError::InvalidTime(time))
+}
+
+fn replace_ingest(task: &State<IngestTaskHolder>, ingest_task: IngestTask) -> Result<(), DebugApiError> {
+    let mut latest_ingest = task.latest_ingest.lock().map_err(|_| DebugApiError::LockPoisoned)?;
+    if let Some(previous) = latest_ingest.as_ref() {
+        previous.stop();
     }
+    *latest_ingest = Some(ingest_task);
+    Ok(())
+}
+
+#[get("/focus_report")]
+pub async fn focus_report(task: &State<IngestTaskHolder>, conn: BlarserDbConn) -> Result<Json<serde_json::Value>, DebugApiError> {
+    let ingest_id = task.latest_ingest_id().ok_or(DebugApiError::NoActiveIngest)?;
+    let conflicts = conn.run(move |c| get_conflicts_for_ingest(c, ingest_id)).await?;
+
+    let items = conflicts.into_iter()
+        .into_group_map_by(|conflict| (conflict.entity_type, conflict.entity_id))
+        .into_iter()
+        .map(|((ty, id), conflicts)| json!({
+            "type": ty,
+            "id": id,
+            "conflicts": conflicts.into_iter()
+                .map(|conflict| json!({
+                    "perceived_at": conflict.perceived_at,
+                    "property": conflict.property,
+                    "message": conflict.message,
+                    "resolved": conflict.resolved,
+                    "severity": Severity::from_level(conflict.severity).map(|severity| severity.to_string()),
+                }))
+                .collect::<Vec<_>>(),
+        }))
+        .collect();
+
+    Ok(Json(json!({
+        "ingest_id": ingest_id,
+        "entities": Value::Array(items),
+    })))
 }
 
 pub fn routes() -> Vec<Route> {
-    rocket::routes![entities, entity, version, pause_state, post_pause, post_resume]
-}
\ No newline at end of file
+    rocket::routes![entities, entity, version, version_by_id, entity_fields, validate_entity, validate_all, live_diff, timed_events, play_event, stadium_report, pause_state, post_pause, post_resume, post_focus, post_backfill, post_resume_backfill, event_log, post_replay_event_log, focus_report]
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use serde_json::json;
+    use uuid::Uuid;
+    use blarser::events::{AnyEvent, GameOver, WinCollectedRegular};
+    use blarser::ingest::TimedEventQueue;
+    use super::timed_event_items;
+
+    #[test]
+    fn timed_events_are_listed_in_time_order() {
+        let time = Utc::now();
+        let (early_game, late_game) = (Uuid::new_v4(), Uuid::new_v4());
+        // The successors two game ends would schedule, queued with the later game first
+        let mut queue = TimedEventQueue::new();
+        queue.extend([
+            AnyEvent::from(WinCollectedRegular::new(time + Duration::seconds(60), late_game)),
+            GameOver::new(time + Duration::seconds(70), late_game).into(),
+            WinCollectedRegular::new(time, early_game).into(),
+            GameOver::new(time + Duration::seconds(10), early_game).into(),
+        ]);
+
+        let items = timed_event_items(&queue);
+        let times = items.iter().map(|item| item["time"].clone()).collect::<Vec<_>>();
+        let expected_times = [0, 10, 60, 70].map(|s| json!(time + Duration::seconds(s)));
+        assert_eq!(times, expected_times);
+        assert_eq!(items[0]["targets"], json!([{ "type": "Game", "id": early_game }]));
+        assert_eq!(items[3]["targets"], json!([{ "type": "Game", "id": late_game }]));
+    }
+}
@@ -1,5 +1,7 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
+use partial_information::{Conflict, PartialInformationCompare};
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, PartialEq)]
 #[repr(i64)]
@@ -61,3 +63,41 @@ pub struct RunnerAdvancement {
     pub from_base: i32,
     pub to_base: i32,
 }
+
+// A list of the ids of things that belong to something else, like a division's teams. Membership
+// changes are significant (a team moving divisions is an event we should have seen), but the order
+// isn't, so a different order is taken from the observation without a conflict.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Members(pub Vec<Uuid>);
+
+impl PartialInformationCompare for Members {
+    type Raw = Vec<Uuid>;
+    type Diff<'d> = <Vec<Uuid> as PartialInformationCompare>::Diff<'d>;
+
+    fn diff<'d>(&'d self, observed: &'d Self::Raw, time: DateTime<Utc>) -> Self::Diff<'d> {
+        self.0.diff(observed, time)
+    }
+
+    fn observe(&mut self, observed: &Self::Raw) -> Vec<Conflict> {
+        let removed = self.0.iter()
+            .filter(|id| !observed.contains(id))
+            .map(|id| Conflict::new(String::new(),
+                                    format!("Expected {} to be a member, but it was removed", id)));
+        let added = observed.iter()
+            .filter(|id| !self.0.contains(id))
+            .map(|id| Conflict::new(String::new(),
+                                    format!("Observed {} as a member, but it wasn't expected", id)));
+        let conflicts = removed.chain(added).collect::<Vec<_>>();
+
+        if conflicts.is_empty() {
+            self.0 = observed.clone();
+        }
+        conflicts
+    }
+
+    fn is_ambiguous(&self) -> bool { false }
+
+    fn from_raw(raw: Self::Raw) -> Self { Self(raw) }
+    fn raw_approximation(self) -> Self::Raw { self.0 }
+}
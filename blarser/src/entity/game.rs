@@ -5,12 +5,69 @@ use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use serde_with::with_prefix;
 use uuid::Uuid;
-use partial_information::{PartialInformationCompare, MaybeKnown, RangeInclusive};
+use partial_information::{Conflict, PartialInformationCompare, MaybeKnown, MaybeKnownDiff, Nullable, RangeInclusive};
 use partial_information_derive::PartialInformationCompare;
 
 use crate::entity::{Base, Entity, EntityRaw, RunnerAdvancement};
+use crate::events::EventCategory;
 use crate::state::EntityType;
 
+// Weather is rolled when the game is created, but it isn't always known then. An unknown weather
+// gets filled in by the first observation. A known weather should never change, so if it does
+// that's reported in terms of weather instead of as a generic mismatch. Chron reports weather that
+// isn't known yet as null.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Weather(pub MaybeKnown<i32>);
+
+impl Weather {
+    pub fn unknown() -> Self {
+        Self(MaybeKnown::Unknown)
+    }
+
+    pub fn known(&self) -> Option<i32> {
+        self.0.known().copied()
+    }
+}
+
+impl PartialInformationCompare for Weather {
+    type Raw = Option<i32>;
+    type Diff<'d> = <MaybeKnown<i32> as PartialInformationCompare>::Diff<'d>;
+
+    fn diff<'d>(&'d self, observed: &'d Self::Raw, time: DateTime<Utc>) -> Self::Diff<'d> {
+        match observed {
+            Some(observed) => { self.0.diff(observed, time) }
+            None => { MaybeKnownDiff::NoDiff }
+        }
+    }
+
+    fn observe(&mut self, observed: &Self::Raw) -> Vec<Conflict> {
+        match (self.known(), observed) {
+            // An observation without a weather doesn't tell us anything
+            (_, None) => { Vec::new() }
+            (Some(expected), Some(observed)) if expected != *observed => {
+                vec![Conflict::new(String::new(),
+                                   format!("Expected weather {}, but observed {}. Weather \
+                                   shouldn't change during a game.", expected, observed))]
+            }
+            (_, Some(observed)) => { self.0.observe(observed) }
+        }
+    }
+
+    fn is_ambiguous(&self) -> bool { self.0.is_ambiguous() }
+
+    fn from_raw(raw: Self::Raw) -> Self {
+        match raw {
+            Some(weather) => { Self(MaybeKnown::Known(weather)) }
+            None => { Self::unknown() }
+        }
+    }
+
+    fn raw_approximation(self) -> Self::Raw {
+        self.known()
+    }
+}
+
 // This only existed in Short Circuits
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize, PartialInformationCompare)]
 #[serde(deny_unknown_fields)]
@@ -104,6 +161,43 @@ pub struct GameByTeam {
     pub team_secondary_color: String,
 }
 
+// The balls and strikes of the current at-bat, along with the limits for the team at bat. Game
+// stores these as separate fields because that's how they're observed, so this is a snapshot that
+// gets written back with Game::set_at_bat_count.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtBatCount {
+    pub balls: i32,
+    pub strikes: i32,
+    pub max_balls: i32,
+    pub max_strikes: i32,
+}
+
+impl AtBatCount {
+    pub fn add_ball(&mut self) {
+        self.balls += 1;
+    }
+
+    // A foul ball is a strike, except that it can never be the strike that strikes the batter out
+    pub fn add_strike(&mut self, is_foul: bool) {
+        if !is_foul || self.strikes + 1 < self.max_strikes {
+            self.strikes += 1;
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.balls = 0;
+        self.strikes = 0;
+    }
+
+    pub fn is_strikeout(&self) -> bool {
+        self.strikes >= self.max_strikes
+    }
+
+    pub fn is_walk(&self) -> bool {
+        self.balls >= self.max_balls
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize, PartialInformationCompare)]
 // Can't use deny_unknown_fields here because of the prefixed sub-objects
 #[serde(rename_all = "camelCase")]
@@ -112,15 +206,18 @@ pub struct Game {
     pub id: Uuid,
     pub day: i32,
     pub sim: Option<String>,
-    pub loser: Option<Uuid>,
+    pub loser: Nullable<Uuid>,
     pub phase: i32,
     pub rules: Option<Uuid>,
-    pub shame: bool,
+    // Whether a game ends in shame depends on rules Blarser doesn't fully know yet, so this is
+    // left unknown whenever it might be true and filled in by observation. The other flags are
+    // only ever changed by an event that says what they became, so they stay plain bools.
+    pub shame: MaybeKnown<bool>,
     pub state: Option<GameState>,
     pub inning: i32,
     pub season: i32,
-    pub winner: Option<Uuid>,
-    pub weather: i32,
+    pub winner: Nullable<Uuid>,
+    pub weather: Weather,
     pub end_phase: Option<i32>,
     pub outcomes: Option<Vec<String>>,
     pub season_id: Option<Uuid>,
@@ -150,7 +247,9 @@ pub struct Game {
     pub base_runner_mods: Vec<String>,
     pub game_start_phase: i32,
     pub half_inning_outs: i32,
-    pub last_update_full: Option<Vec<UpdateFull>>,
+    // Blarser doesn't reconstruct this yet, so it's left null and filled in by observation
+    #[partial_information(severity = "info")]
+    pub last_update_full: Nullable<Vec<UpdateFull>>,
     pub new_inning_phase: i32,
     pub top_inning_score: f32,
     pub base_runner_names: Vec<String>,
@@ -169,6 +268,12 @@ pub struct Game {
     pub away: GameByTeam,
 }
 
+impl UpdateFull {
+    pub fn category(&self) -> Option<EventCategory> {
+        self.category.try_into().ok()
+    }
+}
+
 with_prefix!(prefix_home "home");
 with_prefix!(prefix_away "away");
 
@@ -183,9 +288,9 @@ impl Entity for Game {
     fn id(&self) -> Uuid { self.id }
 
     fn description(&self) -> String {
-        format!("{} @ {}: {}", self.away.team_nickname, self.home.team_nickname,
-                // ew
-                self.last_update.as_ref().map_or("", String::as_str))
+        // Day and inning are 0-indexed in the data but 1-indexed everywhere people see them
+        format!("Day {}: {} @ {}, inning {}", self.day + 1, self.away.team_nickname,
+                self.home.team_nickname, self.inning + 1)
     }
 }
 
@@ -222,6 +327,24 @@ impl Game {
         }
     }
 
+    // None if the team at bat's strike limit isn't known, which happens when {home/away}Strikes
+    // hasn't been populated yet
+    pub fn at_bat_count(&self) -> Option<AtBatCount> {
+        Some(AtBatCount {
+            balls: self.at_bat_balls,
+            strikes: self.at_bat_strikes,
+            max_balls: self.team_at_bat().balls,
+            max_strikes: self.team_at_bat().strikes?,
+        })
+    }
+
+    // Only the count is written back. The limits belong to the team at bat and aren't changed by
+    // anything that happens during an at-bat.
+    pub fn set_at_bat_count(&mut self, count: AtBatCount) {
+        self.at_bat_balls = count.balls;
+        self.at_bat_strikes = count.strikes;
+    }
+
     pub(crate) fn team_at_bat_mut(&mut self) -> &mut GameByTeam {
         if self.top_of_inning {
             &mut self.away
@@ -230,6 +353,12 @@ impl Game {
         }
     }
 
+    // Scores are only set once the game starts. Runs shouldn't be scored before then, but if the
+    // score is missing anyway it's counted from 0 instead of panicking.
+    pub(crate) fn add_runs_for_team_at_bat(&mut self, runs: f32) {
+        *self.team_at_bat_mut().score.get_or_insert(0.0) += runs;
+    }
+
     pub(crate) fn current_half_score_mut(&mut self) -> &mut f32 {
         if self.top_of_inning {
             &mut self.top_inning_score
@@ -274,6 +403,18 @@ impl Game {
     // }
     //
     pub(crate) fn out(&mut self, outs_added: i32) {
+        self.add_outs(outs_added);
+        self.end_at_bat()
+    }
+
+    pub(crate) fn reverse_out(&mut self, outs_added: i32, other: &Self) {
+        self.reverse_add_outs(outs_added, other);
+        self.reverse_end_at_bat(other);
+    }
+
+    // Like out, but leaves the at-bat alone. This is for outs that don't involve the batter, like
+    // a runner caught stealing. Returns whether the outs ended the half-inning.
+    pub(crate) fn add_outs(&mut self, outs_added: i32) -> bool {
         let end_of_half_inning = self.half_inning_outs + outs_added == 3;
         if end_of_half_inning {
             self.half_inning_outs = 0;
@@ -297,14 +438,14 @@ impl Game {
             self.half_inning_outs += outs_added;
         }
 
-        self.end_at_bat()
+        end_of_half_inning
     }
 
-    pub(crate) fn reverse_out(&mut self, outs_added: i32, other: &Self) {
+    pub(crate) fn reverse_add_outs(&mut self, outs_added: i32, other: &Self) -> bool {
         let end_of_half_inning = other.half_inning_outs + outs_added == 3;
         if end_of_half_inning {
             self.half_inning_outs = other.half_inning_outs;
-            self.phase = other.half_inning_outs;
+            self.phase = other.phase;
             self.reverse_clear_bases(other);
 
             // Reset both top and bottom inning scored only when the bottom half ends
@@ -324,7 +465,7 @@ impl Game {
             self.half_inning_outs -= outs_added;
         }
 
-        self.reverse_end_at_bat(other);
+        end_of_half_inning
     }
 
     fn game_should_end(&self) -> bool {
@@ -361,6 +502,7 @@ impl Game {
         self.team_at_bat_mut().batter = None;
         self.team_at_bat_mut().batter_name = Some("".to_string());
         self.team_at_bat_mut().batter_mod = String::new();
+        // Not using at_bat_count here because {home/away}Strikes isn't always set when this runs
         self.at_bat_balls = 0;
         self.at_bat_strikes = 0;
     }
@@ -401,25 +543,48 @@ impl Game {
     pub fn advance_runners(&mut self, advancements: &[RunnerAdvancement]) {
         for (i, advancement) in advancements.iter().enumerate() {
             assert_eq!(self.base_runners[i], advancement.runner_id);
-            assert!(self.bases_occupied[i].could_be(&advancement.from_base));
-            self.bases_occupied[i].update(advancement.to_base);
+            // The advancement proves where the runner was, so anything else is a bug
+            let from_base = self.bases_occupied[i].intersect(&RangeInclusive::from_raw(advancement.from_base))
+                .expect("Runner advanced from a base they couldn't have been on");
+            self.bases_occupied[i] = from_base;
+            self.bases_occupied[i].add_constant(advancement.to_base - advancement.from_base);
         }
     }
 
+    // Index of the runner with this id who could have been on the base before `to_base`
+    pub fn steal_candidate(&self, runner_id: Uuid, to_base: Base) -> Option<usize> {
+        let from_base = to_base as i32 - 1;
+        self.base_runners.iter().zip(&self.bases_occupied)
+            .position(|(&id, base)| id == runner_id && base.could_be(&from_base))
+    }
+
+    // Runners who have definitely made it all the way around are taken off the bases. Returns how
+    // many there were.
+    pub(crate) fn remove_runners_who_scored(&mut self) -> i32 {
+        // Bases are numbered from 0, so home is one less than the number of bases
+        let home = self.team_at_bat().bases - 1;
+        let mut runs = 0;
+        while let Some(idx) = self.bases_occupied.iter().position(|base| base.lower >= home) {
+            self.remove_base_runner(idx);
+            runs += 1;
+        }
+        runs
+    }
+
     pub fn advance_runners_by(&mut self, by: i32) {
         for runner_base in &mut self.bases_occupied {
             runner_base.add_constant(by);
         }
     }
 
-    // pub(crate) fn remove_base_runner(&mut self, runner_idx: usize) {
-    //     self.base_runners.remove(runner_idx);
-    //     self.base_runner_names.remove(runner_idx);
-    //     self.base_runner_mods.remove(runner_idx);
-    //     self.bases_occupied.remove(runner_idx);
-    //     self.baserunner_count -= 1;
-    // }
-    //
+    pub(crate) fn remove_base_runner(&mut self, runner_idx: usize) {
+        self.base_runners.remove(runner_idx);
+        self.base_runner_names.remove(runner_idx);
+        self.base_runner_mods.remove(runner_idx);
+        self.bases_occupied.remove(runner_idx);
+        self.baserunner_count -= 1;
+    }
+
     // pub(crate) fn remove_each_base_runner(self) -> impl Iterator<Item=Self> {
     //     // Intended for cases when we know some base runner got out, but we don't know which (i.e.,
     //     // double plays)
@@ -520,4 +685,195 @@ impl Game {
     //         }
     //     }
     // }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use serde_json::json;
+    use super::*;
+
+    // A game in the middle of the top of the first inning with nobody on base and nobody out.
+    // Tests change whatever they need from here.
+    pub(crate) fn test_game() -> Game {
+        let raw: <Game as PartialInformationCompare>::Raw = serde_json::from_value(json!({
+            "id": "b1e2a1b4-3c0a-4a6c-9a57-1a4b1f3c2d01",
+            "day": 10,
+            "sim": "thisidisstaticyo",
+            "loser": null,
+            "phase": 6,
+            "rules": null,
+            "shame": false,
+            "state": null,
+            "inning": 0,
+            "season": 12,
+            "winner": null,
+            "weather": 1,
+            "endPhase": null,
+            "outcomes": [],
+            "seasonId": null,
+            "finalized": false,
+            "gameStart": true,
+            "playCount": 10,
+            "stadiumId": null,
+            "statsheet": null,
+            "atBatBalls": 0,
+            "lastUpdate": "",
+            "tournament": -1,
+            "baseRunners": [],
+            "repeatCount": 0,
+            "scoreLedger": "",
+            "scoreUpdate": "",
+            "seriesIndex": 1,
+            "terminology": null,
+            "topOfInning": true,
+            "atBatStrikes": 0,
+            "gameComplete": false,
+            "isPostseason": false,
+            "isPrizeMatch": false,
+            "isTitleMatch": false,
+            "queuedEvents": [],
+            "seriesLength": 3,
+            "basesOccupied": [],
+            "baseRunnerMods": [],
+            "gameStartPhase": 10,
+            "halfInningOuts": 0,
+            "lastUpdateFull": null,
+            "newInningPhase": -1,
+            "topInningScore": 0.0,
+            "baseRunnerNames": [],
+            "baserunnerCount": 0,
+            "halfInningScore": 0.0,
+            "tournamentRound": null,
+            "secretBaserunner": null,
+            "bottomInningScore": 0.0,
+            "newHalfInningPhase": -1,
+            "tournamentRoundGameIndex": null,
+            "homeOdds": 0.5,
+            "homeOuts": 3,
+            "homeTeam": "b1e2a1b4-3c0a-4a6c-9a57-1a4b1f3c2d02",
+            "homeBalls": 4,
+            "homeBases": 4,
+            "homeScore": 0.0,
+            "homeBatter": null,
+            "homePitcher": "b1e2a1b4-3c0a-4a6c-9a57-1a4b1f3c2d03",
+            "homeStrikes": 3,
+            "homeTeamName": "Home Team",
+            "homeTeamRuns": null,
+            "homeTeamColor": "#000000",
+            "homeTeamEmoji": "0x1F3E0",
+            "homeBatterMod": "",
+            "homeBatterName": "",
+            "homePitcherMod": "",
+            "homePitcherName": "Home Pitcher",
+            "homeTeamNickname": "Home",
+            "homeTeamBatterCount": -1,
+            "homeTeamSecondaryColor": "#ffffff",
+            "awayOdds": 0.5,
+            "awayOuts": 3,
+            "awayTeam": "b1e2a1b4-3c0a-4a6c-9a57-1a4b1f3c2d04",
+            "awayBalls": 4,
+            "awayBases": 4,
+            "awayScore": 0.0,
+            "awayBatter": "b1e2a1b4-3c0a-4a6c-9a57-1a4b1f3c2d05",
+            "awayPitcher": "b1e2a1b4-3c0a-4a6c-9a57-1a4b1f3c2d06",
+            "awayStrikes": 3,
+            "awayTeamName": "Away Team",
+            "awayTeamRuns": null,
+            "awayTeamColor": "#000000",
+            "awayTeamEmoji": "0x1F697",
+            "awayBatterMod": "",
+            "awayBatterName": "Away Batter",
+            "awayPitcherMod": "",
+            "awayPitcherName": "Away Pitcher",
+            "awayTeamNickname": "Away",
+            "awayTeamBatterCount": 0,
+            "awayTeamSecondaryColor": "#ffffff",
+        })).expect("Test game JSON should match the Game schema");
+
+        Game::from_raw(raw)
+    }
+
+    // Puts a runner on each of the given bases, in order from the lead runner back
+    pub(crate) fn with_runners(mut game: Game, bases: &[Base]) -> Game {
+        for (i, &base) in bases.iter().enumerate() {
+            game.base_runners.push(Uuid::from_u128(0x100 + i as u128));
+            game.base_runner_names.push(format!("Runner {i}"));
+            game.base_runner_mods.push(String::new());
+            game.bases_occupied.push(RangeInclusive::from_raw(base as i32));
+            game.baserunner_count += 1;
+        }
+        game
+    }
+
+    #[test]
+    fn advancing_narrows_where_the_runner_was() {
+        let mut game = with_runners(test_game(), &[Base::First]);
+        game.bases_occupied[0] = RangeInclusive { lower: 0, upper: 1 };
+
+        game.advance_runners(&[RunnerAdvancement {
+            runner_id: game.base_runners[0],
+            from_base: 1,
+            to_base: 3,
+        }]);
+        assert_eq!((game.bases_occupied[0].lower, game.bases_occupied[0].upper), (3, 3));
+    }
+
+    #[test]
+    fn out_that_ends_the_half_inning_round_trips() {
+        let mut old_game = with_runners(test_game(), &[Base::Second]);
+        old_game.half_inning_outs = 2;
+
+        let mut game = old_game.clone();
+        game.out(1);
+        assert_eq!(game.half_inning_outs, 0);
+        assert_eq!(game.phase, 3);
+        assert!(game.base_runners.is_empty());
+
+        game.reverse_out(1, &old_game);
+        assert_eq!(game, old_game);
+    }
+
+    #[test]
+    fn observing_weather_narrows_an_unknown_weather() {
+        let mut weather = Weather::from_raw(None);
+        assert_eq!(weather, Weather::unknown());
+        assert_eq!(weather.raw_approximation(), None);
+
+        assert!(weather.observe(&Some(7)).is_empty());
+        assert_eq!(weather.known(), Some(7));
+        assert_eq!(weather.raw_approximation(), Some(7));
+
+        // An observation that doesn't have the weather leaves it alone
+        assert!(weather.observe(&None).is_empty());
+        assert_eq!(weather.known(), Some(7));
+    }
+
+    #[test]
+    fn changed_weather_conflicts() {
+        let mut weather = Weather::from_raw(Some(1));
+        assert_eq!(weather.observe(&Some(7)).len(), 1);
+        assert_eq!(weather.known(), Some(1));
+    }
+
+    #[test]
+    fn unknown_weather_approximates_as_null() {
+        let mut game = test_game();
+        game.weather = Weather::unknown();
+
+        let raw = serde_json::to_value(game.raw_approximation())
+            .expect("Game should serialize");
+        assert_eq!(raw["weather"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn unknown_shame_is_narrowed_by_observation() {
+        let mut game = test_game();
+        game.shame = MaybeKnown::Unknown;
+        let mut observed = test_game().raw_approximation();
+        observed.shame = true;
+
+        let conflicts = game.observe(&observed);
+        assert!(conflicts.iter().all(|conflict| conflict.property() != "shame"), "{conflicts:?}");
+        assert_eq!(game.shame, MaybeKnown::Known(true));
+    }
+}
@@ -5,6 +5,10 @@ mod game;
 mod team;
 mod standings;
 mod season;
+mod stadium;
+mod league;
+mod subleague;
+mod division;
 mod common;
 
 use std::fmt::{Display, Formatter};
@@ -12,17 +16,31 @@ use uuid::Uuid;
 use derive_more::{From, TryInto, Unwrap};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use partial_information::PartialInformationCompare;
+use partial_information::{Conflict, FieldAccess, FieldValue, PartialInformationCompare};
 
 // use crate::events::AnyEvent;
 
-pub use common::{Base, RunnerAdvancement};
+pub use common::{Base, RunnerAdvancement, Members};
 pub use sim::Sim;
-pub use player::Player;
-pub use team::Team;
-pub use game::{Game, GameByTeam, UpdateFull, UpdateFullMetadata};
+pub use player::{Player, HitStreak};
+pub use team::{Team, RosterSlot, ModDuration};
+pub use game::{AtBatCount, Game, GameByTeam, GameState, UpdateFull, UpdateFullMetadata, Weather};
+#[cfg(test)]
+pub(crate) use game::tests::{test_game, with_runners};
+#[cfg(test)]
+pub(crate) use team::tests::test_team;
+#[cfg(test)]
+pub(crate) use player::tests::test_player;
+#[cfg(test)]
+pub(crate) use sim::tests::test_sim;
+#[cfg(test)]
+pub(crate) use stadium::tests::{test_stadium, test_stadium_json};
 pub use standings::Standings;
 pub use season::Season;
+pub use stadium::{Stadium, StadiumState};
+pub use league::League;
+pub use subleague::Subleague;
+pub use division::Division;
 use crate::polymorphic_enum::polymorphic_enum;
 use crate::state::EntityType;
 
@@ -55,11 +73,63 @@ polymorphic_enum! {
         Game(Game),
         Standings(Standings),
         Season(Season),
+        Stadium(Stadium),
+        League(League),
+        Subleague(Subleague),
+        Division(Division),
     }
 }
 
 pub(crate) use with_entity;
 
+// Like with_entity, but for when all you have is the EntityType. Together with the AnyEntity and
+// AnyEntityRaw enums this is the only list of entity types, so anything that needs to dispatch on
+// the type should go through one of their macros instead of writing out its own match. Adding an
+// entity type means implementing Entity and EntityRaw for it and adding one line to each of the
+// three lists, plus the EntityType variant (which is a database enum, so it needs a migration too).
+macro_rules! with_entity_type {
+    ($on:expr, |$bound_type:ident| $body:block) => {
+        match $on {
+            $crate::state::EntityType::Sim => { type $bound_type = $crate::entity::Sim; $body }
+            $crate::state::EntityType::Player => { type $bound_type = $crate::entity::Player; $body }
+            $crate::state::EntityType::Team => { type $bound_type = $crate::entity::Team; $body }
+            $crate::state::EntityType::Game => { type $bound_type = $crate::entity::Game; $body }
+            $crate::state::EntityType::Standings => { type $bound_type = $crate::entity::Standings; $body }
+            $crate::state::EntityType::Season => { type $bound_type = $crate::entity::Season; $body }
+            $crate::state::EntityType::Stadium => { type $bound_type = $crate::entity::Stadium; $body }
+            $crate::state::EntityType::League => { type $bound_type = $crate::entity::League; $body }
+            $crate::state::EntityType::Subleague => { type $bound_type = $crate::entity::Subleague; $body }
+            $crate::state::EntityType::Division => { type $bound_type = $crate::entity::Division; $body }
+        }
+    };
+}
+
+pub(crate) use with_entity_type;
+
+pub trait EntityRaw: Serialize + for<'de> Deserialize<'de> {
+    type Entity: Entity + PartialInformationCompare<Raw=Self> + Serialize + for<'de> Deserialize<'de>;
+
+    fn name() -> &'static str;
+    fn id(&self) -> Uuid;
+}
+
+polymorphic_enum! {
+    #[derive(Debug, Clone, Deserialize, Serialize, From, TryInto, Unwrap)]
+    #[try_into(owned, ref, ref_mut)]
+    pub AnyEntityRaw: with_entity_raw {
+        SimRaw(<Sim as PartialInformationCompare>::Raw),
+        PlayerRaw(<Player as PartialInformationCompare>::Raw),
+        TeamRaw(<Team as PartialInformationCompare>::Raw),
+        GameRaw(<Game as PartialInformationCompare>::Raw),
+        StandingsRaw(<Standings as PartialInformationCompare>::Raw),
+        SeasonRaw(<Season as PartialInformationCompare>::Raw),
+        StadiumRaw(<Stadium as PartialInformationCompare>::Raw),
+        LeagueRaw(<League as PartialInformationCompare>::Raw),
+        SubleagueRaw(<Subleague as PartialInformationCompare>::Raw),
+        DivisionRaw(<Division as PartialInformationCompare>::Raw),
+    }
+}
+
 impl Display for AnyEntity {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         with_entity!(self, |e| { e.fmt(f) })
@@ -109,58 +179,97 @@ impl AnyEntity {
     }
 
     pub fn from_raw_json(entity_type: EntityType, raw_json: serde_json::Value) -> serde_json::Result<Self> {
-        match entity_type {
-            EntityType::Sim => { Self::from_raw_json_typed::<Sim>(raw_json) }
-            EntityType::Player => { Self::from_raw_json_typed::<Player>(raw_json) }
-            EntityType::Team => { Self::from_raw_json_typed::<Team>(raw_json) }
-            EntityType::Game => { Self::from_raw_json_typed::<Game>(raw_json) }
-            EntityType::Standings => { Self::from_raw_json_typed::<Standings>(raw_json) }
-            EntityType::Season => { Self::from_raw_json_typed::<Season>(raw_json) }
-        }
+        with_entity_type!(entity_type, |EntityT| { Self::from_raw_json_typed::<EntityT>(raw_json) })
     }
 
     pub fn from_raw(raw: AnyEntityRaw) -> Self {
-        match raw {
-            AnyEntityRaw::SimRaw(r) => { AnyEntity::Sim(Sim::from_raw(r)) }
-            AnyEntityRaw::PlayerRaw(r) => { AnyEntity::Player(Player::from_raw(r)) }
-            AnyEntityRaw::TeamRaw(r) => { AnyEntity::Team(Team::from_raw(r)) }
-            AnyEntityRaw::GameRaw(r) => { AnyEntity::Game(Game::from_raw(r)) }
-            AnyEntityRaw::StandingsRaw(r) => { AnyEntity::Standings(Standings::from_raw(r)) }
-            AnyEntityRaw::SeasonRaw(r) => { AnyEntity::Season(Season::from_raw(r)) }
-        }
+        with_entity_raw!(raw, |r: RawT| { AnyEntity::from(<RawT as EntityRaw>::Entity::from_raw(r)) })
+    }
+
+    // The inverse of to_json
+    pub fn from_json(entity_type: EntityType, json: serde_json::Value) -> serde_json::Result<Self> {
+        with_entity_type!(entity_type, |EntityT| { serde_json::from_value::<EntityT>(json).map(AnyEntity::from) })
+    }
+
+    // Like from_json, but borrows the JSON so the caller still has it if it doesn't parse
+    pub fn from_json_ref(entity_type: EntityType, json: &serde_json::Value) -> serde_json::Result<Self> {
+        with_entity_type!(entity_type, |EntityT| { EntityT::deserialize(json).map(AnyEntity::from) })
     }
 
     pub fn to_json(&self) -> serde_json::Value {
         with_entity!(&self, |e| { serde_json::to_value(e).unwrap() })
     }
 
+    // The closest thing to what Chron would have stored for this entity. Ambiguous fields are
+    // replaced by a plausible value, so it's always in the same shape as the Chron JSON.
+    pub fn to_raw_approximation_json(&self) -> serde_json::Result<serde_json::Value> {
+        self.clone().to_raw_json()
+    }
+
+    // Same as to_raw_approximation_json, but without the clone for callers that are done with the
+    // entity
+    pub fn to_raw_json(self) -> serde_json::Result<serde_json::Value> {
+        with_entity!(self, |e| { serde_json::to_value(e.raw_approximation()) })
+    }
+
+    // For callers that serialize the result themselves, so it doesn't have to go through a Value
+    // first. See UntaggedRaw.
+    pub fn into_raw_approximation(self) -> AnyEntityRaw {
+        with_entity!(self, |e| { AnyEntityRaw::from(e.raw_approximation()) })
+    }
+
     pub fn is_ambiguous(&self) -> bool {
         with_entity!(&self, |e| { e.is_ambiguous() })
     }
 
+    pub fn fields_for_type(entity_type: EntityType) -> &'static [&'static str] {
+        with_entity_type!(entity_type, |EntityT| { EntityT::fields() })
+    }
+
+    pub fn get_field(&self, name: &str) -> Option<FieldValue> {
+        with_entity!(&self, |e| { e.get_field(name) })
+    }
+
+    // Conflicts that observing `raw` would produce, without changing this entity
+    pub fn observe_raw(&self, raw: &AnyEntityRaw) -> Vec<Conflict> {
+        with_entity!(self, |e: EntityT| {
+            let typed_raw: Result<&<EntityT as PartialInformationCompare>::Raw, _> = raw.try_into();
+            match typed_raw {
+                Ok(r) => { e.clone().observe(r) }
+                Err(_) => {
+                    vec![Conflict::new(String::new(),
+                                       format!("Can't observe a different entity type on {}", e.entity_type()))]
+                }
+            }
+        })
+    }
+
+    // Fraction of the entity's fields that are fully known, from 0 to 1
+    pub fn confidence(&self) -> f32 {
+        with_entity!(&self, |e| { e.count_known().confidence() })
+    }
+
     impl_as_ref!(Sim, AnyEntity::Sim, as_sim, as_sim_mut);
     impl_as_ref!(Game, AnyEntity::Game, as_game, as_game_mut);
     impl_as_ref!(Team, AnyEntity::Team, as_team, as_team_mut);
     impl_as_ref!(Player, AnyEntity::Player, as_player, as_player_mut);
-}
-
+    impl_as_ref!(Stadium, AnyEntity::Stadium, as_stadium, as_stadium_mut);
+    impl_as_ref!(Standings, AnyEntity::Standings, as_standings, as_standings_mut);
+    impl_as_ref!(Season, AnyEntity::Season, as_season, as_season_mut);
+    impl_as_ref!(League, AnyEntity::League, as_league, as_league_mut);
+    impl_as_ref!(Subleague, AnyEntity::Subleague, as_subleague, as_subleague_mut);
+    impl_as_ref!(Division, AnyEntity::Division, as_division, as_division_mut);
 
-pub trait EntityRaw: Serialize + for<'de> Deserialize<'de> {
-    type Entity: Entity + PartialInformationCompare<Raw=Self> + Serialize + for<'de> Deserialize<'de>;
-
-    fn name() -> &'static str;
-    fn id(&self) -> Uuid;
-}
+    // For generic code that knows which entity type it wants but not which as_* to call
+    pub fn try_as<'a, EntityT>(&'a self) -> Option<&'a EntityT>
+        where EntityT: Entity, &'a AnyEntity: TryInto<&'a EntityT> {
+        self.try_into().ok()
+    }
 
-#[derive(Debug, Clone, Deserialize, Serialize, From, TryInto, Unwrap)]
-#[try_into(owned, ref, ref_mut)]
-pub enum AnyEntityRaw {
-    SimRaw(<Sim as PartialInformationCompare>::Raw),
-    PlayerRaw(<Player as PartialInformationCompare>::Raw),
-    TeamRaw(<Team as PartialInformationCompare>::Raw),
-    GameRaw(<Game as PartialInformationCompare>::Raw),
-    StandingsRaw(<Standings as PartialInformationCompare>::Raw),
-    SeasonRaw(<Season as PartialInformationCompare>::Raw),
+    pub fn try_as_mut<'a, EntityT>(&'a mut self) -> Option<&'a mut EntityT>
+        where EntityT: Entity, &'a mut AnyEntity: TryInto<&'a mut EntityT> {
+        self.try_into().ok()
+    }
 }
 
 impl AnyEntityRaw {
@@ -171,28 +280,30 @@ impl AnyEntityRaw {
     }
 
     pub fn from_json(entity_type: EntityType, json: serde_json::Value) -> serde_json::Result<Self> {
-        match entity_type {
-            EntityType::Sim => { Self::from_json_typed::<Sim>(json) }
-            EntityType::Player => { Self::from_json_typed::<Player>(json) }
-            EntityType::Team => { Self::from_json_typed::<Team>(json) }
-            EntityType::Game => { Self::from_json_typed::<Game>(json) }
-            EntityType::Standings => { Self::from_json_typed::<Standings>(json) }
-            EntityType::Season => { Self::from_json_typed::<Season>(json) }
-        }
+        with_entity_type!(entity_type, |EntityT| { Self::from_json_typed::<EntityT>(json) })
     }
 
     pub fn to_json(self) -> serde_json::Result<serde_json::Value> {
-        match self {
-            AnyEntityRaw::SimRaw(r) => { serde_json::to_value(r) }
-            AnyEntityRaw::PlayerRaw(r) => { serde_json::to_value(r) }
-            AnyEntityRaw::TeamRaw(r) => { serde_json::to_value(r) }
-            AnyEntityRaw::GameRaw(r) => { serde_json::to_value(r) }
-            AnyEntityRaw::StandingsRaw(r) => { serde_json::to_value(r) }
-            AnyEntityRaw::SeasonRaw(r) => { serde_json::to_value(r) }
-        }
+        with_entity_raw!(self, |r| { serde_json::to_value(r) })
+    }
+}
+
+// AnyEntityRaw serializes with its variant name. This serializes as the raw entity alone, the way
+// Chron has it.
+pub struct UntaggedRaw(pub AnyEntityRaw);
+
+impl Serialize for UntaggedRaw {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        with_entity_raw!(&self.0, |r| { r.serialize(serializer) })
     }
 }
 
+// For callers that only have the entity's JSON, e.g. straight out of the versions table. See
+// AnyEntity::to_raw_approximation_json.
+pub fn entity_to_raw_approximation(entity_type: EntityType, json: serde_json::Value) -> serde_json::Result<serde_json::Value> {
+    AnyEntity::from_json(entity_type, json)?.to_raw_json()
+}
+
 #[derive(Debug, Error)]
 pub enum EntityParseError {
     #[error("Unknown entity type {0}")]
@@ -200,4 +311,157 @@ pub enum EntityParseError {
 
     #[error(transparent)]
     DeserializeFailed(#[from] serde_json::Error),
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use super::*;
+    use partial_information::MaybeKnown;
+
+    #[test]
+    fn every_entity_type_is_registered() {
+        let all_types = [
+            EntityType::Sim, EntityType::Player, EntityType::Team, EntityType::Game,
+            EntityType::Standings, EntityType::Season, EntityType::Stadium, EntityType::League,
+            EntityType::Subleague, EntityType::Division,
+        ];
+        for entity_type in all_types {
+            assert!(!AnyEntity::fields_for_type(entity_type).is_empty(), "{entity_type} has no fields");
+        }
+    }
+
+    #[test]
+    fn fully_known_entities_are_more_confident_than_ambiguous_ones() {
+        let known = test_player(None);
+        let mut ambiguous = known.clone();
+        ambiguous.defense_rating = Some(MaybeKnown::Unknown);
+        ambiguous.hitting_rating = Some(MaybeKnown::Unknown);
+        ambiguous.pitching_rating = Some(MaybeKnown::Unknown);
+        ambiguous.baserunning_rating = Some(MaybeKnown::Unknown);
+
+        let (known, ambiguous) = (AnyEntity::from(known), AnyEntity::from(ambiguous));
+        assert_eq!(known.confidence(), 1.0);
+        assert!(ambiguous.confidence() < known.confidence());
+        assert!(ambiguous.confidence() > 0.0);
+    }
+
+    #[test]
+    fn observation_dispatches_to_the_right_entity() {
+        let game = AnyEntity::from(test_game());
+        let raw_json = game.to_raw_approximation_json()
+            .expect("Test game should serialize");
+
+        let raw = AnyEntityRaw::from_json(EntityType::Game, raw_json)
+            .expect("Test game's raw JSON should parse as a game");
+        assert!(game.observe_raw(&raw).is_empty());
+
+        let from_raw = AnyEntity::from_raw(raw);
+        assert_eq!(from_raw.entity_type(), EntityType::Game);
+        assert_eq!(from_raw.id(), game.id());
+    }
+
+    #[test]
+    fn take_field_only_replaces_the_named_field() {
+        let mut game = test_game();
+        let mut observed = test_game();
+        observed.inning = game.inning + 3;
+        observed.top_of_inning = !game.top_of_inning;
+
+        assert!(game.take_field(&observed, "inning"));
+        assert_eq!(game.inning, observed.inning);
+        assert_ne!(game.top_of_inning, observed.top_of_inning);
+        assert!(!game.take_field(&observed, "not_a_field"));
+    }
+
+    #[test]
+    fn fully_known_standings_approximate_to_the_original_chron_json() {
+        let (team_a, team_b) = ("3f8bbb15-61c0-4e3f-8e4a-907a5fb1565e", "979aee4a-6d80-4863-bf1c-ee1a78e06024");
+        let chron_json = serde_json::json!({
+            "__v": 0,
+            "id": "dbcde8fc-cca6-42ad-b4c8-2a7b32f25bb5",
+            "runs": { team_a: 652.5, team_b: 701.25 },
+            "wins": { team_a: 50, team_b: 49 },
+            "losses": { team_a: 49, team_b: 50 },
+            "gamesPlayed": { team_a: 99, team_b: 99 },
+        });
+
+        // Maps serialize with their keys sorted, so comparing the strings compares canonical forms
+        let from_stored = entity_to_raw_approximation(EntityType::Standings, chron_json.clone())
+            .expect("Standings JSON should parse");
+        assert_eq!(from_stored.to_string(), chron_json.to_string());
+
+        let from_raw = AnyEntity::from_raw_json(EntityType::Standings, chron_json.clone())
+            .expect("Standings JSON should parse")
+            .to_raw_approximation_json()
+            .expect("Standings should serialize");
+        assert_eq!(from_raw.to_string(), chron_json.to_string());
+    }
+
+    #[test]
+    fn as_season_only_matches_seasons() {
+        let season_json = serde_json::json!({
+            "id": "cd1b6714-f4de-4dfc-a030-851b3459d8d1",
+            "rules": "b3ab0e6e-e8fd-40b8-8c1e-d6fe1bbb4e95",
+            "stats": "f1f3fd7e-e2f4-4cd6-9bfc-c34d1cd6ee27",
+            "league": "d8545021-e9fc-48a3-af74-48685950a183",
+            "schedule": null,
+            "standings": "dbcde8fc-cca6-42ad-b4c8-2a7b32f25bb5",
+            "terminology": "b67e9bbb-1495-4e1b-b517-f1444b0a6c8b",
+            "seasonNumber": 10,
+            "totalDaysInSeason": 99,
+        });
+        let mut season = AnyEntity::from_raw_json(EntityType::Season, season_json)
+            .expect("Season JSON should parse");
+
+        assert_eq!(season.as_season().map(|s| s.season_number), Some(10));
+        assert_eq!(season.try_as::<Season>().map(|s| s.season_number), Some(10));
+        season.as_season_mut().expect("A season should be a season").season_number = 11;
+        assert_eq!(season.as_season().map(|s| s.season_number), Some(11));
+
+        let game = AnyEntity::from(test_game());
+        assert!(game.as_season().is_none());
+        assert!(game.try_as::<Season>().is_none());
+    }
+
+    // What the /entities route stores for each row of a page. 100 rows is its default page size.
+    fn stored_player_page() -> Vec<serde_json::Value> {
+        (0..100).map(|_| AnyEntity::from(test_player(None)).to_json()).collect()
+    }
+
+    fn serialize_from_parsed_entity(json: &serde_json::Value) -> String {
+        let entity = AnyEntity::from_json_ref(EntityType::Player, json)
+            .expect("Test player should parse");
+        serde_json::to_string(&UntaggedRaw(entity.into_raw_approximation()))
+            .expect("Test player should serialize")
+    }
+
+    #[test]
+    fn untagged_raw_serializes_the_same_as_the_json_round_trip() {
+        let json = AnyEntity::from(test_player(None)).to_json();
+        let round_trip = entity_to_raw_approximation(EntityType::Player, json.clone())
+            .expect("Test player should parse");
+        // Compared as Values because the keys come out in a different order
+        let direct: serde_json::Value = serde_json::from_str(&serialize_from_parsed_entity(&json))
+            .expect("Serialized player should be valid JSON");
+        assert_eq!(direct, round_trip);
+    }
+
+    #[bench]
+    fn page_of_raw_approximations_through_values(b: &mut test::Bencher) {
+        let page = stored_player_page();
+        b.iter(|| page.iter()
+            .map(|json| entity_to_raw_approximation(EntityType::Player, json.clone())
+                .expect("Test player should parse")
+                .to_string())
+            .collect::<Vec<_>>());
+    }
+
+    #[bench]
+    fn page_of_raw_approximations_from_parsed_entities(b: &mut test::Bencher) {
+        let page = stored_player_page();
+        b.iter(|| page.iter()
+            .map(serialize_from_parsed_entity)
+            .collect::<Vec<_>>());
+    }
+}
@@ -1,11 +1,13 @@
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use partial_information::{Rerollable, PartialInformationCompare, MaybeKnown};
+use partial_information::{Conflict, Rerollable, PartialInformationCompare, MaybeKnown};
 use partial_information_derive::PartialInformationCompare;
 
-use crate::entity::{Entity, EntityRaw};
+use crate::entity::{Entity, EntityRaw, ModDuration};
+use crate::events::FREE_REFILL_MOD;
 use crate::state::EntityType;
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize, PartialInformationCompare)]
@@ -41,6 +43,36 @@ pub struct PlayerElsewhereInfo {
     pub season: i32,
 }
 
+// Wrapper around consecutive_hits so that a mismatch gets a more useful message. The streak can
+// only be wrong if we missed a hit or an out, so that's what the conflict says.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct HitStreak(pub i32);
+
+impl PartialInformationCompare for HitStreak {
+    type Raw = i32;
+    type Diff<'d> = <i32 as PartialInformationCompare>::Diff<'d>;
+
+    fn diff<'d>(&'d self, observed: &'d Self::Raw, time: DateTime<Utc>) -> Self::Diff<'d> {
+        self.0.diff(observed, time)
+    }
+
+    fn observe(&mut self, observed: &Self::Raw) -> Vec<Conflict> {
+        if self.0 == *observed {
+            Vec::new()
+        } else {
+            vec![Conflict::new(String::new(),
+                               format!("Expected a streak of {} consecutive hits, but observed {}. \
+                               A hit or an out was probably missed.", self.0, observed))]
+        }
+    }
+
+    fn is_ambiguous(&self) -> bool { false }
+
+    fn from_raw(raw: Self::Raw) -> Self { Self(raw) }
+    fn raw_approximation(self) -> Self::Raw { self.0 }
+}
+
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize, PartialInformationCompare)]
 #[serde(deny_unknown_fields)]
 #[serde(rename_all = "camelCase")]
@@ -65,7 +97,7 @@ pub struct Player {
     pub items: Option<Vec<Item>>,
     pub state: Option<PlayerState>,
     pub hit_streak: Option<i32>,
-    pub consecutive_hits: Option<i32>,
+    pub consecutive_hits: Option<HitStreak>,
 
     pub game_attr: Option<Vec<String>>,
     pub week_attr: Option<Vec<String>>,
@@ -132,11 +164,116 @@ impl Entity for Player {
     fn id(&self) -> Uuid { self.id }
 
     fn description(&self) -> String {
-        self.name.to_string()
+        // The team's name isn't available from here
+        match self.league_team_id {
+            Some(team_id) => { format!("{} (team {})", self.name, team_id) }
+            None => { format!("{} (no team)", self.name) }
+        }
     }
 }
 
 impl Player {
+    // For players that events create (hatched, joined from the ILB, incineration replacements).
+    // Nothing about them is known until they're observed except who they are and which team they
+    // joined, so attributes can be anywhere in the usual 0 to 1 and the rest is a placeholder. The
+    // first observation replaces all of it; see ingest_observation.
+    pub fn new_from_event(id: Uuid, league_team_id: Option<Uuid>) -> Player {
+        let attr = || {
+            let mut value = Rerollable::from_raw(0.5);
+            value.add_range(-0.5, 0.5);
+            value
+        };
+        Player {
+            id,
+            name: String::new(),
+            ritual: None,
+            fate: None,
+            soul: 0,
+            blood: None,
+            coffee: None,
+            peanut_allergy: None,
+            bat: None,
+            armor: None,
+            league_team_id,
+            tournament_team_id: None,
+            deceased: Some(false),
+            evolution: None,
+            items: None,
+            state: None,
+            hit_streak: None,
+            consecutive_hits: None,
+            game_attr: None,
+            week_attr: None,
+            seas_attr: None,
+            item_attr: None,
+            perm_attr: None,
+            buoyancy: attr(),
+            cinnamon: Some(attr()),
+            coldness: attr(),
+            chasiness: attr(),
+            divinity: attr(),
+            martyrdom: attr(),
+            base_thirst: attr(),
+            indulgence: attr(),
+            musclitude: attr(),
+            tragicness: attr(),
+            omniscience: attr(),
+            patheticism: attr(),
+            suppression: attr(),
+            continuation: attr(),
+            ruthlessness: attr(),
+            watchfulness: attr(),
+            laserlikeness: attr(),
+            overpowerment: attr(),
+            tenaciousness: attr(),
+            thwackability: attr(),
+            anticapitalism: attr(),
+            ground_friction: attr(),
+            pressurization: attr(),
+            unthwackability: attr(),
+            shakespearianism: attr(),
+            moxie: attr(),
+            total_fingers: 10,
+            defense_rating: Some(MaybeKnown::Unknown),
+            hitting_rating: Some(MaybeKnown::Unknown),
+            pitching_rating: Some(MaybeKnown::Unknown),
+            baserunning_rating: Some(MaybeKnown::Unknown),
+            edensity: None,
+        }
+    }
+
+    // Not every player has consecutive_hits (Phantom Sixpack, for one), so these just do nothing
+    // for those players instead of panicking
+    pub fn increment_consecutive_hits(&mut self) {
+        if let Some(streak) = &mut self.consecutive_hits {
+            streak.0 += 1;
+        }
+    }
+
+    pub fn reset_consecutive_hits(&mut self) {
+        if let Some(streak) = &mut self.consecutive_hits {
+            streak.0 = 0;
+        }
+    }
+
+    pub fn mods(&self, duration: ModDuration) -> &[String] {
+        match duration {
+            ModDuration::Permanent => { self.perm_attr.as_deref() }
+            ModDuration::Season => { self.seas_attr.as_deref() }
+            ModDuration::Weekly => { self.week_attr.as_deref() }
+            ModDuration::Game => { self.game_attr.as_deref() }
+        }.unwrap_or_default()
+    }
+
+    pub fn mods_mut(&mut self, duration: ModDuration) -> &mut Vec<String> {
+        match duration {
+            ModDuration::Permanent => { &mut self.perm_attr }
+            ModDuration::Season => { &mut self.seas_attr }
+            ModDuration::Weekly => { &mut self.week_attr }
+            ModDuration::Game => { &mut self.game_attr }
+        }.as_mut().expect("Everyone but Phantom Sixpack has this")
+    }
+
     // fn apply_feed_event(&mut self, event: &EventuallyEvent) -> FeedEventChangeResult {
     //     match event.r#type {
     //         EventType::Hit | EventType::HomeRun => {
@@ -246,6 +383,10 @@ impl Player {
             self.item_attr.iter().flatten().any(|n| n == mod_name)
     }
 
+    pub fn has_free_refill(&self) -> bool {
+        self.has_mod(FREE_REFILL_MOD)
+    }
+
     pub fn is_wielding(&self, bat_name: &str) -> bool {
         self.bat.as_ref().map_or(false, |n| n == bat_name)
     }
@@ -257,6 +398,40 @@ impl Player {
         self.adjust_defense(lower, upper);
     }
 
+    // Undoes adjust_attributes by copying every attribute (and the ratings derived from them) back
+    // from an earlier version of this player
+    pub fn restore_attributes(&mut self, old: &Player) {
+        self.buoyancy = old.buoyancy;
+        self.divinity = old.divinity;
+        self.martyrdom = old.martyrdom;
+        self.moxie = old.moxie;
+        self.musclitude = old.musclitude;
+        self.patheticism = old.patheticism;
+        self.thwackability = old.thwackability;
+        self.tragicness = old.tragicness;
+        self.coldness = old.coldness;
+        self.overpowerment = old.overpowerment;
+        self.ruthlessness = old.ruthlessness;
+        self.shakespearianism = old.shakespearianism;
+        self.suppression = old.suppression;
+        self.unthwackability = old.unthwackability;
+        self.base_thirst = old.base_thirst;
+        self.continuation = old.continuation;
+        self.ground_friction = old.ground_friction;
+        self.indulgence = old.indulgence;
+        self.laserlikeness = old.laserlikeness;
+        self.anticapitalism = old.anticapitalism;
+        self.chasiness = old.chasiness;
+        self.omniscience = old.omniscience;
+        self.tenaciousness = old.tenaciousness;
+        self.watchfulness = old.watchfulness;
+
+        self.hitting_rating = old.hitting_rating.clone();
+        self.pitching_rating = old.pitching_rating.clone();
+        self.baserunning_rating = old.baserunning_rating.clone();
+        self.defense_rating = old.defense_rating.clone();
+    }
+
     fn adjust_batting(&mut self, lower: f32, upper: f32) {
         self.buoyancy.add_range(lower, upper);
         self.divinity.add_range(lower, upper);
@@ -300,4 +475,103 @@ impl Player {
 
         *self.defense_rating.as_mut().expect("Everyone but Phantom Sixpack has this") = MaybeKnown::Unknown;
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // An unremarkable player with every attribute at 0.5. Tests change whatever they need from here.
+    pub(crate) fn test_player(league_team_id: Option<Uuid>) -> Player {
+        let attr = || Rerollable::from_raw(0.5);
+        Player {
+            id: Uuid::new_v4(),
+            name: "Test Player".to_string(),
+            ritual: None,
+            fate: None,
+            soul: 5,
+            blood: None,
+            coffee: None,
+            peanut_allergy: Some(false),
+            bat: None,
+            armor: None,
+            league_team_id,
+            tournament_team_id: None,
+            deceased: Some(false),
+            evolution: None,
+            items: None,
+            state: None,
+            hit_streak: None,
+            consecutive_hits: None,
+            game_attr: Some(Vec::new()),
+            week_attr: Some(Vec::new()),
+            seas_attr: Some(Vec::new()),
+            item_attr: Some(Vec::new()),
+            perm_attr: Some(Vec::new()),
+            buoyancy: attr(),
+            cinnamon: Some(attr()),
+            coldness: attr(),
+            chasiness: attr(),
+            divinity: attr(),
+            martyrdom: attr(),
+            base_thirst: attr(),
+            indulgence: attr(),
+            musclitude: attr(),
+            tragicness: attr(),
+            omniscience: attr(),
+            patheticism: attr(),
+            suppression: attr(),
+            continuation: attr(),
+            ruthlessness: attr(),
+            watchfulness: attr(),
+            laserlikeness: attr(),
+            overpowerment: attr(),
+            tenaciousness: attr(),
+            thwackability: attr(),
+            anticapitalism: attr(),
+            ground_friction: attr(),
+            pressurization: attr(),
+            unthwackability: attr(),
+            shakespearianism: attr(),
+            moxie: attr(),
+            total_fingers: 10,
+            defense_rating: Some(MaybeKnown::Known(0.5)),
+            hitting_rating: Some(MaybeKnown::Known(0.5)),
+            pitching_rating: Some(MaybeKnown::Known(0.5)),
+            baserunning_rating: Some(MaybeKnown::Known(0.5)),
+            edensity: None,
+        }
+    }
+
+    #[test]
+    fn missed_out_shows_up_as_a_hit_streak_conflict() {
+        let mut player = test_player(None);
+        player.consecutive_hits = Some(HitStreak(1));
+        player.increment_consecutive_hits();
+        // The player actually made an out that never got applied, so their streak was reset
+        let mut observed = player.clone().raw_approximation();
+        observed.consecutive_hits = Some(0);
+
+        let conflicts = player.observe(&observed);
+        assert_eq!(conflicts.len(), 1, "{conflicts:?}");
+        assert!(conflicts[0].message().contains("A hit or an out was probably missed"), "{}", conflicts[0]);
+    }
+
+    #[test]
+    fn matching_hit_streak_has_no_conflict() {
+        let mut player = test_player(None);
+        player.consecutive_hits = Some(HitStreak(1));
+        player.increment_consecutive_hits();
+        let observed = player.clone().raw_approximation();
+        assert_eq!(observed.consecutive_hits, Some(2));
+
+        assert!(player.observe(&observed).is_empty());
+    }
+
+    #[test]
+    fn players_without_a_hit_streak_are_left_alone() {
+        let mut player = test_player(None);
+        player.increment_consecutive_hits();
+        player.reset_consecutive_hits();
+        assert_eq!(player.consecutive_hits, None);
+    }
+}
@@ -12,7 +12,8 @@ use crate::state::EntityType;
 #[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
 pub struct Season {
-    #[serde(rename = "__v")]
+    // Not every version has this, and the raw approximation should only have it if Chron did
+    #[serde(rename = "__v", skip_serializing_if = "Option::is_none")]
     pub version: Option<i32>,
 
     #[serde(alias = "_id")]
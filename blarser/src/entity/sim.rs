@@ -77,4 +77,53 @@ impl Entity for Sim {
     fn description(&self) -> String {
         "Sim".to_string()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use serde_json::json;
+    use super::*;
+
+    // The sim partway through the Latepostseason. Tests change whatever they need from here.
+    pub(crate) fn test_sim() -> Sim {
+        let date = "2021-03-01T15:31:00Z";
+        let raw: <Sim as PartialInformationCompare>::Raw = serde_json::from_value(json!({
+            "phase": 11,
+            "id": "thisidisstaticyo",
+            "day": 112,
+            "attr": [],
+            "menu": null,
+            "rules": "b1e2a1b4-3c0a-4a6c-9a57-1a4b1f3c2d11",
+            "state": {},
+            "league": "b1e2a1b4-3c0a-4a6c-9a57-1a4b1f3c2d12",
+            "season": 12,
+            "simEnd": null,
+            "eraColor": "#000000",
+            "eraTitle": "Test Era",
+            "playoffs": "b1e2a1b4-3c0a-4a6c-9a57-1a4b1f3c2d13",
+            "seasonId": "b1e2a1b4-3c0a-4a6c-9a57-1a4b1f3c2d14",
+            "simStart": null,
+            "agitations": 0,
+            "tournament": -1,
+            "godsDayDate": date,
+            "salutations": 0,
+            "subEraColor": "#000000",
+            "subEraTitle": "Test Sub Era",
+            "terminology": "b1e2a1b4-3c0a-4a6c-9a57-1a4b1f3c2d15",
+            "electionDate": date,
+            "endseasonDate": date,
+            "midseasonDate": date,
+            "nextPhaseTime": date,
+            "preseasonDate": date,
+            "earlseasonDate": date,
+            "earlsiestaDate": date,
+            "lateseasonDate": date,
+            "latesiestaDate": date,
+            "tournamentRound": -1,
+            "playOffRound": 3,
+            "earlpostseasonDate": date,
+            "latepostseasonDate": date,
+        })).expect("Test sim should deserialize");
+        Sim::from_raw(raw)
+    }
+}
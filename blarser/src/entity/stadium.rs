@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use partial_information::{MaybeKnown, PartialInformationCompare};
+use partial_information_derive::PartialInformationCompare;
+
+use crate::entity::{Entity, EntityRaw};
+use crate::state::EntityType;
+
+// Stadiums pick up new fields whenever a renovation needs somewhere to keep its state, so unlike
+// the other entities these don't deny unknown fields. Otherwise the first new field would stop
+// every stadium from parsing.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, PartialInformationCompare)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+pub struct StadiumState {
+    #[serde(rename = "air_balloons")] // override the rename_all = "camelCase"
+    pub air_balloons: Option<i32>,
+    #[serde(rename = "flood_balloons")] // override the rename_all = "camelCase"
+    pub flood_balloons: Option<i32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, PartialInformationCompare)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+pub struct Stadium {
+    pub id: Uuid,
+    pub name: String,
+    pub nickname: String,
+    pub team_id: Uuid,
+    pub model: Option<i32>,
+    pub mods: Vec<String>,
+    pub birds: i32,
+    pub state: StadiumState,
+    pub main_color: String,
+    pub secondary_color: String,
+    pub tertiary_color: String,
+
+    // Renovations change these by amounts that aren't in the Feed, so they have to be able to be
+    // unknown until the next observation
+    pub hype: MaybeKnown<f32>,
+    pub mysticism: MaybeKnown<f32>,
+    pub viscosity: MaybeKnown<f32>,
+    pub elongation: MaybeKnown<f32>,
+    pub filthiness: MaybeKnown<f32>,
+    pub obtuseness: MaybeKnown<f32>,
+    pub forwardness: MaybeKnown<f32>,
+    pub grandiosity: MaybeKnown<f32>,
+    pub ominousness: MaybeKnown<f32>,
+    pub fortification: MaybeKnown<f32>,
+    pub inconvenience: MaybeKnown<f32>,
+    pub luxuriousness: MaybeKnown<f32>,
+
+    pub weather: HashMap<String, i32>,
+    pub reno_cost: i32,
+    pub reno_log: HashMap<String, i32>,
+    pub reno_hand: Vec<String>,
+    pub reno_discard: Vec<String>,
+}
+
+impl Stadium {
+    pub fn attribute_mut(&mut self, name: &str) -> Option<&mut MaybeKnown<f32>> {
+        match name {
+            "hype" => { Some(&mut self.hype) }
+            "mysticism" => { Some(&mut self.mysticism) }
+            "viscosity" => { Some(&mut self.viscosity) }
+            "elongation" => { Some(&mut self.elongation) }
+            "filthiness" => { Some(&mut self.filthiness) }
+            "obtuseness" => { Some(&mut self.obtuseness) }
+            "forwardness" => { Some(&mut self.forwardness) }
+            "grandiosity" => { Some(&mut self.grandiosity) }
+            "ominousness" => { Some(&mut self.ominousness) }
+            "fortification" => { Some(&mut self.fortification) }
+            "inconvenience" => { Some(&mut self.inconvenience) }
+            "luxuriousness" => { Some(&mut self.luxuriousness) }
+            _ => { None }
+        }
+    }
+
+    pub fn attributes(&self) -> [(&'static str, MaybeKnown<f32>); 12] {
+        [
+            ("hype", self.hype),
+            ("mysticism", self.mysticism),
+            ("viscosity", self.viscosity),
+            ("elongation", self.elongation),
+            ("filthiness", self.filthiness),
+            ("obtuseness", self.obtuseness),
+            ("forwardness", self.forwardness),
+            ("grandiosity", self.grandiosity),
+            ("ominousness", self.ominousness),
+            ("fortification", self.fortification),
+            ("inconvenience", self.inconvenience),
+            ("luxuriousness", self.luxuriousness),
+        ]
+    }
+}
+
+impl Display for Stadium {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Stadium: {}", self.name)
+    }
+}
+
+impl EntityRaw for <Stadium as PartialInformationCompare>::Raw {
+    type Entity = Stadium;
+
+    fn name() -> &'static str { "stadium" }
+    fn id(&self) -> Uuid { self.id }
+}
+
+impl Entity for Stadium {
+    fn entity_type(&self) -> EntityType { EntityType::Stadium }
+    fn id(&self) -> Uuid { self.id }
+
+    fn description(&self) -> String {
+        format!("{} ({})", self.name, self.nickname)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use serde_json::json;
+    use partial_information::{MaybeKnown, PartialInformationCompare};
+    use super::Stadium;
+
+    // A stadium as Chron stores it
+    pub(crate) fn test_stadium_json() -> serde_json::Value {
+        json!({
+            "id": "2d9f5c7a-3b1e-4c1f-9a6d-8e7b5c4a3f21",
+            "name": "Test Stadium",
+            "nickname": "The Test",
+            "teamId": "b1e2a1b4-3c0a-4a6c-9a57-1a4b1f3c2d01",
+            "model": null,
+            "mods": ["BIG_BUCKET"],
+            "birds": 1000,
+            "state": { "air_balloons": 3, "flood_balloons": null },
+            "mainColor": "#000000",
+            "secondaryColor": "#ffffff",
+            "tertiaryColor": "#888888",
+            "hype": 0.1,
+            "mysticism": 0.5,
+            "viscosity": 0.5,
+            "elongation": 0.5,
+            "filthiness": 0.0,
+            "obtuseness": 0.5,
+            "forwardness": 0.5,
+            "grandiosity": 0.5,
+            "ominousness": 0.5,
+            "fortification": 0.5,
+            "inconvenience": 0.5,
+            "luxuriousness": 0.5,
+            "weather": { "1": 2 },
+            "renoCost": 300000,
+            "renoLog": { "bigBucket": 1 },
+            "renoHand": ["light_switch_toggle"],
+            "renoDiscard": [],
+        })
+    }
+
+    pub(crate) fn test_stadium() -> Stadium {
+        let raw: <Stadium as PartialInformationCompare>::Raw = serde_json::from_value(test_stadium_json())
+            .expect("Test stadium should deserialize");
+        Stadium::from_raw(raw)
+    }
+
+    #[test]
+    fn stadiums_with_new_fields_still_parse() {
+        let mut data = test_stadium_json();
+        data["someNewRenovationField"] = json!(12);
+        data["state"]["someNewState"] = json!(true);
+
+        let raw: <Stadium as PartialInformationCompare>::Raw = serde_json::from_value(data)
+            .expect("Unknown stadium fields should be ignored");
+        let stadium = Stadium::from_raw(raw);
+        assert_eq!(stadium.name, "Test Stadium");
+        assert_eq!(stadium.mysticism, MaybeKnown::Known(0.5));
+    }
+}
@@ -13,17 +13,18 @@ use crate::state::EntityType;
 #[serde(rename_all = "camelCase")]
 #[allow(dead_code)]
 pub struct Standings {
-    #[serde(rename = "__v")]
+    // Not every version has this, and the raw approximation should only have it if Chron did
+    #[serde(rename = "__v", skip_serializing_if = "Option::is_none")]
     pub version: Option<i32>,
 
     #[serde(alias = "_id")]
     pub id: Uuid,
 
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub runs: HashMap<Uuid, f32>,
     pub wins: HashMap<Uuid, i32>,
     pub losses: HashMap<Uuid, i32>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub games_played: HashMap<Uuid, i32>,
 }
 
@@ -44,10 +45,17 @@ impl Entity for Standings {
     fn entity_type(&self) -> EntityType { EntityType::Standings }
     fn id(&self) -> Uuid { self.id }
 
+    // Standings don't know their season. Where the season is available, use description_for_season.
     fn description(&self) -> String {
         format!("Standings {}", self.id)
     }
 }
+
+impl Standings {
+    pub fn description_for_season(&self, season_number: i32) -> String {
+        format!("Standings for season {season_number}")
+    }
+}
 // impl Standings {
 //     fn apply_feed_event(&mut self, event: &EventuallyEvent, state: &StateInterface) -> FeedEventChangeResult {
 //         match event.r#type {
@@ -0,0 +1,46 @@
+use std::fmt::{Display, Formatter};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use partial_information::PartialInformationCompare;
+use partial_information_derive::PartialInformationCompare;
+
+use crate::entity::{Entity, EntityRaw, Members};
+use crate::state::EntityType;
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize, PartialInformationCompare)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+#[allow(dead_code)]
+pub struct Subleague {
+    // Not every version has this, and the raw approximation should only have it if Chron did
+    #[serde(rename = "__v", skip_serializing_if = "Option::is_none")]
+    pub version: Option<i32>,
+
+    #[serde(alias = "_id")]
+    pub id: Uuid,
+
+    pub name: String,
+    pub divisions: Members,
+}
+
+impl Display for Subleague {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Subleague: {}", self.name)
+    }
+}
+
+impl EntityRaw for <Subleague as PartialInformationCompare>::Raw {
+    type Entity = Subleague;
+
+    fn name() -> &'static str { "subleague" }
+    fn id(&self) -> Uuid { self.id }
+}
+
+impl Entity for Subleague {
+    fn entity_type(&self) -> EntityType { EntityType::Subleague }
+    fn id(&self) -> Uuid { self.id }
+
+    fn description(&self) -> String {
+        format!("{} ({} divisions)", self.name, self.divisions.0.len())
+    }
+}
@@ -2,7 +2,7 @@ use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use partial_information::{PartialInformationCompare, Spurious};
+use partial_information::{MaybeKnown, PartialInformationCompare};
 use partial_information_derive::PartialInformationCompare;
 
 use crate::entity::{Entity, EntityRaw};
@@ -72,12 +72,13 @@ pub struct Team {
     pub main_color: String,
     pub shame_runs: f32,
     pub shorthand: String,
-    pub win_streak: Option<Spurious<i32>>,
+    pub win_streak: Option<i32>,
     pub division_id: Option<Uuid>,
     pub team_spirit: i32,
     pub subleague_id: Option<Uuid>,
     pub total_shames: i32,
-    pub rotation_slot: i32,
+    // Counted up by events, so it can become unknown without breaking them
+    pub rotation_slot: MaybeKnown<i32>,
     pub season_shames: i32,
     pub championships: i32,
     pub total_shamings: i32,
@@ -121,7 +122,159 @@ impl Team {
         self.lineup[count % self.lineup.len()]
     }
 
-    pub fn active_pitcher(&self, day: i32) -> Uuid {
-        self.rotation[day as usize % self.rotation.len()]
+    // None if the rotation is empty
+    pub fn active_pitcher(&self, day: i32) -> Option<Uuid> {
+        let num_pitchers = i32::try_from(self.rotation.len()).ok().filter(|&n| n > 0)?;
+        Some(self.rotation[day.rem_euclid(num_pitchers) as usize])
     }
-}
\ No newline at end of file
+
+    pub fn roster_slot_of(&self, player_id: Uuid) -> Option<RosterSlot> {
+        [RosterSlot::Lineup, RosterSlot::Rotation, RosterSlot::Shadows, RosterSlot::Bench, RosterSlot::Bullpen]
+            .into_iter()
+            .find(|&slot| self.roster(slot).contains(&player_id))
+    }
+
+    pub fn roster(&self, slot: RosterSlot) -> &[Uuid] {
+        match slot {
+            RosterSlot::Lineup => { &self.lineup }
+            RosterSlot::Rotation => { &self.rotation }
+            RosterSlot::Shadows => { self.shadows.as_deref().unwrap_or_default() }
+            RosterSlot::Bench => { self.bench.as_deref().unwrap_or_default() }
+            RosterSlot::Bullpen => { self.bullpen.as_deref().unwrap_or_default() }
+        }
+    }
+
+    pub fn roster_mut(&mut self, slot: RosterSlot) -> &mut Vec<Uuid> {
+        match slot {
+            RosterSlot::Lineup => { &mut self.lineup }
+            RosterSlot::Rotation => { &mut self.rotation }
+            // These only exist in some eras, so it's a bug to try to move players into them otherwise
+            RosterSlot::Shadows => { self.shadows.as_mut().expect("Team must have shadows to modify them") }
+            RosterSlot::Bench => { self.bench.as_mut().expect("Team must have a bench to modify it") }
+            RosterSlot::Bullpen => { self.bullpen.as_mut().expect("Team must have a bullpen to modify it") }
+        }
+    }
+
+    pub fn mods(&self, duration: ModDuration) -> &[String] {
+        match duration {
+            ModDuration::Permanent => { &self.perm_attr }
+            ModDuration::Season => { &self.seas_attr }
+            ModDuration::Weekly => { &self.week_attr }
+            ModDuration::Game => { &self.game_attr }
+        }
+    }
+
+    pub fn has_mod(&self, mod_name: &str) -> bool {
+        [ModDuration::Permanent, ModDuration::Season, ModDuration::Weekly, ModDuration::Game].into_iter()
+            .any(|duration| self.mods(duration).iter().any(|n| n == mod_name))
+    }
+
+    pub fn is_in_party_time(&self) -> bool {
+        self.has_mod("PARTY_TIME")
+    }
+
+    pub fn mods_mut(&mut self, duration: ModDuration) -> &mut Vec<String> {
+        match duration {
+            ModDuration::Permanent => { &mut self.perm_attr }
+            ModDuration::Season => { &mut self.seas_attr }
+            ModDuration::Weekly => { &mut self.week_attr }
+            ModDuration::Game => { &mut self.game_attr }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RosterSlot {
+    Lineup,
+    Rotation,
+    Shadows,
+    Bench,
+    Bullpen,
+}
+
+// Matches the numbering the feed uses for mod types
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ModDuration {
+    Permanent,
+    Season,
+    Weekly,
+    Game,
+}
+
+impl ModDuration {
+    // The name of the list these mods are stored in, on both players and teams
+    pub fn attr_name(&self) -> &'static str {
+        match self {
+            ModDuration::Permanent => { "permAttr" }
+            ModDuration::Season => { "seasAttr" }
+            ModDuration::Weekly => { "weekAttr" }
+            ModDuration::Game => { "gameAttr" }
+        }
+    }
+}
+
+impl TryFrom<i32> for ModDuration {
+    type Error = i32;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ModDuration::Permanent),
+            1 => Ok(ModDuration::Season),
+            2 => Ok(ModDuration::Weekly),
+            3 => Ok(ModDuration::Game),
+            other => Err(other),
+        }
+    }
+}
+#[cfg(test)]
+pub(crate) mod tests {
+    use uuid::Uuid;
+    use super::Team;
+
+    // A team with nothing going on besides the given roster
+    pub(crate) fn test_team(lineup: Vec<Uuid>, rotation: Vec<Uuid>) -> Team {
+        Team {
+            id: Uuid::new_v4(),
+            card: None,
+            emoji: String::new(),
+            level: None,
+            state: None,
+            lineup,
+            slogan: String::new(),
+            shadows: Some(Vec::new()),
+            bench: None,
+            bullpen: None,
+            stadium: None,
+            deceased: None,
+            full_name: "Test Team".to_string(),
+            game_attr: Vec::new(),
+            league_id: None,
+            location: "Test".to_string(),
+            nickname: "Team".to_string(),
+            perm_attr: Vec::new(),
+            rotation,
+            seas_attr: Vec::new(),
+            week_attr: Vec::new(),
+            evolution: None,
+            main_color: "#000000".to_string(),
+            shame_runs: 0.0,
+            shorthand: "TEST".to_string(),
+            win_streak: None,
+            division_id: None,
+            team_spirit: 0,
+            subleague_id: None,
+            total_shames: 0,
+            rotation_slot: MaybeKnown::Known(0),
+            season_shames: 0,
+            championships: 0,
+            total_shamings: 0,
+            season_shamings: 0,
+            secondary_color: "#ffffff".to_string(),
+            tournament_wins: None,
+            underchampionships: None,
+            edensity: None,
+            evelocity: None,
+            imposition: None,
+        }
+    }
+}
@@ -32,7 +32,9 @@ impl Event for Strike {
 
             self.game_update.forward(game);
 
-            game.at_bat_strikes += 1;
+            let Some(mut count) = game.at_bat_count() else { return entity; };
+            count.add_strike(false);
+            game.set_at_bat_count(count);
         }
         entity
     }
@@ -71,7 +73,9 @@ impl Event for Ball {
 
             self.game_update.forward(game);
 
-            game.at_bat_balls += 1;
+            let Some(mut count) = game.at_bat_count() else { return entity; };
+            count.add_ball();
+            game.set_at_bat_count(count);
         }
         entity
     }
@@ -110,11 +114,9 @@ impl Event for FoulBall {
 
             self.game_update.forward(game);
 
-            let strikes_to_strike_out = game.team_at_bat().strikes
-                .expect("{home/away}Strikes must be set during FoulBall event");
-            if game.at_bat_strikes + 1 < strikes_to_strike_out {
-                game.at_bat_strikes += 1;
-            }
+            let Some(mut count) = game.at_bat_count() else { return entity; };
+            count.add_strike(true);
+            game.set_at_bat_count(count);
         }
         entity
     }
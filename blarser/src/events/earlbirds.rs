@@ -0,0 +1,121 @@
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::entity::ModDuration;
+use crate::events::{AnyEffect, AnyEvent, Event, TeamModAddedEffect, TeamModRemovedEffect};
+use crate::ingest::StateGraph;
+use crate::state::EntityType;
+
+pub const EARLBIRDS_MOD: &str = "EARLBIRDS";
+
+// Earlbirds is only active for part of the season. When the end of that window is known as soon as
+// it starts, the removal is scheduled as a successor instead of waiting for something to notice.
+// Other modifiers that switch on and off during a season can follow the same pattern. The Feed
+// announces the removal itself, so events from the Feed don't know (or need) the end time.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EarlbirdsAdded {
+    time: DateTime<Utc>,
+    team_id: Uuid,
+    ends_at: Option<DateTime<Utc>>,
+}
+
+impl EarlbirdsAdded {
+    pub fn new(time: DateTime<Utc>, team_id: Uuid, ends_at: Option<DateTime<Utc>>) -> Self {
+        EarlbirdsAdded { time, team_id, ends_at }
+    }
+}
+
+impl Event for EarlbirdsAdded {
+    fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    fn generate_successors(&self, _: &StateGraph) -> Vec<AnyEvent> {
+        match self.ends_at {
+            Some(ends_at) if ends_at > self.time => {
+                vec![EarlbirdsRemoved::new(ends_at, self.team_id).into()]
+            }
+            Some(ends_at) => {
+                // Scheduling the removal in the past would put it out of order with everything else
+                warn!("Earlbirds for {} ends at {ends_at}, which isn't after it starts at {}. Not scheduling its removal.",
+                      self.team_id, self.time);
+                Vec::new()
+            }
+            None => { Vec::new() }
+        }
+    }
+
+    fn into_effects(self, _: &StateGraph) -> Vec<AnyEffect> {
+        vec![TeamModAddedEffect::new(self.team_id, EARLBIRDS_MOD.to_string(), ModDuration::Season).into()]
+    }
+
+    fn targets(&self) -> Vec<(EntityType, Uuid)> {
+        vec![(EntityType::Team, self.team_id)]
+    }
+}
+
+impl Display for EarlbirdsAdded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EarlbirdsAdded for {} at {}", self.team_id, self.time)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EarlbirdsRemoved {
+    time: DateTime<Utc>,
+    team_id: Uuid,
+}
+
+impl EarlbirdsRemoved {
+    pub fn new(time: DateTime<Utc>, team_id: Uuid) -> Self {
+        EarlbirdsRemoved { time, team_id }
+    }
+}
+
+impl Event for EarlbirdsRemoved {
+    fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    fn into_effects(self, _: &StateGraph) -> Vec<AnyEffect> {
+        vec![TeamModRemovedEffect::new(self.team_id, EARLBIRDS_MOD.to_string(), ModDuration::Season).into()]
+    }
+
+    fn targets(&self) -> Vec<(EntityType, Uuid)> {
+        vec![(EntityType::Team, self.team_id)]
+    }
+}
+
+impl Display for EarlbirdsRemoved {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EarlbirdsRemoved for {} at {}", self.team_id, self.time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+    use super::EarlbirdsAdded;
+    use crate::events::{AnyEvent, Event};
+    use crate::ingest::StateGraph;
+
+    #[test]
+    fn removal_is_only_scheduled_after_the_start() {
+        let state = StateGraph::new();
+        let start = Utc::now();
+        let team_id = Uuid::new_v4();
+
+        let successors = EarlbirdsAdded::new(start, team_id, Some(start + Duration::days(1)))
+            .generate_successors(&state);
+        assert_eq!(successors.len(), 1);
+        assert!(matches!(successors[0], AnyEvent::EarlbirdsRemoved(_)));
+        assert_eq!(successors[0].time(), start + Duration::days(1));
+
+        assert!(EarlbirdsAdded::new(start, team_id, Some(start)).generate_successors(&state).is_empty());
+        assert!(EarlbirdsAdded::new(start, team_id, None).generate_successors(&state).is_empty());
+    }
+}
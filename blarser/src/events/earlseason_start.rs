@@ -44,6 +44,10 @@ impl Event for EarlseasonStart {
             EarlseasonStartEffect.into(),
         ]
     }
+
+    fn targets(&self) -> Vec<(EntityType, Uuid)> {
+        vec![(EntityType::Sim, Uuid::nil())]
+    }
 }
 
 impl Display for EarlseasonStart {
@@ -67,7 +71,7 @@ impl Effect for EarlseasonStartEffect {
     }
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct EarlseasonStartEffectVariant {
     next_phase_ns: MaybeKnown<u32>,
     gods_day_ns: MaybeKnown<u32>,
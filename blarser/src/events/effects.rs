@@ -5,23 +5,25 @@ use as_any::AsAny;
 use derive_more::{From, TryInto};
 use fed::FreeRefill;
 use itertools::zip_eq;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use partial_information::MaybeKnown;
 use partial_information_derive::PartialInformationCompare;
 use crate::entity::{AnyEntity, Entity, Game};
 use crate::events::event_util::{get_displayed_mod_excluding, PITCHER_MOD_PRECEDENCE, RUNNER_MOD_PRECEDENCE};
+use crate::events::FREE_REFILL_MOD;
 use crate::ingest::StateGraph;
 use crate::polymorphic_enum::polymorphic_enum;
 use crate::state::EntityType;
 
 pub trait Extrapolated: Debug + AsAny {}
 
-#[derive(Default, Debug, Clone, PartialInformationCompare)]
+#[derive(Default, Debug, Clone, Serialize, PartialInformationCompare)]
 pub struct NullExtrapolated {}
 
 impl Extrapolated for NullExtrapolated {}
 
-#[derive(Default, Debug, Clone, PartialInformationCompare)]
+#[derive(Default, Debug, Clone, Serialize, PartialInformationCompare)]
 pub struct EarlseasonStartSubsecondsExtrapolated {
     pub(crate) gods_day_ns: MaybeKnown<u32>,
     pub(crate) next_phase_ns: MaybeKnown<u32>,
@@ -29,7 +31,7 @@ pub struct EarlseasonStartSubsecondsExtrapolated {
 
 impl Extrapolated for EarlseasonStartSubsecondsExtrapolated {}
 
-#[derive(Debug, Clone, PartialInformationCompare)]
+#[derive(Debug, Clone, Serialize, PartialInformationCompare)]
 pub struct GamePlayerExtrapolated {
     pub(crate) player_id: Uuid,
     pub(crate) player_mod: String,
@@ -43,7 +45,7 @@ impl GamePlayerExtrapolated {
 
 impl Extrapolated for GamePlayerExtrapolated {}
 
-#[derive(Debug, Clone, PartialInformationCompare)]
+#[derive(Debug, Clone, Serialize, PartialInformationCompare)]
 pub struct HitExtrapolated {
     pub(crate) runner: GamePlayerExtrapolated,
     pub(crate) advancements: AdvancementExtrapolated,
@@ -52,7 +54,7 @@ pub struct HitExtrapolated {
 
 impl Extrapolated for HitExtrapolated {}
 
-#[derive(Debug, Clone, PartialInformationCompare)]
+#[derive(Debug, Clone, Serialize, PartialInformationCompare)]
 pub struct DisplayedModChangeExtrapolated {
     pub(crate) new_pitcher_mod: Option<String>,
     pub(crate) new_runner_mods: HashMap<Uuid, Option<String>>,
@@ -76,7 +78,7 @@ impl DisplayedModChangeExtrapolated {
 
         fn displayed_mod(state: &StateGraph, refills: &[FreeRefill], player_id: Uuid, mods_to_display: &[&str]) -> Option<String> {
             if refills.iter().any(|refill| refill.player_id == player_id) {
-                Some(get_displayed_mod_excluding(state, player_id, &["COFFEE_RALLY"], mods_to_display))
+                Some(get_displayed_mod_excluding(state, player_id, &[FREE_REFILL_MOD], mods_to_display))
             } else {
                 None
             }
@@ -128,14 +130,14 @@ impl DisplayedModChangeExtrapolated {
 
 impl Extrapolated for DisplayedModChangeExtrapolated {}
 
-#[derive(Default, Debug, Clone, PartialInformationCompare)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialInformationCompare)]
 pub struct PitcherExtrapolated {
     pub pitcher_id: MaybeKnown<Uuid>,
     pub pitcher_name: MaybeKnown<String>,
     pub pitcher_mod: MaybeKnown<String>,
 }
 
-#[derive(Default, Debug, Clone, PartialInformationCompare)]
+#[derive(Default, Debug, Clone, Serialize, PartialInformationCompare)]
 pub struct PitchersExtrapolated {
     pub away: PitcherExtrapolated,
     pub home: PitcherExtrapolated,
@@ -149,7 +151,7 @@ impl PitchersExtrapolated {
 
 impl Extrapolated for PitchersExtrapolated {}
 
-#[derive(Debug, Clone, PartialInformationCompare)]
+#[derive(Debug, Clone, Serialize, PartialInformationCompare)]
 pub struct OddsAndPitchersExtrapolated {
     pub away: PitcherExtrapolated,
     pub home: PitcherExtrapolated,
@@ -170,7 +172,7 @@ impl Default for OddsAndPitchersExtrapolated {
     }
 }
 
-#[derive(Debug, Clone, PartialInformationCompare)]
+#[derive(Debug, Clone, Serialize, PartialInformationCompare)]
 pub struct AdvancementExtrapolated {
     // This is a vec parallel to `baserunners`, `basesOccupied`, etc. Each element a MaybeUnknown
     // bool representing whether that player advanced (or, for hit events, whether they advanced an
@@ -210,6 +212,12 @@ pub trait Effect {
     fn entity_id(&self) -> Option<Uuid>;
 
     fn variant(&self) -> Self::Variant;
+
+    // Most effects have exactly one outcome. Ambiguous ones can override this to return every
+    // candidate, and each one becomes its own branch in the graph.
+    fn variants(&self) -> Vec<Self::Variant> {
+        vec![self.variant()]
+    }
 }
 
 polymorphic_enum! {
@@ -221,6 +229,44 @@ polymorphic_enum! {
         GameUpcoming(crate::events::GameUpcomingEffect),
         PlayBallForGame(crate::events::PlayBallGameEffect),
         PlayBallForTeam(crate::events::PlayBallTeamEffect),
+        FireproofIncineration(crate::events::FireproofIncinerationEffect),
+        SalmonSwim(crate::events::SalmonSwimEffect),
+        RenovationBuilt(crate::events::RenovationBuiltEffect),
+        LineupSorted(crate::events::LineupSortedEffect),
+        TeamWonInternetSeries(crate::events::TeamWonInternetSeriesEffect),
+        PostseasonEnd(crate::events::PostseasonEndEffect),
+        GameUpdate(crate::events::GameUpdateEffect),
+        Pitch(crate::events::PitchEffect),
+        Out(crate::events::OutEffect),
+        BatterReachedBase(crate::events::BatterReachedBaseEffect),
+        BatterUp(crate::events::BatterUpEffect),
+        PitcherChange(crate::events::PitcherChangeEffect),
+        FloodingSwept(crate::events::FloodingSweptEffect),
+        GameEnd(crate::events::GameEndEffect),
+        GameEndPhase(crate::events::GameEndPhaseEffect),
+        TopInningEnd(crate::events::TopInningEndEffect),
+        SnowflakesForGame(crate::events::SnowflakesGameEffect),
+        PlayerModsAdded(crate::events::PlayerModsAddedEffect),
+        PlayerModsRemoved(crate::events::PlayerModsRemovedEffect),
+        CoffeeBean(crate::events::CoffeeBeanEffect),
+        AmbushedByCrows(crate::events::AmbushedByCrowsEffect),
+        HomeRun(crate::events::HomeRunEffect),
+        StolenBase(crate::events::StolenBaseEffect),
+        CaughtStealing(crate::events::CaughtStealingEffect),
+        TeamModAdded(crate::events::TeamModAddedEffect),
+        TeamModRemoved(crate::events::TeamModRemovedEffect),
+        TeamWinStreak(crate::events::TeamWinStreakEffect),
+        PlayerAttributesAdjusted(crate::events::PlayerAttributesAdjustedEffect),
+        PartyBoost(crate::events::PartyBoostEffect),
+        PlayerLeftTeam(crate::events::PlayerLeftTeamEffect),
+        PlayerJoinedTeam(crate::events::PlayerJoinedTeamEffect),
+        PlayerRejoinedTeam(crate::events::PlayerRejoinedTeamEffect),
+        PlayerTeamChanged(crate::events::PlayerTeamChangedEffect),
+        PlayerHit(crate::events::PlayerHitEffect),
+        PlayerReplaced(crate::events::PlayerReplacedEffect),
+        PlayerIncinerated(crate::events::PlayerIncineratedEffect),
+        SnowflakesForTeam(crate::events::SnowflakesTeamEffect),
+        SnowflakesForPlayer(crate::events::SnowflakesPlayerEffect),
     }
 }
 
@@ -237,6 +283,76 @@ impl AnyEffect {
     pub fn variant(&self) -> AnyEffectVariant {
         with_effect!(self, |e| { e.variant().into() })
     }
+
+    pub fn variants(&self) -> Vec<AnyEffectVariant> {
+        with_effect!(self, |e| { e.variants().into_iter().map(|v| v.into()).collect() })
+    }
+
+    // Where this kind of effect goes when one event produces several. Broad effects go before
+    // narrow ones, so the sim and game are updated before the teams and players that depend on
+    // them (e.g. standings are computed from a game that has already ended). When players move
+    // between teams, the team they leave goes before the team they join.
+    pub fn application_order(&self) -> u8 {
+        match self {
+            AnyEffect::EarlseasonStart(_) => { 0 }
+            AnyEffect::PostseasonEnd(_) => { 1 }
+            AnyEffect::LetsGo(_) => { 10 }
+            AnyEffect::GameUpcoming(_) => { 11 }
+            AnyEffect::PlayBallForGame(_) => { 12 }
+            AnyEffect::FireproofIncineration(_) => { 13 }
+            AnyEffect::SalmonSwim(_) => { 14 }
+            AnyEffect::AmbushedByCrows(_) => { 15 }
+            AnyEffect::GameUpdate(_) => { 16 }
+            AnyEffect::HomeRun(_) => { 17 }
+            AnyEffect::StolenBase(_) => { 18 }
+            AnyEffect::CaughtStealing(_) => { 19 }
+            AnyEffect::GameEnd(_) => { 20 }
+            AnyEffect::GameEndPhase(_) => { 21 }
+            AnyEffect::TopInningEnd(_) => { 22 }
+            AnyEffect::SnowflakesForGame(_) => { 23 }
+            AnyEffect::BatterUp(_) => { 24 }
+            AnyEffect::RenovationBuilt(_) => { 25 }
+            AnyEffect::PitcherChange(_) => { 26 }
+            AnyEffect::FloodingSwept(_) => { 27 }
+            AnyEffect::Pitch(_) => { 28 }
+            AnyEffect::Out(_) => { 29 }
+            AnyEffect::BatterReachedBase(_) => { 30 }
+            AnyEffect::PlayBallForTeam(_) => { 40 }
+            AnyEffect::TeamWonInternetSeries(_) => { 41 }
+            AnyEffect::TeamModRemoved(_) => { 42 }
+            AnyEffect::TeamModAdded(_) => { 43 }
+            AnyEffect::TeamWinStreak(_) => { 44 }
+            AnyEffect::PlayerLeftTeam(_) => { 45 }
+            AnyEffect::PlayerJoinedTeam(_) => { 46 }
+            AnyEffect::PlayerRejoinedTeam(_) => { 47 }
+            AnyEffect::LineupSorted(_) => { 48 }
+            AnyEffect::SnowflakesForTeam(_) => { 49 }
+            AnyEffect::PlayerReplaced(_) => { 50 }
+            AnyEffect::PlayerTeamChanged(_) => { 51 }
+            AnyEffect::PlayerModsRemoved(_) => { 52 }
+            AnyEffect::PlayerModsAdded(_) => { 53 }
+            AnyEffect::CoffeeBean(_) => { 54 }
+            AnyEffect::PlayerAttributesAdjusted(_) => { 55 }
+            AnyEffect::PlayerHit(_) => { 56 }
+            AnyEffect::SnowflakesForPlayer(_) => { 57 }
+            AnyEffect::PlayerIncinerated(_) => { 58 }
+            AnyEffect::PartyBoost(_) => { 59 }
+        }
+    }
+}
+
+// Puts an event's effects in the order they should be applied, regardless of the order the event
+// built them in. Only the order between different entities is changed. Each entity goes where its
+// earliest effect's application_order puts it, ties are broken by entity id, and effects on the
+// same entity stay in the order the event built them, since the event knows how they depend on
+// each other (e.g. a mod that's removed and then added back).
+pub fn sort_effects(effects: &mut [AnyEffect]) {
+    let mut entity_orders = HashMap::new();
+    for effect in effects.iter() {
+        let order = entity_orders.entry((effect.entity_type(), effect.entity_id())).or_insert(u8::MAX);
+        *order = (*order).min(effect.application_order());
+    }
+    effects.sort_by_key(|effect| (entity_orders[&(effect.entity_type(), effect.entity_id())], effect.entity_id()));
 }
 
 impl Display for AnyEffect {
@@ -254,7 +370,7 @@ pub trait EffectVariant {
 }
 
 polymorphic_enum! {
-    #[derive(From, TryInto, Clone, Debug)]
+    #[derive(From, TryInto, Clone, Debug, Serialize, Deserialize)]
     #[try_into(owned, ref, ref_mut)]
     pub AnyEffectVariant: with_effect_variant {
         EarlseasonStart(crate::events::EarlseasonStartEffectVariant),
@@ -262,6 +378,44 @@ polymorphic_enum! {
         GameUpcoming(crate::events::GameUpcomingEffectVariant),
         PlayBallForGame(crate::events::PlayBallGameEffectVariant),
         PlayBallForTeam(crate::events::PlayBallTeamEffectVariant),
+        FireproofIncineration(crate::events::FireproofIncinerationEffectVariant),
+        SalmonSwim(crate::events::SalmonSwimEffectVariant),
+        RenovationBuilt(crate::events::RenovationBuiltEffectVariant),
+        LineupSorted(crate::events::LineupSortedEffectVariant),
+        TeamWonInternetSeries(crate::events::TeamWonInternetSeriesEffectVariant),
+        PostseasonEnd(crate::events::PostseasonEndEffectVariant),
+        GameUpdate(crate::events::GameUpdateEffectVariant),
+        Pitch(crate::events::PitchEffectVariant),
+        Out(crate::events::OutEffectVariant),
+        BatterReachedBase(crate::events::BatterReachedBaseEffectVariant),
+        BatterUp(crate::events::BatterUpEffectVariant),
+        PitcherChange(crate::events::PitcherChangeEffectVariant),
+        FloodingSwept(crate::events::FloodingSweptEffectVariant),
+        GameEnd(crate::events::GameEndEffectVariant),
+        GameEndPhase(crate::events::GameEndPhaseEffectVariant),
+        TopInningEnd(crate::events::TopInningEndEffectVariant),
+        SnowflakesForGame(crate::events::SnowflakesGameEffectVariant),
+        PlayerModsAdded(crate::events::PlayerModsAddedEffectVariant),
+        PlayerModsRemoved(crate::events::PlayerModsRemovedEffectVariant),
+        CoffeeBean(crate::events::CoffeeBeanEffectVariant),
+        AmbushedByCrows(crate::events::AmbushedByCrowsEffectVariant),
+        HomeRun(crate::events::HomeRunEffectVariant),
+        StolenBase(crate::events::StolenBaseEffectVariant),
+        CaughtStealing(crate::events::CaughtStealingEffectVariant),
+        TeamModAdded(crate::events::TeamModAddedEffectVariant),
+        TeamModRemoved(crate::events::TeamModRemovedEffectVariant),
+        TeamWinStreak(crate::events::TeamWinStreakEffectVariant),
+        PlayerAttributesAdjusted(crate::events::PlayerAttributesAdjustedEffectVariant),
+        PartyBoost(crate::events::PartyBoostEffectVariant),
+        PlayerLeftTeam(crate::events::PlayerLeftTeamEffectVariant),
+        PlayerJoinedTeam(crate::events::PlayerJoinedTeamEffectVariant),
+        PlayerRejoinedTeam(crate::events::PlayerRejoinedTeamEffectVariant),
+        PlayerTeamChanged(crate::events::PlayerTeamChangedEffectVariant),
+        PlayerHit(crate::events::PlayerHitEffectVariant),
+        PlayerReplaced(crate::events::PlayerReplacedEffectVariant),
+        PlayerIncinerated(crate::events::PlayerIncineratedEffectVariant),
+        SnowflakesForTeam(crate::events::SnowflakesTeamEffectVariant),
+        SnowflakesForPlayer(crate::events::SnowflakesPlayerEffectVariant),
     }
 }
 
@@ -272,4 +426,37 @@ impl Display for AnyEffectVariant {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         with_effect_variant!(self, |e| { e.fmt(f) })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+    use crate::entity::ModDuration;
+    use crate::events::{PlayerTeamChangedEffect, TeamModAddedEffect, TeamModRemovedEffect};
+    use super::{sort_effects, AnyEffect};
+
+    #[test]
+    fn effects_on_the_same_entity_keep_their_order() {
+        let team_id = Uuid::new_v4();
+        // Removing a mod sorts before adding one, but not within a single team
+        let mut effects: Vec<AnyEffect> = vec![
+            TeamModAddedEffect::new(team_id, "TEST_MOD".to_string(), ModDuration::Game).into(),
+            TeamModRemovedEffect::new(team_id, "TEST_MOD".to_string(), ModDuration::Game).into(),
+        ];
+        sort_effects(&mut effects);
+        assert!(matches!(effects[..], [AnyEffect::TeamModAdded(_), AnyEffect::TeamModRemoved(_)]));
+    }
+
+    #[test]
+    fn effects_on_different_entities_are_sorted() {
+        let (team_id, player_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut effects: Vec<AnyEffect> = vec![
+            PlayerTeamChangedEffect::new(player_id, team_id).into(),
+            TeamModAddedEffect::new(team_id, "TEST_MOD".to_string(), ModDuration::Game).into(),
+            TeamModRemovedEffect::new(team_id, "OTHER_MOD".to_string(), ModDuration::Game).into(),
+        ];
+        sort_effects(&mut effects);
+        // The team's effects go first as a block, still in the order they were built
+        assert!(matches!(effects[..], [AnyEffect::TeamModAdded(_), AnyEffect::TeamModRemoved(_), AnyEffect::PlayerTeamChanged(_)]));
+    }
+}
@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+
+// These are the values of the `category` field on Feed events (and lastUpdateFull)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EventCategory {
+    Game,
+    Changes,
+    Special,
+    Outcomes,
+    Narrative,
+}
+
+impl TryFrom<i32> for EventCategory {
+    type Error = i32;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => { Self::Game }
+            1 => { Self::Changes }
+            2 => { Self::Special }
+            3 => { Self::Outcomes }
+            4 => { Self::Narrative }
+            other => { return Err(other); }
+        })
+    }
+}
+
+impl From<EventCategory> for i32 {
+    fn from(value: EventCategory) -> Self {
+        match value {
+            EventCategory::Game => { 0 }
+            EventCategory::Changes => { 1 }
+            EventCategory::Special => { 2 }
+            EventCategory::Outcomes => { 3 }
+            EventCategory::Narrative => { 4 }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use chrono::Utc;
+    use uuid::Uuid;
+    use crate::events::{AnyEvent, GameOver, GameUpcoming};
+    use super::EventCategory;
+
+    #[test]
+    fn feed_categories_group_by_kind() {
+        let raw_categories = [0, 2, 1, 0, 4, 3, 2, 0, 9];
+        let mut groups: HashMap<EventCategory, usize> = HashMap::new();
+        let mut unknown = Vec::new();
+        for raw in raw_categories {
+            match EventCategory::try_from(raw) {
+                Ok(category) => { *groups.entry(category).or_default() += 1 }
+                Err(raw) => { unknown.push(raw) }
+            }
+        }
+
+        assert_eq!(groups, HashMap::from([
+            (EventCategory::Game, 3),
+            (EventCategory::Changes, 1),
+            (EventCategory::Special, 2),
+            (EventCategory::Outcomes, 1),
+            (EventCategory::Narrative, 1),
+        ]));
+        assert_eq!(unknown, vec![9]);
+    }
+
+    #[test]
+    fn categories_round_trip_through_their_feed_values() {
+        for raw in 0..5 {
+            let category = EventCategory::try_from(raw).expect("Values 0 through 4 should all be categories");
+            assert_eq!(i32::from(category), raw);
+        }
+    }
+
+    #[test]
+    fn blarsers_own_events_have_no_category() {
+        let events: Vec<AnyEvent> = vec![
+            GameOver::new(Utc::now(), Uuid::new_v4()).into(),
+            GameUpcoming::new(Utc::now(), Uuid::new_v4()).into(),
+        ];
+        assert!(events.iter().all(|event| event.category().is_none()));
+    }
+}
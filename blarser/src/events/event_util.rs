@@ -1,16 +1,18 @@
+use itertools::Itertools;
 use uuid::Uuid;
-use crate::events::Effect;
+use crate::entity::Player;
+use crate::events::{Effect, FREE_REFILL_MOD};
 use crate::events::effects::GamePlayerExtrapolated;
 use crate::ingest::StateGraph;
 use crate::state::EntityType;
 
 // Theses lists are very much in flux
 pub const BATTER_MOD_PRECEDENCE: [&'static str; 1] = [
-    "COFFEE_RALLY",
+    FREE_REFILL_MOD,
 ];
 pub const RUNNER_MOD_PRECEDENCE: [&'static str; 2] = [
     "BLASERUNNING",
-    "COFFEE_RALLY",
+    FREE_REFILL_MOD,
 ];
 pub const PITCHER_MOD_PRECEDENCE: [&'static str; 0] = [];
 
@@ -19,18 +21,29 @@ pub fn get_displayed_mod(state: &StateGraph, player_id: Uuid, mods_to_display: &
 }
 
 pub fn get_displayed_mod_excluding(state: &StateGraph, player_id: Uuid, mods_to_exclude: &[&str], mods_to_display: &[&str]) -> String {
-    state.query_player_unique(player_id, |player| {
-        for &mod_name in mods_to_display {
-            if mods_to_exclude.iter().any(|&n| n == mod_name) { continue }
-            
-            if player.has_mod(mod_name) ||
-                // Special logic for legacy items, I guess
-                (mod_name == "BLASERUNNING" && player.is_wielding("AN_ACTUAL_AIRPLANE")) {
-                return mod_name.to_string();
-            }
+    state.query_player_unique(player_id, |player| displayed_mod(player, mods_to_exclude, mods_to_display))
+}
+
+// Like get_displayed_mod, but with one entry per distinct possibility instead of panicking when
+// the player is ambiguous. Empty if the player doesn't exist.
+pub fn get_displayed_mod_candidates(state: &StateGraph, player_id: Uuid, mods_to_display: &[&str]) -> Vec<String> {
+    state.query_player_all(player_id, |player| displayed_mod(player, &[], mods_to_display))
+        .into_iter()
+        .unique()
+        .collect()
+}
+
+fn displayed_mod(player: &Player, mods_to_exclude: &[&str], mods_to_display: &[&str]) -> String {
+    for &mod_name in mods_to_display {
+        if mods_to_exclude.iter().any(|&n| n == mod_name) { continue }
+
+        if player.has_mod(mod_name) ||
+            // Special logic for legacy items, I guess
+            (mod_name == "BLASERUNNING" && player.is_wielding("AN_ACTUAL_AIRPLANE")) {
+            return mod_name.to_string();
         }
-        String::new()
-    })
+    }
+    String::new()
 }
 
 // fn game_effect_with_modified_batter(game_id: Uuid, state: &StateGraph, add: isize) -> Effect {
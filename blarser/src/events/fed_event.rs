@@ -1,15 +1,22 @@
+use std::cmp::{max, min};
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::sync::Arc;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use enum_flatten::EnumFlatten;
-use fed::{FedEvent as BaseFedEvent, FedEventData, FedEventFlat, FedEventLetsGo, FedEventPlayBall};
+use itertools::Itertools;
+use log::warn;
+use fed::{ElectionEffect, FedEvent as BaseFedEvent, FedEventAmbushedByCrows, FedEventBatterUp, FedEventCaughtStealing, FedEventData, FedEventEcho, FedEventEchoIntoStatic, FedEventEchoReceiver, FedEventPsychoacoustics, FedEventFireproofIncineration, FedEventFlat, FedEventGameEnd, FedEventHomeRun, FedEventLetsGo, FedEventLineupSorted, FedEventPlayBall, FedEventRenovationBuilt, FedEventSalmonSwim, FedEventStolenBase, FedEventTeamWonInternetSeries};
 use uuid::Uuid;
-use partial_information::MaybeKnown;
-use crate::entity::{Game, Team};
-use crate::events::{AnyEffect, AnyEvent, Effect, EffectVariant, Event};
-use crate::events::EarlseasonStart;
+use partial_information::{MaybeKnown, Nullable, PartialInformationCompare, RangeInclusive};
+use crate::entity::{AnyEntity, Base, Game, ModDuration, Player, RosterSlot, Sim, Stadium, Team};
+use crate::events::{AnyEffect, AnyEvent, Effect, EffectVariant, Event, EventCategory, PreconditionFailure};
+use crate::events::{EarlbirdsAdded, EarlbirdsRemoved, EarlseasonStart, Feedback, FloodingSwept, Snowflakes};
+use crate::events::game_over::game_end_successors;
+use crate::events::top_inning_end::third_out_successors;
+use crate::events::pitcher_change::missing_pitcher_predecessor;
+use crate::events::event_util::{get_displayed_mod, get_displayed_mod_candidates, BATTER_MOD_PRECEDENCE, RUNNER_MOD_PRECEDENCE};
 use crate::ingest::StateGraph;
 use crate::state::EntityType;
 
@@ -37,13 +44,107 @@ impl Event for FedEvent {
                     None
                 }
             }
+            FedEventData::HalfInning { game, .. } => {
+                missing_pitcher_predecessor(self.0.created, game.game_id, state)
+            }
             _ => { None }
         }
     }
 
-    fn into_effects(self, _: &StateGraph) -> Vec<AnyEffect> {
+    fn precondition_failures(&self, state: &StateGraph) -> Vec<PreconditionFailure> {
+        match &self.0.data {
+            FedEventData::Party { player_id, .. } => {
+                party_eligibility_failures(*player_id, state)
+            }
+            FedEventData::Incineration { player_id, team_id, .. } => {
+                incineration_failures(*player_id, *team_id, state)
+            }
+            FedEventData::StolenBase { free_refill, .. } => {
+                free_refill_failures(free_refill.as_ref().map(core::slice::from_ref).unwrap_or_default(), state)
+            }
+            FedEventData::ModExpires { player_id, mods, mod_type, .. } => {
+                match ModDuration::try_from(*mod_type) {
+                    Ok(duration) => { expiring_mod_failures(*player_id, mods, duration, state) }
+                    Err(_) => { mod_type_failures(EntityType::Player, *player_id, *mod_type) }
+                }
+            }
+            FedEventData::Echo { receiver_id, mod_type, .. } | FedEventData::EchoReceiver { receiver_id, mod_type, .. } => {
+                mod_type_failures(EntityType::Player, *receiver_id, *mod_type)
+            }
+            FedEventData::EchoIntoStatic { player_id, mod_type, .. } => {
+                mod_type_failures(EntityType::Player, *player_id, *mod_type)
+            }
+            FedEventData::TarotReadingAddedMod { team_id, mod_type, .. } => {
+                mod_type_failures(EntityType::Team, *team_id, *mod_type)
+            }
+            FedEventData::Hit { game, num_bases, .. } => {
+                if Base::try_from(*num_bases).is_ok() { return Vec::new(); }
+                vec![PreconditionFailure {
+                    entity_type: EntityType::Game,
+                    entity_id: game.game_id,
+                    property: "basesOccupied".to_string(),
+                    message: format!("Hit event has invalid num_bases {num_bases}"),
+                    skips_event: true,
+                }]
+            }
+            FedEventData::Feedback { game, .. } => {
+                match Feedback::from_tags(self.0.created, &self.0.player_tags, &self.0.team_tags) {
+                    Some(feedback) => { feedback.precondition_failures(state) }
+                    None => {
+                        vec![PreconditionFailure {
+                            entity_type: EntityType::Game,
+                            entity_id: game.game_id,
+                            property: "lastUpdate".to_string(),
+                            message: "Feedback event should tag exactly two players and their two teams".to_string(),
+                            skips_event: true,
+                        }]
+                    }
+                }
+            }
+            FedEventData::Tidings { effects, .. } | FedEventData::WillReceived { effects, .. } |
+            FedEventData::BlessingWon { effects, .. } | FedEventData::DecreePassed { effects, .. } => {
+                election_failures(effects, state)
+            }
+            _ => { Vec::new() }
+        }
+    }
+
+    fn generate_successors(&self, state: &StateGraph) -> Vec<AnyEvent> {
+        match &self.0.data {
+            FedEventData::GameEnd { game, .. } => {
+                game_end_successors(self.0.created, game.game_id)
+            }
+            // Everything that makes exactly one out
+            FedEventData::AmbushedByCrows { game, .. } | FedEventData::CaughtStealing { game, .. } |
+            FedEventData::Strikeout { game, .. } | FedEventData::FlyOut { game, .. } |
+            FedEventData::GroundOut { game, .. } => {
+                third_out_successors(self.0.created, game.game_id, state)
+            }
+            _ => { Vec::new() }
+        }
+    }
+
+    fn new_entities(&self) -> Vec<AnyEntity> {
+        match &self.0.data {
+            FedEventData::PlayerHatched { player_id, team_id, .. } => {
+                vec![Player::new_from_event(*player_id, Some(*team_id)).into()]
+            }
+            FedEventData::PlayerJoinedILB { player_id, .. } => {
+                vec![Player::new_from_event(*player_id, None).into()]
+            }
+            FedEventData::Incineration { replacement_player_id, team_id, .. } => {
+                vec![Player::new_from_event(*replacement_player_id, Some(*team_id)).into()]
+            }
+            _ => { Vec::new() }
+        }
+    }
+
+    fn into_effects(self, state: &StateGraph) -> Vec<AnyEffect> {
         // Perhaps one day I will remove the clone requirement here but this is not that day
         let last_update = self.0.clone().last_update();
+        let player_tags = self.0.player_tags.clone();
+        let team_tags = self.0.team_tags.clone();
+        let created = self.0.created;
         // IDE keeps trying to use Iterator::flatten so I'm using UFCS to force it to get the right one
         match EnumFlatten::flatten(self.0) {
             FedEventFlat::BeingSpeech(_) => {
@@ -56,9 +157,278 @@ impl Event for FedEvent {
             FedEventFlat::PlayBall(event) => {
                 vec![PlayBallGameEffect::new(event, last_update).into()]
             }
-            _ => { todo!() }
+            FedEventFlat::FireproofIncineration(event) => {
+                // The player survives, so the only thing that changes is the game update
+                vec![FireproofIncinerationEffect::new(event, last_update).into()]
+            }
+            FedEventFlat::Incineration(event) => {
+                // The replacement is created from new_entities, so it
+                // only needs to be put in the victim's place on the team
+                vec![
+                    GameUpdateEffect::new(event.game.clone(), last_update).into(),
+                    PlayerIncineratedEffect::new(event.player_id).into(),
+                    PlayerReplacedEffect::new(event.team_id, event.player_id, event.replacement_player_id).into(),
+                ]
+            }
+            FedEventFlat::SalmonSwim(event) => {
+                vec![SalmonSwimEffect::new(event, last_update).into()]
+            }
+            FedEventFlat::RenovationBuilt(event) => {
+                vec![RenovationBuiltEffect::new(event).into()]
+            }
+            FedEventFlat::BatterUp(event) => {
+                vec![BatterUpEffect::new(event, last_update, state).into()]
+            }
+            FedEventFlat::LineupSorted(event) => {
+                vec![LineupSortedEffect::new(event, state).into()]
+            }
+            FedEventFlat::Echo(event) => {
+                echo_effects(&event.game, event.receiver_id, event.source_id, event.mod_type, last_update, state)
+            }
+            FedEventFlat::EchoReceiver(event) => {
+                echo_effects(&event.game, event.receiver_id, event.source_id, event.mod_type, last_update, state)
+            }
+            FedEventFlat::EchoIntoStatic(event) => {
+                let game_update: AnyEffect = GameUpdateEffect::new(event.game.clone(), last_update).into();
+                // precondition_failures already reported a bad mod type
+                let Ok(duration) = ModDuration::try_from(event.mod_type) else { return vec![game_update]; };
+                vec![
+                    game_update,
+                    PlayerModsRemovedEffect::new(event.player_id, event.mods.clone(), duration).into(),
+                ]
+            }
+            FedEventFlat::Psychoacoustics(event) => {
+                // This copies a mod from the opposing team for the rest of the game
+                vec![
+                    GameUpdateEffect::new(event.game.clone(), last_update).into(),
+                    TeamModAddedEffect::new(event.team_id, event.mod_name.clone(), ModDuration::Game).into(),
+                ]
+            }
+            FedEventFlat::AmbushedByCrows(event) => {
+                vec![AmbushedByCrowsEffect::new(event, last_update).into()]
+            }
+            FedEventFlat::BirdsCircle(event) => {
+                // The birds circle but nothing happens
+                vec![GameUpdateEffect::new(event.game, last_update).into()]
+            }
+            FedEventFlat::BirdsUnshell(event) => {
+                let mut effects: Vec<AnyEffect> = vec![
+                    GameUpdateEffect::new(event.game.clone(), last_update).into(),
+                    PlayerModsRemovedEffect::new(event.player_id, vec!["SHELLED".to_string()], ModDuration::Permanent).into(),
+                ];
+                // The player's own record is the only place that says which team they're going
+                // back to
+                match unshelled_player_team(event.player_id, state) {
+                    Some(team_id) => {
+                        effects.push(PlayerRejoinedTeamEffect::new(team_id, event.player_id).into());
+                    }
+                    None => {
+                        warn!("Couldn't tell which team unshelled player {} belongs to; not returning them to a roster", event.player_id);
+                    }
+                }
+                effects
+            }
+            FedEventFlat::TeamEnteredPartyTime(event) => {
+                // Party time lasts until the end of the season
+                vec![
+                    GameUpdateEffect::new(event.game.clone(), last_update).into(),
+                    TeamModAddedEffect::new(event.team_id, "PARTY_TIME".to_string(), ModDuration::Season).into(),
+                ]
+            }
+            FedEventFlat::Party(event) => {
+                let mut effects: Vec<AnyEffect> = vec![GameUpdateEffect::new(event.game.clone(), last_update).into()];
+                // precondition_failures already reported an ineligible player. The game update still
+                // happened, but boosting them would only compound the mistake.
+                if party_eligibility_failures(event.player_id, state).is_empty() {
+                    effects.push(PartyBoostEffect::new(event.player_id).into());
+                }
+                effects
+            }
+            FedEventFlat::ModExpires(event) => {
+                // Missing mods and bad mod types are reported by precondition_failures, and removing
+                // a mod that isn't there is a no-op, so this doesn't need to check
+                let Ok(duration) = ModDuration::try_from(event.mod_type) else { return Vec::new(); };
+                vec![PlayerModsRemovedEffect::new(event.player_id, event.mods, duration).into()]
+            }
+            FedEventFlat::PlayerStatReroll(event) => {
+                player_stat_reroll_effects(event.player_id, event.game, last_update)
+            }
+            FedEventFlat::Feedback(event) => {
+                let mut effects = vec![GameUpdateEffect::new(event.game, last_update).into()];
+                // precondition_failures already reported tags that don't describe a swap
+                if let Some(feedback) = Feedback::from_tags(created, &player_tags, &team_tags) {
+                    effects.extend(feedback.into_effects(state));
+                }
+                effects
+            }
+            // The Feed announces Earlbirds ending on its own, so nothing is scheduled here
+            FedEventFlat::EarlbirdsAdded(_) => {
+                team_tags.iter()
+                    .flat_map(|&team_id| EarlbirdsAdded::new(created, team_id, None).into_effects(state))
+                    .collect()
+            }
+            FedEventFlat::EarlbirdsRemoved(_) => {
+                team_tags.iter()
+                    .flat_map(|&team_id| EarlbirdsRemoved::new(created, team_id).into_effects(state))
+                    .collect()
+            }
+            FedEventFlat::FloodingSwept(event) => {
+                // Only the runners who were swept Elsewhere are named, and so only they are tagged
+                FloodingSwept::new(created, event.game.game_id, last_update, player_tags).into_effects(state)
+            }
+            FedEventFlat::Snowflakes(event) => {
+                // The frozen players are the ones tagged, since each freezing is its own child event
+                Snowflakes::new(created, event.game.game_id, last_update, player_tags).into_effects(state)
+            }
+            FedEventFlat::CoffeeBean(event) => {
+                vec![
+                    GameUpdateEffect::new(event.game.clone(), last_update).into(),
+                    CoffeeBeanEffect::new(event.player_id, event.is_wired).into(),
+                ]
+            }
+            FedEventFlat::HomeRun(event) => {
+                let batter_id = event.batter_id;
+                vec![
+                    HomeRunEffect::new(event, last_update).into(),
+                    PlayerHitEffect::new(batter_id).into(),
+                ]
+            }
+            FedEventFlat::StolenBase(event) => {
+                let mut effects = free_refill_effects(event.free_refill.as_ref()
+                    .map(core::slice::from_ref)
+                    .unwrap_or_default());
+                effects.push(StolenBaseEffect::new(event, last_update).into());
+                effects
+            }
+            FedEventFlat::CaughtStealing(event) => {
+                // The runner is the only player tagged
+                vec![CaughtStealingEffect::new(event, last_update, player_tags.first().copied()).into()]
+            }
+            FedEventFlat::BecameMagmatic(event) => {
+                vec![
+                    GameUpdateEffect::new(event.game.clone(), last_update).into(),
+                    PlayerModsAddedEffect::new(event.player_id, vec!["MAGMATIC".to_string()], ModDuration::Permanent).into(),
+                ]
+            }
+            FedEventFlat::GainFreeRefill(event) => {
+                // The refill stays with the player until they use it by scoring
+                vec![
+                    GameUpdateEffect::new(event.game.clone(), last_update).into(),
+                    PlayerModsAddedEffect::new(event.player_id, vec![FREE_REFILL_MOD.to_string()], ModDuration::Permanent).into(),
+                ]
+            }
+            FedEventFlat::GameEnd(event) => {
+                vec![
+                    TeamWinStreakEffect::new(event.winner_id, true).into(),
+                    TeamWinStreakEffect::new(event.loser_id, false).into(),
+                    GameEndEffect::new(event, last_update).into(),
+                ]
+            }
+            FedEventFlat::TeamWonInternetSeries(event) => {
+                // Winning the internet series is what ends the season, so the sim moves out of the
+                // postseason at the same time
+                vec![
+                    TeamWonInternetSeriesEffect::new(event).into(),
+                    PostseasonEndEffect.into(),
+                ]
+            }
+            // All the election results are just containers for a list of the things they did
+            FedEventFlat::Tidings(event) => {
+                election_effects(&event.effects, state)
+            }
+            FedEventFlat::WillReceived(event) => {
+                election_effects(&event.effects, state)
+            }
+            FedEventFlat::BlessingWon(event) => {
+                election_effects(&event.effects, state)
+            }
+            FedEventFlat::DecreePassed(event) => {
+                election_effects(&event.effects, state)
+            }
+            // A reading can hit several teams at once, and lists each outcome the same way
+            // election results do
+            FedEventFlat::TarotReading(event) => {
+                tarot_reading_effects(&event.effects)
+            }
+            FedEventFlat::PlayerHatched(event) => {
+                // The player itself is created from new_entities. All that changes here is that
+                // they show up in their new team's shadows.
+                vec![PlayerJoinedTeamEffect::new(event.team_id, event.player_id, RosterSlot::Shadows).into()]
+            }
+            FedEventFlat::PlayerJoinedILB(_) => {
+                // Nothing that exists yet is affected. See new_entities.
+                Vec::new()
+            }
+            FedEventFlat::PlayerBoosted(event) => {
+                vec![PlayerAttributesAdjustedEffect::new(event.player_id, *PLAYER_BOOST.start(), *PLAYER_BOOST.end()).into()]
+            }
+            FedEventFlat::TarotReadingAddedMod(event) => {
+                // precondition_failures already reported a bad mod type
+                let Ok(duration) = ModDuration::try_from(event.mod_type) else { return Vec::new(); };
+                vec![TeamModAddedEffect::new(event.team_id, event.mod_name.clone(), duration).into()]
+            }
+            FedEventFlat::Ball(event) => {
+                vec![PitchEffect::new(event.game, last_update, Pitch::Ball).into()]
+            }
+            FedEventFlat::StrikeSwinging(event) => {
+                vec![PitchEffect::new(event.game, last_update, Pitch::Strike).into()]
+            }
+            FedEventFlat::StrikeLooking(event) => {
+                vec![PitchEffect::new(event.game, last_update, Pitch::Strike).into()]
+            }
+            FedEventFlat::StrikeFlinching(event) => {
+                vec![PitchEffect::new(event.game, last_update, Pitch::Strike).into()]
+            }
+            FedEventFlat::FoulBall(event) => {
+                vec![PitchEffect::new(event.game, last_update, Pitch::Foul).into()]
+            }
+            FedEventFlat::Walk(event) => {
+                let batter_mod = get_displayed_mod(state, event.batter_id, &RUNNER_MOD_PRECEDENCE);
+                vec![BatterReachedBaseEffect::new(event.game, last_update, event.batter_id, event.batter_name, batter_mod, Base::First, false).into()]
+            }
+            FedEventFlat::Hit(event) => {
+                let Ok(to_base) = Base::try_from(event.num_bases) else {
+                    // precondition_failures already reported this
+                    return vec![GameUpdateEffect::new(event.game, last_update).into()];
+                };
+                let batter_mod = get_displayed_mod(state, event.batter_id, &RUNNER_MOD_PRECEDENCE);
+                vec![
+                    BatterReachedBaseEffect::new(event.game, last_update, event.batter_id, event.batter_name, batter_mod, to_base, true).into(),
+                    PlayerHitEffect::new(event.batter_id).into(),
+                ]
+            }
+            // As far as the game is concerned these are all the same
+            FedEventFlat::Strikeout(event) => {
+                vec![OutEffect::new(event.game, last_update).into()]
+            }
+            FedEventFlat::FlyOut(event) => {
+                vec![OutEffect::new(event.game, last_update).into()]
+            }
+            FedEventFlat::GroundOut(event) => {
+                vec![OutEffect::new(event.game, last_update).into()]
+            }
+            other => {
+                // Crashing the whole ingest over one event is worse than getting it wrong. Whatever
+                // it should have changed will show up as conflicts when it's observed.
+                let debug = format!("{other:?}");
+                let variant_name = debug.split(|c: char| !c.is_alphanumeric()).next().unwrap_or_default();
+                warn!("Fed event type {variant_name} isn't implemented yet; ignoring it");
+                Vec::new()
+            }
         }
     }
+
+    fn category(&self) -> Option<EventCategory> {
+        self.0.category.try_into().ok()
+    }
+
+    fn game_tags(&self) -> &[Uuid] {
+        &self.0.game_tags
+    }
+
+    fn team_tags(&self) -> &[Uuid] {
+        &self.0.team_tags
+    }
 }
 
 impl Display for FedEvent {
@@ -72,11 +442,18 @@ pub fn game_forward(game: &mut Game, game_event: &fed::GameEvent, description: S
 
     game.last_update = Some(description);
 
-    game.last_update_full = None;
+    // The Feed event doesn't carry everything lastUpdateFull has (its id, nuts, metadata...), so it
+    // can't be rebuilt here. Leaving it unknown means the observation fills it in.
+    game.last_update_full = Nullable::unknown();
 
-    // TODO Check the conditionals on this
-    game.shame = (game.inning > 8 || game.inning > 7 && !game.top_of_inning) &&
-        game.home.score.unwrap() > game.away.score.unwrap();
+    // TODO Check the conditionals on this. Until then, only trust them to rule shame out.
+    let home_ahead = match (game.home.score, game.away.score) {
+        (Some(home), Some(away)) => { home > away }
+        // Without both scores there's no ruling it out
+        _ => { true }
+    };
+    let might_be_shame = (game.inning > 8 || game.inning > 7 && !game.top_of_inning) && home_ahead;
+    game.shame = if might_be_shame { MaybeKnown::Unknown } else { MaybeKnown::Known(false) };
 }
 
 pub fn game_reverse(old_game: &Game, new_game: &mut Game, game_event: &fed::GameEvent) {
@@ -92,6 +469,36 @@ pub fn game_reverse(old_game: &Game, new_game: &mut Game, game_event: &fed::Game
     new_game.shame = old_game.shame;
 }
 
+pub const FREE_REFILL_MOD: &str = "COFFEE_RALLY";
+
+// Only players holding a Free Refill can use one to refill an out when they score. Scoring events
+// should report these before applying game_score_forward, since an unexpected refill changes the
+// number of outs.
+pub fn free_refill_failures(free_refills: &[fed::FreeRefill], state: &StateGraph) -> Vec<PreconditionFailure> {
+    free_refills.iter()
+        // Players that aren't being tracked can't be checked
+        .filter(|refill| state.entity_graph(EntityType::Player, refill.player_id).is_some())
+        // Only rule it out if no possible version of the player has one
+        .filter(|refill| !state.query_player_all(refill.player_id, |player| player.has_free_refill()).contains(&true))
+        .map(|refill| PreconditionFailure {
+            entity_type: EntityType::Player,
+            entity_id: refill.player_id,
+            property: "permAttr".to_string(),
+            message: format!("Player {} used a Free Refill they didn't have", refill.player_id),
+            skips_event: true,
+        })
+        .collect()
+}
+
+// Using a Free Refill spends it
+pub fn free_refill_effects(free_refills: &[fed::FreeRefill]) -> Vec<AnyEffect> {
+    free_refills.iter()
+        .map(|refill| {
+            PlayerModsRemovedEffect::new(refill.player_id, vec![FREE_REFILL_MOD.to_string()], ModDuration::Permanent).into()
+        })
+        .collect()
+}
+
 pub fn game_score_forward(game: &mut Game, scoring_players: &[fed::ScoringPlayer], free_refills: &[fed::FreeRefill]) {
     let mut runs_scored = 0.;
     for score in scoring_players {
@@ -101,7 +508,7 @@ pub fn game_score_forward(game: &mut Game, scoring_players: &[fed::ScoringPlayer
     game.score_update = Some(format!("{runs_scored} Run{} scored!",
                                      if runs_scored != 1. { "s" } else { "" }));
     game.half_inning_score += runs_scored;
-    *game.team_at_bat_mut().score.as_mut().unwrap() += runs_scored;
+    game.add_runs_for_team_at_bat(runs_scored);
     *game.current_half_score_mut() += runs_scored;
     // There cant be free refills without scores [falsehoods] so it's fine to do this here
     game.half_inning_outs -= free_refills.len() as i32;
@@ -157,7 +564,7 @@ impl Effect for LetsGoEffect {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LetsGoEffectVariant {
     event: Arc<FedEventLetsGo>,
     // TODO: Try making this a &str (borrowing from LetsGoEffect) and see if it explodes
@@ -211,7 +618,7 @@ impl Effect for PlayBallGameEffect {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PlayBallGameEffectVariant {
     event: Arc<FedEventPlayBall>,
     // TODO: Try making this a &str (borrowing from PlayBallEffect) and see if it explodes
@@ -283,7 +690,7 @@ impl Effect for PlayBallTeamEffect {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PlayBallTeamEffectVariant;
 
 impl PlayBallTeamEffectVariant {
@@ -300,4 +707,2868 @@ impl EffectVariant for PlayBallTeamEffectVariant {
     fn reverse(&mut self, _: &Team, new_team: &mut Team) {
         new_team.rotation_slot -= 1;
     }
-}
\ No newline at end of file
+}
+#[derive(Clone, Debug)]
+pub struct FireproofIncinerationEffect {
+    event: Arc<FedEventFireproofIncineration>,
+    last_update: String,
+}
+
+impl FireproofIncinerationEffect {
+    pub fn new(event: FedEventFireproofIncineration, last_update: String) -> Self {
+        Self { event: Arc::new(event), last_update }
+    }
+}
+
+impl Effect for FireproofIncinerationEffect {
+    type Variant = FireproofIncinerationEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Game }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.event.game.game_id) }
+
+    fn variant(&self) -> Self::Variant {
+        FireproofIncinerationEffectVariant::new(self.event.clone(), self.last_update.clone())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FireproofIncinerationEffectVariant {
+    event: Arc<FedEventFireproofIncineration>,
+    last_update: String,
+}
+
+impl FireproofIncinerationEffectVariant {
+    pub fn new(event: Arc<FedEventFireproofIncineration>, description: String) -> Self {
+        Self { event, last_update: description }
+    }
+}
+
+impl EffectVariant for FireproofIncinerationEffectVariant {
+    type EntityType = Game;
+
+    fn forward(&self, game: &mut Game) {
+        // Nothing happens to the player, but it still takes up a play
+        game_forward(game, &self.event.game, self.last_update.clone());
+    }
+
+    fn reverse(&mut self, old_game: &Game, new_game: &mut Game) {
+        game_reverse(old_game, new_game, &self.event.game);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SalmonSwimEffect {
+    event: Arc<FedEventSalmonSwim>,
+    last_update: String,
+}
+
+impl SalmonSwimEffect {
+    pub fn new(event: FedEventSalmonSwim, last_update: String) -> Self {
+        Self { event: Arc::new(event), last_update }
+    }
+}
+
+impl Effect for SalmonSwimEffect {
+    type Variant = SalmonSwimEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Game }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.event.game.game_id) }
+
+    fn variant(&self) -> Self::Variant {
+        SalmonSwimEffectVariant::new(self.event.clone(), self.last_update.clone())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SalmonSwimEffectVariant {
+    event: Arc<FedEventSalmonSwim>,
+    last_update: String,
+}
+
+impl SalmonSwimEffectVariant {
+    pub fn new(event: Arc<FedEventSalmonSwim>, description: String) -> Self {
+        Self { event, last_update: description }
+    }
+}
+
+impl EffectVariant for SalmonSwimEffectVariant {
+    type EntityType = Game;
+
+    fn forward(&self, game: &mut Game) {
+        game_forward(game, &self.event.game, self.last_update.clone());
+
+        // The inning gets reset but the runs already on the board stay there. Any runs the salmon
+        // take away come from a separate event.
+        game.half_inning_score = 0.0;
+        game.top_inning_score = 0.0;
+        game.bottom_inning_score = 0.0;
+        game.half_inning_outs = 0;
+        game.clear_bases();
+    }
+
+    fn reverse(&mut self, old_game: &Game, new_game: &mut Game) {
+        new_game.half_inning_score = old_game.half_inning_score;
+        new_game.top_inning_score = old_game.top_inning_score;
+        new_game.bottom_inning_score = old_game.bottom_inning_score;
+        new_game.half_inning_outs = old_game.half_inning_outs;
+        new_game.reverse_clear_bases(old_game);
+
+        game_reverse(old_game, new_game, &self.event.game);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RenovationBuiltEffect {
+    event: Arc<FedEventRenovationBuilt>,
+}
+
+impl RenovationBuiltEffect {
+    pub fn new(event: FedEventRenovationBuilt) -> Self {
+        Self { event: Arc::new(event) }
+    }
+}
+
+impl Effect for RenovationBuiltEffect {
+    type Variant = RenovationBuiltEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Stadium }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.event.stadium_id) }
+
+    fn variant(&self) -> Self::Variant {
+        RenovationBuiltEffectVariant::new(self.event.clone())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RenovationBuiltEffectVariant {
+    event: Arc<FedEventRenovationBuilt>,
+}
+
+impl RenovationBuiltEffectVariant {
+    pub fn new(event: Arc<FedEventRenovationBuilt>) -> Self {
+        Self { event }
+    }
+}
+
+impl EffectVariant for RenovationBuiltEffectVariant {
+    type EntityType = Stadium;
+
+    fn forward(&self, stadium: &mut Stadium) {
+        apply_renovation(stadium, &self.event.renovation_id);
+    }
+
+    fn reverse(&mut self, old_stadium: &Stadium, new_stadium: &mut Stadium) {
+        match old_stadium.reno_log.get(&self.event.renovation_id) {
+            Some(&count) => { new_stadium.reno_log.insert(self.event.renovation_id.clone(), count); }
+            None => { new_stadium.reno_log.remove(&self.event.renovation_id); }
+        }
+        new_stadium.mods = old_stadium.mods.clone();
+        for (name, value) in old_stadium.attributes() {
+            *new_stadium.attribute_mut(name)
+                .expect("Every stadium attribute name must be settable") = value;
+        }
+    }
+}
+
+// Renovations that add a stadium mod, by renovation id
+const RENOVATION_MODS: [(&str, &str); 12] = [
+    ("air_balloons_mod", "AIR_BALLOONS"),
+    ("flood_balloons_mod", "FLOOD_BALLOONS"),
+    ("flood_pumps_mod", "FLOOD_PUMPS"),
+    ("big_buckets_mod", "BIG_BUCKET"),
+    ("hoops_mod", "HOOPS"),
+    ("event_horizon_mod", "EVENT_HORIZON"),
+    ("salmon_cannons_mod", "SALMON_CANNONS"),
+    ("peanut_mister_mod", "PEANUT_MISTER"),
+    ("echo_chamber_mod", "ECHO_CHAMBER"),
+    ("solar_panels_mod", "SOLAR_PANELS"),
+    ("grind_rail_mod", "GRIND_RAIL"),
+    ("secret_base_mod", "SECRET_BASE"),
+];
+
+fn apply_renovation(stadium: &mut Stadium, renovation_id: &str) {
+    *stadium.reno_log.entry(renovation_id.to_string()).or_insert(0) += 1;
+
+    if let Some((_, mod_name)) = RENOVATION_MODS.iter().find(|(id, _)| *id == renovation_id) {
+        if !stadium.mods.iter().any(|m| m == mod_name) {
+            stadium.mods.push(mod_name.to_string());
+        }
+        return;
+    }
+
+    // Attribute renovations are named after the attribute they change (e.g. "grandiosity_up").
+    // The amount isn't in the Feed, so the attribute is unknown until the next observation.
+    let attribute = renovation_id.split('_').next().unwrap_or(renovation_id);
+    if let Some(value) = stadium.attribute_mut(attribute) {
+        *value = MaybeKnown::Unknown;
+    } else {
+        warn!("Unknown renovation {renovation_id} in {}. Only the reno log will be updated.", stadium.name);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct LineupSortedEffect {
+    event: Arc<FedEventLineupSorted>,
+    candidate_lineups: Vec<Vec<Uuid>>,
+}
+
+impl LineupSortedEffect {
+    pub fn new(event: FedEventLineupSorted, state: &StateGraph) -> Self {
+        let lineup = state.query_team_unique(event.team_id, |team| team.lineup.clone());
+        let ratings = lineup.iter()
+            .map(|&player_id| {
+                // f32 isn't Eq, so this has to go through the bits
+                let rating = state.query_player_unique(player_id, |player| {
+                    player.hitting_rating
+                        .and_then(|rating| rating.map(f32::to_bits).known().copied())
+                });
+                (player_id, rating.map(f32::from_bits))
+            })
+            .collect_vec();
+
+        Self { event: Arc::new(event), candidate_lineups: sorted_lineup_candidates(ratings) }
+    }
+}
+
+// Each candidate becomes its own branch, and the count grows factorially with the number of unknown
+// ratings, so past this there's only one guess
+const MAX_LINEUP_CANDIDATES: usize = 64;
+
+// Known players are sorted best-first. Players with unknown ratings could be anywhere, so every
+// placement of them is a candidate. If that's too many candidates, the unknown players are put at
+// the end in their current order instead, and it's up to observations to catch it if that's wrong.
+fn sorted_lineup_candidates(ratings: Vec<(Uuid, Option<f32>)>) -> Vec<Vec<Uuid>> {
+    let (known, unknown): (Vec<_>, Vec<_>) = ratings.into_iter()
+        .partition(|(_, rating)| rating.is_some());
+
+    let sorted_known = known.into_iter()
+        .sorted_by(|(_, a), (_, b)| b.partial_cmp(a).expect("Ratings should never be NaN"))
+        .map(|(id, _)| id)
+        .collect_vec();
+
+    // Inserting the i-th unknown player has (known + i + 1) places to go
+    let num_candidates = (0..unknown.len())
+        .try_fold(1usize, |count, i| count.checked_mul(sorted_known.len() + i + 1));
+    if num_candidates.map_or(true, |count| count > MAX_LINEUP_CANDIDATES) {
+        warn!("{} players with unknown ratings is too many to try every lineup order; guessing", unknown.len());
+        return vec![sorted_known.into_iter()
+            .chain(unknown.into_iter().map(|(id, _)| id))
+            .collect()];
+    }
+
+    unknown.into_iter()
+        .fold(vec![sorted_known], |candidates, (unknown_id, _)| {
+            candidates.into_iter()
+                .flat_map(|candidate| {
+                    (0..=candidate.len())
+                        .map(move |i| {
+                            let mut new_candidate = candidate.clone();
+                            new_candidate.insert(i, unknown_id);
+                            new_candidate
+                        })
+                })
+                .collect()
+        })
+}
+
+impl Effect for LineupSortedEffect {
+    type Variant = LineupSortedEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Team }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.event.team_id) }
+
+    // Ingest goes through variants(), so this is only for callers that want one representative
+    // outcome. There's always at least one candidate.
+    fn variant(&self) -> Self::Variant {
+        let lineup = self.candidate_lineups.first()
+            .expect("sorted_lineup_candidates always returns at least one candidate");
+        LineupSortedEffectVariant::new(lineup.clone())
+    }
+
+    fn variants(&self) -> Vec<Self::Variant> {
+        self.candidate_lineups.iter()
+            .map(|lineup| LineupSortedEffectVariant::new(lineup.clone()))
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LineupSortedEffectVariant {
+    lineup: Vec<Uuid>,
+}
+
+impl LineupSortedEffectVariant {
+    pub fn new(lineup: Vec<Uuid>) -> Self {
+        Self { lineup }
+    }
+}
+
+impl EffectVariant for LineupSortedEffectVariant {
+    type EntityType = Team;
+
+    fn forward(&self, team: &mut Team) {
+        team.lineup = self.lineup.clone();
+    }
+
+    fn reverse(&mut self, old_team: &Team, new_team: &mut Team) {
+        new_team.lineup = old_team.lineup.clone();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BatterUpEffect {
+    event: Arc<FedEventBatterUp>,
+    last_update: String,
+    // Each batter who could be up, with the mod that would be displayed for them
+    candidate_batters: Vec<(Uuid, String)>,
+}
+
+impl BatterUpEffect {
+    pub fn new(event: FedEventBatterUp, last_update: String, state: &StateGraph) -> Self {
+        let candidate_batters = batter_up_candidates(state, event.game.game_id);
+        if candidate_batters.is_empty() {
+            warn!("Couldn't work out who's up to bat in {}. The batter will be left unknown.", event.game.game_id);
+        }
+
+        Self { event: Arc::new(event), last_update, candidate_batters }
+    }
+}
+
+// The lineup order is often ambiguous (see LineupSorted), and then so is the next batter. Every
+// possibility is returned, and each becomes its own branch for observations to sort out.
+fn batter_up_candidates(state: &StateGraph, game_id: Uuid) -> Vec<(Uuid, String)> {
+    state.query_game_all(game_id, |game| {
+        let team = game.team_at_bat();
+        (team.team, team.team_batter_count)
+    })
+        .into_iter()
+        .unique()
+        .flat_map(|(team_id, team_batter_count)| {
+            // A missing TeamBatterCount would be a conflict in the game, which observations report
+            let Some(team_batter_count) = team_batter_count else { return Vec::new(); };
+            state.query_team_all(team_id, |team| {
+                let num_batters = i32::try_from(team.lineup.len()).ok().filter(|&n| n > 0)?;
+                Some(team.lineup[(team_batter_count + 1).rem_euclid(num_batters) as usize])
+            })
+        })
+        .flatten()
+        .unique()
+        .flat_map(|batter_id| {
+            let mods = get_displayed_mod_candidates(state, batter_id, &BATTER_MOD_PRECEDENCE);
+            if mods.is_empty() {
+                warn!("Batter {batter_id} isn't in the state. Assuming they have no displayed mod.");
+                vec![(batter_id, String::new())]
+            } else {
+                mods.into_iter().map(|batter_mod| (batter_id, batter_mod)).collect()
+            }
+        })
+        .collect()
+}
+
+impl Effect for BatterUpEffect {
+    type Variant = BatterUpEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Game }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.event.game.game_id) }
+
+    // Ingest goes through variants(), so this is only for callers that want one representative
+    // outcome
+    fn variant(&self) -> Self::Variant {
+        let (batter_id, batter_mod) = self.candidate_batters.first()
+            .map(|(batter_id, batter_mod)| (Some(*batter_id), batter_mod.clone()))
+            .unwrap_or_default();
+        BatterUpEffectVariant::new(self.event.clone(), self.last_update.clone(), batter_id, batter_mod)
+    }
+
+    fn variants(&self) -> Vec<Self::Variant> {
+        // With no candidates the batter is left unknown, rather than having no outcomes at all
+        if self.candidate_batters.is_empty() {
+            return vec![self.variant()];
+        }
+
+        self.candidate_batters.iter()
+            .map(|(batter_id, batter_mod)| {
+                BatterUpEffectVariant::new(self.event.clone(), self.last_update.clone(), Some(*batter_id), batter_mod.clone())
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatterUpEffectVariant {
+    event: Arc<FedEventBatterUp>,
+    last_update: String,
+    batter_id: Option<Uuid>,
+    batter_mod: String,
+}
+
+impl BatterUpEffectVariant {
+    pub fn new(event: Arc<FedEventBatterUp>, last_update: String, batter_id: Option<Uuid>, batter_mod: String) -> Self {
+        Self { event, last_update, batter_id, batter_mod }
+    }
+}
+
+impl EffectVariant for BatterUpEffectVariant {
+    type EntityType = Game;
+
+    fn forward(&self, game: &mut Game) {
+        game_forward(game, &self.event.game, self.last_update.clone());
+
+        let team = game.team_at_bat_mut();
+        team.team_batter_count = team.team_batter_count.map(|count| count + 1);
+        team.batter = self.batter_id;
+        team.batter_name = Some(self.event.batter_name.clone());
+        team.batter_mod = self.batter_mod.clone();
+    }
+
+    fn reverse(&mut self, old_game: &Game, new_game: &mut Game) {
+        // The team at bat doesn't change during a BatterUp
+        let old_team = old_game.team_at_bat();
+        let new_team = new_game.team_at_bat_mut();
+        new_team.team_batter_count = old_team.team_batter_count;
+        new_team.batter = old_team.batter;
+        new_team.batter_name = old_team.batter_name.clone();
+        new_team.batter_mod = old_team.batter_mod.clone();
+
+        game_reverse(old_game, new_game, &self.event.game);
+    }
+}
+
+// Rerolls are usually folded into a parent event, but Snow emits them as top-level events that
+// also update the game. The reroll itself is the same either way.
+fn player_stat_reroll_effects(player_id: Uuid, game: Option<fed::GameEvent>, last_update: String) -> Vec<AnyEffect> {
+    // I think this is pretty close to the actual range.
+    let reroll: AnyEffect = PlayerAttributesAdjustedEffect::new(player_id, -0.03, 0.03).into();
+    if let Some(game) = game {
+        vec![GameUpdateEffect::new(game, last_update).into(), reroll]
+    } else {
+        vec![reroll]
+    }
+}
+
+fn echo_effects(game_event: &fed::GameEvent, receiver_id: Uuid, source_id: Uuid, mod_type: i32, last_update: String, state: &StateGraph) -> Vec<AnyEffect> {
+    let game_update: AnyEffect = GameUpdateEffect::new(game_event.clone(), last_update).into();
+    // precondition_failures already reported a bad mod type
+    let Ok(duration) = ModDuration::try_from(mod_type) else { return vec![game_update]; };
+    // Echoes copy everything that isn't going to expire on its own soon
+    let mods = state.query_player_unique(source_id, |player| {
+        player.mods(ModDuration::Permanent).iter()
+            .chain(player.mods(ModDuration::Season))
+            .cloned()
+            .collect_vec()
+    });
+
+    vec![
+        game_update,
+        PlayerModsAddedEffect::new(receiver_id, mods, duration).into(),
+    ]
+}
+
+#[derive(Clone, Debug)]
+pub struct AmbushedByCrowsEffect {
+    event: Arc<FedEventAmbushedByCrows>,
+    last_update: String,
+}
+
+impl AmbushedByCrowsEffect {
+    pub fn new(event: FedEventAmbushedByCrows, last_update: String) -> Self {
+        Self { event: Arc::new(event), last_update }
+    }
+}
+
+impl Effect for AmbushedByCrowsEffect {
+    type Variant = AmbushedByCrowsEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Game }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.event.game.game_id) }
+
+    fn variant(&self) -> Self::Variant {
+        AmbushedByCrowsEffectVariant::new(self.event.clone(), self.last_update.clone())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AmbushedByCrowsEffectVariant {
+    event: Arc<FedEventAmbushedByCrows>,
+    last_update: String,
+}
+
+impl AmbushedByCrowsEffectVariant {
+    pub fn new(event: Arc<FedEventAmbushedByCrows>, description: String) -> Self {
+        Self { event, last_update: description }
+    }
+}
+
+impl EffectVariant for AmbushedByCrowsEffectVariant {
+    type EntityType = Game;
+
+    // As far as the game is concerned this is a strikeout
+    fn forward(&self, game: &mut Game) {
+        game_forward(game, &self.event.game, self.last_update.clone());
+        game.out(1);
+    }
+
+    fn reverse(&mut self, old_game: &Game, new_game: &mut Game) {
+        new_game.reverse_out(1, old_game);
+        game_reverse(old_game, new_game, &self.event.game);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GameEndEffect {
+    event: Arc<FedEventGameEnd>,
+    last_update: String,
+}
+
+impl GameEndEffect {
+    pub fn new(event: FedEventGameEnd, last_update: String) -> Self {
+        Self { event: Arc::new(event), last_update }
+    }
+}
+
+impl Effect for GameEndEffect {
+    type Variant = GameEndEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Game }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.event.game.game_id) }
+
+    fn variant(&self) -> Self::Variant {
+        GameEndEffectVariant::new(self.event.clone(), self.last_update.clone())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameEndEffectVariant {
+    event: Arc<FedEventGameEnd>,
+    last_update: String,
+}
+
+impl GameEndEffectVariant {
+    pub fn new(event: Arc<FedEventGameEnd>, description: String) -> Self {
+        Self { event, last_update: description }
+    }
+}
+
+impl EffectVariant for GameEndEffectVariant {
+    type EntityType = Game;
+
+    // The rest of the end phases are timed events, see game_over.rs
+    fn forward(&self, game: &mut Game) {
+        game_forward(game, &self.event.game, self.last_update.clone());
+        game.phase = 7;
+        game.end_phase = Some(3);
+    }
+
+    fn reverse(&mut self, old_game: &Game, new_game: &mut Game) {
+        new_game.phase = old_game.phase;
+        new_game.end_phase = old_game.end_phase;
+        game_reverse(old_game, new_game, &self.event.game);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct HomeRunEffect {
+    event: Arc<FedEventHomeRun>,
+    last_update: String,
+}
+
+impl HomeRunEffect {
+    pub fn new(event: FedEventHomeRun, last_update: String) -> Self {
+        Self { event: Arc::new(event), last_update }
+    }
+}
+
+impl Effect for HomeRunEffect {
+    type Variant = HomeRunEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Game }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.event.game.game_id) }
+
+    fn variant(&self) -> Self::Variant {
+        HomeRunEffectVariant::new(self.event.clone(), self.last_update.clone())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HomeRunEffectVariant {
+    event: Arc<FedEventHomeRun>,
+    last_update: String,
+}
+
+impl HomeRunEffectVariant {
+    pub fn new(event: Arc<FedEventHomeRun>, description: String) -> Self {
+        Self { event, last_update: description }
+    }
+}
+
+impl EffectVariant for HomeRunEffectVariant {
+    type EntityType = Game;
+
+    fn forward(&self, game: &mut Game) {
+        game_forward(game, &self.event.game, self.last_update.clone());
+        home_run_forward(game, self.event.num_runs as f32);
+    }
+
+    fn reverse(&mut self, old_game: &Game, new_game: &mut Game) {
+        home_run_reverse(old_game, new_game);
+        game_reverse(old_game, new_game, &self.event.game);
+    }
+}
+
+// Everyone on base scores, plus the batter. The event doesn't list scorers the way other scoring
+// events do, so this can't use game_score_forward.
+fn home_run_forward(game: &mut Game, runs_scored: f32) {
+    game.score_update = Some(format!("{runs_scored} Run{} scored!",
+                                     if runs_scored != 1. { "s" } else { "" }));
+    game.half_inning_score += runs_scored;
+    game.add_runs_for_team_at_bat(runs_scored);
+    *game.current_half_score_mut() += runs_scored;
+
+    game.clear_bases();
+    game.end_at_bat();
+}
+
+// The runners who scored are all still in old_game, so there's nothing to extrapolate
+fn home_run_reverse(old_game: &Game, new_game: &mut Game) {
+    new_game.reverse_end_at_bat(old_game);
+    new_game.reverse_clear_bases(old_game);
+
+    new_game.half_inning_score = old_game.half_inning_score;
+    new_game.team_at_bat_mut().score = old_game.team_at_bat().score;
+    *new_game.current_half_score_mut() = old_game.current_half_score();
+}
+
+#[derive(Clone, Debug)]
+pub struct PlayerHitEffect {
+    player_id: Uuid,
+}
+
+impl PlayerHitEffect {
+    pub fn new(player_id: Uuid) -> Self {
+        Self { player_id }
+    }
+}
+
+impl Effect for PlayerHitEffect {
+    type Variant = PlayerHitEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Player }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.player_id) }
+
+    fn variant(&self) -> Self::Variant {
+        PlayerHitEffectVariant
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerHitEffectVariant;
+
+impl EffectVariant for PlayerHitEffectVariant {
+    type EntityType = Player;
+
+    fn forward(&self, player: &mut Player) {
+        player.increment_consecutive_hits();
+    }
+
+    fn reverse(&mut self, old_player: &Player, new_player: &mut Player) {
+        new_player.consecutive_hits = old_player.consecutive_hits;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct StolenBaseEffect {
+    event: Arc<FedEventStolenBase>,
+    last_update: String,
+}
+
+impl StolenBaseEffect {
+    pub fn new(event: FedEventStolenBase, last_update: String) -> Self {
+        Self { event: Arc::new(event), last_update }
+    }
+}
+
+impl Effect for StolenBaseEffect {
+    type Variant = StolenBaseEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Game }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.event.game.game_id) }
+
+    fn variant(&self) -> Self::Variant {
+        StolenBaseEffectVariant::new(self.event.clone(), self.last_update.clone())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StolenBaseEffectVariant {
+    event: Arc<FedEventStolenBase>,
+    last_update: String,
+}
+
+impl StolenBaseEffectVariant {
+    pub fn new(event: Arc<FedEventStolenBase>, description: String) -> Self {
+        Self { event, last_update: description }
+    }
+
+    fn to_base(&self) -> Base {
+        Base::try_from(self.event.base_stolen)
+            .expect("Invalid base_stolen in StolenBase event")
+    }
+
+    // Stealing home is scored like any other run
+    fn scorers(&self) -> Vec<fed::ScoringPlayer> {
+        vec![fed::ScoringPlayer {
+            player_id: self.event.runner_id,
+            player_name: self.event.runner_name.clone(),
+            item_damage: None,
+        }]
+    }
+
+    fn free_refills(&self) -> &[fed::FreeRefill] {
+        self.event.free_refill.as_ref()
+            .map(core::slice::from_ref)
+            .unwrap_or_default()
+    }
+}
+
+impl EffectVariant for StolenBaseEffectVariant {
+    type EntityType = Game;
+
+    fn forward(&self, game: &mut Game) {
+        game_forward(game, &self.event.game, self.last_update.clone());
+
+        // Pretending fifth base doesn't exist
+        let to_base = self.to_base();
+        if to_base == Base::Fourth {
+            // This also pops the runner
+            game_score_forward(game, &self.scorers(), self.free_refills());
+        } else if let Some(idx) = game.steal_candidate(self.event.runner_id, to_base) {
+            game.bases_occupied[idx] = RangeInclusive::from_raw(to_base as i32);
+        } else {
+            // This branch is wrong somehow. Observations will show where.
+            warn!("Runner {} stole {} but wasn't on the base before it; leaving the bases alone",
+                self.event.runner_id, to_base.name());
+        }
+    }
+
+    fn reverse(&mut self, old_game: &Game, new_game: &mut Game) {
+        if self.to_base() == Base::Fourth {
+            game_score_reverse(old_game, new_game, &self.scorers(), self.free_refills());
+        } else {
+            new_game.bases_occupied = old_game.bases_occupied.clone();
+        }
+        game_reverse(old_game, new_game, &self.event.game);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CaughtStealingEffect {
+    event: Arc<FedEventCaughtStealing>,
+    last_update: String,
+    runner_id: Option<Uuid>,
+}
+
+impl CaughtStealingEffect {
+    pub fn new(event: FedEventCaughtStealing, last_update: String, runner_id: Option<Uuid>) -> Self {
+        Self { event: Arc::new(event), last_update, runner_id }
+    }
+}
+
+impl Effect for CaughtStealingEffect {
+    type Variant = CaughtStealingEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Game }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.event.game.game_id) }
+
+    fn variant(&self) -> Self::Variant {
+        CaughtStealingEffectVariant::new(self.event.clone(), self.last_update.clone(), self.runner_id)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CaughtStealingEffectVariant {
+    event: Arc<FedEventCaughtStealing>,
+    last_update: String,
+    runner_id: Option<Uuid>,
+}
+
+impl CaughtStealingEffectVariant {
+    pub fn new(event: Arc<FedEventCaughtStealing>, description: String, runner_id: Option<Uuid>) -> Self {
+        Self { event, last_update: description, runner_id }
+    }
+}
+
+impl EffectVariant for CaughtStealingEffectVariant {
+    type EntityType = Game;
+
+    fn forward(&self, game: &mut Game) {
+        game_forward(game, &self.event.game, self.last_update.clone());
+
+        match Base::try_from(self.event.base_stolen) {
+            Ok(to_base) => { caught_stealing_forward(game, self.runner_id, to_base) }
+            Err(base) => { warn!("Invalid base_stolen {base} in CaughtStealing event; only updating the play") }
+        }
+    }
+
+    fn reverse(&mut self, old_game: &Game, new_game: &mut Game) {
+        caught_stealing_reverse(old_game, new_game);
+        game_reverse(old_game, new_game, &self.event.game);
+    }
+}
+
+// These are separate from the variant so they can be tested without building a Feed event
+fn caught_stealing_forward(game: &mut Game, runner_id: Option<Uuid>, to_base: Base) {
+    let from_base = to_base as i32 - 1;
+    let idx = match runner_id {
+        Some(runner_id) => { game.steal_candidate(runner_id, to_base) }
+        // Without the runner's id it can still be worked out if only one runner could have been
+        // going for this base
+        None => {
+            game.bases_occupied.iter()
+                .positions(|base| base.could_be(&from_base))
+                .exactly_one()
+                .ok()
+        }
+    };
+    match idx {
+        Some(idx) => { game.remove_base_runner(idx) }
+        None => {
+            // This branch is wrong somehow. Observations will show where.
+            warn!("Couldn't tell who was caught stealing {}; leaving the bases alone", to_base.name());
+        }
+    }
+
+    // An out on the bases only ends the at-bat if it ends the half-inning. When it does, the game
+    // lets the batter have another PA at the start of the next inning by backing up the count.
+    if game.add_outs(1) {
+        game.end_at_bat();
+        if let Some(team_batter_count) = game.team_at_bat_mut().team_batter_count.as_mut() {
+            *team_batter_count -= 1;
+        }
+    }
+}
+
+fn caught_stealing_reverse(old_game: &Game, new_game: &mut Game) {
+    if new_game.reverse_add_outs(1, old_game) {
+        new_game.reverse_end_at_bat(old_game);
+        new_game.team_at_bat_mut().team_batter_count = old_game.team_at_bat().team_batter_count;
+    }
+    // If that out ended the inning the bases were already restored, but if not the caught runner
+    // still needs to go back
+    new_game.reverse_clear_bases(old_game);
+}
+
+// For game events whose only effect on the game is to take up a play
+#[derive(Clone, Debug)]
+pub struct GameUpdateEffect {
+    game_event: Arc<fed::GameEvent>,
+    last_update: String,
+}
+
+impl GameUpdateEffect {
+    pub fn new(game_event: fed::GameEvent, last_update: String) -> Self {
+        Self { game_event: Arc::new(game_event), last_update }
+    }
+}
+
+impl Effect for GameUpdateEffect {
+    type Variant = GameUpdateEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Game }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.game_event.game_id) }
+
+    fn variant(&self) -> Self::Variant {
+        GameUpdateEffectVariant::new(self.game_event.clone(), self.last_update.clone())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameUpdateEffectVariant {
+    game_event: Arc<fed::GameEvent>,
+    last_update: String,
+}
+
+impl GameUpdateEffectVariant {
+    pub fn new(game_event: Arc<fed::GameEvent>, description: String) -> Self {
+        Self { game_event, last_update: description }
+    }
+}
+
+impl EffectVariant for GameUpdateEffectVariant {
+    type EntityType = Game;
+
+    fn forward(&self, game: &mut Game) {
+        game_forward(game, &self.game_event, self.last_update.clone());
+    }
+
+    fn reverse(&mut self, old_game: &Game, new_game: &mut Game) {
+        game_reverse(old_game, new_game, &self.game_event);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Pitch {
+    Ball,
+    Strike,
+    Foul,
+}
+
+#[derive(Clone, Debug)]
+pub struct PitchEffect {
+    game_event: Arc<fed::GameEvent>,
+    last_update: String,
+    pitch: Pitch,
+}
+
+impl PitchEffect {
+    pub fn new(game_event: fed::GameEvent, last_update: String, pitch: Pitch) -> Self {
+        Self { game_event: Arc::new(game_event), last_update, pitch }
+    }
+}
+
+impl Effect for PitchEffect {
+    type Variant = PitchEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Game }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.game_event.game_id) }
+
+    fn variant(&self) -> Self::Variant {
+        PitchEffectVariant::new(self.game_event.clone(), self.last_update.clone(), self.pitch)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PitchEffectVariant {
+    game_event: Arc<fed::GameEvent>,
+    last_update: String,
+    pitch: Pitch,
+}
+
+impl PitchEffectVariant {
+    pub fn new(game_event: Arc<fed::GameEvent>, description: String, pitch: Pitch) -> Self {
+        Self { game_event, last_update: description, pitch }
+    }
+}
+
+impl EffectVariant for PitchEffectVariant {
+    type EntityType = Game;
+
+    // Pitches that end the at-bat are their own events (Walk, Strikeout, etc), so this only ever
+    // changes the count
+    fn forward(&self, game: &mut Game) {
+        game_forward(game, &self.game_event, self.last_update.clone());
+        pitch_forward(game, self.pitch);
+    }
+
+    fn reverse(&mut self, old_game: &Game, new_game: &mut Game) {
+        pitch_reverse(old_game, new_game);
+        game_reverse(old_game, new_game, &self.game_event);
+    }
+}
+
+// These are separate from the variant so they can be tested without building a Feed event
+fn pitch_forward(game: &mut Game, pitch: Pitch) {
+    let Some(mut count) = game.at_bat_count() else {
+        // Without the strike limit there's no telling whether a foul counts, so it's left for
+        // observations to fill in. Balls and strikes always count.
+        match pitch {
+            Pitch::Ball => { game.at_bat_balls += 1 }
+            Pitch::Strike => { game.at_bat_strikes += 1 }
+            Pitch::Foul => {
+                warn!("Strike limit for game {} isn't known, so the effect of a foul ball is unknown", game.id);
+            }
+        }
+        return;
+    };
+    match pitch {
+        Pitch::Ball => { count.add_ball() }
+        Pitch::Strike => { count.add_strike(false) }
+        Pitch::Foul => { count.add_strike(true) }
+    }
+    game.set_at_bat_count(count);
+}
+
+fn pitch_reverse(old_game: &Game, new_game: &mut Game) {
+    new_game.at_bat_balls = old_game.at_bat_balls;
+    new_game.at_bat_strikes = old_game.at_bat_strikes;
+}
+
+#[derive(Clone, Debug)]
+pub struct OutEffect {
+    game_event: Arc<fed::GameEvent>,
+    last_update: String,
+}
+
+impl OutEffect {
+    pub fn new(game_event: fed::GameEvent, last_update: String) -> Self {
+        Self { game_event: Arc::new(game_event), last_update }
+    }
+}
+
+impl Effect for OutEffect {
+    type Variant = OutEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Game }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.game_event.game_id) }
+
+    fn variant(&self) -> Self::Variant {
+        OutEffectVariant::new(self.game_event.clone(), self.last_update.clone())
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OutEffectVariant {
+    game_event: Arc<fed::GameEvent>,
+    last_update: String,
+}
+
+impl OutEffectVariant {
+    pub fn new(game_event: Arc<fed::GameEvent>, description: String) -> Self {
+        Self { game_event, last_update: description }
+    }
+}
+
+impl EffectVariant for OutEffectVariant {
+    type EntityType = Game;
+
+    fn forward(&self, game: &mut Game) {
+        game_forward(game, &self.game_event, self.last_update.clone());
+        game.out(1);
+    }
+
+    fn reverse(&mut self, old_game: &Game, new_game: &mut Game) {
+        new_game.reverse_out(1, old_game);
+        game_reverse(old_game, new_game, &self.game_event);
+    }
+}
+
+// Covers both hits and walks, which differ only in which base the batter ends up on and whether
+// the runners ahead of them are pushed along
+#[derive(Clone, Debug)]
+pub struct BatterReachedBaseEffect {
+    game_event: Arc<fed::GameEvent>,
+    last_update: String,
+    batter_id: Uuid,
+    batter_name: String,
+    batter_mod: String,
+    to_base: Base,
+    is_hit: bool,
+}
+
+impl BatterReachedBaseEffect {
+    pub fn new(game_event: fed::GameEvent, last_update: String, batter_id: Uuid, batter_name: String, batter_mod: String, to_base: Base, is_hit: bool) -> Self {
+        Self { game_event: Arc::new(game_event), last_update, batter_id, batter_name, batter_mod, to_base, is_hit }
+    }
+}
+
+impl Effect for BatterReachedBaseEffect {
+    type Variant = BatterReachedBaseEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Game }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.game_event.game_id) }
+
+    fn variant(&self) -> Self::Variant {
+        BatterReachedBaseEffectVariant {
+            game_event: self.game_event.clone(),
+            last_update: self.last_update.clone(),
+            batter_id: self.batter_id,
+            batter_name: self.batter_name.clone(),
+            batter_mod: self.batter_mod.clone(),
+            to_base: self.to_base,
+            is_hit: self.is_hit,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatterReachedBaseEffectVariant {
+    game_event: Arc<fed::GameEvent>,
+    last_update: String,
+    batter_id: Uuid,
+    batter_name: String,
+    batter_mod: String,
+    to_base: Base,
+    is_hit: bool,
+}
+
+impl EffectVariant for BatterReachedBaseEffectVariant {
+    type EntityType = Game;
+
+    fn forward(&self, game: &mut Game) {
+        game_forward(game, &self.game_event, self.last_update.clone());
+        batter_reached_base_forward(game, self.batter_id, self.batter_name.clone(), self.batter_mod.clone(),
+                                    self.to_base, self.is_hit);
+    }
+
+    fn reverse(&mut self, old_game: &Game, new_game: &mut Game) {
+        batter_reached_base_reverse(old_game, new_game);
+        game_reverse(old_game, new_game, &self.game_event);
+    }
+}
+
+fn batter_reached_base_forward(game: &mut Game, batter_id: Uuid, batter_name: String, batter_mod: String,
+                               to_base: Base, is_hit: bool) {
+    // On a hit everyone advances at least as far as the batter did. On a walk runners only move if
+    // they're forced, which push_base_runner takes care of.
+    if is_hit {
+        advance_runners_on_hit(game, to_base as i32 + 1);
+    }
+    game.push_base_runner(batter_id, batter_name, batter_mod, to_base);
+
+    // Anyone pushed past third scores. Like HomeRun, the event doesn't list them.
+    let runs = game.remove_runners_who_scored();
+    if runs > 0 {
+        add_runs(game, runs);
+    }
+    game.end_at_bat();
+}
+
+// Runners holding a Free Refill (which shows up as their displayed mod) sometimes take an extra
+// base on a hit. The Feed doesn't say whether they did, so for them both are left open until an
+// observation narrows it down. Runners are ordered lead runner first, and nobody can end up on or
+// past the base of the runner ahead of them.
+fn advance_runners_on_hit(game: &mut Game, by: i32) {
+    game.advance_runners_by(by);
+    for idx in 0..game.bases_occupied.len() {
+        let extra_base = game.bases_occupied[idx].upper + 1;
+        let blocked = idx > 0 && game.bases_occupied[idx - 1].lower <= extra_base;
+        if game.base_runner_mods[idx] == FREE_REFILL_MOD && !blocked {
+            game.bases_occupied[idx].upper = extra_base;
+        }
+    }
+}
+
+fn batter_reached_base_reverse(old_game: &Game, new_game: &mut Game) {
+    new_game.reverse_end_at_bat(old_game);
+    new_game.reverse_clear_bases(old_game);
+    reverse_add_runs(old_game, new_game);
+}
+
+// For events that only say how many runs scored, not who scored them
+fn add_runs(game: &mut Game, runs: i32) {
+    let runs_scored = runs as f32;
+    game.score_update = Some(format!("{runs} Run{} scored!", if runs != 1 { "s" } else { "" }));
+    game.half_inning_score += runs_scored;
+    match game.team_at_bat_mut().score.as_mut() {
+        Some(score) => { *score += runs_scored }
+        None => { warn!("Team at bat has no score; not adding {runs} run(s) to it") }
+    }
+    *game.current_half_score_mut() += runs_scored;
+}
+
+fn reverse_add_runs(old_game: &Game, new_game: &mut Game) {
+    new_game.score_update = old_game.score_update.clone();
+    new_game.half_inning_score = old_game.half_inning_score;
+    new_game.team_at_bat_mut().score = old_game.team_at_bat().score;
+    *new_game.current_half_score_mut() = old_game.current_half_score();
+}
+
+#[derive(Clone, Debug)]
+pub struct PlayerModsAddedEffect {
+    player_id: Uuid,
+    mods: Vec<String>,
+    duration: ModDuration,
+}
+
+impl PlayerModsAddedEffect {
+    pub fn new(player_id: Uuid, mods: Vec<String>, duration: ModDuration) -> Self {
+        Self { player_id, mods, duration }
+    }
+}
+
+impl Effect for PlayerModsAddedEffect {
+    type Variant = PlayerModsAddedEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Player }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.player_id) }
+
+    fn variant(&self) -> Self::Variant {
+        PlayerModsAddedEffectVariant::new(self.mods.clone(), self.duration)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerModsAddedEffectVariant {
+    mods: Vec<String>,
+    duration: ModDuration,
+}
+
+impl PlayerModsAddedEffectVariant {
+    pub fn new(mods: Vec<String>, duration: ModDuration) -> Self {
+        Self { mods, duration }
+    }
+}
+
+impl EffectVariant for PlayerModsAddedEffectVariant {
+    type EntityType = Player;
+
+    fn forward(&self, player: &mut Player) {
+        let mod_list = player.mods_mut(self.duration);
+        for mod_name in &self.mods {
+            // A player can't have the same mod twice in the same list
+            if !mod_list.contains(mod_name) {
+                mod_list.push(mod_name.clone());
+            }
+        }
+    }
+
+    fn reverse(&mut self, old_player: &Player, new_player: &mut Player) {
+        *new_player.mods_mut(self.duration) = old_player.mods(self.duration).to_vec();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PlayerModsRemovedEffect {
+    player_id: Uuid,
+    mods: Vec<String>,
+    duration: ModDuration,
+}
+
+impl PlayerModsRemovedEffect {
+    pub fn new(player_id: Uuid, mods: Vec<String>, duration: ModDuration) -> Self {
+        Self { player_id, mods, duration }
+    }
+}
+
+impl Effect for PlayerModsRemovedEffect {
+    type Variant = PlayerModsRemovedEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Player }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.player_id) }
+
+    fn variant(&self) -> Self::Variant {
+        PlayerModsRemovedEffectVariant::new(self.mods.clone(), self.duration)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerModsRemovedEffectVariant {
+    mods: Vec<String>,
+    duration: ModDuration,
+}
+
+impl PlayerModsRemovedEffectVariant {
+    pub fn new(mods: Vec<String>, duration: ModDuration) -> Self {
+        Self { mods, duration }
+    }
+}
+
+impl EffectVariant for PlayerModsRemovedEffectVariant {
+    type EntityType = Player;
+
+    fn forward(&self, player: &mut Player) {
+        player.mods_mut(self.duration).retain(|mod_name| !self.mods.contains(mod_name));
+    }
+
+    fn reverse(&mut self, old_player: &Player, new_player: &mut Player) {
+        *new_player.mods_mut(self.duration) = old_player.mods(self.duration).to_vec();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CoffeeBeanEffect {
+    player_id: Uuid,
+    is_wired: bool,
+}
+
+impl CoffeeBeanEffect {
+    pub fn new(player_id: Uuid, is_wired: bool) -> Self {
+        Self { player_id, is_wired }
+    }
+}
+
+impl Effect for CoffeeBeanEffect {
+    type Variant = CoffeeBeanEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Player }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.player_id) }
+
+    fn variant(&self) -> Self::Variant {
+        CoffeeBeanEffectVariant::new(self.is_wired)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CoffeeBeanEffectVariant {
+    is_wired: bool,
+}
+
+impl CoffeeBeanEffectVariant {
+    pub fn new(is_wired: bool) -> Self {
+        Self { is_wired }
+    }
+}
+
+impl EffectVariant for CoffeeBeanEffectVariant {
+    type EntityType = Player;
+
+    fn forward(&self, player: &mut Player) {
+        // A bean flips a player who's already Wired or Tired to the other one. Otherwise the event
+        // says which one they got.
+        let mods = player.mods_mut(ModDuration::Game);
+        let new_mod = if mods.iter().any(|m| m == "WIRED") {
+            "TIRED"
+        } else if mods.iter().any(|m| m == "TIRED") {
+            "WIRED"
+        } else if self.is_wired {
+            "WIRED"
+        } else {
+            "TIRED"
+        };
+        mods.retain(|m| m != "WIRED" && m != "TIRED");
+        mods.push(new_mod.to_string());
+    }
+
+    fn reverse(&mut self, old_player: &Player, new_player: &mut Player) {
+        *new_player.mods_mut(ModDuration::Game) = old_player.mods(ModDuration::Game).to_vec();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TeamWonInternetSeriesEffect {
+    event: Arc<FedEventTeamWonInternetSeries>,
+}
+
+impl TeamWonInternetSeriesEffect {
+    pub fn new(event: FedEventTeamWonInternetSeries) -> Self {
+        Self { event: Arc::new(event) }
+    }
+}
+
+impl Effect for TeamWonInternetSeriesEffect {
+    type Variant = TeamWonInternetSeriesEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Team }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.event.team_id) }
+
+    fn variant(&self) -> Self::Variant {
+        TeamWonInternetSeriesEffectVariant::new()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TeamWonInternetSeriesEffectVariant;
+
+impl TeamWonInternetSeriesEffectVariant {
+    pub fn new() -> Self { Self }
+}
+
+impl EffectVariant for TeamWonInternetSeriesEffectVariant {
+    type EntityType = Team;
+
+    fn forward(&self, team: &mut Team) {
+        team.championships += 1;
+    }
+
+    fn reverse(&mut self, _: &Team, new_team: &mut Team) {
+        new_team.championships -= 1;
+    }
+}
+
+// The sim phase for after the internet series is won and before the election. Season rollover
+// waits for the sim to reach this.
+pub const POSTSEASON_END_PHASE: i32 = 12;
+
+#[derive(Clone, Debug)]
+pub struct PostseasonEndEffect;
+
+impl Effect for PostseasonEndEffect {
+    type Variant = PostseasonEndEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Sim }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(Uuid::nil()) }
+
+    fn variant(&self) -> Self::Variant {
+        PostseasonEndEffectVariant
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PostseasonEndEffectVariant;
+
+impl EffectVariant for PostseasonEndEffectVariant {
+    type EntityType = Sim;
+
+    fn forward(&self, sim: &mut Sim) {
+        sim.phase = POSTSEASON_END_PHASE;
+        // There are no more playoff rounds to play
+        sim.play_off_round = None;
+    }
+
+    fn reverse(&mut self, old_sim: &Sim, new_sim: &mut Sim) {
+        new_sim.phase = old_sim.phase;
+        new_sim.play_off_round = old_sim.play_off_round;
+    }
+}
+
+// Party boosts only go to players on teams that are in party time. Anything else is most likely a
+// data error, or a sign that blarser missed the TeamEnteredPartyTime event. Only what rules out
+// every possible version of the player and their team counts. The rest of the event still happens,
+// so these are recorded without skipping it, and the boost itself is left out (see into_effects).
+// Neither of these stop the incineration. Its effects leave a dead player dead and a roster
+// without the victim alone, and the observations afterwards will say what really happened.
+fn incineration_failures(player_id: Uuid, team_id: Uuid, state: &StateGraph) -> Vec<PreconditionFailure> {
+    let mut failures = Vec::new();
+    // Untracked entities have no versions, so they can't fail either check
+    let deceased = state.query_player_all(player_id, |player| player.deceased == Some(true));
+    if !deceased.is_empty() && !deceased.contains(&false) {
+        failures.push(PreconditionFailure {
+            entity_type: EntityType::Player,
+            entity_id: player_id,
+            property: "deceased".to_string(),
+            message: format!("Player {player_id} was incinerated, but they were already dead"),
+            skips_event: false,
+        });
+    }
+    let on_roster = state.query_team_all(team_id, |team| team.roster_slot_of(player_id).is_some());
+    if !on_roster.is_empty() && !on_roster.contains(&true) {
+        failures.push(PreconditionFailure {
+            entity_type: EntityType::Team,
+            entity_id: team_id,
+            property: "lineup".to_string(),
+            message: format!("Player {player_id} was incinerated and replaced on team {team_id}, but they weren't on its roster"),
+            skips_event: false,
+        });
+    }
+    failures
+}
+
+fn party_eligibility_failures(player_id: Uuid, state: &StateGraph) -> Vec<PreconditionFailure> {
+    // Players that aren't being tracked can't be checked
+    if state.entity_graph(EntityType::Player, player_id).is_none() { return Vec::new(); }
+    let team_ids = state.query_player_all(player_id, |player| player.league_team_id)
+        .into_iter()
+        .flatten()
+        .collect_vec();
+    if team_ids.is_empty() {
+        return vec![PreconditionFailure {
+            entity_type: EntityType::Player,
+            entity_id: player_id,
+            property: "leagueTeamId".to_string(),
+            message: format!("Player {player_id} got a party boost but isn't on a team"),
+            skips_event: false,
+        }];
+    }
+    // Teams that aren't being tracked can't be checked either
+    if team_ids.iter().any(|&team_id| state.entity_graph(EntityType::Team, team_id).is_none()) {
+        return Vec::new();
+    }
+
+    let might_be_partying = team_ids.iter()
+        .any(|&team_id| state.query_team_all(team_id, |team| team.is_in_party_time()).contains(&true));
+    if might_be_partying {
+        Vec::new()
+    } else {
+        team_ids.into_iter()
+            .map(|team_id| PreconditionFailure {
+                entity_type: EntityType::Team,
+                entity_id: team_id,
+                property: ModDuration::Season.attr_name().to_string(),
+                message: format!("Player {player_id} got a party boost but their team {team_id} isn't in party time"),
+                skips_event: false,
+            })
+            .collect()
+    }
+}
+
+fn expiring_mod_failures(player_id: Uuid, mods: &[String], duration: ModDuration, state: &StateGraph) -> Vec<PreconditionFailure> {
+    // Players that aren't being tracked can't be checked
+    if state.entity_graph(EntityType::Player, player_id).is_none() { return Vec::new(); }
+    // Only a mod that none of the player's possible versions have is definitely missing
+    let possible_mods = state.query_player_all(player_id, |player| player.mods(duration).to_vec());
+
+    mods.iter()
+        .filter(|mod_name| !possible_mods.iter().any(|current_mods| current_mods.contains(mod_name)))
+        .map(|mod_name| PreconditionFailure {
+            entity_type: EntityType::Player,
+            entity_id: player_id,
+            property: duration.attr_name().to_string(),
+            message: format!("Player {player_id}'s {mod_name} mod expired but it wasn't in their {}",
+                             duration.attr_name()),
+            skips_event: true,
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct PlayerAttributesAdjustedEffect {
+    player_id: Uuid,
+    lower: f32,
+    upper: f32,
+}
+
+impl PlayerAttributesAdjustedEffect {
+    pub fn new(player_id: Uuid, lower: f32, upper: f32) -> Self {
+        Self { player_id, lower, upper }
+    }
+}
+
+impl Effect for PlayerAttributesAdjustedEffect {
+    type Variant = PlayerAttributesAdjustedEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Player }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.player_id) }
+
+    fn variant(&self) -> Self::Variant {
+        PlayerAttributesAdjustedEffectVariant::new(self.lower, self.upper)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerAttributesAdjustedEffectVariant {
+    lower: f32,
+    upper: f32,
+}
+
+impl PlayerAttributesAdjustedEffectVariant {
+    pub fn new(lower: f32, upper: f32) -> Self {
+        Self { lower, upper }
+    }
+}
+
+impl EffectVariant for PlayerAttributesAdjustedEffectVariant {
+    type EntityType = Player;
+
+    fn forward(&self, player: &mut Player) {
+        player.adjust_attributes(self.lower, self.upper);
+    }
+
+    fn reverse(&mut self, old_player: &Player, new_player: &mut Player) {
+        new_player.restore_attributes(old_player);
+    }
+}
+
+// The event doesn't say how big a boost was. This is an estimate that's wide enough for the boosts
+// that have been observed; the observation afterwards narrows it down, and one outside the range
+// shows up as a conflict.
+pub const PLAYER_BOOST: std::ops::RangeInclusive<f32> = 0.0..=0.2;
+
+// Parties boost every attribute by a random amount in this range. The exact amount isn't in the
+// event, so each attribute tracks the range until the player is next observed.
+pub const PARTY_BOOST: std::ops::RangeInclusive<f32> = 0.04..=0.08;
+
+#[derive(Clone, Debug)]
+pub struct PartyBoostEffect {
+    player_id: Uuid,
+}
+
+impl PartyBoostEffect {
+    pub fn new(player_id: Uuid) -> Self {
+        Self { player_id }
+    }
+}
+
+impl Effect for PartyBoostEffect {
+    type Variant = PartyBoostEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Player }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.player_id) }
+
+    fn variant(&self) -> Self::Variant {
+        PartyBoostEffectVariant
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PartyBoostEffectVariant;
+
+impl EffectVariant for PartyBoostEffectVariant {
+    type EntityType = Player;
+
+    fn forward(&self, player: &mut Player) {
+        player.adjust_attributes(*PARTY_BOOST.start(), *PARTY_BOOST.end());
+    }
+
+    fn reverse(&mut self, old_player: &Player, new_player: &mut Player) {
+        new_player.restore_attributes(old_player);
+    }
+}
+
+// Anything election_effects would have to give up on is reported here, so it ends up as a conflict
+// instead of being silently dropped
+fn election_failures(effects: &[ElectionEffect], state: &StateGraph) -> Vec<PreconditionFailure> {
+    effects.iter()
+        .flat_map(|effect| -> Vec<PreconditionFailure> {
+            match effect {
+                ElectionEffect::TeamModAdded { team_id, mod_type, .. } => {
+                    mod_type_failures(EntityType::Team, *team_id, *mod_type)
+                }
+                ElectionEffect::PlayerMoved { player_id, from_team_id, .. } => {
+                    // Teams that aren't being tracked can't be checked
+                    if state.entity_graph(EntityType::Team, *from_team_id).is_none() { return Vec::new(); }
+                    if moved_player_slot(*player_id, *from_team_id, state).is_some() { return Vec::new(); }
+                    vec![PreconditionFailure {
+                        entity_type: EntityType::Team,
+                        entity_id: *from_team_id,
+                        property: "lineup".to_string(),
+                        message: format!("Player {player_id} moved away from team {from_team_id}, but \
+                                          they aren't in exactly one of its roster slots"),
+                        skips_event: true,
+                    }]
+                }
+                _ => { Vec::new() }
+            }
+        })
+        .collect()
+}
+
+// Fed events carry mod types as plain numbers, so this is where an unexpected one gets caught
+fn mod_type_failures(entity_type: EntityType, entity_id: Uuid, mod_type: i32) -> Vec<PreconditionFailure> {
+    match ModDuration::try_from(mod_type) {
+        Ok(_) => { Vec::new() }
+        Err(mod_type) => {
+            vec![PreconditionFailure {
+                entity_type,
+                entity_id,
+                property: "modType".to_string(),
+                message: format!("Unexpected mod type {mod_type}"),
+                skips_event: true,
+            }]
+        }
+    }
+}
+
+// The player keeps the same role on their new team. This is None if the team doesn't have them,
+// or if its possible versions disagree about where they are.
+fn moved_player_slot(player_id: Uuid, from_team_id: Uuid, state: &StateGraph) -> Option<RosterSlot> {
+    if state.entity_graph(EntityType::Team, from_team_id).is_none() { return None; }
+    state.query_team_all(from_team_id, |team| team.roster_slot_of(player_id))
+        .into_iter()
+        .exactly_one()
+        .ok()
+        .flatten()
+}
+
+// Readings only ever change team mods. They share the outcome type with elections, but anything
+// else showing up in one means something is off.
+fn tarot_reading_effects(effects: &[ElectionEffect]) -> Vec<AnyEffect> {
+    effects.iter()
+        .flat_map(|effect| -> Vec<AnyEffect> {
+            match effect {
+                ElectionEffect::TeamModAdded { team_id, mod_name, mod_type } => {
+                    let Ok(duration) = ModDuration::try_from(*mod_type) else {
+                        warn!("Tarot reading gave team {team_id} {mod_name} with unexpected mod type {mod_type}; ignoring it");
+                        return Vec::new();
+                    };
+                    vec![TeamModAddedEffect::new(*team_id, mod_name.clone(), duration).into()]
+                }
+                other => {
+                    warn!("Tarot reading outcome {other:?} isn't implemented yet; ignoring it");
+                    Vec::new()
+                }
+            }
+        })
+        .collect()
+}
+
+// None if the player or their team isn't tracked, or if the player's possible versions disagree
+// about their team
+fn unshelled_player_team(player_id: Uuid, state: &StateGraph) -> Option<Uuid> {
+    let team_id = state.query_player_all(player_id, |player| player.league_team_id)
+        .into_iter()
+        .exactly_one()
+        .ok()
+        .flatten()?;
+    state.entity_graph(EntityType::Team, team_id).map(|_| team_id)
+}
+
+fn election_effects(effects: &[ElectionEffect], state: &StateGraph) -> Vec<AnyEffect> {
+    effects.iter()
+        .flat_map(|effect| -> Vec<AnyEffect> {
+            match effect {
+                ElectionEffect::TeamModAdded { team_id, mod_name, mod_type } => {
+                    // precondition_failures already reported this
+                    let Ok(duration) = ModDuration::try_from(*mod_type) else { return Vec::new(); };
+                    vec![TeamModAddedEffect::new(*team_id, mod_name.clone(), duration).into()]
+                }
+                ElectionEffect::PlayerMoved { player_id, from_team_id, to_team_id } => {
+                    let Some(slot) = moved_player_slot(*player_id, *from_team_id, state) else {
+                        // Either precondition_failures already reported this or the team isn't
+                        // tracked, and either way there's nowhere to put them on the new team
+                        warn!("Couldn't tell which roster slot player {player_id} moved from; skipping the move");
+                        return Vec::new();
+                    };
+                    vec![
+                        PlayerLeftTeamEffect::new(*from_team_id, *player_id).into(),
+                        PlayerJoinedTeamEffect::new(*to_team_id, *player_id, slot).into(),
+                        PlayerTeamChangedEffect::new(*player_id, *to_team_id).into(),
+                    ]
+                }
+                other => {
+                    // Same reasoning as unimplemented Fed events: whatever this should have changed
+                    // shows up as conflicts when it's observed
+                    warn!("Election effect {other:?} isn't implemented yet; ignoring it");
+                    Vec::new()
+                }
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct TeamModAddedEffect {
+    team_id: Uuid,
+    mod_name: String,
+    duration: ModDuration,
+}
+
+impl TeamModAddedEffect {
+    pub fn new(team_id: Uuid, mod_name: String, duration: ModDuration) -> Self {
+        Self { team_id, mod_name, duration }
+    }
+}
+
+impl Effect for TeamModAddedEffect {
+    type Variant = TeamModAddedEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Team }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.team_id) }
+
+    fn variant(&self) -> Self::Variant {
+        TeamModAddedEffectVariant::new(self.mod_name.clone(), self.duration)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TeamModAddedEffectVariant {
+    mod_name: String,
+    duration: ModDuration,
+}
+
+impl TeamModAddedEffectVariant {
+    pub fn new(mod_name: String, duration: ModDuration) -> Self {
+        Self { mod_name, duration }
+    }
+}
+
+impl EffectVariant for TeamModAddedEffectVariant {
+    type EntityType = Team;
+
+    fn forward(&self, team: &mut Team) {
+        team.mods_mut(self.duration).push(self.mod_name.clone());
+    }
+
+    fn reverse(&mut self, old_team: &Team, new_team: &mut Team) {
+        *new_team.mods_mut(self.duration) = old_team.mods(self.duration).to_vec();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TeamModRemovedEffect {
+    team_id: Uuid,
+    mod_name: String,
+    duration: ModDuration,
+}
+
+impl TeamModRemovedEffect {
+    pub fn new(team_id: Uuid, mod_name: String, duration: ModDuration) -> Self {
+        Self { team_id, mod_name, duration }
+    }
+}
+
+impl Effect for TeamModRemovedEffect {
+    type Variant = TeamModRemovedEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Team }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.team_id) }
+
+    fn variant(&self) -> Self::Variant {
+        TeamModRemovedEffectVariant::new(self.mod_name.clone(), self.duration)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TeamModRemovedEffectVariant {
+    mod_name: String,
+    duration: ModDuration,
+}
+
+impl TeamModRemovedEffectVariant {
+    pub fn new(mod_name: String, duration: ModDuration) -> Self {
+        Self { mod_name, duration }
+    }
+}
+
+impl EffectVariant for TeamModRemovedEffectVariant {
+    type EntityType = Team;
+
+    fn forward(&self, team: &mut Team) {
+        team.mods_mut(self.duration).retain(|mod_name| mod_name != &self.mod_name);
+    }
+
+    fn reverse(&mut self, old_team: &Team, new_team: &mut Team) {
+        *new_team.mods_mut(self.duration) = old_team.mods(self.duration).to_vec();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TeamWinStreakEffect {
+    team_id: Uuid,
+    won: bool,
+}
+
+impl TeamWinStreakEffect {
+    pub fn new(team_id: Uuid, won: bool) -> Self {
+        Self { team_id, won }
+    }
+}
+
+impl Effect for TeamWinStreakEffect {
+    type Variant = TeamWinStreakEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Team }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.team_id) }
+
+    fn variant(&self) -> Self::Variant {
+        TeamWinStreakEffectVariant::new(self.won)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TeamWinStreakEffectVariant {
+    won: bool,
+}
+
+impl TeamWinStreakEffectVariant {
+    pub fn new(won: bool) -> Self {
+        Self { won }
+    }
+}
+
+impl EffectVariant for TeamWinStreakEffectVariant {
+    type EntityType = Team;
+
+    fn forward(&self, team: &mut Team) {
+        // Some eras don't have win_streak at all. There's nothing to reconstruct in that case,
+        // and the observation will say so if it shows up later.
+        let Some(win_streak) = team.win_streak.as_mut() else {
+            warn!("Team {} has no win_streak; not updating it", team.id);
+            return;
+        };
+
+        // win_streak is a weird value whose magnitude indicates length of streak and sign indicates
+        // whether it is a winning or losing streak
+        if self.won {
+            *win_streak = max(*win_streak, 0) + 1;
+        } else {
+            *win_streak = min(*win_streak, 0) - 1;
+        }
+    }
+
+    fn reverse(&mut self, old_team: &Team, new_team: &mut Team) {
+        new_team.win_streak = old_team.win_streak;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PlayerLeftTeamEffect {
+    team_id: Uuid,
+    player_id: Uuid,
+}
+
+impl PlayerLeftTeamEffect {
+    pub fn new(team_id: Uuid, player_id: Uuid) -> Self {
+        Self { team_id, player_id }
+    }
+}
+
+impl Effect for PlayerLeftTeamEffect {
+    type Variant = PlayerLeftTeamEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Team }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.team_id) }
+
+    fn variant(&self) -> Self::Variant {
+        PlayerLeftTeamEffectVariant::new(self.player_id)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerLeftTeamEffectVariant {
+    player_id: Uuid,
+}
+
+impl PlayerLeftTeamEffectVariant {
+    pub fn new(player_id: Uuid) -> Self {
+        Self { player_id }
+    }
+}
+
+impl EffectVariant for PlayerLeftTeamEffectVariant {
+    type EntityType = Team;
+
+    fn forward(&self, team: &mut Team) {
+        let slot = team.roster_slot_of(self.player_id)
+            .expect("Player who left must have been on the team");
+        team.roster_mut(slot).retain(|&id| id != self.player_id);
+    }
+
+    fn reverse(&mut self, old_team: &Team, new_team: &mut Team) {
+        let slot = old_team.roster_slot_of(self.player_id)
+            .expect("Player who left must have been on the team");
+        *new_team.roster_mut(slot) = old_team.roster(slot).to_vec();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PlayerJoinedTeamEffect {
+    team_id: Uuid,
+    player_id: Uuid,
+    slot: RosterSlot,
+}
+
+impl PlayerJoinedTeamEffect {
+    pub fn new(team_id: Uuid, player_id: Uuid, slot: RosterSlot) -> Self {
+        Self { team_id, player_id, slot }
+    }
+}
+
+impl Effect for PlayerJoinedTeamEffect {
+    type Variant = PlayerJoinedTeamEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Team }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.team_id) }
+
+    fn variant(&self) -> Self::Variant {
+        PlayerJoinedTeamEffectVariant::new(self.player_id, self.slot)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerJoinedTeamEffectVariant {
+    player_id: Uuid,
+    slot: RosterSlot,
+}
+
+impl PlayerJoinedTeamEffectVariant {
+    pub fn new(player_id: Uuid, slot: RosterSlot) -> Self {
+        Self { player_id, slot }
+    }
+}
+
+impl EffectVariant for PlayerJoinedTeamEffectVariant {
+    type EntityType = Team;
+
+    fn forward(&self, team: &mut Team) {
+        // New arrivals always go at the end
+        team.roster_mut(self.slot).push(self.player_id);
+    }
+
+    fn reverse(&mut self, old_team: &Team, new_team: &mut Team) {
+        *new_team.roster_mut(self.slot) = old_team.roster(self.slot).to_vec();
+    }
+}
+
+// Shelled players usually stay on the roster and just get skipped, but if they were taken off it
+// in the meantime, being freed puts them back in the lineup
+#[derive(Clone, Debug)]
+pub struct PlayerRejoinedTeamEffect {
+    team_id: Uuid,
+    player_id: Uuid,
+}
+
+impl PlayerRejoinedTeamEffect {
+    pub fn new(team_id: Uuid, player_id: Uuid) -> Self {
+        Self { team_id, player_id }
+    }
+}
+
+impl Effect for PlayerRejoinedTeamEffect {
+    type Variant = PlayerRejoinedTeamEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Team }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.team_id) }
+
+    fn variant(&self) -> Self::Variant {
+        PlayerRejoinedTeamEffectVariant::new(self.player_id)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerRejoinedTeamEffectVariant {
+    player_id: Uuid,
+}
+
+impl PlayerRejoinedTeamEffectVariant {
+    pub fn new(player_id: Uuid) -> Self {
+        Self { player_id }
+    }
+}
+
+impl EffectVariant for PlayerRejoinedTeamEffectVariant {
+    type EntityType = Team;
+
+    fn forward(&self, team: &mut Team) {
+        if team.roster_slot_of(self.player_id).is_none() {
+            team.lineup.push(self.player_id);
+        }
+    }
+
+    fn reverse(&mut self, old_team: &Team, new_team: &mut Team) {
+        new_team.lineup = old_team.lineup.clone();
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PlayerTeamChangedEffect {
+    player_id: Uuid,
+    team_id: Uuid,
+}
+
+impl PlayerTeamChangedEffect {
+    pub fn new(player_id: Uuid, team_id: Uuid) -> Self {
+        Self { player_id, team_id }
+    }
+}
+
+impl Effect for PlayerTeamChangedEffect {
+    type Variant = PlayerTeamChangedEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Player }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.player_id) }
+
+    fn variant(&self) -> Self::Variant {
+        PlayerTeamChangedEffectVariant::new(self.team_id)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerTeamChangedEffectVariant {
+    team_id: Uuid,
+}
+
+impl PlayerTeamChangedEffectVariant {
+    pub fn new(team_id: Uuid) -> Self {
+        Self { team_id }
+    }
+}
+
+impl EffectVariant for PlayerTeamChangedEffectVariant {
+    type EntityType = Player;
+
+    fn forward(&self, player: &mut Player) {
+        player.league_team_id = Some(self.team_id);
+    }
+
+    fn reverse(&mut self, old_player: &Player, new_player: &mut Player) {
+        new_player.league_team_id = old_player.league_team_id;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PlayerIncineratedEffect {
+    player_id: Uuid,
+}
+
+impl PlayerIncineratedEffect {
+    pub fn new(player_id: Uuid) -> Self {
+        Self { player_id }
+    }
+}
+
+impl Effect for PlayerIncineratedEffect {
+    type Variant = PlayerIncineratedEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Player }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.player_id) }
+
+    fn variant(&self) -> Self::Variant {
+        PlayerIncineratedEffectVariant
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerIncineratedEffectVariant;
+
+impl EffectVariant for PlayerIncineratedEffectVariant {
+    type EntityType = Player;
+
+    // A player who's already dead is reported by incineration_failures
+    fn forward(&self, player: &mut Player) {
+        player.deceased = Some(true);
+    }
+
+    fn reverse(&mut self, old_player: &Player, new_player: &mut Player) {
+        new_player.deceased = old_player.deceased;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PlayerReplacedEffect {
+    team_id: Uuid,
+    player_id: Uuid,
+    replacement_id: Uuid,
+}
+
+impl PlayerReplacedEffect {
+    pub fn new(team_id: Uuid, player_id: Uuid, replacement_id: Uuid) -> Self {
+        Self { team_id, player_id, replacement_id }
+    }
+}
+
+impl Effect for PlayerReplacedEffect {
+    type Variant = PlayerReplacedEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Team }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.team_id) }
+
+    fn variant(&self) -> Self::Variant {
+        PlayerReplacedEffectVariant::new(self.player_id, self.replacement_id)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PlayerReplacedEffectVariant {
+    player_id: Uuid,
+    replacement_id: Uuid,
+}
+
+impl PlayerReplacedEffectVariant {
+    pub fn new(player_id: Uuid, replacement_id: Uuid) -> Self {
+        Self { player_id, replacement_id }
+    }
+}
+
+impl EffectVariant for PlayerReplacedEffectVariant {
+    type EntityType = Team;
+
+    // If the player isn't on the team there's nobody to replace. incineration_failures reports it.
+    fn forward(&self, team: &mut Team) {
+        // Unlike leaving and joining, the replacement takes the exact spot of the player they replace
+        let Some(slot) = team.roster_slot_of(self.player_id) else { return };
+        for id in team.roster_mut(slot) {
+            if *id == self.player_id {
+                *id = self.replacement_id;
+            }
+        }
+    }
+
+    fn reverse(&mut self, old_team: &Team, new_team: &mut Team) {
+        let Some(slot) = old_team.roster_slot_of(self.player_id) else { return };
+        *new_team.roster_mut(slot) = old_team.roster(slot).to_vec();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::{test_game, test_player, test_sim, test_stadium, test_team, with_runners};
+    use crate::ingest::{test_state, AddedReason};
+
+    // Feed events can't be built by hand, so tests use the first real one of the right kind
+    fn first_fed_event(is_kind: impl Fn(&FedEventFlat) -> bool) -> BaseFedEvent {
+        fed::expansion_era_events()
+            .map(|event| event.expect("Expansion era events should parse"))
+            .find(|event| is_kind(&EnumFlatten::flatten(event.clone())))
+            .expect("The expansion era should have an event of this kind")
+    }
+
+    #[test]
+    fn fireproof_incineration_advances_the_game_without_incinerating_anyone() {
+        let event = first_fed_event(|event| matches!(event, FedEventFlat::FireproofIncineration(_)));
+        let FedEventFlat::FireproofIncineration(flat) = EnumFlatten::flatten(event.clone()) else {
+            panic!("first_fed_event should have found a FireproofIncineration");
+        };
+        let effects = AnyEvent::from(FedEvent::new(event)).into_effects(&StateGraph::new());
+        let [AnyEffect::FireproofIncineration(effect)] = &effects[..] else {
+            panic!("Fireproof incineration should only change the game, but it had effects {effects:?}");
+        };
+
+        let mut old_game = test_game();
+        old_game.play_count = flat.game.play;
+        old_game.last_update = None;
+        let mut game = old_game.clone();
+        let mut variant = effect.variant();
+        variant.forward(&mut game);
+        assert_eq!(game.play_count, old_game.play_count + 1);
+        assert!(game.last_update.is_some());
+
+        variant.reverse(&old_game, &mut game);
+        assert_eq!(game, old_game);
+    }
+
+    #[test]
+    fn salmon_swim_resets_the_inning_but_keeps_the_score() {
+        let event = first_fed_event(|event| matches!(event, FedEventFlat::SalmonSwim(_)));
+        let FedEventFlat::SalmonSwim(flat) = EnumFlatten::flatten(event.clone()) else {
+            panic!("first_fed_event should have found a SalmonSwim");
+        };
+        let effects = AnyEvent::from(FedEvent::new(event)).into_effects(&StateGraph::new());
+        let [AnyEffect::SalmonSwim(effect)] = &effects[..] else {
+            panic!("Salmon swimming upstream should only change the game, but it had effects {effects:?}");
+        };
+
+        let mut old_game = with_runners(test_game(), &[Base::First]);
+        old_game.play_count = flat.game.play;
+        old_game.half_inning_outs = 2;
+        old_game.half_inning_score = 2.0;
+        old_game.top_inning_score = 2.0;
+        old_game.away.score = Some(5.0);
+        let mut game = old_game.clone();
+        let mut variant = effect.variant();
+        variant.forward(&mut game);
+        assert_eq!(game.half_inning_score, 0.0);
+        assert_eq!(game.top_inning_score, 0.0);
+        assert_eq!(game.bottom_inning_score, 0.0);
+        assert_eq!(game.half_inning_outs, 0);
+        assert!(game.base_runners.is_empty());
+        // Runs already scored stay on the board
+        assert_eq!(game.away.score, Some(5.0));
+
+        variant.reverse(&old_game, &mut game);
+        assert_eq!(game, old_game);
+    }
+
+    #[test]
+    fn tarot_reading_adds_team_mods() {
+        let team_id = Uuid::new_v4();
+        let effects = tarot_reading_effects(&[ElectionEffect::TeamModAdded {
+            team_id,
+            mod_name: "FOURTH_STRIKE".to_string(),
+            mod_type: 1,
+        }]);
+
+        assert_eq!(effects.len(), 1);
+        assert_eq!(effects[0].entity_type(), EntityType::Team);
+        assert_eq!(effects[0].entity_id(), Some(team_id));
+    }
+
+    #[test]
+    fn tarot_reading_skips_unknown_mod_types() {
+        let effects = tarot_reading_effects(&[ElectionEffect::TeamModAdded {
+            team_id: Uuid::new_v4(),
+            mod_name: "FOURTH_STRIKE".to_string(),
+            mod_type: 17,
+        }]);
+
+        assert!(effects.is_empty());
+    }
+
+    fn apply_effects(state: &mut StateGraph, effects: Vec<AnyEffect>) {
+        let time = Utc::now();
+        for effect in effects {
+            for id in state.ids_for(&effect) {
+                state.entity_graph_mut(effect.entity_type(), id)
+                    .expect("Test effects should only target entities in the state")
+                    .apply_effect(&effect, time)
+                    .expect("Test effects should apply");
+            }
+        }
+    }
+
+    #[test]
+    fn blessing_adds_a_team_mod() {
+        let team = test_team(Vec::new(), Vec::new());
+        let mut state = test_state([team.clone().into()]);
+        let blessing = [ElectionEffect::TeamModAdded {
+            team_id: team.id,
+            mod_name: "FOURTH_STRIKE".to_string(),
+            mod_type: 1,
+        }];
+        assert!(election_failures(&blessing, &state).is_empty());
+
+        let effects = election_effects(&blessing, &state);
+        apply_effects(&mut state, effects);
+        assert_eq!(state.query_team_all(team.id, |t| t.mods(ModDuration::Season).to_vec()),
+                   vec![vec!["FOURTH_STRIKE".to_string()]]);
+    }
+
+    #[test]
+    fn blessing_moves_a_player_into_the_same_slot_on_their_new_team() {
+        let mut player = test_player(None);
+        let from_team = test_team(Vec::new(), vec![Uuid::new_v4(), player.id]);
+        let to_team = test_team(Vec::new(), vec![Uuid::new_v4()]);
+        player.league_team_id = Some(from_team.id);
+        let mut state = test_state([from_team.clone().into(), to_team.clone().into(), player.clone().into()]);
+        let moved = [ElectionEffect::PlayerMoved { player_id: player.id, from_team_id: from_team.id, to_team_id: to_team.id }];
+        assert!(election_failures(&moved, &state).is_empty());
+
+        let effects = election_effects(&moved, &state);
+        apply_effects(&mut state, effects);
+        assert_eq!(state.query_team_all(from_team.id, |t| t.roster_slot_of(player.id)), vec![None]);
+        assert_eq!(state.query_team_all(to_team.id, |t| t.roster_slot_of(player.id)), vec![Some(RosterSlot::Rotation)]);
+        assert_eq!(state.query_player_all(player.id, |p| p.league_team_id), vec![Some(to_team.id)]);
+    }
+
+    fn echo_test_state() -> (Player, Player, StateGraph) {
+        let mut source = test_player(None);
+        source.mods_mut(ModDuration::Permanent).push("FIREPROOF".to_string());
+        source.mods_mut(ModDuration::Season).push("SWIM_BLADDER".to_string());
+        // Game mods are about to expire, so echoes leave them behind
+        source.mods_mut(ModDuration::Game).push("OVERPERFORMING".to_string());
+        let mut receiver = test_player(None);
+        receiver.mods_mut(ModDuration::Game).push("TRIPLE_THREAT".to_string());
+        let state = test_state([source.clone().into(), receiver.clone().into()]);
+        (source, receiver, state)
+    }
+
+    // For effects that need a real game event but don't care which one
+    fn any_game_event() -> fed::GameEvent {
+        let event = first_fed_event(|event| matches!(event, FedEventFlat::FireproofIncineration(_)));
+        let FedEventFlat::FireproofIncineration(flat) = EnumFlatten::flatten(event) else {
+            panic!("first_fed_event should have found a FireproofIncineration");
+        };
+        flat.game
+    }
+
+    #[test]
+    fn echo_copies_lasting_mods_to_the_receiver() {
+        let (source, receiver, mut state) = echo_test_state();
+        let effects = echo_effects(&any_game_event(), receiver.id, source.id, 3, String::new(), &state);
+        let [AnyEffect::GameUpdate(_), player_effect] = &effects[..] else {
+            panic!("Echo should update the game and the receiver, but it had effects {effects:?}");
+        };
+        assert_eq!(player_effect.entity_id(), Some(receiver.id));
+
+        // The game isn't in the test state, so only apply the player half
+        apply_effects(&mut state, vec![player_effect.clone()]);
+        assert_eq!(state.query_player_all(receiver.id, |p| p.mods(ModDuration::Game).to_vec()),
+                   vec![vec!["TRIPLE_THREAT".to_string(), "FIREPROOF".to_string(), "SWIM_BLADDER".to_string()]]);
+        assert_eq!(state.query_player_all(source.id, |p| p.clone()), vec![source]);
+    }
+
+    #[test]
+    fn echo_into_static_clears_only_the_echoed_mods() {
+        let (source, receiver, mut state) = echo_test_state();
+        let effects = echo_effects(&any_game_event(), receiver.id, source.id, 3, String::new(), &state);
+        apply_effects(&mut state, effects.into_iter().skip(1).collect());
+        let echoed = state.query_player_unique(receiver.id, |p| p.clone());
+
+        // This is what EchoIntoStatic produces for the receiver
+        let mods = vec!["FIREPROOF".to_string(), "SWIM_BLADDER".to_string()];
+        let mut variant = PlayerModsRemovedEffect::new(receiver.id, mods, ModDuration::Game).variant();
+        let mut cleared = echoed.clone();
+        variant.forward(&mut cleared);
+        assert_eq!(cleared.mods(ModDuration::Game), &["TRIPLE_THREAT".to_string()]);
+        assert_eq!(cleared.mods(ModDuration::Permanent), receiver.mods(ModDuration::Permanent));
+
+        variant.reverse(&echoed, &mut cleared);
+        assert_eq!(cleared, echoed);
+    }
+
+    fn assert_reroll_widens_attributes(game: Option<fed::GameEvent>) {
+        let is_top_level = game.is_some();
+        let player = test_player(None);
+        let mut state = test_state([player.clone().into()]);
+        let effects = player_stat_reroll_effects(player.id, game, String::new());
+        let reroll = match &effects[..] {
+            [AnyEffect::GameUpdate(_), reroll] if is_top_level => { reroll.clone() }
+            [reroll] if !is_top_level => { reroll.clone() }
+            _ => { panic!("Unexpected reroll effects {effects:?}") }
+        };
+        assert_eq!(reroll.entity_id(), Some(player.id));
+
+        // The game isn't in the test state, so only apply the player half
+        assert!(!player.buoyancy.could_be(0.52));
+        apply_effects(&mut state, vec![reroll]);
+        let rerolled = state.query_player_unique(player.id, |p| p.clone());
+        assert!(rerolled.buoyancy.could_be(0.52));
+        assert!(rerolled.buoyancy.could_be(0.48));
+        assert!(!rerolled.buoyancy.could_be(0.6));
+    }
+
+    #[test]
+    fn top_level_snow_reroll_widens_attributes() {
+        assert_reroll_widens_attributes(Some(any_game_event()));
+    }
+
+    #[test]
+    fn child_reroll_widens_attributes() {
+        assert_reroll_widens_attributes(None);
+    }
+
+    #[test]
+    fn coffee_bean_gives_the_mod_the_event_says() {
+        for (is_wired, expected) in [(true, "WIRED"), (false, "TIRED")] {
+            let old_player = test_player(None);
+            let mut player = old_player.clone();
+            let mut variant = CoffeeBeanEffect::new(player.id, is_wired).variant();
+
+            variant.forward(&mut player);
+            assert_eq!(player.mods(ModDuration::Game), &[expected.to_string()]);
+            variant.reverse(&old_player, &mut player);
+            assert_eq!(player, old_player);
+        }
+    }
+
+    #[test]
+    fn coffee_bean_flips_wired_and_tired() {
+        for (before, after) in [("WIRED", "TIRED"), ("TIRED", "WIRED")] {
+            let mut old_player = test_player(None);
+            old_player.mods_mut(ModDuration::Game).extend(["TRIPLE_THREAT".to_string(), before.to_string()]);
+            let mut player = old_player.clone();
+            // The flip wins over what the event says
+            let mut variant = CoffeeBeanEffect::new(player.id, before == "TIRED").variant();
+
+            variant.forward(&mut player);
+            assert_eq!(player.mods(ModDuration::Game), &["TRIPLE_THREAT".to_string(), after.to_string()]);
+            variant.reverse(&old_player, &mut player);
+            assert_eq!(player, old_player);
+        }
+    }
+
+    #[test]
+    fn becoming_magmatic_adds_the_mod_once() {
+        let old_player = test_player(None);
+        let mut player = old_player.clone();
+        // This is what BecameMagmatic produces for the player
+        let mut variant = PlayerModsAddedEffect::new(player.id, vec!["MAGMATIC".to_string()], ModDuration::Permanent).variant();
+
+        variant.forward(&mut player);
+        assert_eq!(player.mods(ModDuration::Permanent), &["MAGMATIC".to_string()]);
+        variant.forward(&mut player);
+        assert_eq!(player.mods(ModDuration::Permanent), &["MAGMATIC".to_string()]);
+
+        variant.reverse(&old_player, &mut player);
+        assert_eq!(player, old_player);
+    }
+
+    #[test]
+    fn winning_the_internet_series_counts_a_championship() {
+        let old_team = test_team(Vec::new(), Vec::new());
+        let mut team = old_team.clone();
+        let mut variant = TeamWonInternetSeriesEffectVariant::new();
+
+        variant.forward(&mut team);
+        assert_eq!(team.championships, old_team.championships + 1);
+
+        variant.reverse(&old_team, &mut team);
+        assert_eq!(team, old_team);
+    }
+
+    #[test]
+    fn winning_the_internet_series_ends_the_postseason() {
+        let old_sim = test_sim();
+        let mut sim = old_sim.clone();
+        let mut variant = PostseasonEndEffect.variant();
+
+        variant.forward(&mut sim);
+        assert_eq!(sim.phase, POSTSEASON_END_PHASE);
+        assert_eq!(sim.play_off_round, None);
+
+        variant.reverse(&old_sim, &mut sim);
+        assert_eq!(sim, old_sim);
+    }
+
+    #[test]
+    fn mod_renovations_add_the_mod() {
+        let mut stadium = test_stadium();
+        apply_renovation(&mut stadium, "hoops_mod");
+
+        assert!(stadium.mods.contains(&"HOOPS".to_string()));
+        assert_eq!(stadium.reno_log.get("hoops_mod"), Some(&1));
+
+        // Building it again is logged, but doesn't duplicate the mod
+        apply_renovation(&mut stadium, "hoops_mod");
+        assert_eq!(stadium.mods.iter().filter(|m| *m == "HOOPS").count(), 1);
+        assert_eq!(stadium.reno_log.get("hoops_mod"), Some(&2));
+    }
+
+    #[test]
+    fn attribute_renovations_make_the_attribute_unknown() {
+        let mut stadium = test_stadium();
+        apply_renovation(&mut stadium, "grandiosity_up");
+
+        assert_eq!(stadium.grandiosity, MaybeKnown::Unknown);
+        assert_eq!(stadium.mysticism, MaybeKnown::Known(0.5));
+        assert_eq!(stadium.reno_log.get("grandiosity_up"), Some(&1));
+    }
+
+    #[test]
+    fn play_ball_counts_the_rotation_slot_even_when_its_unknown() {
+        let mut team = test_team(Vec::new(), Vec::new());
+        let mut variant = PlayBallTeamEffectVariant::new();
+        let old_team = team.clone();
+
+        variant.forward(&mut team);
+        assert_eq!(team.rotation_slot, MaybeKnown::Known(1));
+        variant.reverse(&old_team, &mut team);
+        assert_eq!(team.rotation_slot, MaybeKnown::Known(0));
+
+        team.rotation_slot = MaybeKnown::Unknown;
+        variant.forward(&mut team);
+        assert_eq!(team.rotation_slot, MaybeKnown::Unknown);
+    }
+
+    #[test]
+    fn unshelled_players_rejoin_their_team() {
+        let player = test_player(None);
+        let old_team = test_team(Vec::new(), Vec::new());
+        let mut team = old_team.clone();
+        let mut variant = PlayerRejoinedTeamEffectVariant::new(player.id);
+
+        variant.forward(&mut team);
+        assert_eq!(team.lineup, vec![player.id]);
+
+        variant.reverse(&old_team, &mut team);
+        assert_eq!(team, old_team);
+
+        // Players who never left the roster stay where they are
+        let mut team = test_team(Vec::new(), vec![player.id]);
+        variant.forward(&mut team);
+        assert!(team.lineup.is_empty());
+    }
+
+    #[test]
+    fn unshelled_players_go_back_to_their_own_team() {
+        let team = test_team(Vec::new(), Vec::new());
+        let player = test_player(Some(team.id));
+        let state = test_state([team.clone().into(), player.clone().into()]);
+
+        assert_eq!(unshelled_player_team(player.id, &state), Some(team.id));
+        assert_eq!(unshelled_player_team(Uuid::new_v4(), &state), None);
+    }
+
+    #[test]
+    fn lineup_sort_tries_every_place_for_unknown_ratings() {
+        let best = Uuid::new_v4();
+        let worst = Uuid::new_v4();
+        let unknown = Uuid::new_v4();
+        let candidates = sorted_lineup_candidates(vec![
+            (worst, Some(0.1)), (unknown, None), (best, Some(0.9)),
+        ]);
+
+        assert_eq!(candidates.len(), 3);
+        for candidate in &candidates {
+            let known_order = candidate.iter().filter(|id| **id != unknown).collect_vec();
+            assert_eq!(known_order, vec![&best, &worst]);
+        }
+        assert!(candidates.iter().map(|c| c.iter().position(|id| *id == unknown)).all_unique());
+    }
+
+    #[test]
+    fn lineup_sort_guesses_once_there_are_too_many_candidates() {
+        let known = (0..3).map(|i| (Uuid::new_v4(), Some(i as f32))).collect_vec();
+        let unknown = (0..6).map(|_| (Uuid::new_v4(), None)).collect_vec();
+        let candidates = sorted_lineup_candidates(known.iter().chain(&unknown).cloned().collect());
+
+        assert_eq!(candidates.len(), 1);
+        let expected = known.iter().rev().chain(&unknown).map(|(id, _)| *id).collect_vec();
+        assert_eq!(candidates[0], expected);
+    }
+
+    #[test]
+    fn batter_up_branches_on_an_ambiguous_lineup() {
+        let (a, b, c) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let team = test_team(vec![a, b, c], Vec::new());
+        let mut game = test_game();
+        game.top_of_inning = true;
+        game.away.team = team.id;
+        game.away.team_batter_count = Some(0);
+        let mut state = test_state([game.clone().into(), team.clone().into()]);
+
+        // The lineup sort could have gone either way
+        let time = Utc::now();
+        let graph = state.entity_graph_mut(EntityType::Team, team.id).unwrap();
+        let root = graph.roots()[0];
+        let mut resorted = team.clone();
+        resorted.lineup = vec![a, c, b];
+        let left = graph.add_child_version(root, team.clone().into(), time, LineupSortedEffectVariant::new(team.lineup.clone()).into(), AddedReason::NewFromEvent);
+        let right = graph.add_child_version(root, resorted.clone().into(), time, LineupSortedEffectVariant::new(resorted.lineup.clone()).into(), AddedReason::NewFromEvent);
+        graph.set_leafs(vec![left, right]);
+
+        let candidates = batter_up_candidates(&state, game.id);
+        assert_eq!(candidates.len(), 2);
+        assert!(candidates.contains(&(b, String::new())));
+        assert!(candidates.contains(&(c, String::new())));
+    }
+
+    #[test]
+    fn batter_up_with_an_empty_lineup_has_no_candidates() {
+        let team = test_team(Vec::new(), Vec::new());
+        let mut game = test_game();
+        game.top_of_inning = true;
+        game.away.team = team.id;
+        game.away.team_batter_count = Some(0);
+        let state = test_state([game.clone().into(), team.into()]);
+
+        assert!(batter_up_candidates(&state, game.id).is_empty());
+    }
+
+    fn party_test_state(in_party_time: bool) -> (Uuid, StateGraph) {
+        let mut team = test_team(Vec::new(), Vec::new());
+        if in_party_time {
+            team.mods_mut(ModDuration::Season).push("PARTY_TIME".to_string());
+        }
+        let player = test_player(Some(team.id));
+        (player.id, test_state([team.into(), player.into()]))
+    }
+
+    #[test]
+    fn party_boost_on_a_partying_team_is_eligible() {
+        let (player_id, state) = party_test_state(true);
+        assert!(party_eligibility_failures(player_id, &state).is_empty());
+    }
+
+    #[test]
+    fn party_boost_on_a_team_not_in_party_time_is_flagged_without_skipping_the_event() {
+        let (player_id, state) = party_test_state(false);
+        let failures = party_eligibility_failures(player_id, &state);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].entity_type, EntityType::Team);
+        assert!(!failures[0].skips_event);
+    }
+
+    #[test]
+    fn caught_stealing_mid_inning_keeps_the_at_bat() {
+        let old_game = with_runners(test_game(), &[Base::First]);
+        let runner_id = old_game.base_runners[0];
+
+        let mut game = old_game.clone();
+        caught_stealing_forward(&mut game, Some(runner_id), Base::Second);
+        assert!(game.base_runners.is_empty());
+        assert_eq!(game.half_inning_outs, 1);
+        assert_eq!(game.away.batter, old_game.away.batter);
+        assert_eq!(game.away.team_batter_count, old_game.away.team_batter_count);
+
+        caught_stealing_reverse(&old_game, &mut game);
+        assert_eq!(game, old_game);
+    }
+
+    #[test]
+    fn caught_stealing_for_the_third_out_ends_the_at_bat() {
+        let mut old_game = with_runners(test_game(), &[Base::Second, Base::First]);
+        old_game.half_inning_outs = 2;
+        let runner_id = old_game.base_runners[0];
+
+        let mut game = old_game.clone();
+        caught_stealing_forward(&mut game, Some(runner_id), Base::Third);
+        assert!(game.base_runners.is_empty());
+        assert_eq!(game.half_inning_outs, 0);
+        assert_eq!(game.away.batter, None);
+        assert_eq!(game.away.team_batter_count, Some(-1));
+
+        caught_stealing_reverse(&old_game, &mut game);
+        assert_eq!(game, old_game);
+    }
+
+    #[test]
+    fn caught_stealing_without_an_id_uses_the_only_possible_runner() {
+        let old_game = with_runners(test_game(), &[Base::Third, Base::First]);
+
+        let mut game = old_game.clone();
+        caught_stealing_forward(&mut game, None, Base::Second);
+        assert_eq!(game.base_runners, vec![old_game.base_runners[0]]);
+    }
+
+    #[test]
+    fn pitches_round_trip() {
+        for pitch in [Pitch::Ball, Pitch::Strike, Pitch::Foul] {
+            let mut old_game = test_game();
+            old_game.at_bat_balls = 1;
+            old_game.at_bat_strikes = 1;
+
+            let mut game = old_game.clone();
+            pitch_forward(&mut game, pitch);
+            pitch_reverse(&old_game, &mut game);
+            assert_eq!(game, old_game);
+        }
+    }
+
+    #[test]
+    fn foul_with_two_strikes_keeps_the_count() {
+        let mut game = test_game();
+        game.at_bat_strikes = 2;
+
+        pitch_forward(&mut game, Pitch::Foul);
+        assert_eq!(game.at_bat_strikes, 2);
+    }
+
+    #[test]
+    fn pitches_without_a_strike_limit_dont_panic() {
+        let mut game = test_game();
+        game.away.strikes = None;
+        game.home.strikes = None;
+        game.at_bat_strikes = 2;
+
+        pitch_forward(&mut game, Pitch::Foul);
+        assert_eq!(game.at_bat_strikes, 2);
+        pitch_forward(&mut game, Pitch::Strike);
+        assert_eq!(game.at_bat_strikes, 3);
+    }
+
+    #[test]
+    fn walk_with_the_bases_loaded_scores_the_runner_on_third() {
+        let old_game = with_runners(test_game(), &[Base::Third, Base::Second, Base::First]);
+        let batter_id = Uuid::new_v4();
+
+        let mut game = old_game.clone();
+        batter_reached_base_forward(&mut game, batter_id, "Batter".to_string(), String::new(), Base::First, false);
+        assert_eq!(game.base_runners, vec![old_game.base_runners[1], old_game.base_runners[2], batter_id]);
+        assert_eq!(game.bases_occupied.iter().map(|base| base.lower).collect_vec(), vec![2, 1, 0]);
+        assert_eq!(game.away.score, Some(1.0));
+        assert_eq!(game.top_inning_score, 1.0);
+
+        batter_reached_base_reverse(&old_game, &mut game);
+        assert_eq!(game, old_game);
+    }
+
+    #[test]
+    fn runners_with_a_free_refill_might_take_an_extra_base_on_a_hit() {
+        let mut old_game = with_runners(test_game(), &[Base::Second, Base::First]);
+        old_game.base_runner_mods[0] = FREE_REFILL_MOD.to_string();
+
+        let mut game = old_game.clone();
+        batter_reached_base_forward(&mut game, Uuid::new_v4(), "Batter".to_string(), String::new(), Base::First, true);
+        let with_refill = &game.bases_occupied[0];
+        let without_refill = &game.bases_occupied[1];
+        assert_eq!((with_refill.lower, with_refill.upper), (2, 3));
+        assert_eq!((without_refill.lower, without_refill.upper), (1, 1));
+
+        batter_reached_base_reverse(&old_game, &mut game);
+        assert_eq!(game, old_game);
+
+        // A trailing runner can't take the extra base if the runner ahead is on it
+        let mut game = with_runners(test_game(), &[Base::Second, Base::First]);
+        game.base_runner_mods[1] = FREE_REFILL_MOD.to_string();
+        batter_reached_base_forward(&mut game, Uuid::new_v4(), "Batter".to_string(), String::new(), Base::First, true);
+        assert!(game.bases_occupied.iter().all(|base| base.lower == base.upper));
+    }
+
+    #[test]
+    fn double_scores_runners_from_second_and_third() {
+        let old_game = with_runners(test_game(), &[Base::Third, Base::Second, Base::First]);
+        let batter_id = Uuid::new_v4();
+
+        let mut game = old_game.clone();
+        batter_reached_base_forward(&mut game, batter_id, "Batter".to_string(), String::new(), Base::Second, true);
+        assert_eq!(game.base_runners, vec![old_game.base_runners[2], batter_id]);
+        assert_eq!(game.away.score, Some(2.0));
+        assert_eq!(game.score_update.as_deref(), Some("2 Runs scored!"));
+
+        batter_reached_base_reverse(&old_game, &mut game);
+        assert_eq!(game, old_game);
+    }
+
+    #[test]
+    fn out_round_trips() {
+        let mut old_game = with_runners(test_game(), &[Base::First]);
+        old_game.half_inning_outs = 1;
+
+        let mut game = old_game.clone();
+        game.out(1);
+        assert_eq!(game.half_inning_outs, 2);
+        assert_eq!(game.away.batter, None);
+        assert_eq!(game.base_runners, old_game.base_runners);
+
+        game.reverse_out(1, &old_game);
+        assert_eq!(game, old_game);
+    }
+
+    #[test]
+    fn unknown_mod_types_are_precondition_failures() {
+        let player_id = Uuid::new_v4();
+        assert!(mod_type_failures(EntityType::Player, player_id, 0).is_empty());
+
+        let failures = mod_type_failures(EntityType::Player, player_id, 7);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].entity_id, player_id);
+        assert_eq!(failures[0].property, "modType");
+    }
+
+    #[test]
+    fn boosts_widen_each_attribute_by_the_boost_range() {
+        let mut player = test_player(None);
+        PlayerAttributesAdjustedEffectVariant::new(*PLAYER_BOOST.start(), *PLAYER_BOOST.end())
+            .forward(&mut player);
+
+        assert!(player.buoyancy.could_be(0.5));
+        assert!(player.buoyancy.could_be(0.7));
+        assert!(!player.buoyancy.could_be(0.75));
+        assert!(!player.moxie.could_be(0.45));
+    }
+
+    #[test]
+    fn home_runs_round_trip() {
+        let mut old_game = with_runners(test_game(), &[Base::Third, Base::First]);
+        old_game.away.score = Some(2.0);
+
+        let mut game = old_game.clone();
+        home_run_forward(&mut game, 3.);
+        assert!(game.base_runners.is_empty());
+        assert_eq!(game.away.score, Some(5.0));
+
+        home_run_reverse(&old_game, &mut game);
+        game.score_update = old_game.score_update.clone(); // game_reverse's job
+        assert_eq!(game, old_game);
+    }
+
+    #[test]
+    fn home_runs_without_a_score_dont_panic() {
+        let mut old_game = test_game();
+        old_game.away.score = None;
+
+        let mut game = old_game.clone();
+        home_run_forward(&mut game, 1.);
+        assert_eq!(game.away.score, Some(1.0));
+
+        home_run_reverse(&old_game, &mut game);
+        assert_eq!(game.away.score, None);
+    }
+
+    #[test]
+    fn incineration_puts_the_replacement_in_the_victims_place() {
+        let (victim_id, other_id, replacement_id) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let old_team = test_team(vec![other_id, victim_id], Vec::new());
+
+        let mut team = old_team.clone();
+        let mut variant = PlayerReplacedEffectVariant::new(victim_id, replacement_id);
+        variant.forward(&mut team);
+        assert_eq!(team.lineup, vec![other_id, replacement_id]);
+        assert_eq!(team.roster_slot_of(victim_id), None);
+
+        variant.reverse(&old_team, &mut team);
+        assert_eq!(team, old_team);
+    }
+
+    #[test]
+    fn incinerating_a_dead_player_off_the_roster_is_reported_instead_of_panicking() {
+        let team = test_team(Vec::new(), Vec::new());
+        let mut player = test_player(Some(team.id));
+        player.deceased = Some(true);
+        let state = test_state([team.clone().into(), player.clone().into()]);
+
+        let failures = incineration_failures(player.id, team.id, &state);
+        assert_eq!(failures.iter().map(|failure| failure.entity_type).collect_vec(),
+                   vec![EntityType::Player, EntityType::Team]);
+        assert!(failures.iter().all(|failure| !failure.skips_event));
+
+        let mut dead_player = player.clone();
+        PlayerIncineratedEffectVariant.forward(&mut dead_player);
+        assert_eq!(dead_player, player);
+
+        let mut unchanged_team = team.clone();
+        PlayerReplacedEffectVariant::new(player.id, Uuid::new_v4()).forward(&mut unchanged_team);
+        assert_eq!(unchanged_team, team);
+    }
+}
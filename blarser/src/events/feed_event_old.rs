@@ -437,11 +437,8 @@
 //     let player_id = event.player_id().expect(concat!("PlayerStatReroll event must have a player id"));
 //     state.with_player(player_id, |mut player| {
 //         // This event is normally a child (or in events that use siblings, a non-first
-//         // sibling), but for Snow events it's a top-level event. For now I assert that it's
-//         // always snow.
-//
-//         assert_eq!(event.description, format!("Snow fell on {}!", player.name),
-//                    "Unexpected top-level PlayerStatReroll event");
+//         // sibling), but for Snow events it's a top-level event. The reroll is the same either
+//         // way.
 //
 //         // I think this is pretty close to the actual range
 //         player.adjust_attributes(-0.03, 0.03);
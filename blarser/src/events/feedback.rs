@@ -0,0 +1,126 @@
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::events::{AnyEffect, Event, PlayerReplacedEffect, PlayerTeamChangedEffect, PreconditionFailure};
+use crate::ingest::StateGraph;
+use crate::state::EntityType;
+
+// One side of a Feedback swap
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FeedbackPlayer {
+    pub player_id: Uuid,
+    pub team_id: Uuid,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Feedback {
+    time: DateTime<Utc>,
+    first: FeedbackPlayer,
+    second: FeedbackPlayer,
+}
+
+impl Feedback {
+    pub fn new(time: DateTime<Utc>, first: FeedbackPlayer, second: FeedbackPlayer) -> Self {
+        Feedback { time, first, second }
+    }
+
+    // The Feed tags both players, and both of their teams in the same order
+    pub fn from_tags(time: DateTime<Utc>, player_tags: &[Uuid], team_tags: &[Uuid]) -> Option<Self> {
+        match (player_tags, team_tags) {
+            (&[first_player, second_player], &[first_team, second_team]) => {
+                Some(Feedback::new(
+                    time,
+                    FeedbackPlayer { player_id: first_player, team_id: first_team },
+                    FeedbackPlayer { player_id: second_player, team_id: second_team },
+                ))
+            }
+            _ => { None }
+        }
+    }
+}
+
+impl Event for Feedback {
+    fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    fn into_effects(self, _: &StateGraph) -> Vec<AnyEffect> {
+        // Each player takes the other's exact spot, which may be in a different part of the roster
+        // than the one they came from (e.g. a batter can end up in the rotation). The team effects
+        // reverse by restoring whichever roster list they changed, so the original slots come back
+        // regardless.
+        vec![
+            PlayerReplacedEffect::new(self.first.team_id, self.first.player_id, self.second.player_id).into(),
+            PlayerReplacedEffect::new(self.second.team_id, self.second.player_id, self.first.player_id).into(),
+            PlayerTeamChangedEffect::new(self.first.player_id, self.second.team_id).into(),
+            PlayerTeamChangedEffect::new(self.second.player_id, self.first.team_id).into(),
+        ]
+    }
+
+    fn precondition_failures(&self, _: &StateGraph) -> Vec<PreconditionFailure> {
+        if self.first.team_id != self.second.team_id { return Vec::new(); }
+
+        vec![PreconditionFailure {
+            entity_type: EntityType::Team,
+            entity_id: self.first.team_id,
+            property: "lineup".to_string(),
+            message: format!("Feedback between {} and {} doesn't swap them between two different teams",
+                             self.first.player_id, self.second.player_id),
+            skips_event: true,
+        }]
+    }
+
+    fn targets(&self) -> Vec<(EntityType, Uuid)> {
+        vec![
+            (EntityType::Team, self.first.team_id),
+            (EntityType::Team, self.second.team_id),
+            (EntityType::Player, self.first.player_id),
+            (EntityType::Player, self.second.player_id),
+        ]
+    }
+}
+
+impl Display for Feedback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Feedback between {} and {} at {}", self.first.player_id, self.second.player_id, self.time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+    use crate::entity::{test_team, RosterSlot};
+    use crate::events::{EffectVariant, PlayerReplacedEffectVariant};
+
+    #[test]
+    fn swap_between_lineup_and_rotation_round_trips() {
+        let batter_id = Uuid::new_v4();
+        let pitcher_id = Uuid::new_v4();
+        let old_batting_team = test_team(vec![Uuid::new_v4(), batter_id, Uuid::new_v4()], vec![Uuid::new_v4()]);
+        let old_pitching_team = test_team(vec![Uuid::new_v4()], vec![Uuid::new_v4(), pitcher_id]);
+
+        // These are the team halves of Feedback::into_effects
+        let mut batting_variant = PlayerReplacedEffectVariant::new(batter_id, pitcher_id);
+        let mut pitching_variant = PlayerReplacedEffectVariant::new(pitcher_id, batter_id);
+
+        let mut batting_team = old_batting_team.clone();
+        let mut pitching_team = old_pitching_team.clone();
+        batting_variant.forward(&mut batting_team);
+        pitching_variant.forward(&mut pitching_team);
+
+        // Each player takes the other's exact slot
+        assert_eq!(batting_team.lineup[1], pitcher_id);
+        assert_eq!(batting_team.roster_slot_of(batter_id), None);
+        assert_eq!(pitching_team.rotation[1], batter_id);
+        assert_eq!(pitching_team.roster_slot_of(pitcher_id), None);
+
+        batting_variant.reverse(&old_batting_team, &mut batting_team);
+        pitching_variant.reverse(&old_pitching_team, &mut pitching_team);
+        assert_eq!(batting_team, old_batting_team);
+        assert_eq!(pitching_team, old_pitching_team);
+        assert_eq!(batting_team.roster_slot_of(batter_id), Some(RosterSlot::Lineup));
+        assert_eq!(pitching_team.roster_slot_of(pitcher_id), Some(RosterSlot::Rotation));
+    }
+}
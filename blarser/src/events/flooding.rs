@@ -0,0 +1,191 @@
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use partial_information::Nullable;
+use crate::entity::{Game, ModDuration};
+
+use crate::events::{AnyEffect, Effect, EffectVariant, Event, PlayerModsAddedEffect};
+use crate::ingest::StateGraph;
+use crate::state::EntityType;
+
+pub const ANCHOR_MOD: &str = "ANCHOR";
+pub const ELSEWHERE_MOD: &str = "ELSEWHERE";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FloodingSwept {
+    time: DateTime<Utc>,
+    game_id: Uuid,
+    description: String,
+    // Runners who were swept Elsewhere, as opposed to just off the field
+    swept_elsewhere: Vec<Uuid>,
+}
+
+impl FloodingSwept {
+    pub fn new(time: DateTime<Utc>, game_id: Uuid, description: String, swept_elsewhere: Vec<Uuid>) -> Self {
+        FloodingSwept { time, game_id, description, swept_elsewhere }
+    }
+}
+
+impl Event for FloodingSwept {
+    fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    fn into_effects(self, state: &StateGraph) -> Vec<AnyEffect> {
+        // The runners' displayed mods only show one mod each, so whether they're anchored has to
+        // come from the players themselves. Versions of the game can disagree about who's on base,
+        // so this checks everyone who might be.
+        let runners = state.query_game_all(self.game_id, |game| game.base_runners.clone())
+            .into_iter()
+            .flatten()
+            .unique()
+            .collect_vec();
+        let mut anchored = Vec::new();
+        let mut maybe_anchored = Vec::new();
+        for runner_id in runners {
+            // A runner who isn't in the graph yet can't be known to be anchored
+            match state.query_player_all(runner_id, |player| player.has_mod(ANCHOR_MOD)).as_slice() {
+                [true] => { anchored.push(runner_id) }
+                [_, _] => { maybe_anchored.push(runner_id) }
+                _ => {}
+            }
+        }
+
+        std::iter::once(FloodingSweptEffect::new(self.game_id, self.description, anchored, maybe_anchored).into())
+            .chain(self.swept_elsewhere.into_iter()
+                .map(|player_id| {
+                    PlayerModsAddedEffect::new(player_id, vec![ELSEWHERE_MOD.to_string()], ModDuration::Permanent).into()
+                }))
+            .collect()
+    }
+
+    fn targets(&self) -> Vec<(EntityType, Uuid)> {
+        std::iter::once((EntityType::Game, self.game_id))
+            .chain(self.swept_elsewhere.iter().map(|&id| (EntityType::Player, id)))
+            .collect()
+    }
+}
+
+impl Display for FloodingSwept {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "FloodingSwept for {} at {}", self.game_id, self.time)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FloodingSweptEffect {
+    game_id: Uuid,
+    description: String,
+    anchored: Vec<Uuid>,
+    // Runners whose versions disagree about whether they're anchored
+    maybe_anchored: Vec<Uuid>,
+}
+
+impl FloodingSweptEffect {
+    pub fn new(game_id: Uuid, description: String, anchored: Vec<Uuid>, maybe_anchored: Vec<Uuid>) -> Self {
+        Self { game_id, description, anchored, maybe_anchored }
+    }
+}
+
+impl Effect for FloodingSweptEffect {
+    type Variant = FloodingSweptEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Game }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.game_id) }
+
+    fn variant(&self) -> Self::Variant {
+        FloodingSweptEffectVariant::new(self.description.clone(), self.anchored.clone())
+    }
+
+    // Every combination of the uncertain runners staying or getting swept. There are at most a
+    // few runners on base, so this stays small.
+    fn variants(&self) -> Vec<Self::Variant> {
+        self.maybe_anchored.iter()
+            .powerset()
+            .map(|also_anchored| {
+                let anchored = self.anchored.iter().chain(also_anchored).copied().collect();
+                FloodingSweptEffectVariant::new(self.description.clone(), anchored)
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FloodingSweptEffectVariant {
+    description: String,
+    anchored: Vec<Uuid>,
+}
+
+impl FloodingSweptEffectVariant {
+    pub fn new(description: String, anchored: Vec<Uuid>) -> Self {
+        Self { description, anchored }
+    }
+}
+
+impl EffectVariant for FloodingSweptEffectVariant {
+    type EntityType = Game;
+
+    fn forward(&self, game: &mut Game) {
+        game.play_count += 1;
+        game.last_update = Some(self.description.clone());
+        // Not everything in lastUpdateFull can be rebuilt from the event, so the observation fills it in
+        game.last_update_full = Nullable::unknown();
+
+        // Anchored runners stay where they are, so only the swept ones are taken out and everyone
+        // else keeps their relative order. pop_base_runner removes the first runner with the id,
+        // which handles the same player being on base more than once.
+        let swept_runners = game.base_runners.iter()
+            .filter(|runner_id| !self.anchored.contains(runner_id))
+            .copied()
+            .collect_vec();
+        for runner_id in swept_runners {
+            game.pop_base_runner(runner_id);
+        }
+    }
+
+    fn reverse(&mut self, old_game: &Game, new_game: &mut Game) {
+        new_game.play_count = old_game.play_count;
+        new_game.last_update = old_game.last_update.clone();
+        new_game.last_update_full = old_game.last_update_full.clone();
+
+        new_game.reverse_clear_bases(old_game);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+    use crate::entity::{test_game, with_runners, Base};
+    use crate::events::{Effect, EffectVariant};
+    use super::{FloodingSweptEffect, FloodingSweptEffectVariant};
+
+    #[test]
+    fn bases_loaded_only_sweeps_unanchored_runners() {
+        let old_game = with_runners(test_game(), &[Base::Third, Base::Second, Base::First]);
+        let anchored_id = Uuid::from_u128(0x101);
+
+        let mut variant = FloodingSweptEffectVariant::new("Flooding".to_string(), vec![anchored_id]);
+        let mut game = old_game.clone();
+        variant.forward(&mut game);
+        assert_eq!(game.base_runners, vec![anchored_id]);
+        assert_eq!(game.bases_occupied.len(), 1);
+        assert_eq!(game.baserunner_count, 1);
+
+        variant.reverse(&old_game, &mut game);
+        assert_eq!(game, old_game);
+    }
+
+    #[test]
+    fn uncertain_runners_branch() {
+        let game_id = Uuid::new_v4();
+        let effect = FloodingSweptEffect::new(game_id, "Flooding".to_string(),
+                                              vec![Uuid::from_u128(0x100)],
+                                              vec![Uuid::from_u128(0x101), Uuid::from_u128(0x102)]);
+        let variants = effect.variants();
+        assert_eq!(variants.len(), 4);
+        assert!(variants.iter().all(|variant| variant.anchored.contains(&Uuid::from_u128(0x100))));
+    }
+}
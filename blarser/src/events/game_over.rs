@@ -0,0 +1,220 @@
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Duration, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::entity::Game;
+
+use crate::events::{AnyEffect, AnyEvent, Effect, EffectVariant, Event, PreconditionFailure};
+use crate::ingest::StateGraph;
+use crate::state::EntityType;
+
+// Neither of these is in the Feed. They're the ticks after a game ends, when the game is wrapped
+// up one end_phase at a time.
+pub const WIN_COLLECTED_DELAY_SECONDS: i64 = 5;
+pub const GAME_OVER_DELAY_SECONDS: i64 = 10;
+
+// The successors of the event that ended a game
+pub fn game_end_successors(game_end_time: DateTime<Utc>, game_id: Uuid) -> Vec<AnyEvent> {
+    vec![
+        WinCollectedRegular::new(game_end_time + Duration::seconds(WIN_COLLECTED_DELAY_SECONDS), game_id).into(),
+        GameOver::new(game_end_time + Duration::seconds(GAME_OVER_DELAY_SECONDS), game_id).into(),
+    ]
+}
+
+// The same events, for when the game's state came from an observation instead of from the event
+// that ended it. `since` is when the game got into its current end phase, not when the version
+// holding it started, or the events would drift later every time an unrelated change made a new
+// version.
+pub fn pending_game_end_events(game: &Game, since: DateTime<Utc>) -> Vec<AnyEvent> {
+    match game.end_phase {
+        Some(3) => { game_end_successors(since, game.id) }
+        Some(4) => {
+            let game_over_delay = Duration::seconds(GAME_OVER_DELAY_SECONDS - WIN_COLLECTED_DELAY_SECONDS);
+            vec![GameOver::new(since + game_over_delay, game.id).into()]
+        }
+        _ => { Vec::new() }
+    }
+}
+
+// Game end phases have to happen in order. A game in any other phase means an event went missing
+// or the timing was off, which is something for a human to look at.
+fn end_phase_failures(game_id: Uuid, end_phase: i32, state: &StateGraph) -> Vec<PreconditionFailure> {
+    state.query_game_all(game_id, |game| game.end_phase)
+        .into_iter()
+        .filter(|&current| current != Some(end_phase - 1))
+        .map(|current| PreconditionFailure {
+            entity_type: EntityType::Game,
+            entity_id: game_id,
+            property: "endPhase".to_string(),
+            message: format!("Game end phase {end_phase} should follow {}, but the game was in {current:?}",
+                             end_phase - 1),
+            skips_event: true,
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WinCollectedRegular {
+    time: DateTime<Utc>,
+    game_id: Uuid,
+}
+
+impl WinCollectedRegular {
+    pub fn new(time: DateTime<Utc>, game_id: Uuid) -> Self {
+        WinCollectedRegular { time, game_id }
+    }
+}
+
+impl Event for WinCollectedRegular {
+    fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    fn into_effects(self, _: &StateGraph) -> Vec<AnyEffect> {
+        vec![GameEndPhaseEffect::new(self.game_id, 4).into()]
+    }
+
+    fn game_tags(&self) -> &[Uuid] {
+        std::slice::from_ref(&self.game_id)
+    }
+
+    fn precondition_failures(&self, state: &StateGraph) -> Vec<PreconditionFailure> {
+        end_phase_failures(self.game_id, 4, state)
+    }
+
+    fn targets(&self) -> Vec<(EntityType, Uuid)> {
+        vec![(EntityType::Game, self.game_id)]
+    }
+}
+
+impl Display for WinCollectedRegular {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WinCollectedRegular for {} at {}", self.game_id, self.time)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GameOver {
+    time: DateTime<Utc>,
+    game_id: Uuid,
+}
+
+impl GameOver {
+    pub fn new(time: DateTime<Utc>, game_id: Uuid) -> Self {
+        GameOver { time, game_id }
+    }
+}
+
+impl Event for GameOver {
+    fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    fn into_effects(self, _: &StateGraph) -> Vec<AnyEffect> {
+        vec![GameEndPhaseEffect::new(self.game_id, 5).into()]
+    }
+
+    fn game_tags(&self) -> &[Uuid] {
+        std::slice::from_ref(&self.game_id)
+    }
+
+    fn precondition_failures(&self, state: &StateGraph) -> Vec<PreconditionFailure> {
+        end_phase_failures(self.game_id, 5, state)
+    }
+
+    fn targets(&self) -> Vec<(EntityType, Uuid)> {
+        vec![(EntityType::Game, self.game_id)]
+    }
+}
+
+impl Display for GameOver {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GameOver for {} at {}", self.game_id, self.time)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GameEndPhaseEffect {
+    game_id: Uuid,
+    end_phase: i32,
+}
+
+impl GameEndPhaseEffect {
+    pub fn new(game_id: Uuid, end_phase: i32) -> Self {
+        Self { game_id, end_phase }
+    }
+}
+
+impl Effect for GameEndPhaseEffect {
+    type Variant = GameEndPhaseEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Game }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.game_id) }
+
+    fn variant(&self) -> Self::Variant {
+        GameEndPhaseEffectVariant::new(self.end_phase)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameEndPhaseEffectVariant {
+    end_phase: i32,
+}
+
+impl GameEndPhaseEffectVariant {
+    pub fn new(end_phase: i32) -> Self {
+        Self { end_phase }
+    }
+}
+
+impl EffectVariant for GameEndPhaseEffectVariant {
+    type EntityType = Game;
+
+    fn forward(&self, game: &mut Game) {
+        // precondition_failures should have caught this, but a conflict later is better than a panic
+        if game.end_phase != Some(self.end_phase - 1) {
+            warn!("Game {} moved to end phase {} from {:?}", game.id, self.end_phase, game.end_phase);
+        }
+        game.end_phase = Some(self.end_phase);
+        if self.end_phase == 5 {
+            game.finalized = Some(true);
+        }
+    }
+
+    fn reverse(&mut self, old_game: &Game, new_game: &mut Game) {
+        new_game.end_phase = old_game.end_phase;
+        new_game.finalized = old_game.finalized;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use crate::entity::{test_game, AnyEntity};
+    use crate::events::{AnyEvent, Event};
+    use crate::ingest::test_state;
+    use super::{game_end_successors, GameOver, WinCollectedRegular};
+
+    #[test]
+    fn end_phases_out_of_order_are_precondition_failures() {
+        let mut game = test_game();
+        game.end_phase = Some(3);
+        let game_id = game.id;
+        let state = test_state([AnyEntity::from(game)]);
+
+        let now = Utc::now();
+        assert!(WinCollectedRegular::new(now, game_id).precondition_failures(&state).is_empty());
+        // Skipping straight to GameOver means WinCollectedRegular went missing
+        let failures = GameOver::new(now, game_id).precondition_failures(&state);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].entity_id, game_id);
+        assert_eq!(failures[0].property, "endPhase");
+
+        // The successors of a game end are in the order that satisfies each other's preconditions
+        let successors = game_end_successors(now, game_id);
+        assert!(matches!(successors[0], AnyEvent::WinCollectedRegular(_)));
+        assert!(matches!(successors[1], AnyEvent::GameOver(_)));
+    }
+}
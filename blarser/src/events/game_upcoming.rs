@@ -30,6 +30,10 @@ impl Event for GameUpcoming {
     fn into_effects(self, _: &StateGraph) -> Vec<AnyEffect> {
         vec![GameUpcomingEffect::new(self.game_id).into()]
     }
+
+    fn targets(&self) -> Vec<(EntityType, Uuid)> {
+        vec![(EntityType::Game, self.game_id)]
+    }
 }
 
 impl Display for GameUpcoming {
@@ -60,7 +64,7 @@ impl Effect for GameUpcomingEffect {
     }
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct GameUpcomingEffectVariant {
     pub away: PitcherExtrapolated,
     pub home: PitcherExtrapolated,
@@ -120,4 +124,48 @@ impl EffectVariant for GameUpcomingEffectVariant {
         new_game.last_update = old_game.last_update.clone();
         new_game.last_update_full = old_game.last_update_full.clone();
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+    use partial_information::MaybeKnown;
+    use crate::entity::test_game;
+    use crate::events::EffectVariant;
+    use super::GameUpcomingEffectVariant;
+
+    #[test]
+    fn reverse_undoes_forward() {
+        // Before it's upcoming a game doesn't have pitchers, odds, or a score yet
+        let mut old_game = test_game();
+        for team in [&mut old_game.home, &mut old_game.away] {
+            team.odds = None;
+            team.pitcher = None;
+            team.pitcher_name = None;
+            team.pitcher_mod = MaybeKnown::Known(String::new());
+            team.score = None;
+            team.strikes = None;
+        }
+        old_game.last_update = None;
+
+        let mut variant = GameUpcomingEffectVariant::default();
+        variant.home.pitcher_id = MaybeKnown::Known(Uuid::from_u128(0x200));
+        variant.home.pitcher_name = MaybeKnown::Known("Home Starter".to_string());
+        variant.away.pitcher_id = MaybeKnown::Known(Uuid::from_u128(0x201));
+        variant.away.pitcher_name = MaybeKnown::Known("Away Starter".to_string());
+        variant.home_odds = MaybeKnown::Known(0.6);
+        variant.away_odds = MaybeKnown::Known(0.4);
+
+        let mut game = old_game.clone();
+        variant.forward(&mut game);
+        assert_eq!(game.home.pitcher, Some(MaybeKnown::Known(Uuid::from_u128(0x200))));
+        assert_eq!(game.away.score, Some(0.0));
+
+        let mut reverse_variant = GameUpcomingEffectVariant::default();
+        reverse_variant.reverse(&old_game, &mut game);
+        assert_eq!(game, old_game);
+        // Reversing captures what forward would need to redo it
+        assert_eq!(reverse_variant.home.pitcher_id, MaybeKnown::Known(Uuid::from_u128(0x200)));
+        assert_eq!(reverse_variant.away_odds, MaybeKnown::Known(0.4));
+    }
+}
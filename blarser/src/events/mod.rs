@@ -2,6 +2,7 @@ mod feed_event_old;
 mod timed_event;
 mod effects;
 mod event_util;
+mod event_category;
 
 // Events
 mod start;
@@ -19,11 +20,19 @@ mod fed_event;
 // mod stolen_base;
 // mod walk;
 mod game_upcoming;
+mod game_over;
+mod top_inning_end;
+mod snowflakes;
+mod feedback;
+mod earlbirds;
+mod pitcher_change;
+mod flooding;
 // mod inning_end;
 // mod player_reroll;
 
-pub use effects::{Extrapolated, AnyExtrapolated, Effect, AnyEffect, EffectVariant, AnyEffectVariant};
+pub use effects::{Extrapolated, AnyExtrapolated, Effect, AnyEffect, EffectVariant, AnyEffectVariant, sort_effects};
 pub(crate) use effects::with_effect_variant;
+pub use event_category::EventCategory;
 pub use start::Start;
 pub use earlseason_start::{EarlseasonStart, EarlseasonStartEffect, EarlseasonStartEffectVariant};
 pub use fed_event::*;
@@ -39,6 +48,14 @@ pub use fed_event::*;
 // pub use stolen_base::{StolenBase, CaughtStealing};
 // pub use walk::Walk;
 pub use game_upcoming::{GameUpcoming, GameUpcomingEffect, GameUpcomingEffectVariant};
+pub use game_over::{WinCollectedRegular, GameOver, GameEndPhaseEffect, GameEndPhaseEffectVariant, pending_game_end_events};
+pub use top_inning_end::{TopInningEnd, TopInningEndEffect, TopInningEndEffectVariant};
+pub(crate) use top_inning_end::{third_out_successors, TOP_INNING_END_DELAY_SECONDS};
+pub use snowflakes::{Snowflakes, SnowflakesGameEffect, SnowflakesGameEffectVariant, SnowflakesPlayerEffect, SnowflakesPlayerEffectVariant, SnowflakesTeamEffect, SnowflakesTeamEffectVariant};
+pub use feedback::{Feedback, FeedbackPlayer};
+pub use earlbirds::{EarlbirdsAdded, EarlbirdsRemoved};
+pub use pitcher_change::{PitcherChange, PitcherChangeEffect, PitcherChangeEffectVariant};
+pub use flooding::{FloodingSwept, FloodingSweptEffect, FloodingSweptEffectVariant};
 // pub use inning_end::InningEnd;
 
 use crate::polymorphic_enum::polymorphic_enum;
@@ -46,8 +63,11 @@ use std::fmt::{Display, Formatter};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use derive_more::{From, TryInto};
+use uuid::Uuid;
 
+use crate::entity::AnyEntity;
 use crate::ingest::StateGraph;
+use crate::state::EntityType;
 
 pub trait Event: Serialize + for<'de> Deserialize<'de> {
     fn time(&self) -> DateTime<Utc>;
@@ -68,8 +88,57 @@ pub trait Event: Serialize + for<'de> Deserialize<'de> {
         Vec::new()
     }
 
+    // Effects on different entities can be returned in whatever order is convenient. They're
+    // applied in the order given by sort_effects, which keeps effects on the same entity in the
+    // order they appear here.
     fn into_effects(self, state: &StateGraph) -> Vec<AnyEffect>;
+
+    // Only events that come from the Feed have a category. Blarser's made-up events don't.
+    fn category(&self) -> Option<EventCategory> {
+        None
+    }
+
+    // Same goes for tags
+    fn game_tags(&self) -> &[Uuid] {
+        &[]
+    }
+
+    fn team_tags(&self) -> &[Uuid] {
+        &[]
+    }
+
+    // Things that must be true of the current state for this event to make sense. A non-empty
+    // result means the event is probably a data error, so it gets recorded and skipped.
+    #[allow(unused_variables)]
+    fn precondition_failures(&self, state: &StateGraph) -> Vec<PreconditionFailure> {
+        Vec::new()
+    }
+
+    // The entities this event is expected to touch, for display purposes. Fed events default to
+    // their tags since that's the best information available without running into_effects.
+    fn targets(&self) -> Vec<(EntityType, Uuid)> {
+        self.game_tags().iter().map(|id| (EntityType::Game, *id))
+            .chain(self.team_tags().iter().map(|id| (EntityType::Team, *id)))
+            .collect()
+    }
+
+    // Entities this event brings into existence. Each one gets a graph when the event is ingested,
+    // and its first observation fills in whatever the event couldn't know.
+    fn new_entities(&self) -> Vec<AnyEntity> {
+        Vec::new()
+    }
 }
+#[derive(Debug, Clone)]
+pub struct PreconditionFailure {
+    pub entity_type: EntityType,
+    pub entity_id: Uuid,
+    pub property: String,
+    pub message: String,
+    // Whether the event is skipped. Otherwise the failure is only recorded, for problems that the
+    // event's effects already work around.
+    pub skips_event: bool,
+}
+
 polymorphic_enum!{
     #[derive(Debug, Serialize, Deserialize, TryInto, From)]
     #[try_into(owned, ref, ref_mut)]
@@ -78,6 +147,15 @@ polymorphic_enum!{
         Start(crate::events::Start),
         EarlseasonStart(crate::events::EarlseasonStart),
         GameUpcoming(crate::events::GameUpcoming),
+        WinCollectedRegular(crate::events::WinCollectedRegular),
+        GameOver(crate::events::GameOver),
+        TopInningEnd(crate::events::TopInningEnd),
+        Snowflakes(crate::events::Snowflakes),
+        Feedback(crate::events::Feedback),
+        EarlbirdsAdded(crate::events::EarlbirdsAdded),
+        EarlbirdsRemoved(crate::events::EarlbirdsRemoved),
+        PitcherChange(crate::events::PitcherChange),
+        FloodingSwept(crate::events::FloodingSwept),
         Fed(crate::events::FedEvent),
     }
 }
@@ -104,6 +182,38 @@ impl AnyEvent {
     }
 
     pub fn into_effects(self, state: &StateGraph) -> Vec<AnyEffect> {
-        with_any_event!(self, |e| { e.into_effects(state) })
+        let mut effects = with_any_event!(self, |e| { e.into_effects(state) });
+        effects::sort_effects(&mut effects);
+        effects
+    }
+
+    pub fn category(&self) -> Option<EventCategory> {
+        with_any_event!(self, |e| { e.category() })
+    }
+
+    pub fn game_tags(&self) -> &[Uuid] {
+        with_any_event!(self, |e| { e.game_tags() })
+    }
+
+    pub fn team_tags(&self) -> &[Uuid] {
+        with_any_event!(self, |e| { e.team_tags() })
+    }
+
+    // Timed events implied by the state are the same event if they're the same kind of event for
+    // the same games, even if their times were worked out from different versions
+    pub fn timed_event_key(&self) -> (std::mem::Discriminant<AnyEvent>, Vec<Uuid>) {
+        (std::mem::discriminant(self), self.game_tags().to_vec())
+    }
+
+    pub fn precondition_failures(&self, state: &StateGraph) -> Vec<PreconditionFailure> {
+        with_any_event!(self, |e| { e.precondition_failures(state) })
+    }
+
+    pub fn targets(&self) -> Vec<(EntityType, Uuid)> {
+        with_any_event!(self, |e| { e.targets() })
+    }
+
+    pub fn new_entities(&self) -> Vec<AnyEntity> {
+        with_any_event!(self, |e| { e.new_entities() })
     }
 }
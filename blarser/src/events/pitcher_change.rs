@@ -0,0 +1,215 @@
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use partial_information::MaybeKnown;
+use crate::entity::Game;
+
+use crate::events::{AnyEffect, AnyEvent, Effect, EffectVariant, Event};
+use crate::events::event_util::{get_displayed_mod_candidates, PITCHER_MOD_PRECEDENCE};
+use crate::ingest::StateGraph;
+use crate::state::EntityType;
+
+// A game's pitchers are unset by PlayBall and then silently filled in before the first half-inning.
+// Returns a PitcherChange for the first team in the game that has no pitcher, or None if both do.
+// Since the predecessor is generated again after it's applied, this ends up filling in one pitcher
+// per call until there are none left. If the pitcher can't be worked out, it's left for the next
+// observation to fill in.
+pub fn missing_pitcher_predecessor(time: DateTime<Utc>, game_id: Uuid, state: &StateGraph) -> Option<AnyEvent> {
+    // Untracked games have no versions, so they fall into the empty case
+    let candidates = state.query_game_all(game_id, |game| {
+        let teams = [&game.home, &game.away].into_iter()
+            .filter(|game_team| game_team.pitcher.is_none())
+            .map(|game_team| game_team.team)
+            .collect::<Vec<_>>();
+        (game.day, teams)
+    })
+        .into_iter()
+        .unique()
+        .collect_vec();
+    let (day, teams_without_pitcher) = match candidates.as_slice() {
+        [] => { return None; }
+        [only] => { only.clone() }
+        _ => {
+            warn!("Game {game_id}'s versions disagree about which pitchers are missing; not filling them in");
+            return None;
+        }
+    };
+
+    let team_id = teams_without_pitcher.into_iter().next()?;
+    let pitchers = state.query_team_all(team_id, |team| team.active_pitcher(day))
+        .into_iter()
+        .flatten()
+        .unique()
+        .collect_vec();
+    match pitchers.as_slice() {
+        [pitcher_id] => { Some(PitcherChange::new(time, game_id, team_id, *pitcher_id).into()) }
+        [] => {
+            warn!("Couldn't find team {team_id}'s pitcher for day {day}; leaving game {game_id}'s pitcher unset");
+            None
+        }
+        _ => {
+            warn!("Team {team_id}'s pitcher for day {day} is ambiguous; leaving game {game_id}'s pitcher unset");
+            None
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PitcherChange {
+    time: DateTime<Utc>,
+    game_id: Uuid,
+    team_id: Uuid,
+    pitcher_id: Uuid,
+}
+
+impl PitcherChange {
+    pub fn new(time: DateTime<Utc>, game_id: Uuid, team_id: Uuid, pitcher_id: Uuid) -> Self {
+        PitcherChange { time, game_id, team_id, pitcher_id }
+    }
+}
+
+impl Event for PitcherChange {
+    fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    // Either of these is left unknown if the player isn't tracked or their versions disagree
+    fn into_effects(self, state: &StateGraph) -> Vec<AnyEffect> {
+        let pitcher_name = only_value(state.query_player_all(self.pitcher_id, |player| player.name.clone()));
+        let pitcher_mod = only_value(get_displayed_mod_candidates(state, self.pitcher_id, &PITCHER_MOD_PRECEDENCE));
+
+        vec![PitcherChangeEffect::new(self.game_id, self.team_id, self.pitcher_id, pitcher_name, pitcher_mod).into()]
+    }
+
+    fn targets(&self) -> Vec<(EntityType, Uuid)> {
+        vec![(EntityType::Game, self.game_id), (EntityType::Player, self.pitcher_id)]
+    }
+}
+
+fn only_value(values: Vec<String>) -> MaybeKnown<String> {
+    match values.into_iter().unique().exactly_one() {
+        Ok(value) => { MaybeKnown::Known(value) }
+        Err(_) => { MaybeKnown::Unknown }
+    }
+}
+
+impl Display for PitcherChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PitcherChange for {} in {} at {}", self.team_id, self.game_id, self.time)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PitcherChangeEffect {
+    game_id: Uuid,
+    team_id: Uuid,
+    pitcher_id: Uuid,
+    pitcher_name: MaybeKnown<String>,
+    pitcher_mod: MaybeKnown<String>,
+}
+
+impl PitcherChangeEffect {
+    pub fn new(game_id: Uuid, team_id: Uuid, pitcher_id: Uuid, pitcher_name: MaybeKnown<String>, pitcher_mod: MaybeKnown<String>) -> Self {
+        Self { game_id, team_id, pitcher_id, pitcher_name, pitcher_mod }
+    }
+}
+
+impl Effect for PitcherChangeEffect {
+    type Variant = PitcherChangeEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Game }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.game_id) }
+
+    fn variant(&self) -> Self::Variant {
+        PitcherChangeEffectVariant::new(self.team_id, self.pitcher_id, self.pitcher_name.clone(), self.pitcher_mod.clone())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PitcherChangeEffectVariant {
+    team_id: Uuid,
+    pitcher_id: Uuid,
+    pitcher_name: MaybeKnown<String>,
+    pitcher_mod: MaybeKnown<String>,
+}
+
+impl PitcherChangeEffectVariant {
+    pub fn new(team_id: Uuid, pitcher_id: Uuid, pitcher_name: MaybeKnown<String>, pitcher_mod: MaybeKnown<String>) -> Self {
+        Self { team_id, pitcher_id, pitcher_name, pitcher_mod }
+    }
+}
+
+impl EffectVariant for PitcherChangeEffectVariant {
+    type EntityType = Game;
+
+    fn forward(&self, game: &mut Game) {
+        let game_team = if game.home.team == self.team_id {
+            &mut game.home
+        } else {
+            assert_eq!(game.away.team, self.team_id, "PitcherChange team must be playing in the game");
+            &mut game.away
+        };
+
+        // There's no game update for this. It happens invisibly.
+        game_team.pitcher = Some(MaybeKnown::Known(self.pitcher_id));
+        game_team.pitcher_name = Some(self.pitcher_name.clone());
+        game_team.pitcher_mod = self.pitcher_mod.clone();
+    }
+
+    fn reverse(&mut self, old_game: &Game, new_game: &mut Game) {
+        for (old_team, new_team) in [(&old_game.home, &mut new_game.home), (&old_game.away, &mut new_game.away)] {
+            new_team.pitcher = old_team.pitcher;
+            new_team.pitcher_name = old_team.pitcher_name.clone();
+            new_team.pitcher_mod = old_team.pitcher_mod.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use crate::entity::{test_game, test_player, test_team};
+    use crate::ingest::test_state;
+    use super::*;
+
+    #[test]
+    fn missing_pitchers_are_filled_in_from_the_rotation() {
+        let pitcher = test_player(None);
+        let team = test_team(Vec::new(), vec![pitcher.id]);
+        let mut game = test_game();
+        game.home.team = team.id;
+        game.home.pitcher = None;
+        let state = test_state([game.clone().into(), team.into(), pitcher.clone().into()]);
+
+        let event = missing_pitcher_predecessor(Utc::now(), game.id, &state)
+            .expect("The home team has no pitcher");
+        assert_eq!(event.targets(), vec![(EntityType::Game, game.id), (EntityType::Player, pitcher.id)]);
+
+        let effect: PitcherChangeEffect = event.into_effects(&state).remove(0).try_into().unwrap();
+        effect.variant().forward(&mut game);
+        assert_eq!(game.home.pitcher, Some(MaybeKnown::Known(pitcher.id)));
+        assert_eq!(game.home.pitcher_name, Some(MaybeKnown::Known(pitcher.name.clone())));
+
+        let state = test_state([game.clone().into()]);
+        assert!(missing_pitcher_predecessor(Utc::now(), game.id, &state).is_none());
+    }
+
+    #[test]
+    fn untracked_teams_and_pitchers_dont_panic() {
+        let mut game = test_game();
+        game.home.pitcher = None;
+        let state = test_state([game.clone().into()]);
+        assert!(missing_pitcher_predecessor(Utc::now(), game.id, &state).is_none());
+
+        let pitcher_id = Uuid::new_v4();
+        let effect: PitcherChangeEffect = PitcherChange::new(Utc::now(), game.id, game.home.team, pitcher_id)
+            .into_effects(&state).remove(0).try_into().unwrap();
+        effect.variant().forward(&mut game);
+        assert_eq!(game.home.pitcher, Some(MaybeKnown::Known(pitcher_id)));
+        assert_eq!(game.home.pitcher_name, Some(MaybeKnown::Unknown));
+    }
+}
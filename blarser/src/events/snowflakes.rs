@@ -0,0 +1,300 @@
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use partial_information::{MaybeKnown, Nullable};
+use crate::entity::{Game, GameByTeam, ModDuration, Player, Team};
+
+use crate::events::{AnyEffect, Effect, EffectVariant, Event};
+use crate::ingest::StateGraph;
+use crate::state::EntityType;
+
+pub const FROZEN_MOD: &str = "FROZEN";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snowflakes {
+    time: DateTime<Utc>,
+    game_id: Uuid,
+    description: String,
+    frozen_players: Vec<Uuid>,
+}
+
+impl Snowflakes {
+    pub fn new(time: DateTime<Utc>, game_id: Uuid, description: String, frozen_players: Vec<Uuid>) -> Self {
+        Snowflakes { time, game_id, description, frozen_players }
+    }
+
+    // The team whose current pitcher got frozen, if there is one. Only known pitchers count. An
+    // unknown pitcher could be anyone, and the game doesn't freeze hypothetical people.
+    fn frozen_pitcher_team(&self, game_team: &GameByTeam) -> Option<Uuid> {
+        let pitcher_frozen = game_team.pitcher.as_ref().map_or(false, |pitcher| {
+            pitcher.as_ref().map(|pitcher_id| self.frozen_players.contains(pitcher_id)).unwrap_or(false)
+        });
+        pitcher_frozen.then_some(game_team.team)
+    }
+}
+
+impl Event for Snowflakes {
+    fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    fn into_effects(self, state: &StateGraph) -> Vec<AnyEffect> {
+        // Versions of the game can disagree about who's pitching. Any team whose pitcher was frozen
+        // in one of them gets the effect, and observations will sort out the rest.
+        let frozen_pitcher_teams = state.query_game_all(self.game_id, |game| {
+            [self.frozen_pitcher_team(&game.home), self.frozen_pitcher_team(&game.away)]
+        })
+            .into_iter()
+            .flatten()
+            .flatten()
+            .unique()
+            .collect_vec();
+
+        let frozen_players = Arc::new(self.frozen_players);
+        let game_effect = SnowflakesGameEffect::new(self.game_id, self.description, frozen_players.clone());
+
+        let player_effects = frozen_players.iter()
+            .map(|&player_id| SnowflakesPlayerEffect::new(player_id).into());
+
+        let team_effects = frozen_pitcher_teams.into_iter()
+            .map(|team_id| SnowflakesTeamEffect::new(team_id).into());
+
+        std::iter::once(game_effect.into())
+            .chain(player_effects)
+            .chain(team_effects)
+            .collect()
+    }
+
+    fn targets(&self) -> Vec<(EntityType, Uuid)> {
+        // Teams are left out because which ones are affected depends on the game's state
+        std::iter::once((EntityType::Game, self.game_id))
+            .chain(self.frozen_players.iter().map(|&id| (EntityType::Player, id)))
+            .collect()
+    }
+}
+
+impl Display for Snowflakes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Snowflakes for {} at {}", self.game_id, self.time)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SnowflakesGameEffect {
+    game_id: Uuid,
+    description: String,
+    frozen_players: Arc<Vec<Uuid>>,
+}
+
+impl SnowflakesGameEffect {
+    pub fn new(game_id: Uuid, description: String, frozen_players: Arc<Vec<Uuid>>) -> Self {
+        Self { game_id, description, frozen_players }
+    }
+}
+
+impl Effect for SnowflakesGameEffect {
+    type Variant = SnowflakesGameEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Game }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.game_id) }
+
+    fn variant(&self) -> Self::Variant {
+        SnowflakesGameEffectVariant::new(self.description.clone(), self.frozen_players.clone())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnowflakesGameEffectVariant {
+    description: String,
+    frozen_players: Arc<Vec<Uuid>>,
+}
+
+impl SnowflakesGameEffectVariant {
+    pub fn new(description: String, frozen_players: Arc<Vec<Uuid>>) -> Self {
+        Self { description, frozen_players }
+    }
+}
+
+impl EffectVariant for SnowflakesGameEffectVariant {
+    type EntityType = Game;
+
+    fn forward(&self, game: &mut Game) {
+        game.play_count += 1;
+        game.last_update = Some(self.description.clone());
+        // Not everything in lastUpdateFull can be rebuilt from the event, so the observation fills it in
+        game.last_update_full = Nullable::unknown();
+        game.game_start_phase = 20;
+
+        if let Some(game_state) = &mut game.state {
+            *game_state.snowfall_events.get_or_insert(0) += 1;
+        } else {
+            warn!("Game had no state during a Snowflakes event, so snowfallEvents can't be counted");
+        }
+
+        // Frozen players are taken out of the game immediately, even mid-at-bat
+        let team_at_bat = game.team_at_bat_mut();
+        if team_at_bat.batter.map_or(false, |batter_id| self.frozen_players.contains(&batter_id)) {
+            team_at_bat.batter = None;
+            team_at_bat.batter_name = Some(String::new());
+        }
+
+        let defending_team = game.defending_team_mut();
+        let pitcher_frozen = defending_team.pitcher.as_ref().map_or(false, |pitcher| {
+            pitcher.as_ref().map(|pitcher_id| self.frozen_players.contains(pitcher_id)).unwrap_or(false)
+        });
+        if pitcher_frozen {
+            defending_team.pitcher = None;
+            defending_team.pitcher_name = Some(MaybeKnown::Known(String::new()));
+        }
+    }
+
+    fn reverse(&mut self, old_game: &Game, new_game: &mut Game) {
+        new_game.play_count = old_game.play_count;
+        new_game.last_update = old_game.last_update.clone();
+        new_game.last_update_full = old_game.last_update_full.clone();
+        new_game.game_start_phase = old_game.game_start_phase;
+
+        // snowfallEvents is the only part of the state this touches
+        new_game.state = old_game.state.clone();
+
+        for (old_team, new_team) in [(&old_game.home, &mut new_game.home), (&old_game.away, &mut new_game.away)] {
+            new_team.batter = old_team.batter;
+            new_team.batter_name = old_team.batter_name.clone();
+            new_team.pitcher = old_team.pitcher;
+            new_team.pitcher_name = old_team.pitcher_name.clone();
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SnowflakesPlayerEffect {
+    player_id: Uuid,
+}
+
+impl SnowflakesPlayerEffect {
+    pub fn new(player_id: Uuid) -> Self { Self { player_id } }
+}
+
+impl Effect for SnowflakesPlayerEffect {
+    type Variant = SnowflakesPlayerEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Player }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.player_id) }
+
+    fn variant(&self) -> Self::Variant {
+        SnowflakesPlayerEffectVariant
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnowflakesPlayerEffectVariant;
+
+impl EffectVariant for SnowflakesPlayerEffectVariant {
+    type EntityType = Player;
+
+    fn forward(&self, player: &mut Player) {
+        let game_attr = player.mods_mut(ModDuration::Game);
+        // Re-running an ingest applies this again on top of the same parent, so it mustn't stack
+        if !game_attr.iter().any(|mod_name| mod_name == FROZEN_MOD) {
+            game_attr.push(FROZEN_MOD.to_string());
+        }
+    }
+
+    fn reverse(&mut self, old_player: &Player, new_player: &mut Player) {
+        // Restore the whole list rather than removing FROZEN, in case the player was already frozen
+        *new_player.mods_mut(ModDuration::Game) = old_player.mods(ModDuration::Game).to_vec();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SnowflakesTeamEffect {
+    team_id: Uuid,
+}
+
+impl SnowflakesTeamEffect {
+    pub fn new(team_id: Uuid) -> Self { Self { team_id } }
+}
+
+impl Effect for SnowflakesTeamEffect {
+    type Variant = SnowflakesTeamEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Team }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.team_id) }
+
+    fn variant(&self) -> Self::Variant {
+        SnowflakesTeamEffectVariant
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnowflakesTeamEffectVariant;
+
+impl EffectVariant for SnowflakesTeamEffectVariant {
+    type EntityType = Team;
+
+    // A frozen pitcher gets replaced by the next one in the rotation
+    fn forward(&self, team: &mut Team) {
+        team.rotation_slot += 1;
+    }
+
+    // Counting back down keeps anything an observation taught us about the slot
+    fn reverse(&mut self, _: &Team, new_team: &mut Team) {
+        new_team.rotation_slot -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use uuid::Uuid;
+    use partial_information::MaybeKnown;
+    use crate::entity::{test_game, GameState};
+    use crate::events::EffectVariant;
+    use super::SnowflakesGameEffectVariant;
+
+    fn round_trip(mut game: crate::entity::Game, frozen_players: Vec<Uuid>) -> crate::entity::Game {
+        let old_game = game.clone();
+        let mut variant = SnowflakesGameEffectVariant::new("Snowflakes".to_string(), Arc::new(frozen_players));
+        variant.forward(&mut game);
+        let forwarded = game.clone();
+
+        variant.reverse(&old_game, &mut game);
+        assert_eq!(game, old_game);
+        forwarded
+    }
+
+    #[test]
+    fn game_without_state_freezes_the_batter() {
+        let game = test_game();
+        assert!(game.state.is_none());
+        let batter_id = game.away.batter.expect("Test game should have an away batter");
+
+        let game = round_trip(game, vec![batter_id]);
+        assert!(game.state.is_none());
+        assert_eq!(game.away.batter, None);
+        assert_eq!(game.game_start_phase, 20);
+    }
+
+    #[test]
+    fn frozen_pitcher_is_taken_out_and_snowfall_is_counted() {
+        let mut game = test_game();
+        game.state = Some(GameState { snowfall_events: None, postseason: None, ego_player_data: None });
+        let pitcher_id = *game.home.pitcher.as_ref()
+            .and_then(|pitcher| pitcher.known())
+            .expect("Test game should have a known home pitcher");
+
+        let game = round_trip(game, vec![pitcher_id]);
+        assert_eq!(game.state.as_ref().and_then(|state| state.snowfall_events), Some(1));
+        assert_eq!(game.home.pitcher, None);
+        assert_eq!(game.home.pitcher_name, Some(MaybeKnown::Known(String::new())));
+        // The batter wasn't frozen
+        assert!(game.away.batter.is_some());
+    }
+}
@@ -0,0 +1,111 @@
+use std::fmt::{Display, Formatter};
+use chrono::{DateTime, Duration, Utc};
+use itertools::Itertools;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::entity::Game;
+
+use crate::events::{AnyEffect, AnyEvent, Effect, EffectVariant, Event};
+use crate::ingest::StateGraph;
+use crate::state::EntityType;
+
+// The game takes a tick between the third out of the top of an inning and the start of the bottom,
+// and it doesn't put anything in the Feed for it
+pub const TOP_INNING_END_DELAY_SECONDS: i64 = 5;
+
+// The successor of an event that makes one out in this game, if that out will end the top half of
+// the inning. This has to be called before the out is applied.
+pub fn third_out_successors(event_time: DateTime<Utc>, game_id: Uuid, state: &StateGraph) -> Vec<AnyEvent> {
+    // Untracked games have no versions, so they fall into the empty case
+    let ends_top_half = state.query_game_all(game_id, |game| {
+        game.top_of_inning && game.half_inning_outs == 2
+    });
+    match ends_top_half.into_iter().unique().collect_vec().as_slice() {
+        [true] => {
+            vec![TopInningEnd::new(event_time + Duration::seconds(TOP_INNING_END_DELAY_SECONDS), game_id).into()]
+        }
+        [] | [false] => { Vec::new() }
+        _ => {
+            // Scheduling it would end the half-inning early in the versions where this isn't the
+            // third out. Without it, the next observation shows the versions where it was.
+            warn!("Can't tell whether this out ends the top of the inning in game {game_id}; not scheduling TopInningEnd");
+            Vec::new()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TopInningEnd {
+    time: DateTime<Utc>,
+    game_id: Uuid,
+}
+
+impl TopInningEnd {
+    pub fn new(time: DateTime<Utc>, game_id: Uuid) -> Self {
+        TopInningEnd { time, game_id }
+    }
+}
+
+impl Event for TopInningEnd {
+    fn time(&self) -> DateTime<Utc> {
+        self.time
+    }
+
+    fn into_effects(self, _: &StateGraph) -> Vec<AnyEffect> {
+        vec![TopInningEndEffect::new(self.game_id).into()]
+    }
+
+    fn targets(&self) -> Vec<(EntityType, Uuid)> {
+        vec![(EntityType::Game, self.game_id)]
+    }
+}
+
+impl Display for TopInningEnd {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TopInningEnd for {} at {}", self.game_id, self.time)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TopInningEndEffect {
+    game_id: Uuid,
+}
+
+impl TopInningEndEffect {
+    pub fn new(game_id: Uuid) -> Self { Self { game_id } }
+}
+
+impl Effect for TopInningEndEffect {
+    type Variant = TopInningEndEffectVariant;
+
+    fn entity_type(&self) -> EntityType { EntityType::Game }
+
+    fn entity_id(&self) -> Option<Uuid> { Some(self.game_id) }
+
+    fn variant(&self) -> Self::Variant {
+        TopInningEndEffectVariant
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopInningEndEffectVariant;
+
+impl EffectVariant for TopInningEndEffectVariant {
+    type EntityType = Game;
+
+    fn forward(&self, game: &mut Game) {
+        game.phase = 2;
+        game.play_count += 1;
+        game.last_update = Some(String::new());
+        // The update between half-innings is blank, and so is its full version
+        game.last_update_full = Some(Vec::new()).into();
+    }
+
+    fn reverse(&mut self, old_game: &Game, new_game: &mut Game) {
+        new_game.phase = old_game.phase;
+        new_game.play_count = old_game.play_count;
+        new_game.last_update = old_game.last_update.clone();
+        new_game.last_update_full = old_game.last_update_full.clone();
+    }
+}
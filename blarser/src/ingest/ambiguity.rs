@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use partial_information::Conflict;
+
+use crate::state::EntityType;
+
+// What to do when an observation can be placed on more than one version
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AmbiguityStrategy {
+    // Keep every placement that succeeded and let later observations sort it out
+    KeepAll,
+    // Keep only the placements that needed the least weighted overriding. A placement that only
+    // succeeded because a high-confidence field was auto-accepted is less likely to be right than
+    // one that only disagreed on a transient field.
+    PreferMostLikely,
+}
+
+impl Default for AmbiguityStrategy {
+    fn default() -> Self {
+        AmbiguityStrategy::KeepAll
+    }
+}
+
+impl std::str::FromStr for AmbiguityStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep_all" => { Ok(AmbiguityStrategy::KeepAll) }
+            "prefer_most_likely" => { Ok(AmbiguityStrategy::PreferMostLikely) }
+            other => { Err(format!("Unknown ambiguity strategy {other:?}. Expected keep_all or prefer_most_likely.")) }
+        }
+    }
+}
+
+// How much a disagreement on each field counts against a placement. Looked up the same way as
+// ApprovalPolicies, so the most specific configured path wins.
+#[derive(Debug, Clone)]
+pub struct FieldWeights {
+    weights: HashMap<(EntityType, String), f32>,
+    default: f32,
+}
+
+impl FieldWeights {
+    pub fn new(default: f32) -> Self {
+        Self {
+            weights: HashMap::new(),
+            default,
+        }
+    }
+
+    pub fn with(mut self, entity_type: EntityType, field_path: &str, weight: f32) -> Self {
+        self.set(entity_type, field_path, weight);
+        self
+    }
+
+    pub fn set(&mut self, entity_type: EntityType, field_path: &str, weight: f32) {
+        self.weights.insert((entity_type, field_path.trim_end_matches('/').to_string()), weight);
+    }
+
+    pub fn weight_for(&self, entity_type: EntityType, conflict: &Conflict) -> f32 {
+        let path = conflict.path();
+        (1..=path.len()).rev()
+            .find_map(|len| self.weights.get(&(entity_type, path[..len].join("/"))))
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    // Lower is more likely
+    pub fn score(&self, entity_type: EntityType, conflicts: &[Conflict]) -> f32 {
+        conflicts.iter()
+            .map(|conflict| self.weight_for(entity_type, conflict))
+            .sum()
+    }
+}
+
+impl Default for FieldWeights {
+    fn default() -> Self {
+        Self::new(1.0)
+            // Scores only change through events Blarser handles, so they're very trustworthy
+            .with(EntityType::Game, "home/score", 10.0)
+            .with(EntityType::Game, "away/score", 10.0)
+            .with(EntityType::Game, "half_inning_outs", 5.0)
+            // Phase and the update text flicker around too much to mean much
+            .with(EntityType::Game, "phase", 0.25)
+            .with(EntityType::Game, "last_update", 0.25)
+            .with(EntityType::Game, "last_update_full", 0.25)
+            .with(EntityType::Game, "score_update", 0.1)
+            .with(EntityType::Game, "score_ledger", 0.1)
+    }
+}
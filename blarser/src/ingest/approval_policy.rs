@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use partial_information::{Conflict, Severity};
+use uuid::Uuid;
+
+use crate::ingest::Observation;
+use crate::state::EntityType;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ApprovalPolicy {
+    // Take the observed value and move on. For cosmetic stuff nobody should have to click through.
+    AutoAcceptObserved,
+    // Take the observed value, but record the conflict as Info, whatever severity the field gave
+    // it. For things that don't matter to the state but still mean an event handler did something
+    // wrong.
+    LowSeverity,
+    // Always ask a human, even if it's something that could otherwise be resolved
+    AlwaysApprove,
+    // Normal behavior: the version the conflict came from is rejected
+    Conflict,
+    // Keep the value the events produced and ignore the observed one. The conflict is still
+    // recorded. Can't be combined with taking the observed value for other fields.
+    KeepExpected,
+}
+
+// Scores are reported by both Feed events and Chronicler, and they're the field people care about
+// most, so disagreements between the two get their own knob
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScorePolicy {
+    PreferFeed,
+    PreferObservation,
+    AlwaysApprove,
+}
+
+impl ScorePolicy {
+    pub fn approval_policy(self) -> ApprovalPolicy {
+        match self {
+            ScorePolicy::PreferFeed => { ApprovalPolicy::KeepExpected }
+            ScorePolicy::PreferObservation => { ApprovalPolicy::AutoAcceptObserved }
+            ScorePolicy::AlwaysApprove => { ApprovalPolicy::AlwaysApprove }
+        }
+    }
+}
+
+impl std::str::FromStr for ScorePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "prefer_feed" => { Ok(ScorePolicy::PreferFeed) }
+            "prefer_observation" => { Ok(ScorePolicy::PreferObservation) }
+            "always_approve" => { Ok(ScorePolicy::AlwaysApprove) }
+            other => { Err(format!("Unknown score policy {other:?}. Expected prefer_feed, prefer_observation, or always_approve.")) }
+        }
+    }
+}
+
+impl Default for ScorePolicy {
+    fn default() -> Self {
+        ScorePolicy::AlwaysApprove
+    }
+}
+
+const SCORE_FIELDS: [&str; 2] = ["home/score", "away/score"];
+
+#[derive(Debug, Clone)]
+pub struct ApprovalPolicies {
+    // Field paths use the same format as Conflict::property, e.g. "home/score"
+    policies: HashMap<(EntityType, String), ApprovalPolicy>,
+    default: ApprovalPolicy,
+    score_policy: ScorePolicy,
+}
+
+impl ApprovalPolicies {
+    pub fn new(default: ApprovalPolicy) -> Self {
+        Self {
+            policies: HashMap::new(),
+            default,
+            score_policy: ScorePolicy::default(),
+        }
+    }
+
+    pub fn with_score_policy(mut self, score_policy: ScorePolicy) -> Self {
+        self.score_policy = score_policy;
+        self
+    }
+
+    pub fn score_policy(&self) -> ScorePolicy {
+        self.score_policy
+    }
+
+    pub fn with(mut self, entity_type: EntityType, field_path: &str, policy: ApprovalPolicy) -> Self {
+        self.set(entity_type, field_path, policy);
+        self
+    }
+
+    pub fn set(&mut self, entity_type: EntityType, field_path: &str, policy: ApprovalPolicy) {
+        self.policies.insert((entity_type, field_path.trim_end_matches('/').to_string()), policy);
+    }
+
+    pub fn policy_for(&self, entity_type: EntityType, conflict: &Conflict) -> ApprovalPolicy {
+        // The most specific configured path wins, so try the longest prefix of the path first
+        let path = conflict.path();
+        (1..=path.len()).rev()
+            .find_map(|len| self.policies.get(&(entity_type, path[..len].join("/"))))
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    // Like policy_for, but for a conflict against a specific version. If that version's score came
+    // from Feed events (see EntityStateGraph::score_from_feed), score conflicts are a
+    // Feed/Chronicler disagreement and the score policy decides them.
+    pub fn policy_for_placement(&self, entity_type: EntityType, conflict: &Conflict, score_from_feed: bool) -> ApprovalPolicy {
+        if score_from_feed && entity_type == EntityType::Game && SCORE_FIELDS.contains(&conflict.property().as_str()) {
+            self.score_policy.approval_policy()
+        } else {
+            self.policy_for(entity_type, conflict)
+        }
+    }
+}
+
+impl Default for ApprovalPolicies {
+    fn default() -> Self {
+        Self::new(ApprovalPolicy::Conflict)
+            .with(EntityType::Game, "score_update", ApprovalPolicy::AutoAcceptObserved)
+            .with(EntityType::Game, "score_ledger", ApprovalPolicy::AutoAcceptObserved)
+            // A mismatched update message is a strong hint that the handler built it wrong
+            .with(EntityType::Game, "last_update", ApprovalPolicy::LowSeverity)
+            .with(EntityType::Game, "last_update_full", ApprovalPolicy::LowSeverity)
+            // This only covers scores that didn't come from Feed. The rest go through ScorePolicy.
+            .with(EntityType::Game, "home/score", ApprovalPolicy::AlwaysApprove)
+            .with(EntityType::Game, "away/score", ApprovalPolicy::AlwaysApprove)
+    }
+}
+
+#[derive(Debug)]
+pub struct QueuedApproval {
+    pub entity_type: EntityType,
+    pub entity_id: Uuid,
+    pub perceived_at: DateTime<Utc>,
+    pub message: String,
+    // The worst of the conflicts that made this need approval
+    pub severity: Severity,
+    // The observation that's waiting on the approval, to be ingested if it's approved
+    pub observation: Option<Observation>,
+}
+
+#[cfg(test)]
+mod tests {
+    use partial_information::Conflict;
+    use crate::state::EntityType;
+    use super::{ApprovalPolicies, ApprovalPolicy, ScorePolicy};
+
+    fn conflict_at(path: &[&str]) -> Conflict {
+        let (last, rest) = path.split_last().expect("Path should not be empty");
+        rest.iter().rev()
+            .fold(Conflict::new(last.to_string(), String::new()), |conflict, prefix| conflict.with_prefix(prefix))
+    }
+
+    #[test]
+    fn the_most_specific_configured_path_wins() {
+        let policies = ApprovalPolicies::new(ApprovalPolicy::Conflict)
+            .with(EntityType::Game, "home/", ApprovalPolicy::LowSeverity)
+            .with(EntityType::Game, "home/score", ApprovalPolicy::AlwaysApprove);
+
+        assert_eq!(policies.policy_for(EntityType::Game, &conflict_at(&["home", "score"])), ApprovalPolicy::AlwaysApprove);
+        assert_eq!(policies.policy_for(EntityType::Game, &conflict_at(&["home", "odds"])), ApprovalPolicy::LowSeverity);
+        assert_eq!(policies.policy_for(EntityType::Game, &conflict_at(&["away", "score"])), ApprovalPolicy::Conflict);
+        // Policies only apply to the entity type they were configured for
+        assert_eq!(policies.policy_for(EntityType::Team, &conflict_at(&["home", "score"])), ApprovalPolicy::Conflict);
+    }
+
+    #[test]
+    fn score_conflicts_against_feed_scores_follow_the_score_policy() {
+        let policies = ApprovalPolicies::default().with_score_policy(ScorePolicy::PreferFeed);
+        let score = conflict_at(&["home", "score"]);
+
+        assert_eq!(policies.policy_for_placement(EntityType::Game, &score, true), ApprovalPolicy::KeepExpected);
+        assert_eq!(policies.policy_for_placement(EntityType::Game, &score, false), ApprovalPolicy::AlwaysApprove);
+        assert_eq!(policies.policy_for_placement(EntityType::Game, &conflict_at(&["score_update"]), true),
+                   ApprovalPolicy::AutoAcceptObserved);
+    }
+
+    #[test]
+    fn score_policies_parse_from_their_names() {
+        assert_eq!("prefer_observation".parse::<ScorePolicy>(), Ok(ScorePolicy::PreferObservation));
+        assert!("prefer_nothing".parse::<ScorePolicy>().is_err());
+    }
+}
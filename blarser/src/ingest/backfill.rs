@@ -0,0 +1,115 @@
+use chrono::{DateTime, Duration, Utc};
+
+// A backfill replays a fixed historical range instead of following along forever. It works in
+// chunks of game time: after each one the progress is saved to the ingest, so an interrupted
+// backfill can pick up where it left off, and the task pauses briefly so it doesn't starve the
+// web server.
+#[derive(Debug, Copy, Clone)]
+pub struct BackfillConfig {
+    pub until: DateTime<Utc>,
+    pub chunk_size: Duration,
+    pub pause_between_chunks: std::time::Duration,
+    // Building the debug trees is a big part of the time spent on each item, and nobody watches
+    // the debug UI during a bulk backfill
+    pub record_debug_history: bool,
+}
+
+impl BackfillConfig {
+    pub fn new(until: DateTime<Utc>) -> Self {
+        Self {
+            until,
+            chunk_size: Duration::hours(1),
+            pause_between_chunks: std::time::Duration::from_millis(100),
+            record_debug_history: false,
+        }
+    }
+
+    pub fn with_chunk_size(mut self, chunk_size: Duration) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    pub fn with_debug_history(mut self, record_debug_history: bool) -> Self {
+        self.record_debug_history = record_debug_history;
+        self
+    }
+}
+
+// Tracks where the next chunk boundary is during a backfill
+pub(crate) struct BackfillProgress {
+    config: BackfillConfig,
+    next_checkpoint: DateTime<Utc>,
+}
+
+pub(crate) enum BackfillStep {
+    // Still inside the current chunk
+    Continue,
+    // Crossed into a new chunk. Everything before the contained time has been ingested.
+    Checkpoint(DateTime<Utc>),
+    // Reached the end of the range, which is contained
+    Done(DateTime<Utc>),
+}
+
+impl BackfillProgress {
+    pub fn new(config: BackfillConfig, start_time: DateTime<Utc>) -> Self {
+        Self {
+            config,
+            next_checkpoint: (start_time + config.chunk_size).min(config.until),
+        }
+    }
+
+    pub fn pause_between_chunks(&self) -> std::time::Duration {
+        self.config.pause_between_chunks
+    }
+
+    // Call with the time of each item before it's ingested
+    pub fn step(&mut self, time: DateTime<Utc>) -> BackfillStep {
+        if time >= self.config.until {
+            return BackfillStep::Done(self.config.until);
+        }
+        if time < self.next_checkpoint {
+            return BackfillStep::Continue;
+        }
+
+        let checkpoint = self.next_checkpoint;
+        // There may have been a gap longer than a chunk with nothing in it
+        while self.next_checkpoint <= time {
+            self.next_checkpoint = (self.next_checkpoint + self.config.chunk_size).min(self.config.until);
+        }
+        BackfillStep::Checkpoint(checkpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use super::{BackfillConfig, BackfillProgress, BackfillStep};
+
+    #[test]
+    fn backfill_checkpoints_at_each_chunk_and_stops_at_the_end() {
+        let start = Utc.with_ymd_and_hms(2021, 3, 1, 0, 0, 0).unwrap();
+        let config = BackfillConfig::new(start + Duration::minutes(150))
+            .with_chunk_size(Duration::hours(1));
+        let mut progress = BackfillProgress::new(config, start);
+
+        assert!(matches!(progress.step(start + Duration::minutes(30)), BackfillStep::Continue));
+        assert!(matches!(progress.step(start + Duration::minutes(70)), BackfillStep::Checkpoint(t) if t == start + Duration::hours(1)));
+        assert!(matches!(progress.step(start + Duration::minutes(80)), BackfillStep::Continue));
+        // The last chunk is cut short by the end of the range
+        assert!(matches!(progress.step(start + Duration::minutes(140)), BackfillStep::Checkpoint(t) if t == start + Duration::hours(2)));
+        assert!(matches!(progress.step(start + Duration::minutes(150)), BackfillStep::Done(t) if t == start + Duration::minutes(150)));
+    }
+
+    #[test]
+    fn gap_longer_than_a_chunk_checkpoints_once() {
+        let start = Utc.with_ymd_and_hms(2021, 3, 1, 0, 0, 0).unwrap();
+        let config = BackfillConfig::new(start + Duration::days(1))
+            .with_chunk_size(Duration::hours(1));
+        let mut progress = BackfillProgress::new(config, start);
+
+        assert!(matches!(progress.step(start + Duration::minutes(200)), BackfillStep::Checkpoint(t) if t == start + Duration::hours(1)));
+        // The next checkpoint is the first boundary after the gap
+        assert!(matches!(progress.step(start + Duration::minutes(230)), BackfillStep::Continue));
+        assert!(matches!(progress.step(start + Duration::minutes(240)), BackfillStep::Checkpoint(t) if t == start + Duration::hours(4)));
+    }
+}
@@ -1,7 +1,5 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashSet};
 use std::fmt::{Debug, Display, Formatter};
-use std::fs::File;
-use std::io::BufReader;
 use std::iter;
 use std::path::Path;
 use std::pin::Pin;
@@ -10,21 +8,27 @@ use chrono::{DateTime, Utc};
 use futures::{stream, Stream, StreamExt};
 use itertools::Itertools;
 use rocket::info;
-use partial_information::{Conflict, PartialInformationCompare};
+use partial_information::{Conflict, FieldAccess, PartialInformationCompare, Severity};
 use futures::future::join_all;
-use log::error;
+use log::{error, warn};
 use petgraph::stable_graph::NodeIndex;
 use petgraph::visit::Walker;
 use serde::Deserialize;
 use uuid::Uuid;
 
 use crate::api::chronicler;
-use crate::ingest::task::{DebugHistoryVersion, Ingest};
-use crate::entity::{self, AnyEntity, AnyEntityRaw, Entity, EntityParseError};
-use crate::events::{AnyEvent, Event, with_any_event};
-use crate::ingest::GraphDebugHistory;
+use crate::ingest::csv_index;
+use crate::ingest::fed::unqueued_timed_events;
+use crate::ingest::error::{IngestError, IngestResult};
+use crate::ingest::task::{DebugHistoryVersion, EventLogEntry, Ingest};
+use crate::entity::{with_entity, AnyEntity, AnyEntityRaw, Entity, EntityParseError};
+use crate::events::{AnyEvent, Event, EffectVariant, with_any_event, with_effect_variant};
+use crate::ingest::{ApprovalPolicies, ApprovalPolicy, GraphDebugHistory};
+use crate::ingest::approval_policy::QueuedApproval;
+use crate::ingest::ambiguity::AmbiguityStrategy;
 use crate::ingest::observation::Observation;
 use crate::ingest::state::{AddedReason, EntityStateGraph, StateGraphNode};
+use crate::state::{EntityType, NewConflict};
 // use crate::events::Event;
 
 fn initial_state(start_at_time: DateTime<Utc>) -> impl Stream<Item=Observation> {
@@ -109,28 +113,22 @@ pub fn chron_updates_hardcoded(start_at_time: DateTime<Utc>) -> impl Iterator<It
         .chain(iter::once("game"))
         .flat_map(move |entity_type| {
             let path = Path::new("blarser").join("data").join(entity_type.to_owned() + ".csv");
-            let file = File::open(path).ok()?;
-            let rdr = csv::Reader::from_reader(BufReader::new(file));
-
-            let iter = rdr.into_records()
-                .filter_map(move |result| {
-                    let record = result.expect("Reading CSV row failed");
-                    let dt_str = record.get(1).unwrap().replace(" ", "T") + ":00";
+            let iter = csv_index::records_from(&path, start_at_time)?
+                .map(move |record| {
                     let row = CsvRow {
                         entity_id: Uuid::try_parse(record.get(0).unwrap()).unwrap(),
-                        timestamp: DateTime::from(DateTime::parse_from_rfc3339(&dt_str).unwrap()),
+                        timestamp: csv_index::parse_csv_timestamp(&record),
                         hash: record.get(2).unwrap().to_string(),
                         data: serde_json::from_str(&record.get(3).unwrap())
                             .expect("JSON parse from CSV failed"),
                     };
-                    if row.timestamp < start_at_time { return None; }
                     let entity_type = entity_type.try_into().unwrap();
-                    Some(Observation {
+                    Observation {
                         perceived_at: row.timestamp,
                         entity_type,
                         entity_id: row.entity_id,
                         entity_raw: AnyEntityRaw::from_json(entity_type, row.data).unwrap(),
-                    })
+                    }
                 });
 
             Some(iter.peekable())
@@ -139,17 +137,21 @@ pub fn chron_updates_hardcoded(start_at_time: DateTime<Utc>) -> impl Iterator<It
 
     info!("Got {} iterators", iters.len());
 
+    merge_by_time(iters)
+}
+
+// Takes from whichever stream has the earliest next observation, and ends once every stream has
+// run out
+pub(crate) fn merge_by_time<I: Iterator<Item=Observation>>(iters: Vec<iter::Peekable<I>>) -> impl Iterator<Item=Observation> {
     itertools::unfold(iters, |iters| {
-        let peeks = iters.iter_mut()
+        let (chosen_stream, _) = iters.iter_mut()
             .map(|s| s.peek())
-            .collect_vec();
-        let (chosen_stream, _) = peeks.into_iter()
-            .flatten()
             .enumerate()
-            .min_by_key(|(_, obs)| obs.latest_time())
-            .expect("This should never be None");
+            // Streams that have run out are skipped without renumbering the rest
+            .filter_map(|(i, peek)| peek.map(|obs| (i, obs)))
+            .min_by_key(|(_, obs)| obs.latest_time())?;
 
-        Some(iters[chosen_stream].next().unwrap())
+        iters[chosen_stream].next()
     })
 }
 
@@ -160,13 +162,29 @@ pub async fn load_initial_state(start_at_time: DateTime<Utc>) -> Vec<Observation
 #[derive(Debug)]
 pub struct GenerationConflict {
     start_time: DateTime<Utc>,
-    event_name: &'static str,
+    event_name: String,
     version_conflicts: Vec<Vec<Conflict>>,
 }
 
+impl GenerationConflict {
+    pub fn conflicts(&self) -> impl Iterator<Item=&Conflict> {
+        self.version_conflicts.iter().flatten()
+    }
+}
+
 #[derive(Debug)]
 pub struct GenerationConflicts(Vec<GenerationConflict>);
 
+impl GenerationConflicts {
+    pub fn conflicts(&self) -> impl Iterator<Item=&Conflict> {
+        self.0.iter().flat_map(|generation| generation.conflicts())
+    }
+
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.conflicts().map(|conflict| conflict.severity()).max()
+    }
+}
+
 impl Display for GenerationConflicts {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "Couldn't apply observation: ")?;
@@ -187,21 +205,93 @@ impl Display for GenerationConflicts {
     }
 }
 
-pub fn ingest_observation(ingest: &mut Ingest, obs: Observation, debug_history: &mut GraphDebugHistory) -> Vec<AnyEvent> {
-    let obs = Arc::new(obs); // sigh
+// `approved` means a human has already looked at this observation and said to take it, so conflicts
+// that would otherwise need approval or reject it take the observed value instead
+pub fn ingest_observation(ingest: &mut Ingest, obs: Observation, approved: bool, debug_history: &mut GraphDebugHistory) -> IngestResult<Vec<AnyEvent>> {
+    ingest.event_log.lock().unwrap().push(EventLogEntry::Observation { observation: obs.clone(), approved });
     let mut state = ingest.state.lock().unwrap();
+    // Whatever else happens to it, this is the entity's first observation, so it's not expected any
+    // more
+    let expected = state.take_expected_entity(obs.entity_type, obs.entity_id);
+    if state.entity_graph(obs.entity_type, obs.entity_id).is_none() {
+        if state.restore_entity_graph(obs.entity_type, obs.entity_id) {
+            info!("Observed {} {} after it was removed; restoring its previous history",
+                obs.entity_type, obs.entity_id);
+        } else if expected {
+            info!("Observed new {} {}; creating it", obs.entity_type, obs.entity_id);
+            let perceived_at = obs.perceived_at;
+            state.add_observed_entity(obs, perceived_at, "Created", "Created by observation",
+                                      AddedReason::NewFromEvent, debug_history);
+            return Ok(Vec::new());
+        } else {
+            // Entities do show up without warning, e.g. when a player is created by an event
+            // blarser doesn't handle yet. There's nothing to check the observation against,
+            // so it's taken as-is, the same way the initial state is.
+            warn!("Observed {} {}, which did not previously exist and wasn't expected; creating it",
+                obs.entity_type, obs.entity_id);
+            let perceived_at = obs.perceived_at;
+            state.add_observed_entity(obs, perceived_at, "Created", "Created by unexpected observation",
+                                      AddedReason::Observed, debug_history);
+            return Ok(Vec::new());
+        }
+    } else if expected {
+        // The event that created it only knew its id and team, so there's nothing to check the
+        // observation against
+        info!("First observation of new {} {}; filling in what the event that created it couldn't know",
+            obs.entity_type, obs.entity_id);
+        let graph = state.entity_graph_mut(obs.entity_type, obs.entity_id)
+            .expect("Entity graph was just checked");
+        fill_in_new_entity(graph, Arc::new(obs), debug_history);
+        return Ok(Vec::new());
+    }
+    let obs = Arc::new(obs); // sigh
     let graph = state.entity_graph_mut(obs.entity_type, obs.entity_id)
-        .expect("Tried to ingest observation for an entity that did not previously exist. \
-        This should work in the future but is not implemented yet.");
+        .expect("Entity graph was just checked or created");
+
+    if let Some(latest_observed) = graph.latest_observed() && obs.perceived_at < latest_observed {
+        // Chronicler occasionally delivers an older version after a newer one. The versions it
+        // belongs to already have descendants, so it goes through the reverse pass only. If it
+        // contradicts every version it could belong to, it's flagged for a human.
+        warn!("Observation of {} {} at {} arrived after one at {}; using it to refine earlier versions",
+            obs.entity_type, obs.entity_id, obs.perceived_at, latest_observed);
+        let conflicts = ingest_backward_observation(graph, obs.clone(), debug_history);
+        let ingest_id = ingest.ingest_id;
+        ingest.recorded_conflicts.extend(conflicts.into_iter()
+            .map(|conflict| NewConflict {
+                ingest_id,
+                entity_type: obs.entity_type,
+                entity_id: obs.entity_id,
+                perceived_at: obs.perceived_at,
+                property: conflict.property(),
+                message: format!("Observation arrived out of order, after one perceived at {}: {}",
+                                 latest_observed, conflict.message()),
+                severity: conflict.severity().level(),
+            }));
+        return Ok(Vec::new());
+    }
+
+    if let Some(previous) = graph.latest_ingested_observation() && ingest.dedup_windows.is_duplicate(previous, &obs) {
+        info!("Skipping observation of {} {} at {}, which is identical to the one at {}",
+            obs.entity_type, obs.entity_id, obs.perceived_at, previous.perceived_at);
+        return Ok(Vec::new());
+    }
 
     info!("Ingesting observation for {} {} between {} and {}",
         obs.entity_type, obs.entity_id, obs.earliest_time(), obs.latest_time());
 
-    let versions = graph.get_candidate_placements(obs.earliest_time(), obs.latest_time());
-    let mut queued_for_update = versions.clone();
+    // Sorted so the placements are tried, and their results merged, in the same order every run
+    let versions = graph.get_candidate_placements(obs.earliest_time(), obs.latest_time())
+        .into_iter()
+        .sorted_by_key(|&idx| {
+            let node = graph.get_version(idx)
+                .expect("Expected node index from get_candidate_placements to be valid");
+            (node.valid_from, idx)
+        })
+        .collect_vec();
+    let mut queued_for_update: HashSet<_> = versions.iter().copied().collect();
 
     let debug_key = (obs.entity_type, obs.entity_id);
-    debug_history.push(&debug_key, DebugHistoryVersion {
+    debug_history.push(&debug_key, || DebugHistoryVersion {
         event_human_name: format!("Before ingesting observation at {}", obs.perceived_at),
         time: obs.perceived_at,
         tree: graph.get_debug_tree(),
@@ -210,6 +300,7 @@ pub fn ingest_observation(ingest: &mut Ingest, obs: Observation, debug_history:
         queued_for_delete: None,
     });
 
+    let mut recorded = Vec::new();
     let (successes, failures): (Vec<_>, Vec<_>) = versions.into_iter()
         .map(|version_idx| {
             info!("Running ingest on version {version_idx:?}");
@@ -218,18 +309,20 @@ pub fn ingest_observation(ingest: &mut Ingest, obs: Observation, debug_history:
 
             queued_for_update.remove(&version_idx);
 
-            match &node.entity {
-                AnyEntity::Sim(_) => { ingest_for_version::<entity::Sim>(graph, version_idx, obs.clone(), debug_history, &queued_for_update, obs.perceived_at) }
-                AnyEntity::Player(_) => { ingest_for_version::<entity::Player>(graph, version_idx, obs.clone(), debug_history, &queued_for_update, obs.perceived_at) }
-                AnyEntity::Team(_) => { ingest_for_version::<entity::Team>(graph, version_idx, obs.clone(), debug_history, &queued_for_update, obs.perceived_at) }
-                AnyEntity::Game(_) => { ingest_for_version::<entity::Game>(graph, version_idx, obs.clone(), debug_history, &queued_for_update, obs.perceived_at) }
-                AnyEntity::Standings(_) => { ingest_for_version::<entity::Standings>(graph, version_idx, obs.clone(), debug_history, &queued_for_update, obs.perceived_at) }
-                AnyEntity::Season(_) => { ingest_for_version::<entity::Season>(graph, version_idx, obs.clone(), debug_history, &queued_for_update, obs.perceived_at) }
-            }
+            let start_time = node.valid_from;
+            let event_name = graph.created_by(version_idx);
+            with_entity!(&node.entity, |_: EntityT| {
+                ingest_for_version::<EntityT>(graph, version_idx, obs.clone(), &ingest.approval_policies, approved, &mut recorded, debug_history, &queued_for_update, obs.perceived_at)
+            })
+                .map_err(|conflicts| GenerationConflict {
+                    start_time,
+                    event_name,
+                    version_conflicts: vec![conflicts],
+                })
         })
         .partition_result();
 
-    debug_history.push(&debug_key, DebugHistoryVersion {
+    debug_history.push(&debug_key, || DebugHistoryVersion {
         event_human_name: format!("End of ingest at {}", obs.perceived_at),
         time: obs.perceived_at,
         tree: graph.get_debug_tree(),
@@ -239,17 +332,88 @@ pub fn ingest_observation(ingest: &mut Ingest, obs: Observation, debug_history:
     });
 
     if successes.is_empty() {
-        error!("All possible placements failed: {:#?}", failures);
-        assert!(false, "TODO Report failures");
+        // Record every conflict so it can be exported for analysis later, whatever happens to it
+        let ingest_id = ingest.ingest_id;
+        ingest.recorded_conflicts.extend(failures.iter()
+            .flat_map(|generation| generation.conflicts())
+            .map(|conflict| NewConflict {
+                ingest_id,
+                entity_type: obs.entity_type,
+                entity_id: obs.entity_id,
+                perceived_at: obs.perceived_at,
+                property: conflict.property(),
+                message: conflict.message().to_string(),
+                severity: conflict.severity().level(),
+            }));
+
+        let needs_approval = failures.iter()
+            .flat_map(|generation| generation.conflicts())
+            .filter(|conflict| {
+                ingest.approval_policies.policy_for(obs.entity_type, conflict) == ApprovalPolicy::AlwaysApprove
+            })
+            .collect_vec();
+        if let Some(severity) = needs_approval.iter().map(|conflict| conflict.severity()).max() {
+            info!("Observation conflicts with fields that always need approval; queueing it");
+            ingest.queued_approvals.push(QueuedApproval {
+                entity_type: obs.entity_type,
+                entity_id: obs.entity_id,
+                perceived_at: obs.perceived_at,
+                message: needs_approval.iter().map(|conflict| conflict.to_string()).join("\n"),
+                severity,
+                observation: Some((*obs).clone()),
+            });
+            return Ok(Vec::new());
+        }
+
+        return Err(IngestError::Conflict {
+            ty: obs.entity_type,
+            id: obs.entity_id,
+            perceived_at: obs.perceived_at,
+            conflicts: GenerationConflicts(failures),
+        });
     }
 
-    let new_leafs = merge_generations(graph, successes.into_iter().flatten());
+    // The same conflict usually comes up for every placement, so only record each one once
+    let ingest_id = ingest.ingest_id;
+    ingest.recorded_conflicts.extend(recorded.into_iter()
+        .unique_by(|conflict: &Conflict| (conflict.to_string(), conflict.severity()))
+        .map(|conflict| NewConflict {
+            ingest_id,
+            entity_type: obs.entity_type,
+            entity_id: obs.entity_id,
+            perceived_at: obs.perceived_at,
+            property: conflict.property(),
+            message: conflict.message().to_string(),
+            severity: conflict.severity().level(),
+        }));
+
+    let successes = match ingest.ambiguity_strategy {
+        AmbiguityStrategy::KeepAll => { successes }
+        AmbiguityStrategy::PreferMostLikely => {
+            let scored = successes.into_iter()
+                .map(|(nodes, accepted)| (ingest.field_weights.score(obs.entity_type, &accepted), (nodes, accepted)))
+                .collect_vec();
+            let best = scored.iter()
+                .map(|(score, _)| *score)
+                .fold(f32::INFINITY, f32::min);
+            info!("Keeping placements with score {best} out of {} candidates", scored.len());
+            scored.into_iter()
+                .filter(|(score, _)| *score <= best)
+                .map(|(_, success)| success)
+                .collect()
+        }
+    };
 
-    let prev_nodes = get_reachable_nodes(graph, graph.leafs().clone());
-    let keep_nodes = get_reachable_nodes(graph, new_leafs.clone());
-    let delete_nodes: HashSet<_> = prev_nodes.difference(&keep_nodes).copied().collect();
+    // Whatever the old leafs implied is already in the timed event queue
+    let prev_timed_events = graph.leafs().iter()
+        .flat_map(|&idx| graph.timed_events_from(idx))
+        .collect_vec();
 
-    debug_history.push(&debug_key, DebugHistoryVersion {
+    let new_leafs = merge_generations(graph, successes.into_iter().flat_map(|(nodes, _)| nodes));
+
+    let delete_nodes = get_unreachable_nodes(graph, &new_leafs);
+
+    debug_history.push(&debug_key, || DebugHistoryVersion {
         event_human_name: format!("Before delete from ingest at {}", obs.perceived_at),
         time: obs.perceived_at,
         tree: graph.get_debug_tree(),
@@ -258,13 +422,15 @@ pub fn ingest_observation(ingest: &mut Ingest, obs: Observation, debug_history:
         queued_for_delete: Some(delete_nodes.clone()),
     });
 
+    // This also drops any roots that only led to deleted nodes
     for &node_idx in &delete_nodes {
         graph.remove_node(node_idx);
     }
 
     graph.set_leafs(new_leafs);
+    graph.record_observed(obs.clone());
 
-    debug_history.push(&debug_key, DebugHistoryVersion {
+    debug_history.push(&debug_key, || DebugHistoryVersion {
         event_human_name: format!("After delete from ingest at {}", obs.perceived_at),
         time: obs.perceived_at,
         tree: graph.get_debug_tree(),
@@ -273,12 +439,145 @@ pub fn ingest_observation(ingest: &mut Ingest, obs: Observation, debug_history:
         queued_for_delete: Some(delete_nodes), // leave it here to make problems more obvious
     });
 
-    Vec::new() // TODO Generate new timed events
+    let new_timed_events = unqueued_timed_events(&prev_timed_events, graph.leafs().iter()
+        .flat_map(|&idx| graph.timed_events_from(idx))
+        .collect());
+    if !new_timed_events.is_empty() {
+        info!("Observation implied {} new timed events", new_timed_events.len());
+    }
+
+    Ok(new_timed_events)
+}
+
+// Every leaf of a graph that an event created (see StateGraph::add_new_entity) is replaced by the
+// observed entity
+fn fill_in_new_entity(graph: &mut EntityStateGraph, obs: Arc<Observation>, debug_history: &mut GraphDebugHistory) {
+    let entity = AnyEntity::from_raw(obs.entity_raw.clone());
+    for idx in graph.leafs().clone() {
+        let node = graph.get_version_mut(idx)
+            .expect("Indices in State.leafs should always be valid");
+        node.entity = entity.clone();
+        node.observed = Some(obs.clone());
+    }
+    graph.record_observed(obs.clone());
+
+    debug_history.push(&(obs.entity_type, obs.entity_id), || DebugHistoryVersion {
+        event_human_name: format!("First observation at {}", obs.perceived_at),
+        time: obs.perceived_at,
+        tree: graph.get_debug_tree(),
+        queued_for_update: None,
+        currently_updating: None,
+        queued_for_delete: None,
+    });
+}
+
+// For observations that arrive after a newer one. Each version the observation could belong to is
+// refined by it and then reconciled with its parents by the reverse pass. There's no forward pass,
+// so descendants of a refined version are moved over to it unchanged. They were computed from a
+// less precise version, so they're still right, just less precise than they could be. Returns the
+// conflicts if the observation didn't fit any version.
+fn ingest_backward_observation(graph: &mut EntityStateGraph, obs: Arc<Observation>, debug_history: &mut GraphDebugHistory) -> Vec<Conflict> {
+    let placements = graph.get_candidate_placements(obs.earliest_time(), obs.latest_time());
+    let mut any_placed = false;
+    let mut conflicts = Vec::new();
+    for version_idx in placements {
+        // An earlier placement's reverse pass may have replaced this one
+        let Some(node) = graph.get_version(version_idx) else { continue };
+        let result = with_entity!(&node.entity, |_: EntityT| {
+            refine_from_backward_observation::<EntityT>(graph, version_idx, obs.clone(), debug_history)
+        });
+        match result {
+            Ok(()) => { any_placed = true; }
+            Err(version_conflicts) => { conflicts.extend(version_conflicts); }
+        }
+    }
+
+    if !any_placed {
+        // Nothing was changed, so there's nothing to clean up. Branches the observation
+        // contradicts are left alone, since newer observations already vouched for them.
+        return conflicts;
+    }
+
+    let leafs = graph.leafs().clone();
+    let reachable = get_reachable_nodes(graph, leafs);
+    let unreachable = graph.graph.graph().node_indices()
+        .filter(|idx| !reachable.contains(idx))
+        .collect_vec();
+    for node_idx in unreachable {
+        graph.remove_node(node_idx);
+    }
+
+    Vec::new()
+}
+
+fn refine_from_backward_observation<EntityT>(
+    graph: &mut EntityStateGraph,
+    entity_idx: NodeIndex,
+    obs: Arc<Observation>,
+    debug_history: &mut GraphDebugHistory,
+) -> Result<(), Vec<Conflict>>
+    where EntityT: Entity + PartialInformationCompare + Into<AnyEntity> + 'static,
+          AnyEntity: TryInto<EntityT>,
+          <AnyEntity as TryInto<EntityT>>::Error: Debug,
+          for<'a> &'a AnyEntity: TryInto<&'a EntityT>,
+          for<'a> <&'a AnyEntity as TryInto<&'a EntityT>>::Error: Debug,
+          for<'a> &'a AnyEntityRaw: TryInto<&'a EntityT::Raw>,
+          for<'a> <&'a AnyEntityRaw as TryInto<&'a EntityT::Raw>>::Error: Debug {
+    let node = graph.get_version(entity_idx)
+        .expect("Expected node index from get_candidate_placements to be valid");
+    let entity: &EntityT = (&node.entity).try_into()
+        .expect("This coercion should always succeed");
+    let raw: &EntityT::Raw = (&obs.entity_raw).try_into()
+        .expect("Observation must be for the same entity type as its graph");
+
+    let valid_from = node.valid_from;
+
+    let mut new_entity = entity.clone();
+    let conflicts = new_entity.observe(raw);
+    if !conflicts.is_empty() {
+        return Err(conflicts);
+    }
+
+    if &new_entity == entity {
+        let node = graph.get_version_mut(entity_idx)
+            .expect("This version should exist");
+        if node.observed.is_none() {
+            node.observed = Some(obs);
+        }
+        return Ok(());
+    }
+
+    let new_entity_idx = graph.add_observed_child_disconnected(
+        new_entity.into(),
+        valid_from,
+        AddedReason::RefinedFromObservation,
+        obs.clone(),
+    );
+    if let Err(conflicts) = ingest_changed_entity::<EntityT>(graph, entity_idx, new_entity_idx, debug_history, &HashSet::new(), obs.perceived_at) {
+        graph.remove_node(new_entity_idx);
+        return Err(conflicts);
+    }
+
+    let children = graph.graph.children(entity_idx).iter(&graph.graph).collect_vec();
+    for (edge_idx, child_idx) in children {
+        let effect = graph.remove_edge(edge_idx)
+            .expect("Edge was just found by walking the children");
+        graph.add_edge(new_entity_idx, child_idx, effect);
+    }
+    let leafs = graph.leafs().iter()
+        .map(|&leaf| if leaf == entity_idx { new_entity_idx } else { leaf })
+        .collect();
+    graph.set_leafs(leafs);
+    // The old version is now unreachable, so ingest_backward_observation removes it
+
+    Ok(())
 }
 
 fn merge_generations(graph: &mut EntityStateGraph, first_generation: impl IntoIterator<Item=NodeIndex>) -> Vec<NodeIndex> {
-    let mut generation: HashSet<NodeIndex> = first_generation.into_iter().collect();
-    let mut next_generation = HashSet::new();
+    // Ordered so that which node of a merge group is kept, and so the order of the new leafs, is
+    // the same every run
+    let mut generation: BTreeSet<NodeIndex> = first_generation.into_iter().collect();
+    let mut next_generation = BTreeSet::new();
     let mut new_leafs = None;
     while !generation.is_empty() {
         let mut merge_groups: Vec<(&_, Vec<_>)> = Vec::new();
@@ -335,6 +634,13 @@ fn merge_generations(graph: &mut EntityStateGraph, first_generation: impl IntoIt
     new_leafs.expect("There should have been at least one generation")
 }
 
+// Nodes that can be reached from the current leafs but won't be reachable from the new ones
+fn get_unreachable_nodes(graph: &EntityStateGraph, new_leafs: &[NodeIndex]) -> HashSet<NodeIndex> {
+    let prev_nodes = get_reachable_nodes(graph, graph.leafs().clone());
+    let keep_nodes = get_reachable_nodes(graph, new_leafs.to_vec());
+    prev_nodes.difference(&keep_nodes).copied().collect()
+}
+
 fn get_reachable_nodes(graph: &EntityStateGraph, mut stack: Vec<NodeIndex>) -> HashSet<NodeIndex> {
     let mut output = HashSet::new();
     while let Some(node_idx) = stack.pop() {
@@ -351,7 +657,8 @@ fn get_reachable_nodes(graph: &EntityStateGraph, mut stack: Vec<NodeIndex>) -> H
 }
 
 // Within this function, the "old" and "new" prefixes refer to two parallel subtrees. "old" already
-// exists and "new" is being built
+// exists and "new" is being built. Returns the conflicts from every branch if none of the new
+// child's parents could be reconstructed.
 fn ingest_changed_entity<EntityT>(
     graph: &mut EntityStateGraph,
     old_child_idx: NodeIndex,
@@ -359,15 +666,18 @@ fn ingest_changed_entity<EntityT>(
     debug_history: &mut GraphDebugHistory,
     queued_for_update: &HashSet<NodeIndex>,
     debug_time: DateTime<Utc>,
-) where EntityT: Entity + PartialInformationCompare + 'static,
-        AnyEntity: TryInto<EntityT>,
-        <AnyEntity as TryInto<EntityT>>::Error: Debug,
-        for<'a> &'a AnyEntityRaw: TryInto<&'a EntityT::Raw>,
-        for<'a> <&'a AnyEntityRaw as TryInto<&'a EntityT::Raw>>::Error: Debug {
+) -> Result<(), Vec<Conflict>>
+    where EntityT: Entity + PartialInformationCompare + 'static,
+          AnyEntity: TryInto<EntityT>,
+          <AnyEntity as TryInto<EntityT>>::Error: Debug,
+          for<'a> &'a AnyEntityRaw: TryInto<&'a EntityT::Raw>,
+          for<'a> <&'a AnyEntityRaw as TryInto<&'a EntityT::Raw>>::Error: Debug {
     let mut all_parents_had_conflicts = true; // starts as vacuous truth
-    // let mut version_conflicts = Vec::new();
+    let mut has_parents = false;
+    let mut version_conflicts = Vec::new();
     let mut parent_walker = graph.graph.parents(old_child_idx);
     while let Some((old_edge_idx, old_parent_idx)) = parent_walker.walk_next(&graph.graph) {
+        has_parents = true;
         let old_effect = graph.graph.edge_weight(old_edge_idx)
             .expect("This should always be a valid edge index");
 
@@ -379,104 +689,103 @@ fn ingest_changed_entity<EntityT>(
 
         let mut new_effect = old_effect.clone();
         let mut new_parent = new_child_node.entity.clone();
-        todo!();
-        // event.reverse(&old_parent_node.entity, &mut new_extrapolated, &mut new_parent);
-        //
-        // { // Debug stuff
-        //     // Need to call get_version again because of lifetimes
-        //     let new_child = &graph.get_version(new_child_idx)
-        //         .expect("Come on I literally just added this node")
-        //         .entity;
-        //
-        //     let mut debug_graph = graph.clone();
-        //     debug_graph.add_edge(old_parent_idx, new_child_idx, new_effect);
-        //     debug_history.push(&(new_child.entity_type(), new_child.id()), DebugHistoryVersion {
-        //         event_human_name: format!("After adding parent {old_parent_idx:?} to {}", new_child.description()),
-        //         time: debug_time,
-        //         tree: debug_graph.get_debug_tree(),
-        //         queued_for_update: Some(queued_for_update.clone()),
-        //         currently_updating: Some(old_child_idx),
-        //         queued_for_delete: None,
-        //     });
-        // }
-        //
-        // // Never recurse past an observed node, but do use our reconstructed parent to check that
-        // // this is working
-        // let new_parent_idx = if let Some(obs) = &old_parent_node.observed {
-        //     info!("Parent has been observed; checking compatibility");
-        //     let raw: &EntityT::Raw = (&obs.entity_raw).try_into()
-        //         .expect("Mismatched entity types");
-        //     let mut new_parent: EntityT = new_parent.try_into()
-        //         .expect("I just added this one");
-        //     let conflicts = new_parent.observe(raw);
-        //     if conflicts.is_empty() {
-        //         all_parents_had_conflicts = false;
-        //         graph.add_edge(old_parent_idx, new_child_idx, new_effect);
-        //     } else {
-        //         error!("Parent was not reconstructed correctly. Conflicts: {:#?}", conflicts);
-        //         version_conflicts.push(conflicts);
-        //         todo!("Early exit")
-        //     }
-        //     old_parent_idx
-        // } else {
-        //     info!("Parent has not been observed; propagating changes");
-        //     all_parents_had_conflicts = false;
-        //     if &old_parent_node.entity != &new_parent {
-        //         info!("Saving changes and recursing");
-        //         // Then a change was made and we need to save it to the graph and then recurse
-        //         let new_parent_idx = graph.add_child_disconnected(new_parent.into(), todo!(), AddedReason::RefinedFromObservation);
-        //         graph.add_edge(new_parent_idx, new_child_idx, new_effect);
-        //         // Recurse with the different event type
-        //         // let child_event_arc = &graph.get_version(old_parent_idx)
-        //         //     .expect("Expected node index supplied to ingest_changed_event to be valid")
-        //         //     .event;
-        //         todo!();
-        //         // with_any_event!(child_event_arc.as_ref(), |_: EventT| {
-        //         //     ingest_changed_entity::<EntityT, EventT>(graph, old_parent_idx, new_parent_idx, debug_history, queued_for_update, debug_time)
-        //         // });
-        //
-        //         new_parent_idx
-        //     } else {
-        //         info!("No changes to save");
-        //         graph.add_edge(old_parent_idx, new_child_idx, new_effect);
-        //         old_parent_idx
-        //     }
-        // };
-        //
-        // let new_parent_node = graph.get_version(new_parent_idx)
-        //     .expect("This should always be a valid node index");
-        // let new_child_node = &graph.get_version(new_child_idx)
-        //     .expect("This should always be a valid node index");
-        //
-        // // Test reconstructing child from parent, now that parent should be appropriately modified
-        // todo!();
-        // // assert_eq!(&event.forward(&new_parent_node.entity, &new_extrapolated), &new_child_node.entity);
-    }
-
-    todo!("just uncomment this")
-    // if all_parents_had_conflicts {
-    //     // oh no!
-    //     panic!("All parents had conflicts: \n{version_conflicts:?}");
-    // }
+        with_effect_variant!(&mut new_effect, |effect: EffectT| {
+            let old_parent: &<EffectT as EffectVariant>::EntityType = (&old_parent_node.entity).try_into()
+                .expect("Tried to reverse an effect on the wrong entity");
+            let new_parent: &mut <EffectT as EffectVariant>::EntityType = (&mut new_parent).try_into()
+                .expect("Tried to reverse an effect on the wrong entity");
+            effect.reverse(old_parent, new_parent);
+        });
+
+        debug_history.push(&(new_parent.entity_type(), new_parent.id()), || {
+            let mut debug_graph = graph.clone();
+            debug_graph.add_edge(old_parent_idx, new_child_idx, new_effect.clone());
+            DebugHistoryVersion {
+                event_human_name: format!("After adding parent {old_parent_idx:?} to {new_child_idx:?}"),
+                time: debug_time,
+                tree: debug_graph.get_debug_tree(),
+                queued_for_update: Some(queued_for_update.clone()),
+                currently_updating: Some(old_child_idx),
+                queued_for_delete: None,
+            }
+        });
+
+        let parent_valid_from = old_parent_node.valid_from;
+        let parent_was_changed = old_parent_node.entity != new_parent;
+        // Never recurse past an observed node, but do use our reconstructed parent to check that
+        // this is working
+        if let Some(obs) = old_parent_node.observed.clone() {
+            info!("Parent has been observed; checking compatibility");
+            let raw: &EntityT::Raw = (&obs.entity_raw).try_into()
+                .expect("Mismatched entity types");
+            let mut new_parent: EntityT = new_parent.try_into()
+                .expect("Reversing an effect should never change the entity type");
+            let conflicts = new_parent.observe(raw);
+            if conflicts.is_empty() {
+                all_parents_had_conflicts = false;
+                graph.add_edge(old_parent_idx, new_child_idx, new_effect);
+            } else {
+                // The new child can't have come from this parent, so this branch is a dead end
+                error!("Parent was not reconstructed correctly. Conflicts: {:#?}", conflicts);
+                version_conflicts.extend(conflicts);
+            }
+        } else if parent_was_changed {
+            info!("Parent has not been observed; saving changes and recursing");
+            let new_parent_idx = graph.add_child_disconnected(new_parent, parent_valid_from, AddedReason::RefinedFromObservation);
+            graph.add_edge(new_parent_idx, new_child_idx, new_effect);
+            match ingest_changed_entity::<EntityT>(graph, old_parent_idx, new_parent_idx, debug_history, queued_for_update, debug_time) {
+                Ok(()) => {
+                    all_parents_had_conflicts = false;
+                }
+                Err(conflicts) => {
+                    // Every branch above the new parent was already cleaned up, so removing it
+                    // takes the whole failed branch with it
+                    graph.remove_node(new_parent_idx);
+                    version_conflicts.extend(conflicts);
+                }
+            }
+        } else {
+            info!("Parent has not been observed and has no changes to save");
+            all_parents_had_conflicts = false;
+            graph.add_edge(old_parent_idx, new_child_idx, new_effect);
+        }
+    }
+
+    if !has_parents {
+        // Nothing to reconcile with, so the new child just starts its own history. The old child
+        // stays a root for now, because other branches may still come from it. If none do, it's
+        // removed along with everything else that became unreachable, and that drops it as a root.
+        graph.add_root(new_child_idx);
+        Ok(())
+    } else if all_parents_had_conflicts {
+        Err(version_conflicts)
+    } else {
+        Ok(())
+    }
 }
 
 fn ingest_for_version<EntityT>(
     graph: &mut EntityStateGraph,
     entity_idx: NodeIndex,
     obs: Arc<Observation>,
+    policies: &ApprovalPolicies,
+    // See ingest_observation
+    approved: bool,
+    // Conflicts that didn't stop the observation from being applied, but should be recorded
+    recorded: &mut Vec<Conflict>,
     debug_history: &mut GraphDebugHistory,
     queued_for_update: &HashSet<NodeIndex>,
     debug_time: DateTime<Utc>,
-) -> Result<Vec<NodeIndex>, Vec<Conflict>>
+) -> Result<(Vec<NodeIndex>, Vec<Conflict>), Vec<Conflict>>
 // Disgustang
-    where EntityT: Entity + PartialInformationCompare + Into<AnyEntity> + 'static,
+    where EntityT: Entity + PartialInformationCompare + FieldAccess + Into<AnyEntity> + 'static,
           AnyEntity: TryInto<EntityT>,
           <AnyEntity as TryInto<EntityT>>::Error: Debug,
           for<'a> &'a AnyEntity: TryInto<&'a EntityT>,
           for<'a> &'a AnyEntityRaw: TryInto<&'a EntityT::Raw>,
           for<'a> <&'a AnyEntity as TryInto<&'a EntityT>>::Error: Debug,
           for<'a> <&'a AnyEntityRaw as TryInto<&'a <EntityT as PartialInformationCompare>::Raw>>::Error: Debug {
-    debug_history.push(&(obs.entity_type, obs.entity_id), DebugHistoryVersion {
+    debug_history.push(&(obs.entity_type, obs.entity_id), || DebugHistoryVersion {
         event_human_name: format!("Updating {:?} during ingest at {}", entity_idx, obs.perceived_at),
         time: obs.perceived_at,
         tree: graph.get_debug_tree(),
@@ -494,9 +803,25 @@ fn ingest_for_version<EntityT>(
     let mut new_entity = entity.clone();
     let raw: &EntityT::Raw = (&obs.entity_raw).try_into()
         .expect("TODO: use Result to report this error");
-    let conflicts = new_entity.observe(raw);
+    let conflicts = explain_win_streak_conflicts(obs.entity_type, obs.entity_id, entity, new_entity.observe(raw));
+    let score_from_feed = graph.score_from_feed(entity_idx);
+    // Conflicts that were overridden to make this placement work, for the ambiguity strategy
+    let mut accepted = Vec::new();
     if !conflicts.is_empty() {
-        return Err(conflicts);
+        let conflict_policies = conflicts.iter()
+            .map(|conflict| policies.policy_for_placement(obs.entity_type, conflict, score_from_feed))
+            .map(|policy| match policy {
+                ApprovalPolicy::AlwaysApprove | ApprovalPolicy::Conflict if approved => { ApprovalPolicy::AutoAcceptObserved }
+                other => { other }
+            })
+            .collect_vec();
+        if conflict_policies.iter().any(|policy| matches!(policy, ApprovalPolicy::AlwaysApprove | ApprovalPolicy::Conflict)) {
+            return Err(conflicts);
+        }
+
+        let observed_entity = EntityT::from_raw(raw.clone());
+        apply_conflict_policies(&mut new_entity, &observed_entity, &conflicts, &conflict_policies, recorded);
+        accepted = conflicts;
     }
 
     let entity_was_changed = &new_entity != entity;
@@ -509,7 +834,7 @@ fn ingest_for_version<EntityT>(
             AddedReason::RefinedFromObservation,
             obs.clone(),
         );
-        ingest_changed_entity::<EntityT>(
+        let reverse_result = ingest_changed_entity::<EntityT>(
             graph,
             entity_idx,
             new_entity_idx,
@@ -517,6 +842,10 @@ fn ingest_for_version<EntityT>(
             queued_for_update,
             debug_time,
         );
+        if let Err(conflicts) = reverse_result {
+            graph.remove_node(new_entity_idx);
+            return Err(conflicts);
+        }
 
         new_entity_idx
     } else {
@@ -526,7 +855,7 @@ fn ingest_for_version<EntityT>(
         entity_idx
     };
 
-   debug_history.push(&(obs.entity_type, obs.entity_id), DebugHistoryVersion {
+   debug_history.push(&(obs.entity_type, obs.entity_id), || DebugHistoryVersion {
         event_human_name: format!("After updating entity and parents {}", obs.perceived_at),
         time: obs.perceived_at,
         tree: graph.get_debug_tree(),
@@ -591,7 +920,7 @@ fn ingest_for_version<EntityT>(
                 });
                 next_generation.push((old_child_idx, new_child_idx));
 
-               debug_history.push(&(obs.entity_type, obs.entity_id), DebugHistoryVersion {
+               debug_history.push(&(obs.entity_type, obs.entity_id), || DebugHistoryVersion {
                     event_human_name: format!("After forward pass step for {event_description} at {}", obs.perceived_at),
                     time: obs.perceived_at,
                     tree: graph.get_debug_tree(),
@@ -602,7 +931,7 @@ fn ingest_for_version<EntityT>(
             }
         }
         if next_generation.is_empty() {
-            return Ok(generation.into_iter().map(|(_old, new)| new).collect());
+            return Ok((generation.into_iter().map(|(_old, new)| new).collect(), accepted));
         }
         generation = next_generation;
     }
@@ -767,4 +1096,410 @@ fn ingest_for_version<EntityT>(
 //
 // fn add_manual_event<EntityRawT: EntityRaw>(state: &StateInterface, entity_raw: &EntityRawT, perceived_at: DateTime<Utc>) -> ChronIngestResult<()> {
 //     todo!()
-// }
\ No newline at end of file
+// }
+
+// Resolves each conflict according to its own policy. None of them can be ones that need approval or
+// reject the version. observe leaves mismatched fields alone, so new_entity starts with the expected
+// value for all of them and the observed value for everything else.
+fn apply_conflict_policies<EntityT: FieldAccess + Display>(
+    new_entity: &mut EntityT,
+    observed_entity: &EntityT,
+    conflicts: &[Conflict],
+    policies: &[ApprovalPolicy],
+    recorded: &mut Vec<Conflict>,
+) {
+    let kept_fields = conflicts.iter()
+        .zip(policies)
+        .filter(|(_, policy)| **policy == ApprovalPolicy::KeepExpected)
+        .map(|(conflict, _)| conflict.path().first().map_or("", String::as_str))
+        .collect::<HashSet<_>>();
+    for (conflict, policy) in conflicts.iter().zip(policies) {
+        match policy {
+            ApprovalPolicy::KeepExpected => {
+                info!("Keeping the expected value of {}", conflict.property());
+                recorded.push(conflict.clone());
+            }
+            ApprovalPolicy::AutoAcceptObserved | ApprovalPolicy::LowSeverity => {
+                // Only whole top-level fields can be taken, so one that also holds a kept value
+                // stays as it is
+                let field = conflict.path().first().map_or("", String::as_str);
+                if kept_fields.contains(field) {
+                    warn!("Can't take the observed value of {} without overwriting a value that \
+                        should be kept; keeping the expected value", conflict.property());
+                } else if !new_entity.take_field(observed_entity, field) {
+                    warn!("Conflict on {} isn't on a field of {}; can't take the observed value",
+                        conflict.property(), new_entity);
+                }
+                if *policy == ApprovalPolicy::LowSeverity {
+                    recorded.push(conflict.clone().with_severity(Severity::Info));
+                }
+            }
+            ApprovalPolicy::AlwaysApprove | ApprovalPolicy::Conflict => {
+                unreachable!("Conflicts that need approval or reject the version must be handled before this")
+            }
+        }
+    }
+}
+
+// Nothing else is derived from game results, so a win streak conflict is the only sign of a missed
+// game. It gets a message that says so and names the team, since the bare property isn't much help.
+fn explain_win_streak_conflicts(entity_type: EntityType, entity_id: Uuid, entity: &impl Display, conflicts: Vec<Conflict>) -> Vec<Conflict> {
+    if entity_type != EntityType::Team { return conflicts; }
+
+    conflicts.into_iter()
+        .map(|conflict| {
+            if conflict.property() != "win_streak" { return conflict; }
+            let message = format!("Reconstructed win streak for {} ({}) doesn't match the observed one ({}). \
+                A game result was probably missed or miscounted.", entity, entity_id, conflict.message());
+            conflict.with_message(message)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use chrono::{Duration, Utc};
+    use itertools::Itertools;
+    use partial_information::{Conflict, MaybeKnown, PartialInformationCompare, Severity};
+    use petgraph::visit::Walker;
+    use uuid::Uuid;
+    use crate::entity::{test_game, test_player, test_team, AnyEntity, AnyEntityRaw, Entity, Game, Player, Team, UpdateFull};
+    use crate::events::GameUpcomingEffectVariant;
+    use crate::ingest::state::AddedReason;
+    use crate::ingest::{test_state, ApprovalPolicies, ApprovalPolicy, GraphDebugHistory, Observation, StateGraph};
+    use crate::ingest::task::tests::test_ingest;
+    use crate::state::EntityType;
+    use super::{apply_conflict_policies, explain_win_streak_conflicts, fill_in_new_entity, get_reachable_nodes, get_unreachable_nodes, ingest_backward_observation, ingest_changed_entity, ingest_observation, merge_by_time};
+
+    #[test]
+    fn miscounted_win_streak_conflicts_name_the_team() {
+        let mut team = test_team(Vec::new(), Vec::new());
+        team.win_streak = Some(3);
+        let mut observed = team.clone();
+        observed.win_streak = Some(2);
+
+        let mut new_team = team.clone();
+        let conflicts = new_team.observe(&observed.clone().raw_approximation());
+        let conflicts = explain_win_streak_conflicts(EntityType::Team, team.id, &team, conflicts);
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].property(), "win_streak");
+        assert!(conflicts[0].message().contains(&team.id.to_string()));
+        assert!(conflicts[0].message().contains("Test Team"));
+    }
+
+    #[test]
+    fn deleting_unreachable_nodes_keeps_roots_and_leafs_consistent() {
+        let game = test_game();
+        let key = (EntityType::Game, game.id());
+        let mut state = test_state([game.clone().into()]);
+        let graph = state.entity_graph_mut(key.0, key.1).unwrap();
+
+        // Two separate histories: root -> a, and a second root that was added when an observation
+        // couldn't be connected to anything -> b. The observation then rules out the second one.
+        let time = Utc::now();
+        let root = graph.roots()[0];
+        let a = graph.add_child_version(root, game.clone().into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        let obs = Arc::new(graph.latest_ingested_observation().unwrap().clone());
+        let other_root = graph.add_observed_child_disconnected(game.clone().into(), time, AddedReason::RefinedFromObservation, obs);
+        graph.add_root(other_root);
+        let b = graph.add_child_version(other_root, game.clone().into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        graph.set_leafs(vec![a, b]);
+
+        assert_eq!(get_reachable_nodes(graph, vec![a]), HashSet::from([root, a]));
+        let delete_nodes = get_unreachable_nodes(graph, &[a]);
+        assert_eq!(delete_nodes, HashSet::from([other_root, b]));
+
+        for &node_idx in &delete_nodes {
+            graph.remove_node(node_idx);
+        }
+        graph.set_leafs(vec![a]);
+
+        assert_eq!(graph.roots(), &vec![root]);
+        assert!(graph.validate().is_empty());
+    }
+
+    #[test]
+    fn conflicting_with_an_observed_parent_returns_the_conflicts() {
+        let game = test_game();
+        let key = (EntityType::Game, game.id());
+        let mut state = test_state([game.clone().into()]);
+        let graph = state.entity_graph_mut(key.0, key.1).unwrap();
+
+        // The observed root can't have led to a game on a different day
+        let time = Utc::now();
+        let root = graph.roots()[0];
+        let child = graph.add_child_version(root, game.clone().into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        let mut changed = game.clone();
+        changed.day += 1;
+        let new_child = graph.add_child_disconnected(changed.into(), time, AddedReason::RefinedFromObservation);
+
+        let mut debug_history = GraphDebugHistory::new(true, 0);
+        let result = ingest_changed_entity::<Game>(graph, child, new_child, &mut debug_history, &HashSet::new(), time);
+
+        let conflicts = result.expect_err("Reconstructing the observed parent should have conflicted");
+        assert!(conflicts.iter().any(|conflict| conflict.property() == "day"));
+        assert_eq!(graph.graph.parents(new_child).walk_next(&graph.graph), None);
+    }
+
+    #[test]
+    fn replacing_a_root_drops_the_old_root_once_it_is_deleted() {
+        let game = test_game();
+        let key = (EntityType::Game, game.id());
+        let mut state = test_state([game.clone().into()]);
+        let graph = state.entity_graph_mut(key.0, key.1).unwrap();
+
+        let time = Utc::now();
+        let root = graph.roots()[0];
+        let mut changed = game.clone();
+        changed.day += 1;
+        let new_root = graph.add_child_disconnected(changed.into(), time, AddedReason::RefinedFromObservation);
+
+        let mut debug_history = GraphDebugHistory::new(true, 0);
+        let result = ingest_changed_entity::<Game>(graph, root, new_root, &mut debug_history, &HashSet::new(), time);
+        assert!(result.is_ok());
+        assert_eq!(graph.roots(), &vec![root, new_root]);
+
+        for &node_idx in &get_unreachable_nodes(graph, &[new_root]) {
+            graph.remove_node(node_idx);
+        }
+        graph.set_leafs(vec![new_root]);
+
+        assert_eq!(graph.roots(), &vec![new_root]);
+        assert!(graph.validate().is_empty());
+    }
+
+    #[test]
+    fn backward_observations_refine_earlier_versions_through_the_reverse_pass() {
+        let game = test_game();
+        let key = (EntityType::Game, game.id());
+        let mut state = test_state([game.clone().into()]);
+        let graph = state.entity_graph_mut(key.0, key.1).unwrap();
+
+        // The root doesn't know about shame yet, and a newer observation has already been made
+        let root = graph.roots()[0];
+        let root_time = graph.get_version(root).unwrap().valid_from;
+        let mut unknown_shame = game.clone();
+        unknown_shame.shame = MaybeKnown::Unknown;
+        graph.get_version_mut(root).unwrap().entity = unknown_shame.clone().into();
+        let leaf = graph.add_child_version(root, unknown_shame.into(), root_time + Duration::hours(1), GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        graph.set_leafs(vec![leaf]);
+        let mut newer = graph.latest_ingested_observation().unwrap().clone();
+        newer.perceived_at = root_time + Duration::hours(2);
+        graph.record_observed(Arc::new(newer.clone()));
+
+        // Then an observation from back when the root was current turns up
+        let mut shamed = game.clone();
+        shamed.shame = MaybeKnown::Known(true);
+        let mut older = newer;
+        older.perceived_at = root_time + Duration::seconds(5);
+        older.entity_raw = shamed.clone().raw_approximation().into();
+
+        let mut debug_history = GraphDebugHistory::new(true, 0);
+        let conflicts = ingest_backward_observation(graph, Arc::new(older), &mut debug_history);
+        assert!(conflicts.is_empty(), "{conflicts:?}");
+
+        // The root was replaced by a refined, observed copy, and the leaf was moved over to it
+        assert_eq!(graph.leafs(), &vec![leaf]);
+        let [new_root] = graph.roots()[..] else { panic!("Expected exactly one root") };
+        assert_ne!(new_root, root);
+        assert!(graph.get_version(root).is_none());
+        let new_root_node = graph.get_version(new_root).unwrap();
+        assert!(new_root_node.observed.is_some());
+        let new_root_game: &Game = (&new_root_node.entity).try_into().unwrap();
+        assert_eq!(new_root_game.shame, MaybeKnown::Known(true));
+        let (_, parent) = graph.graph.parents(leaf).walk_next(&graph.graph).unwrap();
+        assert_eq!(parent, new_root);
+        assert!(graph.validate().is_empty());
+    }
+
+    #[test]
+    fn each_conflict_is_resolved_by_its_own_policy() {
+        let mut expected = test_game();
+        expected.home.score = Some(1.0);
+        expected.last_update = Some("Expected update".to_string());
+        let mut observed = expected.clone();
+        observed.home.score = Some(5.0);
+        observed.last_update = Some("Observed update".to_string());
+
+        let conflicts = [
+            Conflict::new("score".to_string(), String::new()).with_prefix("home"),
+            Conflict::new("last_update".to_string(), String::new()),
+        ];
+        let mut new_game = expected.clone();
+        let mut recorded = Vec::new();
+        apply_conflict_policies(&mut new_game, &observed, &conflicts,
+                                &[ApprovalPolicy::KeepExpected, ApprovalPolicy::LowSeverity], &mut recorded);
+
+        assert_eq!(new_game.home.score, Some(1.0));
+        assert_eq!(new_game.last_update, observed.last_update);
+        // Keeping the expected score is worth a proper look, unlike a mismatched update
+        let recorded = recorded.iter()
+            .map(|conflict| (conflict.property(), conflict.severity()))
+            .collect_vec();
+        assert_eq!(recorded, vec![("home/score".to_string(), Severity::Error), ("last_update".to_string(), Severity::Info)]);
+    }
+
+    #[test]
+    fn first_observation_of_a_hatched_player_fills_it_in() {
+        let team_id = Uuid::new_v4();
+        let mut observed = test_player(Some(team_id));
+        observed.name = "Hatched Player".to_string();
+        let key = (EntityType::Player, observed.id);
+        let mut state = StateGraph::new();
+        let mut debug_history = GraphDebugHistory::new(true, 0);
+        state.add_new_entity(Player::new_from_event(observed.id, Some(team_id)).into(), Utc::now(), "Player hatched", &mut debug_history);
+        let graph = state.entity_graph_mut(key.0, key.1).unwrap();
+
+        let raw_json = AnyEntity::from(observed.clone()).to_raw_approximation_json().unwrap();
+        let obs = Arc::new(Observation {
+            perceived_at: Utc::now(),
+            entity_type: key.0,
+            entity_id: key.1,
+            entity_raw: AnyEntityRaw::from_json(key.0, raw_json).unwrap(),
+        });
+        fill_in_new_entity(graph, obs.clone(), &mut debug_history);
+
+        let node = graph.get_version(graph.leafs()[0]).unwrap();
+        assert_eq!(node.entity.as_player().unwrap().name, "Hatched Player");
+        assert!(node.observed.is_some());
+        assert_eq!(graph.latest_observed(), Some(obs.perceived_at));
+    }
+
+    pub(crate) fn player_observation(player: &Player, perceived_at: chrono::DateTime<Utc>) -> Observation {
+        Observation {
+            perceived_at,
+            entity_type: EntityType::Player,
+            entity_id: player.id,
+            entity_raw: AnyEntity::Player(player.clone()).into_raw_approximation(),
+        }
+    }
+
+    pub(crate) fn game_observation(game: &Game, perceived_at: chrono::DateTime<Utc>) -> Observation {
+        Observation {
+            perceived_at,
+            entity_type: EntityType::Game,
+            entity_id: game.id,
+            entity_raw: AnyEntity::Game(game.clone()).into_raw_approximation(),
+        }
+    }
+
+    pub(crate) fn team_observation(team: &Team, perceived_at: chrono::DateTime<Utc>) -> Observation {
+        Observation {
+            perceived_at,
+            entity_type: EntityType::Team,
+            entity_id: team.id,
+            entity_raw: AnyEntity::Team(team.clone()).into_raw_approximation(),
+        }
+    }
+
+    #[test]
+    fn merged_streams_keep_going_after_an_earlier_stream_runs_out() {
+        let start = Utc::now();
+        let players = (0..3).map(|_| test_player(None)).collect_vec();
+        // The first stream runs out first, which used to make the merge take from the wrong stream
+        let streams = vec![
+            vec![player_observation(&players[0], start)].into_iter().peekable(),
+            vec![
+                player_observation(&players[1], start + Duration::seconds(1)),
+                player_observation(&players[2], start + Duration::seconds(2)),
+            ].into_iter().peekable(),
+        ];
+
+        let merged = merge_by_time(streams)
+            .map(|obs| obs.entity_id)
+            .collect_vec();
+        assert_eq!(merged, players.iter().map(|player| player.id).collect_vec());
+    }
+
+    #[test]
+    fn merged_streams_end_when_every_stream_is_empty() {
+        let streams: Vec<std::iter::Peekable<std::vec::IntoIter<Observation>>> = vec![
+            Vec::new().into_iter().peekable(),
+            Vec::new().into_iter().peekable(),
+        ];
+        assert_eq!(merge_by_time(streams).count(), 0);
+    }
+
+    #[rocket::async_test]
+    async fn auto_accepted_fields_are_taken_and_always_approve_fields_wait_for_approval() {
+        for (policy, needs_approval) in [(ApprovalPolicy::AutoAcceptObserved, false), (ApprovalPolicy::AlwaysApprove, true)] {
+            let mut ingest = test_ingest().await;
+            ingest.approval_policies = ApprovalPolicies::new(ApprovalPolicy::Conflict)
+                .with(EntityType::Player, "name", policy);
+            let mut history = GraphDebugHistory::new(true, 0);
+            let start = Utc::now();
+            let player = test_player(None);
+            ingest_observation(&mut ingest, player_observation(&player, start), false, &mut history)
+                .expect("The first observation of a player should create it");
+
+            let mut renamed = player.clone();
+            renamed.name = "Renamed Player".to_string();
+            let new_events = ingest_observation(&mut ingest, player_observation(&renamed, start + Duration::seconds(1)), false, &mut history)
+                .expect("Neither policy should reject the observation");
+            assert!(new_events.is_empty());
+
+            let names = ingest.state.lock().unwrap()
+                .query_player_all(player.id, |player| player.name.clone());
+            if needs_approval {
+                assert_eq!(ingest.queued_approvals.len(), 1);
+                assert!(ingest.queued_approvals[0].observation.is_some());
+                assert_eq!(names, vec!["Test Player".to_string()]);
+            } else {
+                assert!(ingest.queued_approvals.is_empty());
+                assert_eq!(names, vec!["Renamed Player".to_string()]);
+            }
+        }
+    }
+
+    fn update_full(game: &Game, description: &str) -> UpdateFull {
+        UpdateFull {
+            id: Uuid::from_u128(1),
+            day: game.day,
+            nuts: 0,
+            r#type: 2,
+            blurb: String::new(),
+            phase: game.phase,
+            season: game.season,
+            created: Utc::now(),
+            category: 0,
+            metadata: Default::default(),
+            game_tags: vec![game.id],
+            team_tags: Vec::new(),
+            player_tags: Vec::new(),
+            tournament: -1,
+            description: description.to_string(),
+        }
+    }
+
+    #[rocket::async_test]
+    async fn wrong_update_full_is_recorded_as_a_low_severity_conflict() {
+        let mut ingest = test_ingest().await;
+        let mut history = GraphDebugHistory::new(true, 0);
+        let start = Utc::now();
+        let mut game = test_game();
+        game.last_update_full = Some(vec![update_full(&game, "Reconstructed wrong")]).into();
+        ingest_observation(&mut ingest, game_observation(&game, start), false, &mut history)
+            .expect("The first observation of a game should create it");
+
+        let mut observed = game.clone();
+        observed.last_update_full = Some(vec![update_full(&game, "Observed right")]).into();
+        ingest_observation(&mut ingest, game_observation(&observed, start + Duration::seconds(1)), false, &mut history)
+            .expect("A mismatched update shouldn't reject the observation");
+
+        // It's recorded, but doesn't hold up the ingest
+        assert!(ingest.queued_approvals.is_empty());
+        let conflicts = ingest.recorded_conflicts.iter()
+            .map(|conflict| (conflict.entity_id, conflict.property.as_str(), conflict.severity))
+            .collect_vec();
+        assert_eq!(conflicts.len(), 1, "{conflicts:?}");
+        assert_eq!(conflicts[0].0, game.id);
+        assert!(conflicts[0].1.starts_with("last_update_full"), "{}", conflicts[0].1);
+        assert_eq!(conflicts[0].2, Severity::Info.level());
+        let updates = ingest.state.lock().unwrap()
+            .query_game_all(game.id, |game| game.last_update_full.clone());
+        assert_eq!(updates, vec![observed.last_update_full]);
+    }
+}
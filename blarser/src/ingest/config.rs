@@ -0,0 +1,155 @@
+use log::warn;
+
+use crate::ingest::{AmbiguityStrategy, FieldWeights, ScorePolicy};
+use crate::ingest::task::DEFAULT_DEBUG_HISTORY_CAP;
+use crate::state::EntityType;
+
+pub const DEBUG_HISTORY_CAP_VAR: &str = "BLARSER_DEBUG_HISTORY_CAP";
+pub const PAUSE_ON_REJECTED_OBSERVATIONS_VAR: &str = "BLARSER_PAUSE_ON_REJECTED_OBSERVATIONS";
+pub const SCORE_POLICY_VAR: &str = "BLARSER_SCORE_POLICY";
+pub const AMBIGUITY_STRATEGY_VAR: &str = "BLARSER_AMBIGUITY_STRATEGY";
+// Comma-separated entity_type:field/path=weight entries, e.g. "game:phase=0.5,player:name=2". They
+// change the default weights rather than replacing them.
+pub const FIELD_WEIGHTS_VAR: &str = "BLARSER_FIELD_WEIGHTS";
+pub const REPLAY_SPEED_VAR: &str = "BLARSER_REPLAY_SPEED";
+
+// Settings for the live ingest. They're read from the environment, the same as the live API URL,
+// so they can be changed without a rebuild. Anything that's unset or doesn't parse keeps its
+// default.
+#[derive(Debug, Clone)]
+pub struct IngestConfig {
+    pub debug_history_cap: usize,
+    // See Ingest::pause_on_rejected_observations
+    pub pause_on_rejected_observations: bool,
+    pub score_policy: ScorePolicy,
+    pub ambiguity_strategy: AmbiguityStrategy,
+    // Only used by AmbiguityStrategy::PreferMostLikely
+    pub field_weights: FieldWeights,
+    // Set this to watch the debug UI evolve in (scaled) real time. 0 goes as fast as possible. See
+    // run_ingest.
+    pub replay_speed: f64,
+}
+
+impl Default for IngestConfig {
+    fn default() -> Self {
+        Self {
+            debug_history_cap: DEFAULT_DEBUG_HISTORY_CAP,
+            pause_on_rejected_observations: false,
+            score_policy: ScorePolicy::default(),
+            ambiguity_strategy: AmbiguityStrategy::default(),
+            field_weights: FieldWeights::default(),
+            replay_speed: 0.,
+        }
+    }
+}
+
+impl IngestConfig {
+    pub fn from_env() -> Self {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let defaults = Self::default();
+        Self {
+            debug_history_cap: parse_var(&var, DEBUG_HISTORY_CAP_VAR)
+                .unwrap_or(defaults.debug_history_cap),
+            pause_on_rejected_observations: parse_var(&var, PAUSE_ON_REJECTED_OBSERVATIONS_VAR)
+                .unwrap_or(defaults.pause_on_rejected_observations),
+            score_policy: parse_var(&var, SCORE_POLICY_VAR)
+                .unwrap_or(defaults.score_policy),
+            ambiguity_strategy: parse_var(&var, AMBIGUITY_STRATEGY_VAR)
+                .unwrap_or(defaults.ambiguity_strategy),
+            field_weights: match var(FIELD_WEIGHTS_VAR) {
+                Some(entries) => { with_field_weights(defaults.field_weights, &entries) }
+                None => { defaults.field_weights }
+            },
+            replay_speed: parse_var(&var, REPLAY_SPEED_VAR)
+                .unwrap_or(defaults.replay_speed),
+        }
+    }
+}
+
+// Entries that don't parse are skipped, the same as a var that doesn't parse
+fn with_field_weights(mut weights: FieldWeights, entries: &str) -> FieldWeights {
+    for entry in entries.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let parsed = entry.split_once('=')
+            .and_then(|(field, weight)| Some((field.split_once(':')?, weight.trim().parse::<f32>().ok()?)))
+            .and_then(|((entity_type, path), weight)| {
+                Some((EntityType::try_from(entity_type.trim()).ok()?, path.trim(), weight))
+            });
+        match parsed {
+            Some((entity_type, path, weight)) => { weights.set(entity_type, path, weight) }
+            None => { warn!("Ignoring {FIELD_WEIGHTS_VAR} entry {entry:?}. Expected entity_type:field/path=weight.") }
+        }
+    }
+    weights
+}
+
+fn parse_var<T: std::str::FromStr>(var: &impl Fn(&str) -> Option<String>, name: &str) -> Option<T>
+    where T::Err: std::fmt::Display {
+    let value = var(name).filter(|value| !value.is_empty())?;
+    match value.parse() {
+        Ok(parsed) => { Some(parsed) }
+        Err(e) => {
+            warn!("Ignoring {name}={value:?}: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use partial_information::Conflict;
+    use crate::ingest::{AmbiguityStrategy, ScorePolicy};
+    use crate::state::EntityType;
+    use super::{IngestConfig, SCORE_POLICY_VAR, AMBIGUITY_STRATEGY_VAR, DEBUG_HISTORY_CAP_VAR, FIELD_WEIGHTS_VAR, PAUSE_ON_REJECTED_OBSERVATIONS_VAR, REPLAY_SPEED_VAR};
+
+    fn config_from(vars: &[(&str, &str)]) -> IngestConfig {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        IngestConfig::from_vars(|name| vars.get(name).map(|value| value.to_string()))
+    }
+
+    #[test]
+    fn unset_and_invalid_vars_keep_the_defaults() {
+        let config = config_from(&[(DEBUG_HISTORY_CAP_VAR, "lots")]);
+        assert_eq!(config.debug_history_cap, IngestConfig::default().debug_history_cap);
+        assert!(!config.pause_on_rejected_observations);
+    }
+
+    #[test]
+    fn set_vars_are_used() {
+        let config = config_from(&[(DEBUG_HISTORY_CAP_VAR, "20"), (PAUSE_ON_REJECTED_OBSERVATIONS_VAR, "true")]);
+        assert_eq!(config.debug_history_cap, 20);
+        assert!(config.pause_on_rejected_observations);
+    }
+
+    #[test]
+    fn ambiguity_strategy_and_field_weights_are_configurable() {
+        let config = config_from(&[
+            (AMBIGUITY_STRATEGY_VAR, "prefer_most_likely"),
+            (FIELD_WEIGHTS_VAR, "game:phase=3, nonsense, team:win_streak=4"),
+        ]);
+        assert_eq!(config.ambiguity_strategy, AmbiguityStrategy::PreferMostLikely);
+
+        let phase = Conflict::new("phase".to_string(), String::new());
+        assert_eq!(config.field_weights.weight_for(EntityType::Game, &phase), 3.0);
+        let win_streak = Conflict::new("win_streak".to_string(), String::new());
+        assert_eq!(config.field_weights.weight_for(EntityType::Team, &win_streak), 4.0);
+        // Weights that weren't mentioned keep their defaults
+        let score = Conflict::new("score".to_string(), String::new()).with_prefix("home");
+        assert_eq!(config.field_weights.weight_for(EntityType::Game, &score), 10.0);
+    }
+
+    #[test]
+    fn score_policy_is_configurable() {
+        assert_eq!(config_from(&[]).score_policy, ScorePolicy::AlwaysApprove);
+        assert_eq!(config_from(&[(SCORE_POLICY_VAR, "prefer_feed")]).score_policy, ScorePolicy::PreferFeed);
+    }
+
+    #[test]
+    fn replay_speed_is_configurable() {
+        assert_eq!(config_from(&[]).replay_speed, 0.);
+        assert_eq!(config_from(&[(REPLAY_SPEED_VAR, "2.5")]).replay_speed, 2.5);
+    }
+}
@@ -0,0 +1,184 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+// The index records the position of every this many rows. A replay that starts partway through a
+// file seeks to the closest indexed row before the start time and scans from there.
+const INDEX_INTERVAL: u64 = 1000;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvIndexEntry {
+    timestamp: DateTime<Utc>,
+    byte: u64,
+    line: u64,
+    record: u64,
+}
+
+impl CsvIndexEntry {
+    fn position(&self) -> csv::Position {
+        let mut pos = csv::Position::new();
+        pos.set_byte(self.byte).set_line(self.line).set_record(self.record);
+        pos
+    }
+}
+
+pub fn parse_csv_timestamp(record: &csv::StringRecord) -> DateTime<Utc> {
+    let dt_str = record.get(1).unwrap().replace(" ", "T") + ":00";
+    DateTime::from(DateTime::parse_from_rfc3339(&dt_str).unwrap())
+}
+
+fn index_path(csv_path: &Path) -> PathBuf {
+    csv_path.with_extension("csv.idx")
+}
+
+fn index_is_fresh(csv_path: &Path, index_path: &Path) -> bool {
+    let modified = |path: &Path| path.metadata().and_then(|m| m.modified()).ok();
+    match (modified(csv_path), modified(index_path)) {
+        (Some(csv_modified), Some(index_modified)) => { index_modified >= csv_modified }
+        _ => { false }
+    }
+}
+
+fn load_index(index_path: &Path) -> Option<Vec<CsvIndexEntry>> {
+    let mut rdr = csv::Reader::from_path(index_path).ok()?;
+    rdr.deserialize().collect::<Result<_, _>>().ok()
+}
+
+fn build_index(csv_path: &Path, index_path: &Path) -> Option<Vec<CsvIndexEntry>> {
+    info!("Building replay index for {}", csv_path.display());
+    let file = File::open(csv_path).ok()?;
+    let mut rdr = csv::Reader::from_reader(BufReader::new(file));
+
+    let mut entries = Vec::new();
+    let mut record = csv::StringRecord::new();
+    let mut i = 0;
+    // Reading into a single StringRecord skips all the JSON parsing, which is most of the work
+    while rdr.read_record(&mut record).ok()? {
+        if i % INDEX_INTERVAL == 0 {
+            let pos = record.position().expect("Records read from a file always have a position");
+            entries.push(CsvIndexEntry {
+                timestamp: parse_csv_timestamp(&record),
+                byte: pos.byte(),
+                line: pos.line(),
+                record: pos.record(),
+            });
+        }
+        i += 1;
+    }
+
+    // Failing to save the index just means it has to be built again next time
+    match csv::Writer::from_path(index_path) {
+        Ok(mut writer) => {
+            for entry in &entries {
+                if let Err(err) = writer.serialize(entry) {
+                    warn!("Couldn't write replay index {}: {}", index_path.display(), err);
+                    break;
+                }
+            }
+        }
+        Err(err) => {
+            warn!("Couldn't create replay index {}: {}", index_path.display(), err);
+        }
+    }
+
+    Some(entries)
+}
+
+// Returns the position of the last indexed row strictly before `start_at_time`. Rows are in time
+// order, so everything before that position can be skipped. Returns None if there's no usable
+// index, in which case the caller should scan from the top.
+pub fn seek_position(csv_path: &Path, start_at_time: DateTime<Utc>) -> Option<csv::Position> {
+    let index_path = index_path(csv_path);
+    let entries = if index_is_fresh(csv_path, &index_path) {
+        load_index(&index_path)
+    } else {
+        None
+    }.or_else(|| build_index(csv_path, &index_path))?;
+
+    entries.iter()
+        .take_while(|entry| entry.timestamp < start_at_time)
+        .last()
+        .map(|entry| entry.position())
+}
+
+// The rows of a replay CSV from start_at_time on, skipping ahead with the index if there is one.
+// Returns None if the file can't be opened.
+pub fn records_from(csv_path: &Path, start_at_time: DateTime<Utc>) -> Option<impl Iterator<Item=csv::StringRecord>> {
+    let file = File::open(csv_path).ok()?;
+    let mut rdr = csv::Reader::from_reader(BufReader::new(file));
+    if let Some(pos) = seek_position(csv_path, start_at_time) {
+        // Headers have to be read before seeking or the reader will try to read them from
+        // the middle of the file
+        rdr.headers().expect("Reading CSV headers failed");
+        rdr.seek(pos).expect("Seeking in CSV failed");
+    }
+
+    let records = rdr.into_records()
+        .map(|result| result.expect("Reading CSV row failed"))
+        .filter(move |record| parse_csv_timestamp(record) >= start_at_time);
+    Some(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::PathBuf;
+    use chrono::{Duration, TimeZone, Utc};
+    use uuid::Uuid;
+    use super::{index_path, records_from, seek_position, INDEX_INTERVAL};
+
+    // A replay CSV with a row every minute, long enough to have several index entries
+    fn write_test_csv(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("blarser-{name}-{}.csv", Uuid::new_v4()));
+        let mut file = std::fs::File::create(&path).unwrap();
+        writeln!(file, "entity_id,timestamp,hash,data").unwrap();
+        let start = Utc.with_ymd_and_hms(2021, 3, 1, 0, 0, 0).unwrap();
+        for i in 0..(INDEX_INTERVAL as i64 * 3 + 10) {
+            let time = start + Duration::minutes(i);
+            writeln!(file, "{},{}+00,{i},\"{{\"\"row\"\": {i}}}\"", Uuid::new_v4(), time.format("%Y-%m-%d %H:%M:%S")).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn seeked_replay_matches_a_scan_from_the_top() {
+        let path = write_test_csv("seek");
+        let start_at_time = Utc.with_ymd_and_hms(2021, 3, 1, 0, 0, 0).unwrap() + Duration::minutes(INDEX_INTERVAL as i64 * 2 + 500);
+
+        let scanned = csv::Reader::from_path(&path).unwrap()
+            .into_records()
+            .map(Result::unwrap)
+            .filter(|record| super::parse_csv_timestamp(record) >= start_at_time)
+            .collect::<Vec<_>>();
+        // Make sure the replay below actually seeks
+        assert!(seek_position(&path, start_at_time).is_some());
+        let seeked = records_from(&path, start_at_time)
+            .expect("Test CSV should open")
+            .collect::<Vec<_>>();
+
+        assert_eq!(seeked.len(), INDEX_INTERVAL as usize + 10 - 500);
+        assert_eq!(seeked, scanned);
+
+        std::fs::remove_file(index_path(&path)).unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn replay_from_before_the_first_row_reads_everything() {
+        let path = write_test_csv("scan");
+        let start_at_time = Utc.with_ymd_and_hms(2021, 2, 1, 0, 0, 0).unwrap();
+
+        // There's no indexed row before the start, so nothing is skipped
+        assert!(seek_position(&path, start_at_time).is_none());
+        let records = records_from(&path, start_at_time)
+            .expect("Test CSV should open")
+            .count();
+        assert_eq!(records, INDEX_INTERVAL as usize * 3 + 10);
+
+        std::fs::remove_file(index_path(&path)).unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use chrono::Duration;
+
+use crate::ingest::Observation;
+use crate::state::EntityType;
+
+// Observations identical to the last one that was ingested are skipped if they arrive within the
+// window for their type. Entities that rarely change (like the sim) can use a long window so they
+// don't pile up versions, and games can use none so every update is kept. The window is measured
+// from the last ingested observation, so identical observations still get ingested once per
+// window to re-confirm the state.
+#[derive(Debug, Clone)]
+pub struct DedupWindows {
+    windows: HashMap<EntityType, Duration>,
+    default: Duration,
+}
+
+impl DedupWindows {
+    pub fn new(default: Duration) -> Self {
+        Self {
+            windows: HashMap::new(),
+            default,
+        }
+    }
+
+    pub fn with(mut self, entity_type: EntityType, window: Duration) -> Self {
+        self.set(entity_type, window);
+        self
+    }
+
+    pub fn set(&mut self, entity_type: EntityType, window: Duration) {
+        self.windows.insert(entity_type, window);
+    }
+
+    pub fn window_for(&self, entity_type: EntityType) -> Duration {
+        self.windows.get(&entity_type).copied().unwrap_or(self.default)
+    }
+
+    pub fn is_duplicate(&self, previous: &Observation, obs: &Observation) -> bool {
+        let window = self.window_for(obs.entity_type);
+        if window <= Duration::zero() || obs.perceived_at - previous.perceived_at > window {
+            return false;
+        }
+
+        // AnyEntityRaw doesn't implement PartialEq, and comparing the json is just as good
+        serde_json::to_value(&previous.entity_raw).ok() == serde_json::to_value(&obs.entity_raw).ok()
+    }
+}
+
+impl Default for DedupWindows {
+    fn default() -> Self {
+        Self::new(Duration::zero())
+            .with(EntityType::Sim, Duration::hours(1))
+            .with(EntityType::Season, Duration::hours(1))
+            .with(EntityType::Stadium, Duration::hours(1))
+            .with(EntityType::League, Duration::hours(1))
+            .with(EntityType::Subleague, Duration::hours(1))
+            .with(EntityType::Division, Duration::hours(1))
+            .with(EntityType::Standings, Duration::minutes(10))
+            .with(EntityType::Team, Duration::minutes(5))
+            .with(EntityType::Player, Duration::minutes(5))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+    use crate::entity::{test_game, test_sim, AnyEntity};
+    use crate::ingest::Observation;
+    use crate::state::EntityType;
+    use super::DedupWindows;
+
+    // The observations that would be ingested, with each one compared against the last one that
+    // was ingested the way ingest_observation does
+    fn ingested(windows: &DedupWindows, observations: Vec<Observation>) -> Vec<Observation> {
+        let mut ingested: Vec<Observation> = Vec::new();
+        for obs in observations {
+            if ingested.last().map_or(true, |previous| !windows.is_duplicate(previous, &obs)) {
+                ingested.push(obs);
+            }
+        }
+        ingested
+    }
+
+    fn observations_every_five_minutes(entity_type: EntityType, entity_id: Uuid, entity: AnyEntity) -> Vec<Observation> {
+        let start = Utc::now();
+        let entity_raw = entity.into_raw_approximation();
+        (0..12)
+            .map(|i| Observation {
+                perceived_at: start + Duration::minutes(5 * i),
+                entity_type,
+                entity_id,
+                entity_raw: entity_raw.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_sim_observations_over_an_hour_collapse() {
+        let observations = observations_every_five_minutes(EntityType::Sim, Uuid::nil(), test_sim().into());
+        assert_eq!(ingested(&DedupWindows::default(), observations).len(), 1);
+    }
+
+    #[test]
+    fn identical_game_observations_over_an_hour_are_all_kept() {
+        let game = test_game();
+        let observations = observations_every_five_minutes(EntityType::Game, game.id, game.into());
+        assert_eq!(ingested(&DedupWindows::default(), observations).len(), 12);
+    }
+
+    #[test]
+    fn identical_observations_are_ingested_again_once_the_window_passes() {
+        let windows = DedupWindows::new(Duration::zero())
+            .with(EntityType::Sim, Duration::minutes(20));
+        let observations = observations_every_five_minutes(EntityType::Sim, Uuid::nil(), test_sim().into());
+        let times = ingested(&windows, observations).into_iter()
+            .map(|obs| obs.perceived_at)
+            .collect::<Vec<_>>();
+        assert_eq!(times.len(), 3);
+        assert_eq!(times[1] - times[0], Duration::minutes(25));
+    }
+}
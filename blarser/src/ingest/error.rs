@@ -1,11 +1,60 @@
+use chrono::{DateTime, Utc};
 use thiserror::Error;
 use uuid::Uuid;
+use crate::entity::EntityParseError;
+use crate::ingest::chron::GenerationConflicts;
 use crate::state::EntityType;
 
-#[derive(Debug, Clone, Error)]
+#[derive(Debug, Error)]
 pub enum IngestError {
+    #[error("Failed to parse ingest data: {0}")]
+    Parse(#[from] EntityParseError),
+
+    #[error("Every placement of the observation of {ty} {id} at {perceived_at} conflicted. {conflicts}")]
+    Conflict {
+        ty: EntityType,
+        id: Uuid,
+        perceived_at: DateTime<Utc>,
+        // One generation per version the observation was tried against
+        conflicts: GenerationConflicts,
+    },
+
     #[error("Tried to apply event to entity {id} of type {ty}, but that entity does not exist")]
-    EntityDoesNotExist { ty: EntityType, id: Uuid}
+    MissingEntity { ty: EntityType, id: Uuid },
+
+    #[error("Applying {effect} to {ty} {id} turned it into {new_ty} {new_id}")]
+    EffectChangedIdentity {
+        effect: String,
+        ty: EntityType,
+        id: Uuid,
+        new_ty: EntityType,
+        new_id: Uuid,
+    },
+
+    #[error("{effect} had no possible outcomes for {ty} {id}")]
+    NoOutcomes { effect: String, ty: EntityType, id: Uuid },
+
+    #[error(transparent)]
+    Persistence(#[from] diesel::result::Error),
+}
+
+impl IngestError {
+    // The entity the error is about, if it's about one in particular
+    pub fn entity(&self) -> Option<(EntityType, Uuid)> {
+        match self {
+            IngestError::Conflict { ty, id, .. } => { Some((*ty, *id)) }
+            IngestError::MissingEntity { ty, id } => { Some((*ty, *id)) }
+            IngestError::EffectChangedIdentity { ty, id, .. } => { Some((*ty, *id)) }
+            IngestError::NoOutcomes { ty, id, .. } => { Some((*ty, *id)) }
+            IngestError::Parse(_) | IngestError::Persistence(_) => { None }
+        }
+    }
+}
+
+impl From<serde_json::Error> for IngestError {
+    fn from(err: serde_json::Error) -> Self {
+        IngestError::Parse(EntityParseError::from(err))
+    }
 }
 
-pub type IngestResult<T> = Result<T, IngestError>;
\ No newline at end of file
+pub type IngestResult<T> = Result<T, IngestError>;
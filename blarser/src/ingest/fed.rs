@@ -1,16 +1,21 @@
 use std::cmp::{Ordering, Reverse};
-use std::collections::BinaryHeap;
-use std::sync::Arc;
+use std::collections::{BinaryHeap, HashSet};
+use std::sync::{Arc, PoisonError};
 use chrono::{DateTime, Utc};
 use futures::{Stream, stream};
+use itertools::Itertools;
 use fed::{FedEvent as FedEventBase, FedEventFlat};
 use enum_flatten::EnumFlatten;
-use log::info;
+use log::{error, info, warn};
+use uuid::Uuid;
+use partial_information::Severity;
 
-use crate::events::{AnyEvent, FedEvent};
-use crate::ingest::error::IngestResult;
+use crate::entity::Entity;
+use crate::events::{AnyEffect, AnyEvent, FedEvent};
+use crate::ingest::error::{IngestError, IngestResult};
 use crate::ingest::{GraphDebugHistory, StateGraph};
-use crate::ingest::task::{DebugHistoryVersion, Ingest};
+use crate::ingest::task::{AppliedEvents, DebugHistoryVersion, EventLog, EventLogEntry, Ingest};
+use crate::state::{EntityType, NewConflict};
 
 pub struct EventStreamItem {
     last_update_time: DateTime<Utc>,
@@ -18,6 +23,10 @@ pub struct EventStreamItem {
 }
 
 impl EventStreamItem {
+    pub fn new(last_update_time: DateTime<Utc>, event: Option<AnyEvent>) -> Self {
+        EventStreamItem { last_update_time, event }
+    }
+
     pub fn last_update_time(&self) -> DateTime<Utc> {
         self.last_update_time
     }
@@ -38,10 +47,7 @@ pub fn get_fed_event_stream() -> impl Stream<Item=EventStreamItem> {
         .with_timezone(&Utc);
 
     let iter = fed::expansion_era_events()
-        .map(move |event| EventStreamItem {
-            last_update_time: fed_up_to_date_until,
-            event: Some(FedEvent::new(event.unwrap()).into()),
-        });
+        .map(move |event| EventStreamItem::new(fed_up_to_date_until, Some(FedEvent::new(event.unwrap()).into())));
 
     stream::iter(iter)
 }
@@ -82,6 +88,14 @@ impl TimedEventQueue {
     }
 
     pub fn len(&self) -> usize { self.heap.len() }
+
+    // Events in the order they'll be popped
+    pub fn sorted(&self) -> Vec<&AnyEvent> {
+        self.heap.iter()
+            .sorted_by_key(|record| (record.event.time(), record.index))
+            .map(|record| &record.event)
+            .collect()
+    }
 }
 
 impl<T: IntoIterator<Item=AnyEvent>> From<T> for TimedEventQueue {
@@ -132,13 +146,43 @@ impl Ord for TimedEventRecord {
     }
 }
 
-pub async fn get_timed_event_list(ingest: &mut Ingest, start_time: DateTime<Utc>) -> TimedEventQueue {
+pub async fn get_timed_event_list(ingest: &mut Ingest, start_time: DateTime<Utc>, end_time: DateTime<Utc>) -> TimedEventQueue {
+    let events = {
+        let state = ingest.state.lock().unwrap();
+        state.get_timed_events_between(start_time, end_time)
+    };
+
+    events.into_iter()
+        .unique_by(|event| event.timed_event_key())
+        .into()
+}
+
+// Adds the timed events implied by the state in [start_time, end_time) to the queue, skipping any
+// that are already queued. Returns how many were added.
+pub fn refill_timed_events(ingest: &mut Ingest, start_time: DateTime<Utc>, end_time: DateTime<Utc>) -> usize {
     let events = {
         let state = ingest.state.lock().unwrap();
-        state.get_timed_events(start_time)
+        state.get_timed_events_between(start_time, end_time)
     };
 
-    events.into()
+    let mut timed_events = ingest.timed_events.lock().unwrap();
+    let new_events = unqueued_timed_events(timed_events.sorted(), events);
+
+    let num_added = new_events.len();
+    timed_events.extend(new_events);
+    num_added
+}
+
+// The event that caused a state usually scheduled its timed events already, possibly at a slightly
+// different time than the state implies, so events are matched by kind and game rather than by
+// exact equality. Also drops duplicates within `events`.
+pub(crate) fn unqueued_timed_events<'a>(queued: impl IntoIterator<Item=&'a AnyEvent>, events: Vec<AnyEvent>) -> Vec<AnyEvent> {
+    let mut queued: HashSet<_> = queued.into_iter()
+        .map(|event| event.timed_event_key())
+        .collect();
+    events.into_iter()
+        .filter(|event| queued.insert(event.timed_event_key()))
+        .collect_vec()
 }
 
 
@@ -147,51 +191,207 @@ pub async fn ingest_event(ingest: &mut Ingest, event: AnyEvent) -> IngestResult<
     let mut state = ingest.state.lock().unwrap();
     let mut new_timed_events = Vec::new();
 
+    let mismatches = team_tag_mismatches(&state, event.game_tags(), event.team_tags());
+    if !mismatches.is_empty() {
+        // The tags are only metadata, and the game update itself is usually fine, so record it as a
+        // conflict for a human to look at and carry on applying the event
+        error!("Event {event} tags teams that aren't playing in its games");
+        let ingest_id = ingest.ingest_id;
+        ingest.recorded_conflicts.extend(mismatches.into_iter()
+            .map(|(game_id, team_id)| NewConflict {
+                ingest_id,
+                entity_type: EntityType::Game,
+                entity_id: game_id,
+                perceived_at: event.time(),
+                property: "team_tags/".to_string(),
+                message: format!("Event \"{event}\" tags team {team_id}, which isn't playing in this game"),
+                severity: Severity::Error.level(),
+            }));
+    }
+
+    let failures = event.precondition_failures(&state);
+    if !failures.is_empty() {
+        let skip = failures.iter().any(|failure| failure.skips_event);
+        if skip {
+            error!("Event {event} failed {} precondition(s); skipping it", failures.len());
+        } else {
+            error!("Event {event} failed {} precondition(s); applying what it can", failures.len());
+        }
+        let ingest_id = ingest.ingest_id;
+        let perceived_at = event.time();
+        ingest.recorded_conflicts.extend(failures.into_iter()
+            .map(|failure| NewConflict {
+                ingest_id,
+                entity_type: failure.entity_type,
+                entity_id: failure.entity_id,
+                perceived_at,
+                property: failure.property,
+                message: failure.message,
+                severity: Severity::Error.level(),
+            }));
+        if skip {
+            return Ok(new_timed_events);
+        }
+    }
+
     if let Some(predecessor) = event.generate_predecessor(&state) {
         info!("Event {event} has predecessor {predecessor}; ingesting that instead");
-        new_timed_events.extend(ingest_event_internal(&mut state, predecessor, &mut history)?);
+        new_timed_events.extend(ingest_event_internal(&mut state, predecessor, &ingest.event_log, &mut history)?);
         // The original event becomes a timed event. Crucially, it gets inserted *after* the
         // successors of its predecessor.
         new_timed_events.push(event);
     } else {
-        new_timed_events.extend(ingest_event_internal(&mut state, event, &mut history)?);
+        record_applied_event(&ingest.applied_events, &state, &event);
+        new_timed_events.extend(ingest_event_internal(&mut state, event, &ingest.event_log, &mut history)?);
     }
 
 
     Ok(new_timed_events)
 }
 
+// Keeps the original Feed event around so it can be compared with the state it produced. Events
+// are filed under the play they're about to create, which is the game's current play count. If the
+// play count is ambiguous the event is filed under every candidate.
+fn record_applied_event(applied_events: &AppliedEvents, state: &StateGraph, event: &AnyEvent) {
+    let AnyEvent::Fed(fed_event) = event else { return; };
+    let json = match serde_json::to_value(fed_event) {
+        Ok(json) => { json }
+        Err(e) => {
+            warn!("Couldn't serialize {event} for the applied events record: {e}");
+            return;
+        }
+    };
+
+    // This is only a debug record, so a panic while it was locked shouldn't stop the ingest
+    let mut applied_events = applied_events.lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    for &game_id in event.game_tags() {
+        // Untracked games have no candidates
+        for play in state.query_game_all(game_id, |game| game.play_count) {
+            applied_events.record((game_id, play), json.clone());
+        }
+    }
+}
+
+// Every team an event tags should be playing in every game it tags. Returns the (game, team) pairs
+// where that isn't true. If the game is ambiguous, the team only has to be playing in one of the
+// possible versions.
+fn team_tag_mismatches(state: &StateGraph, game_tags: &[Uuid], team_tags: &[Uuid]) -> Vec<(Uuid, Uuid)> {
+    game_tags.iter()
+        .flat_map(|&game_id| {
+            // Games that aren't being tracked have no versions, so they can't be checked
+            let teams = state.query_game_all(game_id, |game| (game.home.team, game.away.team));
+            team_tags.iter()
+                .filter(move |&&team_id| {
+                    !teams.is_empty() && !teams.iter().any(|&(home_id, away_id)| team_id == home_id || team_id == away_id)
+                })
+                .map(move |&team_id| (game_id, team_id))
+        })
+        .collect()
+}
+
+// Applies events from an exported event log. Successors aren't generated because they were
+// recorded in the log themselves, at the point they were applied.
+pub fn replay_event(
+    state: &mut StateGraph,
+    event: AnyEvent,
+    event_log: &EventLog,
+    history: &mut GraphDebugHistory,
+) -> IngestResult<()> {
+    ingest_event_internal(state, event, event_log, history)?;
+    Ok(())
+}
+
 fn ingest_event_internal(
     state: &mut StateGraph,
     event: AnyEvent,
+    event_log: &EventLog,
     history: &mut GraphDebugHistory,
 ) -> IngestResult<Vec<AnyEvent>> {
     let mut new_timed_events = Vec::new();
 
     info!("Ingesting event {event}");
+    let mut created = Vec::new();
+    for entity in event.new_entities() {
+        let (entity_type, id) = (entity.entity_type(), entity.id());
+        if state.entity_graph(entity_type, id).is_some() {
+            // e.g. a replayed event, or a player who was already observed before this arrived
+            info!("{event} creates {entity_type} {id}, which already exists; keeping the existing one");
+        } else {
+            state.add_new_entity(entity, event.time(), &event.to_string(), history);
+            created.push((entity_type, id));
+        }
+    }
+    // Finished games are set aside, but events can still turn up for them
+    for (entity_type, id) in event.targets() {
+        if state.entity_graph(entity_type, id).is_none() && state.restore_entity_graph(entity_type, id) {
+            info!("{event} targets {entity_type} {id}, which was removed; restoring its previous history");
+        }
+    }
+    event_log.lock().unwrap().push(EventLogEntry::event(&event));
     new_timed_events.extend(event.generate_successors(&state));
     let event_time = event.time();
-    for effect in event.into_effects(&state) {
-        let ty = effect.entity_type();
-        for id in state.ids_for(&effect) {
-            info!("Applying {effect} to {ty} {id}");
-            let graph = state.entity_graph_mut(ty, id)
-                .expect("Tried to apply event to entity that does not exist");
-            graph.apply_effect(&effect, event_time);
-            history.push(&(effect.entity_type(), id), DebugHistoryVersion {
-                event_human_name: format!("After applying {effect}"),
-                time: event_time,
-                tree: graph.get_debug_tree(),
-                queued_for_update: None,
-                currently_updating: None,
-                queued_for_delete: None,
-            });
+    let effects = event.into_effects(&state);
+    if let Err(e) = apply_effects(state, &effects, event_time, history) {
+        // A skipped event shouldn't leave anything behind, including the entities it would have
+        // created
+        for (entity_type, id) in created {
+            state.discard_new_entity(entity_type, id);
         }
+        return Err(e);
     }
 
     Ok(new_timed_events)
 }
 
+// Applies all of the effects or none of them. Every target is checked before anything is applied,
+// and if an effect fails partway through, the ones before it are undone.
+fn apply_effects(state: &mut StateGraph, effects: &[AnyEffect], event_time: DateTime<Utc>, history: &mut GraphDebugHistory) -> IngestResult<()> {
+    let targets = effects.iter()
+        .flat_map(|effect| state.ids_for(effect).into_iter().map(move |id| (effect, id)))
+        .collect_vec();
+    if let Some((effect, id)) = targets.iter().find(|(effect, id)| state.entity_graph(effect.entity_type(), *id).is_none()) {
+        return Err(IngestError::MissingEntity { ty: effect.entity_type(), id: *id });
+    }
+
+    let mut applied = Vec::new();
+    for &(effect, id) in &targets {
+        let ty = effect.entity_type();
+        info!("Applying {effect} to {ty} {id}");
+        let graph = state.entity_graph_mut(ty, id)
+            .expect("Targets were all checked before applying anything");
+        match graph.apply_effect(effect, event_time) {
+            Ok(previous_leafs) => { applied.push((ty, id, previous_leafs)) }
+            Err(e) => {
+                // Undone newest first, since an entity can be targeted more than once
+                for (ty, id, previous_leafs) in applied.into_iter().rev() {
+                    state.entity_graph_mut(ty, id)
+                        .expect("Entities that had effects applied should still exist")
+                        .undo_effect(previous_leafs);
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    // The history only hears about effects once they've all gone through
+    for (effect, id) in targets {
+        let key = (effect.entity_type(), id);
+        let graph = state.entity_graph(key.0, key.1)
+            .expect("Entities that had effects applied should still exist");
+        history.push(&key, || DebugHistoryVersion {
+            event_human_name: format!("After applying {effect}"),
+            time: event_time,
+            tree: graph.get_debug_tree(),
+            queued_for_update: None,
+            currently_updating: None,
+            queued_for_delete: None,
+        });
+    }
+
+    Ok(())
+}
+
 // fn blarser_event_from_fed_event(fed_event: FedEvent) -> Option<AnyEvent> {
 //     match fed_event.flat() {
 //         _ => { todo!() }
@@ -519,4 +719,126 @@ fn ingest_event_internal(
 //     //     FedEventData::Roam { .. } => { todo!() }
 //     // })
 //     todo!()
-// }
\ No newline at end of file
+// }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex as StdMutex};
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+    use crate::entity::{test_game, test_player, test_team};
+    use crate::events::{third_out_successors, AnyEvent, Feedback, FeedbackPlayer, GameOver, GameUpcomingEffectVariant, WinCollectedRegular, TOP_INNING_END_DELAY_SECONDS};
+    use crate::ingest::{test_state, AddedReason, EventLogRecorder, GraphDebugHistory, IngestError, StateGraph};
+    use crate::state::EntityType;
+    use super::{ingest_event_internal, team_tag_mismatches, unqueued_timed_events};
+
+    #[test]
+    fn team_tags_are_checked_against_the_games_teams() {
+        let game = test_game();
+        let stranger = Uuid::new_v4();
+        let untracked_game = Uuid::new_v4();
+        let state = test_state([game.clone().into()]);
+
+        let mismatches = team_tag_mismatches(&state, &[game.id, untracked_game], &[game.home.team, game.away.team, stranger]);
+        assert_eq!(mismatches, vec![(game.id, stranger)]);
+    }
+
+    #[test]
+    fn timed_events_are_matched_by_kind_and_game() {
+        let time = Utc::now();
+        let (game_a, game_b) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let queued: Vec<AnyEvent> = vec![GameOver::new(time, game_a).into()];
+
+        let new_events = unqueued_timed_events(&queued, vec![
+            // Same kind and game as one already queued, but implied at a different time
+            GameOver::new(time + Duration::seconds(3), game_a).into(),
+            // Same game, different kind
+            WinCollectedRegular::new(time, game_a).into(),
+            // Same kind, different game, twice
+            GameOver::new(time, game_b).into(),
+            GameOver::new(time + Duration::seconds(3), game_b).into(),
+        ]);
+
+        let expected: Vec<AnyEvent> = vec![
+            WinCollectedRegular::new(time, game_a).into(),
+            GameOver::new(time, game_b).into(),
+        ];
+        assert_eq!(new_events.iter().map(ToString::to_string).collect::<Vec<_>>(),
+                   expected.iter().map(ToString::to_string).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn strikeout_for_the_third_out_ends_the_top_of_the_inning() {
+        let mut game = test_game();
+        game.top_of_inning = true;
+        game.half_inning_outs = 2;
+        let mut state = test_state([game.clone().into()]);
+        let strikeout_time = Utc::now();
+
+        // Successors are generated before the strikeout's effects are applied
+        let mut successors = third_out_successors(strikeout_time, game.id, &state);
+        assert_eq!(successors.len(), 1);
+        assert_eq!(successors[0].time(), strikeout_time + Duration::seconds(TOP_INNING_END_DELAY_SECONDS));
+
+        // Building the strikeout's own effect takes a Feed event, so its out is applied directly
+        let graph = state.entity_graph_mut(EntityType::Game, game.id).unwrap();
+        let root = graph.roots()[0];
+        let mut struck_out = game.clone();
+        struck_out.play_count += 1;
+        struck_out.out(1);
+        let leaf = graph.add_child_version(root, struck_out.into(), strikeout_time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        graph.set_leafs(vec![leaf]);
+
+        let event_log = Arc::new(StdMutex::new(EventLogRecorder::default()));
+        let mut history = GraphDebugHistory::new(true, 0);
+        let new_successors = ingest_event_internal(&mut state, successors.remove(0), &event_log, &mut history)
+            .expect("TopInningEnd should apply to the struck out game");
+        assert!(new_successors.is_empty());
+
+        let after = state.query_game_all(game.id, |g| (g.phase, g.play_count, g.half_inning_outs, g.top_of_inning));
+        assert_eq!(after, vec![(2, game.play_count + 2, 0, true)]);
+    }
+
+    #[test]
+    fn outs_in_the_bottom_half_dont_schedule_a_top_inning_end() {
+        let mut game = test_game();
+        game.top_of_inning = false;
+        game.half_inning_outs = 2;
+        let state = test_state([game.clone().into()]);
+
+        assert!(third_out_successors(Utc::now(), game.id, &state).is_empty());
+    }
+
+    #[test]
+    fn event_with_a_missing_target_changes_nothing() {
+        let (first_id, second_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let first_team = test_team(vec![first_id], vec![]);
+        let second_team = test_team(vec![second_id], vec![]);
+        let mut first_player = test_player(Some(first_team.id));
+        first_player.id = first_id;
+        // The second player is never added, so the last of Feedback's effects has no target
+        let mut state = test_state([first_team.clone().into(), second_team.clone().into(), first_player.clone().into()]);
+        let team_lineups = |state: &StateGraph| (
+            state.query_team_all(first_team.id, |t| t.lineup.clone()),
+            state.query_team_all(second_team.id, |t| t.lineup.clone()),
+        );
+        let lineups_before = team_lineups(&state);
+        let first_player_before = state.query_player_all(first_id, |p| p.league_team_id);
+
+        let feedback = Feedback::new(
+            Utc::now(),
+            FeedbackPlayer { player_id: first_id, team_id: first_team.id },
+            FeedbackPlayer { player_id: second_id, team_id: second_team.id },
+        );
+        let event_log = Arc::new(StdMutex::new(EventLogRecorder::default()));
+        let mut history = GraphDebugHistory::new(true, 0);
+        let result = ingest_event_internal(&mut state, feedback.into(), &event_log, &mut history);
+
+        assert!(matches!(result, Err(IngestError::MissingEntity { ty: EntityType::Player, id }) if id == second_id));
+        assert_eq!(team_lineups(&state), lineups_before);
+        assert_eq!(state.query_player_all(first_id, |p| p.league_team_id), first_player_before);
+        for (ty, id) in [(EntityType::Team, first_team.id), (EntityType::Team, second_team.id), (EntityType::Player, first_id)] {
+            assert_eq!(state.entity_graph(ty, id).unwrap().leafs().len(), 1, "{ty} {id} should still have only its original version");
+        }
+    }
+}
@@ -6,20 +6,59 @@ mod observation_event;
 mod fed;
 mod state;
 mod error;
+mod approval_policy;
+mod ambiguity;
+mod csv_index;
+mod dedup_window;
+mod backfill;
+mod config;
 
-pub use task::{IngestTask, IngestTaskHolder};
+pub use task::{IngestTask, IngestTaskHolder, PauseError, ResumeHandle, DEFAULT_DEBUG_HISTORY_CAP};
 pub use observation::Observation;
 pub use observation_event::ChronObservationEvent;
-pub use state::StateGraph;
+pub use state::{AddedReason, StateGraph, StateSnapshot, SnapshotError, GameStadiumReport, StadiumReport, GraphInvariantViolation};
+pub use approval_policy::{ApprovalPolicy, ApprovalPolicies, ScorePolicy};
+pub use ambiguity::{AmbiguityStrategy, FieldWeights};
+pub use dedup_window::DedupWindows;
+pub use backfill::BackfillConfig;
+pub use config::IngestConfig;
+pub use error::{IngestError, IngestResult};
+pub use fed::TimedEventQueue;
+#[cfg(test)]
+pub(crate) use state::tests::test_state;
 
 use std::cmp::Reverse;
-use chrono::{DateTime, Utc};
-use futures::{pin_mut, StreamExt};
-use log::info;
+use std::pin::Pin;
+use chrono::{DateTime, Duration, Utc};
+use futures::{pin_mut, Stream, StreamExt};
+use futures::stream::Peekable;
+use tokio::sync::watch;
+use log::{error, info};
+use uuid::Uuid;
+use partial_information::Severity;
 
-pub use crate::ingest::task::{Ingest, GraphDebugHistorySync, GraphDebugHistory};
-use crate::ingest::fed::{EventStreamItem, get_fed_event_stream, get_timed_event_list, ingest_event};
+pub use crate::ingest::task::{Ingest, GraphDebugHistorySync, GraphDebugHistory, AppliedEvents, EventLog, EventLogEntry, EventLogExport, EventLogRecorder, ReplayItem, EVENT_LOG_CAP, IngestSnapshot, RestoredSnapshot};
+use crate::ingest::task::save_snapshot;
+use crate::ingest::backfill::{BackfillProgress, BackfillStep};
+use crate::events::AnyEvent;
+use crate::ingest::fed::{EventStreamItem, get_fed_event_stream, get_timed_event_list, ingest_event, refill_timed_events, replay_event};
 use crate::ingest::chron::{chron_updates_hardcoded, ingest_observation, load_initial_state};
+use crate::ingest::approval_policy::QueuedApproval;
+use crate::state::{EntityType, NewConflict};
+
+// How often to prune the state graph, and how much history to keep when it's pruned. Both are in
+// ingest time, not real time.
+const PRUNE_INTERVAL_MINUTES: i64 = 60;
+const PRUNE_KEEP_MINUTES: i64 = 60;
+// How far ahead of the ingest clock timed events implied by the state get loaded into the queue
+const TIMED_EVENT_WINDOW_HOURS: i64 = 6;
+
+// The end of the window that `time` falls in, where windows are `window` long and start at
+// `loaded_until`. `time` must not be before `loaded_until`.
+fn timed_event_window_end(loaded_until: DateTime<Utc>, time: DateTime<Utc>, window: Duration) -> DateTime<Utc> {
+    let windows_ahead = (time - loaded_until).num_milliseconds() / window.num_milliseconds();
+    loaded_until + window * (windows_ahead as i32 + 1)
+}
 
 #[derive(Debug)]
 enum Source {
@@ -28,110 +67,696 @@ enum Source {
     Observation,
 }
 
-pub async fn run_ingest(mut ingest: Ingest, start_time: DateTime<Utc>) {
-    info!("Loading initial state from {start_time}...");
-    let initial_observations = load_initial_state(start_time).await;
-    {
-        let mut history = ingest.debug_history.lock().await;
-        let mut state = ingest.state.lock().unwrap();
-
-        state.populate(initial_observations, start_time, &mut *history);
-    }
+// How long to wait before an item at `time`, when the one before it was at `last_time`. None means
+// don't wait at all, either because the replay is going as fast as possible or because the items
+// are out of order.
+fn replay_delay(last_time: DateTime<Utc>, time: DateTime<Utc>, replay_speed: f64) -> Option<std::time::Duration> {
+    if replay_speed <= 0. { return None; }
+    // to_std fails on negative durations, which just means there's no need to wait
+    let gap = (time - last_time).to_std().ok()?;
+    Some(gap.div_f64(replay_speed))
+}
 
-    let mut timed_events = get_timed_event_list(&mut ingest, start_time).await;
-    info!("Initial state has {} timed events", timed_events.len());
+// replay_speed is a multiplier on real time: 1.0 replays at the speed things originally happened,
+// 60.0 replays an hour per minute, etc. Zero means go as fast as possible. If backfill is set the
+// ingest stops at the end of the backfill range instead of running forever.
+pub async fn run_ingest(ingest: Ingest, start_time: DateTime<Utc>, replay_speed: f64, backfill: Option<BackfillConfig>, snapshot: Option<RestoredSnapshot>) {
+    let initial_state = match snapshot {
+        Some(snapshot) => { InitialState::Snapshot(snapshot) }
+        None => {
+            info!("Loading initial state from {start_time}...");
+            InitialState::Observations(load_initial_state(start_time).await)
+        }
+    };
 
     info!("Getting fed events stream");
-    let fed_events = get_fed_event_stream().peekable();
-    pin_mut!(fed_events);
+    let fed_events = get_fed_event_stream();
     info!("Getting updates stream");
-    let observations = chron_updates_hardcoded(start_time).peekable();
+    let observations = chron_updates_hardcoded(start_time);
     info!("Got updates stream");
+
+    run_ingest_from(ingest, start_time, replay_speed, backfill, initial_state, fed_events, observations).await
+}
+
+enum InitialState {
+    Snapshot(RestoredSnapshot),
+    Observations(Vec<Observation>),
+}
+
+// run_ingest with the sources passed in, so tests can give it ones that end
+async fn run_ingest_from<S, I>(mut ingest: Ingest, start_time: DateTime<Utc>, replay_speed: f64, backfill: Option<BackfillConfig>,
+                               initial_state: InitialState, fed_events: S, observations: I)
+    where S: Stream<Item=EventStreamItem>,
+          I: Iterator<Item=Observation> {
+    let timed_event_window = Duration::hours(TIMED_EVENT_WINDOW_HOURS);
+    // Timed events implied by the state are only loaded up to here. Anything the state implies
+    // past it gets loaded once the ingest clock gets close.
+    let mut timed_events_loaded_until = start_time;
+    match initial_state {
+        InitialState::Snapshot(snapshot) => {
+            info!("Restoring state from the snapshot at {start_time}...");
+            let mut history = ingest.debug_history.lock().await;
+            let mut state = ingest.state.lock().unwrap();
+
+            *state = snapshot.state;
+            state.push_restored_debug_items(start_time, &mut *history);
+
+            *ingest.timed_events.lock().unwrap() = snapshot.timed_events;
+        }
+        InitialState::Observations(initial_observations) => {
+            ingest.event_log.lock().unwrap().set_initial_state(initial_observations.clone());
+            {
+                let mut history = ingest.debug_history.lock().await;
+                let mut state = ingest.state.lock().unwrap();
+
+                state.populate(initial_observations, start_time, &mut *history);
+            }
+
+            timed_events_loaded_until = start_time + timed_event_window;
+            let initial_timed_events = get_timed_event_list(&mut ingest, start_time, timed_events_loaded_until).await;
+            info!("Initial state has {} timed events before {timed_events_loaded_until}", initial_timed_events.len());
+            *ingest.timed_events.lock().unwrap() = initial_timed_events;
+        }
+    }
+
+    let fed_events = fed_events.peekable();
+    pin_mut!(fed_events);
+    let observations = observations.peekable();
     pin_mut!(observations);
 
+    let mut backfill = backfill.map(|config| BackfillProgress::new(config, start_time));
+
+    let mut last_time = start_time;
+    let mut next_prune = start_time + Duration::minutes(PRUNE_INTERVAL_MINUTES);
     loop {
         if let Ok(resumer) = ingest.pause_request.try_recv() {
             info!("Pausing ingest");
-            resumer.await.unwrap();
+            // An error means the resume handle was dropped, which also resumes
+            let _ = resumer.await;
             info!("Resuming ingest");
         }
 
-        let mut latest_feed_update;
-        // TODO this always blocks until the next event comes in, defeating the purpose of having
-        //   event-less "latest ingest time" updates
         info!("Finding next feed event time");
-        let next_fed_event_time = loop {
-            // Consume all the empty ingests from fed_events
-            let next_item: &EventStreamItem = fed_events.as_mut().peek().await
-                .expect("This stream should never terminate");
-            latest_feed_update = next_item.last_update_time();
-            if let Some(event) = next_item.event() {
-                break event.time()
-            } else {
-                info!("Skipping empty event");
-                let n: EventStreamItem = fed_events.as_mut().next().await
-                    .expect("This stream should never terminate");
-                assert!(n.event().is_none(),
-                        "This part of the loop should only ever drain items with no event");
-            }
-        };
-        info!("Next feed event is at {next_fed_event_time}");
-
-        info!("Getting next timed event time");
-        let next_timed_event = timed_events.peek_with_index();
-        if let Some((i, event)) = next_timed_event {
-            info!("Next timed event is at {} (index {i})", event.time());
-        } else {
-            info!("No timed events");
+        let (next_fed_event_time, latest_feed_update) = next_fed_event_time(fed_events.as_mut(), start_time, &ingest.latest_ingest_time).await;
+        match next_fed_event_time {
+            Some(time) => { info!("Next feed event is at {time}") }
+            None => { info!("The Feed stream has ended") }
         }
-        let next_timed_event_time = next_timed_event.map(|(_, event)| event.time());
 
-        // TODO Allow this to be None if there are currently no observations
         info!("Getting next observation time");
         let next_observation_time = observations.as_mut().peek()
-            .expect("This stream should never terminate")
-            .latest_time();
-        info!("Next observation is at {next_observation_time}");
+            .map(|observation| observation.latest_time());
+        if let Some(time) = next_observation_time {
+            info!("Next observation is at {time}");
+        } else {
+            info!("No observations");
+        }
+
+        // Every timed event before the next event from another source has to be in the queue by
+        // now, or it'd be ingested out of order
+        let next_other_time = [next_observation_time, next_fed_event_time].into_iter().flatten().min();
+        if let Some(next_other_time) = next_other_time && timed_events_loaded_until <= next_other_time {
+            // After a long gap in the streams this can be many windows ahead, which are all
+            // loaded in one go
+            let load_until = timed_event_window_end(timed_events_loaded_until, next_other_time, timed_event_window);
+            let num_added = refill_timed_events(&mut ingest, timed_events_loaded_until, load_until);
+            info!("Loaded {num_added} timed events between {timed_events_loaded_until} and {load_until}");
+            timed_events_loaded_until = load_until;
+        }
+
+        info!("Getting next timed event time");
+        let next_timed_event_time = {
+            let timed_events = ingest.timed_events.lock().unwrap();
+            let next_timed_event = timed_events.peek_with_index();
+            if let Some((i, event)) = next_timed_event {
+                info!("Next timed event is at {} (index {i})", event.time());
+            } else {
+                info!("No timed events");
+            }
+            next_timed_event.map(|(_, event)| event.time())
+        };
 
         info!("Selecting source");
         let Some((source, time)) = [
-            Some((Source::Feed, next_fed_event_time)),
+            next_fed_event_time.map(|t| (Source::Feed, t)),
             next_timed_event_time.map(|t| (Source::Timed, t)),
-            Some((Source::Observation, next_observation_time))
+            next_observation_time.map(|t| (Source::Observation, t)),
         ].into_iter()
             .flatten() // Get rid of None options
+            // min_by_key keeps the first of equal elements, so on a tie the Feed goes first. Timed
+            // events that follow a Feed event at the same moment rely on this.
             .min_by_key(|(_, time)| *time) else {
-            todo!(); // should this ever happen?
+            // This is the only way a non-backfill ingest ends. The feed stream is live and doesn't
+            // currently run out, so it shouldn't happen in practice, but if every source is
+            // exhausted there's nothing left to wait for. Once the Feed has ended the other sources
+            // are still drained first.
+            info!("Ingest reached the end of all available data");
+            break;
         };
         info!("Selected {source:?}");
 
-        if time > latest_feed_update {
+        // An ended Feed has nothing more to catch up with
+        if let Some(latest_feed_update) = latest_feed_update && time > latest_feed_update {
             info!("Caught up with the Feed");
             continue;
         }
 
+        if let Some(backfill) = &mut backfill {
+            match backfill.step(time) {
+                BackfillStep::Continue => {}
+                BackfillStep::Checkpoint(backfilled_to) => {
+                    info!("Backfilled to {backfilled_to}");
+                    // A missed checkpoint only means a resumed backfill redoes a bit more, so it's
+                    // not worth stopping the backfill over
+                    if let Err(e) = ingest.run(move |mut state| state.set_backfill_progress(backfilled_to)).await {
+                        error!("Failed to save backfill progress at {backfilled_to}: {e}");
+                    }
+                    tokio::time::sleep(backfill.pause_between_chunks()).await;
+                }
+                BackfillStep::Done(until) => {
+                    info!("Backfill finished at {until}");
+                    if let Err(e) = ingest.run(move |mut state| state.set_backfill_progress(until)).await {
+                        error!("Failed to save backfill progress at {until}: {e}");
+                    }
+                    return;
+                }
+            }
+        }
+
+        if let Some(delay) = replay_delay(last_time, time, replay_speed) {
+            tokio::time::sleep(delay).await;
+        }
+        last_time = time;
+
+        if time >= next_prune {
+            let cutoff = time - Duration::minutes(PRUNE_KEEP_MINUTES);
+            let (pruned, removed) = {
+                let mut state = ingest.state.lock().unwrap();
+                (state.prune_before(cutoff), state.remove_finished_games(cutoff))
+            };
+            info!("Pruned {pruned} versions and set aside {removed} finished games from before {cutoff}");
+            next_prune = time + Duration::minutes(PRUNE_INTERVAL_MINUTES);
+
+            // Pruning is a convenient time for this too, since the graphs are as small as they get
+            // right after pruning
+            if ingest.save_snapshots {
+                save_snapshot(&ingest, time).await;
+            }
+        }
+
         let new_timed_events = match source {
             Source::Feed => {
-                let event = fed_events.next().await
-                    .expect("This stream should never terminate")
-                    .into_event()
-                    .expect("If we got here, the source should not be empty");
-                ingest_event(&mut ingest, event).await.unwrap()
+                // next_fed_event_time only returns a time when there's an event waiting
+                let Some(event) = fed_events.next().await.and_then(EventStreamItem::into_event) else {
+                    error!("The Feed stream lost an event between peeking and taking it");
+                    continue;
+                };
+                ingest_event_or_record_error(&mut ingest, event).await
             }
             Source::Timed => {
-                let event = timed_events.pop()
+                let event = ingest.timed_events.lock().unwrap().pop()
                     .expect("If we got here, the source should not be empty");
-                ingest_event(&mut ingest, event).await.unwrap()
+                ingest_event_or_record_error(&mut ingest, event).await
             }
             Source::Observation => {
                 let observation = observations.next()
-                    .expect("This stream should never terminate");
+                    .expect("If we got here, the source should not be empty");
+                if let Some(focus) = &ingest.focus && !focus.contains(&(observation.entity_type, observation.entity_id)) {
+                    continue;
+                }
                 let debug_history = ingest.debug_history.clone();
-                let mut debug_history = debug_history.lock().await;
-                ingest_observation(&mut ingest, observation, &mut debug_history)
+                let (obs_type, obs_id, perceived_at) = (observation.entity_type, observation.entity_id, observation.perceived_at);
+                // Kept in case it's rejected and then approved
+                let pending_observation = ingest.pause_on_rejected_observations.then(|| observation.clone());
+                let mut new_timed_events = {
+                    let mut debug_history = debug_history.lock().await;
+                    match ingest_observation(&mut ingest, observation, false, &mut debug_history) {
+                        Ok(events) => { events }
+                        Err(IngestError::Conflict { ty, id, perceived_at, conflicts }) => {
+                            error!("Rejected observation of {} {} at {}: {}", ty, id, perceived_at, conflicts);
+                            if ingest.pause_on_rejected_observations {
+                                ingest.queued_approvals.push(QueuedApproval {
+                                    entity_type: ty,
+                                    entity_id: id,
+                                    perceived_at,
+                                    message: conflicts.to_string(),
+                                    severity: conflicts.max_severity().unwrap_or(Severity::Error),
+                                    observation: pending_observation,
+                                });
+                            }
+                            Vec::new()
+                        }
+                        Err(e) => {
+                            error!("Failed to ingest observation of {obs_type} {obs_id} at {perceived_at}: {e}");
+                            record_ingest_error(&mut ingest, Some((obs_type, obs_id)), perceived_at, &e);
+                            Vec::new()
+                        }
+                    }
+                };
+                // The debug history stays unlocked while waiting for approvals. A human could take
+                // a long time, and the debug routes need it in the meantime.
+                for approval in std::mem::take(&mut ingest.queued_approvals) {
+                    info!("Requesting approval for {} {}", approval.entity_type, approval.entity_id);
+                    let approved = match ingest.get_approval(approval.entity_type, approval.entity_id,
+                                                             approval.perceived_at, approval.message, approval.severity).await {
+                        Ok(approved) => { approved }
+                        Err(e) => {
+                            error!("Failed to record the approval for {} {} at {}; treating it as rejected: {e}",
+                                approval.entity_type, approval.entity_id, approval.perceived_at);
+                            false
+                        }
+                    };
+                    if approved {
+                        if let Some(observation) = approval.observation {
+                            let mut debug_history = debug_history.lock().await;
+                            match ingest_observation(&mut ingest, observation, true, &mut debug_history) {
+                                Ok(events) => { new_timed_events.extend(events) }
+                                Err(e) => {
+                                    error!("Failed to ingest approved observation of {} {} at {}: {e}",
+                                        approval.entity_type, approval.entity_id, approval.perceived_at);
+                                    record_ingest_error(&mut ingest, Some((approval.entity_type, approval.entity_id)), approval.perceived_at, &e);
+                                }
+                            }
+                        }
+                        let result = ingest.run(move |mut state| {
+                            state.resolve_conflicts(approval.entity_type, approval.entity_id, approval.perceived_at)
+                        }).await;
+                        if let Err(e) = result {
+                            error!("Failed to mark the conflicts for {} {} at {} resolved: {e}",
+                                approval.entity_type, approval.entity_id, approval.perceived_at);
+                        }
+                    }
+                    info!("Approval for {} {} was {}", approval.entity_type, approval.entity_id,
+                        if approved { "accepted" } else { "rejected" });
+                }
+                new_timed_events
+            }
+        };
+
+        let conflicts = std::mem::take(&mut ingest.recorded_conflicts);
+        if !conflicts.is_empty() {
+            let num_conflicts = conflicts.len();
+            // Losing these only affects the conflict export, so it's not worth stopping the ingest
+            if let Err(e) = ingest.run(move |mut state| state.insert_conflicts(conflicts)).await {
+                error!("Failed to record {num_conflicts} conflicts: {e}");
+            }
+        }
+
+        ingest.timed_events.lock().unwrap().extend(new_timed_events);
+        ingest.latest_ingest_time.send_replace(time);
+    }
+}
+
+// Errors that stop an event from being applied are recorded and the event is skipped, the same as
+// for an event that fails its preconditions. One bad event shouldn't take down the whole ingest.
+async fn ingest_event_or_record_error(ingest: &mut Ingest, event: AnyEvent) -> Vec<AnyEvent> {
+    let description = event.to_string();
+    let time = event.time();
+    let target = event.targets().first().copied();
+    match ingest_event(ingest, event).await {
+        Ok(new_timed_events) => { new_timed_events }
+        Err(e) => {
+            error!("Failed to apply {description}; skipping it: {e}");
+            record_ingest_error(ingest, e.entity().or(target), time, &e);
+            Vec::new()
+        }
+    }
+}
+
+fn record_ingest_error(ingest: &mut Ingest, entity: Option<(EntityType, Uuid)>, time: DateTime<Utc>, e: &IngestError) {
+    // Conflicts have to belong to an entity, so errors that aren't about any are only logged
+    let Some((entity_type, entity_id)) = entity else { return };
+    ingest.recorded_conflicts.push(NewConflict {
+        ingest_id: ingest.ingest_id,
+        entity_type,
+        entity_id,
+        perceived_at: time,
+        property: String::new(),
+        message: e.to_string(),
+        severity: Severity::Error.level(),
+    });
+}
+
+// Drains the Feed stream up to its next event at or after `start_time`, and returns that event's
+// time along with the Feed's latest update time. Returns None for the event time if the stream
+// ended, and None for the update time too if it ended without yielding anything.
+async fn next_fed_event_time<S: Stream<Item=EventStreamItem>>(
+    mut fed_events: Pin<&mut Peekable<S>>,
+    start_time: DateTime<Utc>,
+    latest_ingest_time: &watch::Sender<DateTime<Utc>>,
+) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+    let mut latest_feed_update = None;
+    loop {
+        // Consume all the empty ingests from fed_events
+        let Some(next_item) = fed_events.as_mut().peek().await else {
+            return (None, latest_feed_update);
+        };
+        latest_feed_update = Some(next_item.last_update_time());
+        if let Some(event) = next_item.event() {
+            if event.time() >= start_time {
+                return (Some(event.time()), latest_feed_update);
             }
+            // The Feed stream always starts at the beginning of the era, so when resuming
+            // from a snapshot it first has to be drained of everything that's already applied
+            info!("Skipping event from before the start time");
+        } else {
+            // Empty items still mean the Feed has nothing new up to their update time, so
+            // progress is reported even while no events are coming in
+            latest_ingest_time.send_replace(next_item.last_update_time());
+            info!("Skipping empty event");
+        }
+        fed_events.as_mut().next().await;
+    }
+}
+
+// Nothing in a replay waits on a human. Approvals are skipped, because if one was given in the
+// original ingest the approved observation was logged again right after.
+pub async fn run_replay(mut ingest: Ingest, start_time: DateTime<Utc>, initial_state: Vec<Observation>, items: Vec<ReplayItem>) {
+    info!("Loading initial state from the event log...");
+    ingest.event_log.lock().unwrap().set_initial_state(initial_state.clone());
+    {
+        let mut history = ingest.debug_history.lock().await;
+        let mut state = ingest.state.lock().unwrap();
+        state.populate(initial_state, start_time, &mut *history);
+    }
+
+    let num_items = items.len();
+    for item in items {
+        let debug_history = ingest.debug_history.clone();
+        let mut history = debug_history.lock().await;
+        match item {
+            ReplayItem::Event(event) => {
+                let description = event.to_string();
+                let time = event.time();
+                let target = event.targets().first().copied();
+                let result = {
+                    let mut state = ingest.state.lock().unwrap();
+                    replay_event(&mut state, event, &ingest.event_log, &mut history)
+                };
+                if let Err(e) = result {
+                    error!("Failed to replay {description}; skipping it: {e}");
+                    record_ingest_error(&mut ingest, e.entity().or(target), time, &e);
+                }
+            }
+            ReplayItem::Observation { observation, approved } => {
+                let (obs_type, obs_id, perceived_at) = (observation.entity_type, observation.entity_id, observation.perceived_at);
+                if let Err(e) = ingest_observation(&mut ingest, observation, approved, &mut history) {
+                    error!("Failed to replay observation of {obs_type} {obs_id} at {perceived_at}: {e}");
+                    record_ingest_error(&mut ingest, Some((obs_type, obs_id)), perceived_at, &e);
+                }
+                ingest.queued_approvals.clear();
+            }
+        }
+    }
+    info!("Replayed {num_items} items");
+
+    let conflicts = std::mem::take(&mut ingest.recorded_conflicts);
+    if !conflicts.is_empty() {
+        if let Err(e) = ingest.run(move |mut state| state.insert_conflicts(conflicts)).await {
+            error!("Failed to record the replay's conflicts: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex as StdMutex};
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use futures::{pin_mut, stream, StreamExt};
+    use itertools::Itertools;
+    use tokio::sync::watch;
+    use uuid::Uuid;
+    use enum_flatten::EnumFlatten;
+    use fed::FedEventFlat;
+    use crate::entity::{test_game, test_player, test_team, Game, Team};
+    use crate::events::{FedEvent, GameOver};
+    use crate::ingest::chron::merge_by_time;
+    use crate::ingest::chron::tests::{game_observation, player_observation, team_observation};
+    use crate::ingest::fed::EventStreamItem;
+    use crate::db::tests::test_connection;
+    use crate::ingest::task::tests::test_ingest;
+    use crate::ingest::{BackfillConfig, EventLogEntry, EventLogExport, StateGraph};
+    use crate::state::EntityType;
+    use super::{next_fed_event_time, replay_delay, run_ingest_from, run_replay, timed_event_window_end, InitialState};
+
+    #[test]
+    fn timed_event_window_jumps_to_the_window_containing_the_time() {
+        let loaded_until = Utc.with_ymd_and_hms(2021, 3, 1, 0, 0, 0).unwrap();
+        let window = Duration::hours(6);
+
+        assert_eq!(timed_event_window_end(loaded_until, loaded_until, window), loaded_until + window);
+        assert_eq!(timed_event_window_end(loaded_until, loaded_until + Duration::hours(5), window), loaded_until + window);
+        // A time exactly on a boundary belongs to the window it starts
+        assert_eq!(timed_event_window_end(loaded_until, loaded_until + Duration::hours(6), window), loaded_until + Duration::hours(12));
+        assert_eq!(timed_event_window_end(loaded_until, loaded_until + Duration::days(30), window), loaded_until + Duration::days(30) + window);
+    }
+
+    #[test]
+    fn feed_stream_ending_is_not_an_error() {
+        let start_time = Utc.with_ymd_and_hms(2021, 3, 1, 0, 0, 0).unwrap();
+        let (sender, receiver) = watch::channel(start_time);
+        let game_id = Uuid::from_u128(1);
+
+        let fed_events = stream::iter(vec![
+            EventStreamItem::new(start_time, Some(GameOver::new(start_time - Duration::hours(1), game_id).into())),
+            EventStreamItem::new(start_time + Duration::minutes(1), None),
+            EventStreamItem::new(start_time + Duration::minutes(2), Some(GameOver::new(start_time + Duration::minutes(2), game_id).into())),
+        ]).peekable();
+        pin_mut!(fed_events);
+
+        futures::executor::block_on(async {
+            // Skips the event from before the start and the empty item, reporting the empty
+            // item's progress
+            let (next, latest) = next_fed_event_time(fed_events.as_mut(), start_time, &sender).await;
+            assert_eq!(next, Some(start_time + Duration::minutes(2)));
+            assert_eq!(latest, Some(start_time + Duration::minutes(2)));
+            assert_eq!(*receiver.borrow(), start_time + Duration::minutes(1));
+
+            fed_events.as_mut().next().await;
+            assert_eq!(next_fed_event_time(fed_events.as_mut(), start_time, &sender).await, (None, None));
+        });
+    }
+
+    #[rocket::async_test]
+    async fn ingest_ends_when_finite_observation_streams_run_out() {
+        let ingest = test_ingest().await;
+        let state = ingest.state.clone();
+        let start_time = Utc::now();
+        let players = (0..3).map(|_| test_player(None)).sorted_by_key(|player| player.id).collect_vec();
+        let observations = merge_by_time(vec![
+            vec![player_observation(&players[0], start_time)].into_iter().peekable(),
+            vec![
+                player_observation(&players[1], start_time + Duration::seconds(1)),
+                player_observation(&players[2], start_time + Duration::seconds(2)),
+            ].into_iter().peekable(),
+        ]);
+
+        let run = run_ingest_from(ingest, start_time, 0., None, InitialState::Observations(Vec::new()),
+                                  stream::empty(), observations);
+        tokio::time::timeout(std::time::Duration::from_secs(30), run).await
+            .expect("Ingest should end once every source has run out");
+
+        let ingested_ids = state.lock().unwrap().entity_ids_of_type(EntityType::Player)
+            .into_iter()
+            .sorted()
+            .collect_vec();
+        assert_eq!(ingested_ids, players.iter().map(|player| player.id).collect_vec());
+    }
+
+    #[rocket::async_test]
+    async fn backfill_saves_its_progress_and_stops_at_the_end() {
+        let ingest = test_ingest().await;
+        let ingest_id = ingest.ingest_id;
+        let state = ingest.state.clone();
+        let start_time = Utc::now() - Duration::hours(3);
+        let until = start_time + Duration::minutes(150);
+        let players = (0..4).map(|_| test_player(None)).collect_vec();
+        // One observation in each chunk, and one after the end that shouldn't be ingested
+        let observations = merge_by_time(vec![
+            players.iter().enumerate()
+                .map(|(i, player)| player_observation(player, start_time + Duration::minutes(50 * i as i64)))
+                .collect_vec()
+                .into_iter()
+                .peekable(),
+        ]);
+        let mut backfill = BackfillConfig::new(until).with_chunk_size(Duration::hours(1));
+        backfill.pause_between_chunks = std::time::Duration::ZERO;
+
+        let run = run_ingest_from(ingest, start_time, 0., Some(backfill), InitialState::Observations(Vec::new()),
+                                  stream::empty(), observations);
+        tokio::time::timeout(std::time::Duration::from_secs(30), run).await
+            .expect("Backfill should end at the end of its range");
+
+        let ingested_ids = state.lock().unwrap().entity_ids_of_type(EntityType::Player);
+        assert_eq!(ingested_ids, players[..3].iter().map(|player| player.id).collect_vec());
+        let progress = {
+            use diesel::{QueryDsl, RunQueryDsl};
+            use crate::schema::ingests::dsl::*;
+            ingests.find(ingest_id).select(backfilled_to).first::<Option<DateTime<Utc>>>(&mut test_connection())
+                .expect("Test ingest should still exist")
         };
+        assert_eq!(progress, Some(until));
+    }
+
+    #[rocket::async_test]
+    async fn focused_ingest_skips_other_entities() {
+        let mut ingest = test_ingest().await;
+        let state = ingest.state.clone();
+        let start_time = Utc::now();
+        let (focused, other) = (test_player(None), test_player(None));
+        ingest.focus = Some([(EntityType::Player, focused.id)].into_iter().collect());
+        let observations = merge_by_time(vec![
+            vec![
+                player_observation(&other, start_time),
+                player_observation(&focused, start_time + Duration::seconds(1)),
+            ].into_iter().peekable(),
+        ]);
+
+        let run = run_ingest_from(ingest, start_time, 0., None, InitialState::Observations(Vec::new()),
+                                  stream::empty(), observations);
+        tokio::time::timeout(std::time::Duration::from_secs(30), run).await
+            .expect("Ingest should end once every source has run out");
+
+        assert_eq!(state.lock().unwrap().entity_ids_of_type(EntityType::Player), vec![focused.id]);
+    }
+
+    // Every player's leaf versions, as JSON so they can be compared
+    fn player_leafs(state: &StateGraph) -> Vec<(Uuid, Vec<String>)> {
+        state.entity_ids_of_type(EntityType::Player).into_iter()
+            .map(|id| (id, state.query_player_all(id, |player| serde_json::to_string(player).unwrap())))
+            .collect()
+    }
+
+    #[rocket::async_test]
+    async fn replaying_an_exported_event_log_reproduces_the_state() {
+        let ingest = test_ingest().await;
+        let (state, event_log) = (ingest.state.clone(), ingest.event_log.clone());
+        let start_time = Utc::now();
+        let players = (0..3).map(|_| test_player(None)).collect_vec();
+        let observations = merge_by_time(vec![
+            vec![
+                player_observation(&players[1], start_time + Duration::seconds(1)),
+                player_observation(&players[2], start_time + Duration::seconds(2)),
+                player_observation(&players[0], start_time + Duration::seconds(3)),
+            ].into_iter().peekable(),
+        ]);
+
+        let run = run_ingest_from(ingest, start_time, 0., None,
+                                  InitialState::Observations(vec![player_observation(&players[0], start_time)]),
+                                  stream::empty(), observations);
+        tokio::time::timeout(std::time::Duration::from_secs(30), run).await
+            .expect("Ingest should end once every source has run out");
+
+        // Go through JSON the same way the debug routes do
+        let json = serde_json::to_string(&event_log.lock().unwrap().export(start_time)).unwrap();
+        let export: EventLogExport = serde_json::from_str(&json).unwrap();
+        assert!(!export.truncated);
+        let logged_observations = export.entries.iter()
+            .filter(|entry| matches!(entry, EventLogEntry::Observation { .. }))
+            .count();
+        assert_eq!(logged_observations, 3);
+        let items = export.entries.into_iter()
+            .map(|entry| entry.into_replay_item().expect("Logged entries should parse"))
+            .collect_vec();
+
+        let replay = test_ingest().await;
+        let replayed_state = replay.state.clone();
+        let initial_state = export.initial_state.expect("The ingest should have logged its initial state");
+        run_replay(replay, export.start_time, initial_state, items).await;
+
+        let original = player_leafs(&state.lock().unwrap());
+        assert_eq!(original.len(), 3);
+        assert_eq!(player_leafs(&replayed_state.lock().unwrap()), original);
+    }
+
+    // A game and its teams, and the first game end in the expansion era with its ids changed to match
+    fn game_end_setup() -> (fed::FedEvent, Game, Team, Team) {
+        let game_end = fed::expansion_era_events()
+            .map(|event| event.expect("Expansion era events should parse"))
+            .find(|event| matches!(EnumFlatten::flatten(event.clone()), FedEventFlat::GameEnd(_)))
+            .expect("The expansion era should have a game end");
+        let FedEventFlat::GameEnd(flat) = EnumFlatten::flatten(game_end.clone()) else {
+            panic!("Should have found a GameEnd");
+        };
+        let mut game = test_game();
+        game.id = flat.game.game_id;
+        let (mut winner, mut loser) = (test_team(Vec::new(), Vec::new()), test_team(Vec::new(), Vec::new()));
+        winner.id = flat.winner_id;
+        loser.id = flat.loser_id;
+        (game_end, game, winner, loser)
+    }
+
+    // Runs an ingest that starts a minute before the game end, with the game end as the only event
+    // from the Feed
+    async fn run_game_end_ingest(game_end: &fed::FedEvent, game: &Game, winner: &Team, loser: &Team) -> Arc<StdMutex<StateGraph>> {
+        let ingest = test_ingest().await;
+        let state = ingest.state.clone();
+        let end_time = game_end.created;
+        let start_time = end_time - Duration::minutes(1);
+        let initial_state = vec![
+            game_observation(game, start_time),
+            team_observation(winner, start_time),
+            team_observation(loser, start_time),
+        ];
+        let fed_events = stream::iter(vec![
+            EventStreamItem::new(end_time, Some(FedEvent::new(game_end.clone()).into())),
+        ]);
+
+        let run = run_ingest_from(ingest, start_time, 0., None, InitialState::Observations(initial_state),
+                                  fed_events, std::iter::empty());
+        tokio::time::timeout(std::time::Duration::from_secs(30), run).await
+            .expect("Ingest should end once every source has run out");
+        state
+    }
+
+    #[rocket::async_test]
+    async fn game_end_in_the_feed_finalizes_the_game() {
+        let (game_end, game, winner, loser) = game_end_setup();
+        // Only the game end comes from the Feed. The rest of the end phases have to come from the
+        // timed events it schedules.
+        let state = run_game_end_ingest(&game_end, &game, &winner, &loser).await;
+
+        let end_states = state.lock().unwrap().query_game_all(game.id, |game| (game.end_phase, game.finalized));
+        assert_eq!(end_states, vec![(Some(5), Some(true))]);
+    }
+
+    // The whole state as JSON. Entity types are kept in a HashMap, so the graphs are sorted to make
+    // the order the same every time.
+    fn sorted_snapshot_json(state: &StateGraph) -> serde_json::Value {
+        let mut json = serde_json::to_value(state.snapshot()).unwrap();
+        json["graphs"].as_array_mut()
+            .expect("Snapshot should have a list of graphs")
+            .sort_by_key(|graph| (graph["entity_type"].to_string(), graph["entity_id"].to_string()));
+        json
+    }
+
+    #[rocket::async_test]
+    async fn identical_ingests_make_identical_graphs() {
+        let (game_end, game, winner, loser) = game_end_setup();
+        let first = run_game_end_ingest(&game_end, &game, &winner, &loser).await;
+        let second = run_game_end_ingest(&game_end, &game, &winner, &loser).await;
+
+        let first = sorted_snapshot_json(&first.lock().unwrap());
+        assert_eq!(first["graphs"].as_array().map(Vec::len), Some(3));
+        assert_eq!(sorted_snapshot_json(&second.lock().unwrap()), first);
+    }
+
+    #[test]
+    fn replay_delays_are_proportional_to_the_gap() {
+        let time = Utc.with_ymd_and_hms(2021, 3, 1, 0, 0, 0).unwrap();
+        let ten_seconds_later = time + Duration::seconds(10);
+
+        assert_eq!(replay_delay(time, ten_seconds_later, 1.), Some(std::time::Duration::from_secs(10)));
+        assert_eq!(replay_delay(time, ten_seconds_later, 2.), Some(std::time::Duration::from_secs(5)));
+        assert_eq!(replay_delay(time, time + Duration::seconds(20), 2.), Some(std::time::Duration::from_secs(10)));
+        assert_eq!(replay_delay(time, ten_seconds_later, 0.5), Some(std::time::Duration::from_secs(20)));
+    }
 
-        timed_events.extend(new_timed_events);
+    #[test]
+    fn zero_replay_speed_never_waits() {
+        let time = Utc.with_ymd_and_hms(2021, 3, 1, 0, 0, 0).unwrap();
+        assert_eq!(replay_delay(time, time + Duration::hours(1), 0.), None);
+        // Neither does an item from before the last one
+        assert_eq!(replay_delay(time, time - Duration::seconds(1), 1.), None);
     }
 }
@@ -1,6 +1,6 @@
 use chrono::{DateTime, Duration, Utc};
 use std::cmp::Ordering;
-use thiserror::Error;
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
 use crate::api::ChroniclerItem;
@@ -8,7 +8,7 @@ use crate::entity::{AnyEntityRaw, EntityParseError};
 use crate::state::EntityType;
 
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Observation {
     // TODO Reorganize so this isn't pub
     pub perceived_at: DateTime<Utc>,
@@ -58,6 +58,12 @@ impl Observation {
             EntityType::Season => {
                 self.perceived_at - Duration::minutes(1)
             }
+            EntityType::Stadium => {
+                self.perceived_at - Duration::minutes(1)
+            }
+            EntityType::League | EntityType::Subleague | EntityType::Division => {
+                self.perceived_at - Duration::minutes(1)
+            }
         }
     }
 
@@ -80,6 +86,8 @@ impl Observation {
             }
             EntityType::Standings => { self.perceived_at + Duration::minutes(1) }
             EntityType::Season => { self.perceived_at + Duration::minutes(1) }
+            EntityType::Stadium => { self.perceived_at + Duration::minutes(1) }
+            EntityType::League | EntityType::Subleague | EntityType::Division => { self.perceived_at + Duration::minutes(1) }
         }
     }
 
@@ -217,12 +225,33 @@ impl Ord for Observation {
     }
 }
 
-#[derive(Debug, Error)]
-enum IngestError {
-    // #[error("Needs approval: {0:?}")]
-    // NeedsApproval(Vec<(i32, String)>),
 
-    #[error(transparent)]
-    DieselError(#[from] diesel::result::Error),
-}
 
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+    use crate::api::ChroniclerItem;
+    use crate::entity::{test_stadium_json, AnyEntity, Entity};
+    use crate::state::EntityType;
+    use super::Observation;
+
+    #[test]
+    fn stadium_observations_are_parsed() {
+        let data = test_stadium_json();
+        let entity_id: Uuid = serde_json::from_value(data["id"].clone())
+            .expect("Test stadium should have an id");
+        let item = ChroniclerItem { entity_id, valid_from: Utc::now(), valid_to: None, data };
+
+        let observation = Observation::from_chron("stadium", item)
+            .expect("Stadium observation should parse");
+        assert_eq!(observation.entity_type, EntityType::Stadium);
+        assert_eq!(observation.entity_id, entity_id);
+
+        let entity = AnyEntity::from_raw(observation.entity_raw);
+        assert_eq!(entity.entity_type(), EntityType::Stadium);
+        let stadium = entity.as_stadium().expect("Stadium observation should make a stadium");
+        assert_eq!(stadium.name, "Test Stadium");
+        assert_eq!(stadium.reno_hand, vec!["light_switch_toggle".to_string()]);
+    }
+}
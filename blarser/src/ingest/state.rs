@@ -1,24 +1,29 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::Debug;
 use std::iter;
 use std::sync::Arc;
 use chrono::{DateTime, Utc};
 use itertools::Itertools;
+use partial_information::{Conflict, PartialInformationCompare};
 use daggy::stable_dag::{StableDag, NodeIndex, EdgeIndex};
 use petgraph::visit::Walker;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 use uuid::Uuid;
 
-use crate::entity::{self, AnyEntity, Entity};
-use crate::events::{AnyEvent, Start, AnyEffect, EffectVariant, AnyEffectVariant, with_effect_variant};
+use crate::entity::{self, with_entity, AnyEntity, Entity};
+use crate::events::{AnyEvent, Start, AnyEffect, EffectVariant, AnyEffectVariant, with_effect_variant, pending_game_end_events};
 use crate::ingest::{GraphDebugHistory, Observation};
+use crate::ingest::error::{IngestError, IngestResult};
 use crate::ingest::task::{DebugHistoryItem, DebugHistoryVersion, DebugTree, DebugTreeNode};
 use crate::state::EntityType;
 
-#[derive(Debug, Copy, Clone, Serialize)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum AddedReason {
     Start,
     NewFromEvent,
+    // An entity that no event said was coming, so its first observation is all there is to go on
+    Observed,
     RefinedFromObservation,
     DescendantOfObservedNode,
 }
@@ -50,11 +55,128 @@ impl StateGraphNode {
 
 pub type StateGraphEdge = AnyEffectVariant;
 
+#[derive(Debug, Clone, Serialize, Error)]
+pub enum GraphInvariantViolation {
+    #[error("Node {0:?} is listed as a root or leaf but isn't in the graph")]
+    MissingNode(NodeIndex),
+
+    #[error("Node {0:?} has no parents but isn't a root")]
+    Orphan(NodeIndex),
+
+    #[error("Root {0:?} has parents")]
+    RootHasParents(NodeIndex),
+
+    #[error("Leaf {0:?} has children")]
+    LeafHasChildren(NodeIndex),
+
+    #[error("Applying the edge from {parent:?} to {child:?} doesn't produce the child. Differing fields: {}", fields.join(", "))]
+    EdgeMismatch {
+        parent: NodeIndex,
+        child: NodeIndex,
+        fields: Vec<String>,
+    },
+
+    #[error("Edge from {parent:?} to {child:?} has an effect for the wrong entity type")]
+    EdgeWrongEntityType {
+        parent: NodeIndex,
+        child: NodeIndex,
+    },
+
+    #[error("Node {node:?} was observed as {observed_type} {observed_id}, which is a different entity")]
+    ObservationWrongEntity {
+        node: NodeIndex,
+        observed_type: EntityType,
+        observed_id: Uuid,
+    },
+
+    #[error("Node {node:?} is valid from {valid_from}, after its observation at {perceived_at}")]
+    ObservationBeforeNode {
+        node: NodeIndex,
+        valid_from: DateTime<Utc>,
+        perceived_at: DateTime<Utc>,
+    },
+
+    // Conflicts approved with ApprovalPolicy::KeepExpected also show up as this, so it isn't always
+    // a bug, but it's always worth a look
+    #[error("Node {node:?} disagrees with its own observation. Conflicting fields: {}", fields.join(", "))]
+    ObservationConflicts {
+        node: NodeIndex,
+        fields: Vec<String>,
+    },
+}
+
+// Top-level fields whose serialized values differ between two versions of the same entity, so a
+// violation can say what went wrong instead of just that something did
+fn differing_fields(a: &AnyEntity, b: &AnyEntity) -> Vec<String> {
+    let a = with_entity!(a, |a| { serde_json::to_value(a) });
+    let b = with_entity!(b, |b| { serde_json::to_value(b) });
+    match (a, b) {
+        (Ok(serde_json::Value::Object(a)), Ok(serde_json::Value::Object(b))) => {
+            a.keys().chain(b.keys())
+                .unique()
+                .filter(|&key| a.get(key) != b.get(key))
+                .cloned()
+                .sorted()
+                .collect()
+        }
+        _ => { Vec::new() }
+    }
+}
+
+// Snapshots keep each graph whole, edges included, so a restored graph is indistinguishable from
+// the one that was saved. Node indices are stored as plain numbers and remapped on restore.
+#[derive(Serialize, Deserialize)]
+pub struct StateSnapshot {
+    graphs: Vec<EntitySnapshot>,
+    removed_graphs: Vec<EntitySnapshot>,
+    expected_entities: Vec<(EntityType, Uuid)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EntitySnapshot {
+    entity_type: EntityType,
+    entity_id: Uuid,
+    nodes: Vec<(usize, NodeSnapshot)>,
+    edges: Vec<EdgeSnapshot>,
+    roots: Vec<usize>,
+    leafs: Vec<usize>,
+    latest_observed: Option<DateTime<Utc>>,
+    latest_ingested_observation: Option<Observation>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct NodeSnapshot {
+    entity: AnyEntity,
+    valid_from: DateTime<Utc>,
+    observed: Option<Observation>,
+    added_reason: AddedReason,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EdgeSnapshot {
+    parent: usize,
+    child: usize,
+    effect: AnyEffectVariant,
+}
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("Snapshot of {0} {1} refers to node {2}, which it doesn't contain")]
+    MissingNode(EntityType, Uuid, usize),
+
+    #[error("Snapshot of {0} {1} contains a cycle")]
+    Cycle(EntityType, Uuid),
+}
+
 #[derive(Default, Clone)]
 pub struct EntityStateGraph {
     pub(crate) graph: StableDag<StateGraphNode, StateGraphEdge>,
     roots: Vec<NodeIndex>,
     leafs: Vec<NodeIndex>,
+    // Used to catch observations that arrive out of order
+    latest_observed: Option<DateTime<Utc>>,
+    // Used to skip observations that are identical to the last one. See DedupWindows.
+    latest_ingested_observation: Option<Arc<Observation>>,
 }
 
 impl EntityStateGraph {
@@ -63,6 +185,8 @@ impl EntityStateGraph {
             graph: StableDag::new(),
             roots: Vec::new(),
             leafs: Vec::new(),
+            latest_observed: first_node.observed.as_ref().map(|obs| obs.perceived_at),
+            latest_ingested_observation: first_node.observed.clone(),
         };
 
         let idx = s.graph.add_node(first_node);
@@ -72,6 +196,85 @@ impl EntityStateGraph {
         s
     }
 
+    fn snapshot(&self, entity_type: EntityType, entity_id: Uuid) -> EntitySnapshot {
+        let graph = self.graph.graph();
+        EntitySnapshot {
+            entity_type,
+            entity_id,
+            nodes: graph.node_indices()
+                .map(|idx| {
+                    let node = graph.node_weight(idx)
+                        .expect("Index from node_indices should always be valid");
+                    (idx.index(), NodeSnapshot {
+                        entity: node.entity.clone(),
+                        valid_from: node.valid_from,
+                        observed: node.observed.as_deref().cloned(),
+                        added_reason: node.added_reason,
+                    })
+                })
+                .collect(),
+            edges: graph.edge_indices()
+                .map(|edge_idx| {
+                    let (parent, child) = graph.edge_endpoints(edge_idx)
+                        .expect("Index from edge_indices should always be valid");
+                    let effect = graph.edge_weight(edge_idx)
+                        .expect("Index from edge_indices should always be valid");
+                    EdgeSnapshot { parent: parent.index(), child: child.index(), effect: effect.clone() }
+                })
+                .collect(),
+            roots: self.roots.iter().map(|idx| idx.index()).collect(),
+            leafs: self.leafs.iter().map(|idx| idx.index()).collect(),
+            latest_observed: self.latest_observed,
+            latest_ingested_observation: self.latest_ingested_observation.as_deref().cloned(),
+        }
+    }
+
+    // Indices aren't guaranteed to come back the same (the saved graph may have holes from
+    // pruning), so everything that refers to a node goes through the old -> new mapping
+    fn from_snapshot(snapshot: EntitySnapshot) -> Result<Self, SnapshotError> {
+        let mut s = Self {
+            latest_observed: snapshot.latest_observed,
+            latest_ingested_observation: snapshot.latest_ingested_observation.map(Arc::new),
+            ..Default::default()
+        };
+
+        let mut new_indices = HashMap::new();
+        for (old_idx, node) in snapshot.nodes {
+            let idx = s.graph.add_node(StateGraphNode {
+                entity: node.entity,
+                valid_from: node.valid_from,
+                observed: node.observed.map(Arc::new),
+                added_reason: node.added_reason,
+            });
+            new_indices.insert(old_idx, idx);
+        }
+
+        let lookup = |old_idx: usize| new_indices.get(&old_idx).copied()
+            .ok_or(SnapshotError::MissingNode(snapshot.entity_type, snapshot.entity_id, old_idx));
+        for edge in snapshot.edges {
+            let (parent, child) = (lookup(edge.parent)?, lookup(edge.child)?);
+            s.graph.add_edge(parent, child, edge.effect)
+                .map_err(|_| SnapshotError::Cycle(snapshot.entity_type, snapshot.entity_id))?;
+        }
+        s.roots = snapshot.roots.into_iter().map(&lookup).collect::<Result<_, _>>()?;
+        s.leafs = snapshot.leafs.into_iter().map(&lookup).collect::<Result<_, _>>()?;
+
+        Ok(s)
+    }
+
+    pub fn latest_observed(&self) -> Option<DateTime<Utc>> {
+        self.latest_observed
+    }
+
+    pub fn latest_ingested_observation(&self) -> Option<&Observation> {
+        self.latest_ingested_observation.as_deref()
+    }
+
+    pub fn record_observed(&mut self, obs: Arc<Observation>) {
+        self.latest_observed = self.latest_observed.max(Some(obs.perceived_at));
+        self.latest_ingested_observation = Some(obs);
+    }
+
     pub fn leafs(&self) -> &Vec<NodeIndex> {
         &self.leafs
     }
@@ -85,7 +288,55 @@ impl EntityStateGraph {
     }
 
     pub fn add_root(&mut self, idx: NodeIndex) {
-        self.roots.push(idx)
+        if !self.roots.contains(&idx) {
+            self.roots.push(idx)
+        }
+    }
+
+    // Whether a game's score in this version came from Feed events, as opposed to an observation.
+    // That's the case if some unobserved version between this one and the observations before it
+    // has a different score than its parent. Always false for anything that isn't a game.
+    pub fn score_from_feed(&self, idx: NodeIndex) -> bool {
+        fn score(node: &StateGraphNode) -> Option<(Option<f32>, Option<f32>)> {
+            match &node.entity {
+                AnyEntity::Game(game) => { Some((game.home.score, game.away.score)) }
+                _ => { None }
+            }
+        }
+
+        let mut stack = vec![idx];
+        let mut visited = HashSet::new();
+        while let Some(idx) = stack.pop() {
+            if !visited.insert(idx) { continue; }
+            let Some(node) = self.get_version(idx) else { continue };
+            if node.observed.is_some() { continue; }
+            let Some(node_score) = score(node) else { return false };
+
+            let mut parent_walker = self.graph.parents(idx);
+            while let Some((_, parent_idx)) = parent_walker.walk_next(&self.graph) {
+                let parent_score = self.get_version(parent_idx).and_then(score);
+                if parent_score != Some(node_score) { return true; }
+                stack.push(parent_idx);
+            }
+        }
+        false
+    }
+
+    // What made this version, for messages: the effect on the edge into it, or how it was added if
+    // it has no parents. Every parent edge comes from the same event, so any one of them will do.
+    pub fn created_by(&self, idx: NodeIndex) -> String {
+        match self.graph.parents(idx).walk_next(&self.graph) {
+            Some((edge_idx, _)) => {
+                self.graph.edge_weight(edge_idx)
+                    .expect("Graph gave me an invalid index")
+                    .to_string()
+            }
+            None => {
+                let node = self.get_version(idx)
+                    .expect("Index passed to created_by should be valid");
+                format!("{:?}", node.added_reason)
+            }
+        }
     }
 
     pub fn get_version(&self, idx: NodeIndex) -> Option<&StateGraphNode> {
@@ -150,18 +401,94 @@ impl EntityStateGraph {
         self.graph.remove_edge(idx)
     }
 
+    // Also forgets the node as a root, so the roots never point at nodes that aren't there
     pub fn remove_node(&mut self, idx: NodeIndex) -> Option<StateGraphNode> {
+        self.roots.retain(|&root| root != idx);
         self.graph.remove_node(idx)
     }
 
-    pub fn get_candidate_placements(&self, earliest: DateTime<Utc>, latest: DateTime<Utc>) -> HashSet<NodeIndex> {
+    // Drops the history before the last point where every branch of the graph went through one
+    // unambiguous, observed node that started before `cutoff`. That node becomes the only root.
+    // Only observed nodes are cut at, because an unobserved root would be taken as fact by
+    // everything that walks back to the roots. Branches that haven't merged back together are kept
+    // whole, because an observation might still pick between them. Returns the number of nodes
+    // removed.
+    pub fn prune_before(&mut self, cutoff: DateTime<Utc>) -> usize {
+        let [root] = self.roots[..] else { return 0 };
+        let mut chain = vec![root];
+        let mut new_root_pos = 0;
+        loop {
+            let last = *chain.last().expect("The chain always starts with the root");
+            let Ok((_, child_idx)) = self.graph.children(last).iter(&self.graph).exactly_one() else { break };
+            if self.graph.parents(child_idx).iter(&self.graph).exactly_one().is_err() { break }
+            let child = self.get_version(child_idx)
+                .expect("Graph gave me an invalid index");
+            if child.valid_from > cutoff || child.entity.is_ambiguous() { break }
+
+            chain.push(child_idx);
+            if child.observed.is_some() {
+                new_root_pos = chain.len() - 1;
+            }
+        }
+
+        for &idx in &chain[..new_root_pos] {
+            self.graph.remove_node(idx);
+        }
+        self.roots = vec![chain[new_root_pos]];
+
+        new_root_pos
+    }
+
+    // Timed events implied by the state of a node rather than by an event. Normally the event that
+    // caused the state already scheduled them, so this is only needed when an observation reveals
+    // the state.
+    pub fn timed_events_from(&self, idx: NodeIndex) -> Vec<AnyEvent> {
+        let Some(node) = self.get_version(idx) else { return Vec::new() };
+        match &node.entity {
+            AnyEntity::Game(game) => {
+                let since = self.game_end_phase_since(idx, game.end_phase);
+                pending_game_end_events(game, since)
+            }
+            _ => { Vec::new() }
+        }
+    }
+
+    // When the game got into `end_phase`, found by walking back through the versions that were
+    // already in it. Parents of the same node are different routes to the same state, so any one
+    // of them will do.
+    fn game_end_phase_since(&self, mut idx: NodeIndex, end_phase: Option<i32>) -> DateTime<Utc> {
+        loop {
+            let node = self.get_version(idx)
+                .expect("Index passed to game_end_phase_since should be valid");
+            let parent = self.graph.parents(idx).walk_next(&self.graph)
+                .map(|(_, parent_idx)| parent_idx)
+                .filter(|&parent_idx| {
+                    matches!(self.get_version(parent_idx).map(|parent| &parent.entity),
+                        Some(AnyEntity::Game(parent_game)) if parent_game.end_phase == end_phase)
+                });
+            match parent {
+                Some(parent_idx) => { idx = parent_idx }
+                None => { return node.valid_from }
+            }
+        }
+    }
+
+    // Every version whose validity interval overlaps [earliest, latest]. A version is valid from its
+    // valid_from until its children's valid_from, or forever if it's a leaf. Both ends are
+    // inclusive, so a version that ends exactly at `earliest` or starts exactly at `latest` is a
+    // candidate: the observation could have been taken on either side of the change.
+    //
+    // The result is ordered by node index so that ingest tries the placements in the same order
+    // every time.
+    pub fn get_candidate_placements(&self, earliest: DateTime<Utc>, latest: DateTime<Utc>) -> BTreeSet<NodeIndex> {
         // I couldn't figure out how to do what I wanted with the built-in graph traversal helpers
         // so I made my own traversal
         let mut stack = self.leafs.clone();
         let mut visited = HashSet::new();
-        let mut outputs = HashSet::new();
+        let mut outputs = BTreeSet::new();
         while let Some(node_idx) = stack.pop() {
-            visited.insert(node_idx);
+            // A node can be pushed more than once before it's visited when branches merge
+            if !visited.insert(node_idx) { continue; }
             // Get time span of this node
             let node = self.graph.node_weight(node_idx)
                 .expect("Stack contained a node that was not in the graph");
@@ -193,30 +520,164 @@ impl EntityStateGraph {
         outputs
     }
 
-    pub fn apply_effect(&mut self, effect: &AnyEffect, event_time: DateTime<Utc>) {
-        let new_leafs = self.leafs.clone().into_iter()
-            .map(|entity_idx| {
-                self.apply_effect_to_entity(effect.variant(), entity_idx, event_time)
+    // Either every leaf gets the effect or none of them do. On success, returns the leafs from before
+    // the effect, which undo_effect needs.
+    pub fn apply_effect(&mut self, effect: &AnyEffect, event_time: DateTime<Utc>) -> IngestResult<Vec<NodeIndex>> {
+        let variants = effect.variants();
+        // Applying no variants would leave the graph with no leafs at all, which it can't recover from
+        if variants.is_empty() {
+            if let Some(leaf) = self.leafs.first().and_then(|&idx| self.get_version(idx)) {
+                return Err(IngestError::NoOutcomes {
+                    effect: effect.to_string(),
+                    ty: leaf.entity.entity_type(),
+                    id: leaf.entity.id(),
+                });
+            }
+        }
+
+        // Every new version is worked out before any of them are added, so an effect that fails on
+        // one leaf doesn't leave children behind on the others
+        let new_versions = self.leafs.iter()
+            .flat_map(|&entity_idx| variants.iter().map(move |variant| (entity_idx, variant)))
+            .map(|(entity_idx, variant)| {
+                let new_entity = self.effect_result(effect, variant, entity_idx)?;
+                Ok((entity_idx, variant.clone(), new_entity))
+            })
+            .collect::<IngestResult<Vec<_>>>()?;
+
+        let new_leafs = new_versions.into_iter()
+            .map(|(entity_idx, variant, new_entity)| {
+                self.add_child_version(entity_idx, new_entity, event_time, variant, AddedReason::NewFromEvent)
             })
             .collect();
+        Ok(std::mem::replace(&mut self.leafs, new_leafs))
+    }
 
-        self.leafs = new_leafs;
+    // Takes back the most recent apply_effect, given the leafs it returned
+    pub fn undo_effect(&mut self, previous_leafs: Vec<NodeIndex>) {
+        for idx in std::mem::replace(&mut self.leafs, previous_leafs) {
+            self.graph.remove_node(idx);
+        }
     }
 
-    fn apply_effect_to_entity(&mut self, effect: AnyEffectVariant, entity_idx: NodeIndex, event_time: DateTime<Utc>) -> NodeIndex {
-        let entity_node = &self.get_version(entity_idx)
+    fn effect_result(&self, effect: &AnyEffect, variant: &AnyEffectVariant, entity_idx: NodeIndex) -> IngestResult<AnyEntity> {
+        let entity_node = self.get_version(entity_idx)
             .expect("Indices in State.leafs should always be valid");
+        let (ty, id) = (entity_node.entity.entity_type(), entity_node.entity.id());
 
-
-        let new_entity = with_effect_variant!(&effect, |effect: EffectT| {
-            let entity: &<EffectT as EffectVariant>::EntityType = (&entity_node.entity).try_into()
-                .expect("Tried to apply effect to the wrong entity");
+        let new_entity: AnyEntity = with_effect_variant!(variant, |variant: EffectT| {
+            let entity: Result<&<EffectT as EffectVariant>::EntityType, _> = (&entity_node.entity).try_into();
+            // An effect for a different type of entity would turn this one into that type
+            let Ok(entity) = entity else {
+                return Err(IngestError::EffectChangedIdentity {
+                    effect: effect.to_string(),
+                    ty,
+                    id,
+                    new_ty: effect.entity_type(),
+                    new_id: id,
+                });
+            };
             let mut new_entity = entity.clone();
-            effect.forward(&mut new_entity);
+            variant.forward(&mut new_entity);
             new_entity.into()
         });
 
-        self.add_child_version(entity_idx, new_entity, event_time, effect, AddedReason::NewFromEvent)
+        check_identity_unchanged(effect, &entity_node.entity, &new_entity)?;
+        Ok(new_entity)
+    }
+
+    // Checks the structural invariants the rest of the code relies on. This is slow, so it's only
+    // run on demand from the debug routes.
+    pub fn validate(&self) -> Vec<GraphInvariantViolation> {
+        let mut violations = Vec::new();
+        let graph = self.graph.graph();
+
+        for &idx in self.roots.iter().chain(&self.leafs) {
+            if graph.node_weight(idx).is_none() {
+                violations.push(GraphInvariantViolation::MissingNode(idx));
+            }
+        }
+
+        for idx in graph.node_indices() {
+            let has_parents = self.graph.parents(idx).walk_next(&self.graph).is_some();
+            let is_root = self.roots.contains(&idx);
+            if is_root && has_parents {
+                violations.push(GraphInvariantViolation::RootHasParents(idx));
+            } else if !is_root && !has_parents {
+                violations.push(GraphInvariantViolation::Orphan(idx));
+            }
+
+            if self.leafs.contains(&idx) && self.graph.children(idx).walk_next(&self.graph).is_some() {
+                violations.push(GraphInvariantViolation::LeafHasChildren(idx));
+            }
+
+            let node = graph.node_weight(idx)
+                .expect("Index from node_indices should always be valid");
+            if let Some(obs) = &node.observed {
+                if obs.entity_type != node.entity.entity_type() || obs.entity_id != node.entity.id() {
+                    violations.push(GraphInvariantViolation::ObservationWrongEntity {
+                        node: idx,
+                        observed_type: obs.entity_type,
+                        observed_id: obs.entity_id,
+                    });
+                }
+                if node.valid_from > obs.perceived_at {
+                    violations.push(GraphInvariantViolation::ObservationBeforeNode {
+                        node: idx,
+                        valid_from: node.valid_from,
+                        perceived_at: obs.perceived_at,
+                    });
+                }
+
+                // A wrong-typed observation was already reported above
+                let conflicts: Vec<Conflict> = with_entity!(&node.entity, |entity: EntityT| {
+                    let raw: Option<&<EntityT as PartialInformationCompare>::Raw> = (&obs.entity_raw).try_into().ok();
+                    raw.map(|raw| entity.clone().observe(raw)).unwrap_or_default()
+                });
+                if !conflicts.is_empty() {
+                    violations.push(GraphInvariantViolation::ObservationConflicts {
+                        node: idx,
+                        fields: conflicts.iter().map(|conflict| conflict.property()).unique().collect(),
+                    });
+                }
+            }
+        }
+
+        for edge_idx in graph.edge_indices() {
+            let (parent, child) = graph.edge_endpoints(edge_idx)
+                .expect("Index from edge_indices should always be valid");
+            let effect = graph.edge_weight(edge_idx)
+                .expect("Index from edge_indices should always be valid");
+            let parent_node = graph.node_weight(parent)
+                .expect("Edge endpoints should always be valid");
+            let child_node = graph.node_weight(child)
+                .expect("Edge endpoints should always be valid");
+
+            let forwarded: Option<AnyEntity> = with_effect_variant!(effect, |effect: EffectT| {
+                let entity: Option<&<EffectT as EffectVariant>::EntityType> = (&parent_node.entity).try_into().ok();
+                entity.map(|entity| {
+                    let mut new_entity = entity.clone();
+                    effect.forward(&mut new_entity);
+                    new_entity.into()
+                })
+            });
+
+            match forwarded {
+                None => {
+                    violations.push(GraphInvariantViolation::EdgeWrongEntityType { parent, child });
+                }
+                Some(forwarded) if forwarded != child_node.entity => {
+                    violations.push(GraphInvariantViolation::EdgeMismatch {
+                        parent,
+                        child,
+                        fields: differing_fields(&forwarded, &child_node.entity),
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+
+        violations
     }
 
     pub fn get_debug_tree(&self) -> DebugTree {
@@ -245,6 +706,7 @@ impl EntityStateGraph {
                 data.insert(idx, DebugTreeNode {
                     description: node.entity.description(),
                     is_ambiguous: node.entity.is_ambiguous(),
+                    confidence: node.entity.confidence(),
                     created_at: node.valid_from,
                     observed_at: node.observed.as_ref().map(|obs| obs.perceived_at),
                     added_reason: node.added_reason,
@@ -272,10 +734,66 @@ impl EntityStateGraph {
     }
 }
 
+#[derive(Debug, Serialize)]
+pub struct StadiumReport {
+    pub name: String,
+    pub mods: Vec<String>,
+    pub birds: i32,
+    pub weather: HashMap<String, i32>,
+    // None for attributes a renovation changed by an unknown amount
+    pub attributes: HashMap<&'static str, Option<f32>>,
+}
+
+impl StadiumReport {
+    fn new(stadium: &entity::Stadium) -> Self {
+        Self {
+            name: stadium.name.clone(),
+            mods: stadium.mods.clone(),
+            birds: stadium.birds,
+            weather: stadium.weather.clone(),
+            attributes: stadium.attributes().into_iter()
+                .map(|(name, value)| (name, value.known().copied()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct GameStadiumReport {
+    pub game_id: Uuid,
+    // True if the game has more than one possible weather or stadium. There's one report for each.
+    pub ambiguous: bool,
+    pub weather: Option<i32>,
+    pub stadium_id: Option<Uuid>,
+    // None if the game doesn't have a stadium or the stadium hasn't been observed yet
+    pub stadium: Option<StadiumReport>,
+}
+
+// An effect should only ever change the entity it's applied to, never which entity it is
+fn check_identity_unchanged(effect: &AnyEffect, entity: &AnyEntity, new_entity: &AnyEntity) -> IngestResult<()> {
+    let (ty, id) = (entity.entity_type(), entity.id());
+    let (new_ty, new_id) = (new_entity.entity_type(), new_entity.id());
+    if (new_ty, new_id) != (ty, id) {
+        return Err(IngestError::EffectChangedIdentity {
+            effect: effect.to_string(),
+            ty,
+            id,
+            new_ty,
+            new_id,
+        });
+    }
+    Ok(())
+}
+
 #[derive(Default)]
 pub struct StateGraph {
     pub(crate) graphs: HashMap<(EntityType, Uuid), EntityStateGraph>,
     ids_for_type: HashMap<EntityType, Vec<Uuid>>,
+    // Graphs for entities that were removed (e.g. games that finished). They're kept so that if
+    // the entity shows up again it picks up its old history instead of starting from nothing.
+    removed_graphs: HashMap<(EntityType, Uuid), EntityStateGraph>,
+    // See expect_new_entity
+    expected_entities: HashSet<(EntityType, Uuid)>,
 }
 
 impl StateGraph {
@@ -284,70 +802,230 @@ impl StateGraph {
     pub fn populate(&mut self, obses: Vec<Observation>, start_time: DateTime<Utc>, history: &mut GraphDebugHistory) {
         let start_event: Arc<AnyEvent> = Arc::new(Start::new(start_time).into());
         for obs in obses {
-            let entity = AnyEntity::from_raw(obs.entity_raw.clone());
-
-            // Unfortunately these assignments all have to be in a specific order that makes it
-            // not particularly easy to tell what's going on. Gathering data for the debug view is
-            // interleaved with meaningful work.
-            // Debug
-            let entity_human_name = entity.to_string();
-            let description = start_event.to_string();
-            let json = entity.to_json();
-            let time = obs.perceived_at;
-
-            // Real work
-            let entity_type = obs.entity_type;
-            let entity_id = obs.entity_id;
-            let new_graph = EntityStateGraph::new(StateGraphNode::new_observed(
-                entity, start_time, Arc::new(obs), AddedReason::Start));
-
-            // Debug
-            let generations = vec![new_graph.roots().iter().cloned().collect()];
-            let idx = *new_graph.roots().iter().exactly_one().unwrap();
-
-            // Real work
-            self.graphs.insert((entity_type, entity_id), new_graph);
-            self.ids_for_type.entry(entity_type).or_default().push(entity_id);
-
-            // Debug
-            history.push_item((entity_type, entity_id), DebugHistoryItem {
-                entity_human_name,
-                versions: vec![DebugHistoryVersion {
-                    event_human_name: "Start".to_string(),
-                    time: start_time,
-                    tree: DebugTree {
-                        generations,
-                        edges: Default::default(),
-                        data: iter::once((idx, DebugTreeNode {
-                            description,
-                            is_ambiguous: false, // can't be ambiguous at start
-                            created_at: start_time,
-                            observed_at: Some(time),
-                            added_reason: AddedReason::Start,
-                            json,
-                            order: 0,
-                        })).collect(),
-                        roots: vec![idx],
-                        leafs: vec![idx],
-                    },
-                    queued_for_update: None,
-                    currently_updating: None,
-                    queued_for_delete: None,
-                }],
-            });
+            self.add_observed_entity(obs, start_time, "Start", &start_event.to_string(), AddedReason::Start, history);
+        }
+    }
+
+    pub fn snapshot(&self) -> StateSnapshot {
+        StateSnapshot {
+            // Going through ids_for_type keeps the order that entities were added in
+            graphs: self.all_entities()
+                .map(|(ty, id)| {
+                    self.graphs.get(&(ty, id))
+                        .expect("Every id in ids_for_type should have a graph")
+                        .snapshot(ty, id)
+                })
+                .collect(),
+            removed_graphs: self.removed_graphs.iter()
+                .map(|(&(ty, id), graph)| graph.snapshot(ty, id))
+                .collect(),
+            expected_entities: self.expected_entities.iter().copied().collect(),
+        }
+    }
+
+    pub fn from_snapshot(snapshot: StateSnapshot) -> Result<Self, SnapshotError> {
+        let mut s = Self::new();
+        for entity in snapshot.graphs {
+            let key = (entity.entity_type, entity.entity_id);
+            s.ids_for_type.entry(entity.entity_type).or_default().push(entity.entity_id);
+            s.graphs.insert(key, EntityStateGraph::from_snapshot(entity)?);
+        }
+        for entity in snapshot.removed_graphs {
+            let key = (entity.entity_type, entity.entity_id);
+            s.removed_graphs.insert(key, EntityStateGraph::from_snapshot(entity)?);
         }
+        s.expected_entities = snapshot.expected_entities.into_iter().collect();
+
+        Ok(s)
+    }
+
+    // The debug history needs an item for every entity before anything else can be pushed to it
+    pub fn push_restored_debug_items(&self, time: DateTime<Utc>, history: &mut GraphDebugHistory) {
+        for (&key, graph) in &self.graphs {
+            let Some(&leaf_idx) = graph.leafs().first() else { continue };
+            let entity_human_name = graph.get_version(leaf_idx)
+                .expect("Indices in State.leafs should always be valid")
+                .entity.to_string();
+            history.push_item(key, DebugHistoryItem::new(entity_human_name, DebugHistoryVersion {
+                event_human_name: "Restored from snapshot".to_string(),
+                time,
+                tree: graph.get_debug_tree(),
+                queued_for_update: None,
+                currently_updating: None,
+                queued_for_delete: None,
+            }));
+        }
+    }
+
+    // Starts a new graph for an entity, with the observation as its only version
+    pub fn add_observed_entity(&mut self, obs: Observation, valid_from: DateTime<Utc>, event_human_name: &str, description: &str, added_reason: AddedReason, history: &mut GraphDebugHistory) {
+        let entity = AnyEntity::from_raw(obs.entity_raw.clone());
+        let node = StateGraphNode::new_observed(entity, valid_from, Arc::new(obs), added_reason);
+        self.add_entity_graph(node, event_human_name, description, history);
+    }
+
+    // Starts a new graph for an entity an event created, with what the event knew about it as the
+    // only version. The entity is expected, so its first observation fills in the rest.
+    pub fn add_new_entity(&mut self, entity: AnyEntity, valid_from: DateTime<Utc>, event_human_name: &str, history: &mut GraphDebugHistory) {
+        let (entity_type, id) = (entity.entity_type(), entity.id());
+        let description = format!("Created by {event_human_name}");
+        let node = StateGraphNode {
+            entity,
+            valid_from,
+            observed: None,
+            added_reason: AddedReason::NewFromEvent,
+        };
+        self.add_entity_graph(node, event_human_name, &description, history);
+        self.expect_new_entity(entity_type, id);
+    }
+
+    fn add_entity_graph(&mut self, node: StateGraphNode, event_human_name: &str, description: &str, history: &mut GraphDebugHistory) {
+        // Unfortunately these assignments all have to be in a specific order that makes it
+        // not particularly easy to tell what's going on. Gathering data for the debug view is
+        // interleaved with meaningful work.
+        // Debug
+        let entity_human_name = node.entity.to_string();
+        let description = description.to_string();
+        let json = node.entity.to_json();
+        let is_ambiguous = node.entity.is_ambiguous();
+        let confidence = node.entity.confidence();
+        let observed_at = node.observed.as_ref().map(|obs| obs.perceived_at);
+        let valid_from = node.valid_from;
+        let added_reason = node.added_reason;
+
+        // Real work
+        let (entity_type, entity_id) = (node.entity.entity_type(), node.entity.id());
+        let new_graph = EntityStateGraph::new(node);
+
+        // Debug
+        let generations = vec![new_graph.roots().iter().cloned().collect()];
+        let idx = *new_graph.roots().iter().exactly_one().unwrap();
+
+        // Real work
+        self.graphs.insert((entity_type, entity_id), new_graph);
+        self.ids_for_type.entry(entity_type).or_default().push(entity_id);
+
+        // Debug
+        history.push_item((entity_type, entity_id), DebugHistoryItem::new(entity_human_name, DebugHistoryVersion {
+            event_human_name: event_human_name.to_string(),
+            time: valid_from,
+            tree: DebugTree {
+                generations,
+                edges: Default::default(),
+                data: iter::once((idx, DebugTreeNode {
+                    description,
+                    is_ambiguous,
+                    confidence,
+                    created_at: valid_from,
+                    observed_at,
+                    added_reason,
+                    json,
+                    order: 0,
+                })).collect(),
+                roots: vec![idx],
+                leafs: vec![idx],
+            },
+            queued_for_update: None,
+            currently_updating: None,
+            queued_for_delete: None,
+        }));
+    }
+
+    // Takes back an add_new_entity, for when the event that created the entity couldn't be applied
+    pub fn discard_new_entity(&mut self, entity_type: EntityType, id: Uuid) {
+        self.graphs.remove(&(entity_type, id));
+        if let Some(ids) = self.ids_for_type.get_mut(&entity_type) {
+            ids.retain(|&other_id| other_id != id);
+        }
+        self.expected_entities.remove(&(entity_type, id));
+    }
+
+    // Entities that events created, but that haven't been observed yet. Blarser doesn't know most
+    // of their attributes, so their first observation is taken as-is instead of being checked.
+    pub fn expect_new_entity(&mut self, entity_type: EntityType, id: Uuid) {
+        self.expected_entities.insert((entity_type, id));
+    }
+
+    pub fn take_expected_entity(&mut self, entity_type: EntityType, id: Uuid) -> bool {
+        self.expected_entities.remove(&(entity_type, id))
     }
 
     pub fn entity_graph(&self, entity_type: EntityType, id: Uuid) -> Option<&EntityStateGraph> {
         self.graphs.get(&(entity_type, id))
     }
 
+    pub fn entity_graphs(&self) -> impl Iterator<Item=(&(EntityType, Uuid), &EntityStateGraph)> {
+        self.graphs.iter()
+    }
+
+    // In the order they were added. Removed entities aren't included.
+    pub fn entity_ids_of_type(&self, ty: EntityType) -> Vec<Uuid> {
+        self.ids_for_type.get(&ty).cloned().unwrap_or_default()
+    }
+
+    pub fn all_entities(&self) -> impl Iterator<Item=(EntityType, Uuid)> + '_ {
+        self.ids_for_type.iter()
+            .flat_map(|(&ty, ids)| ids.iter().map(move |&id| (ty, id)))
+    }
+
     pub fn entity_graph_mut(&mut self, entity_type: EntityType, id: Uuid) -> Option<&mut EntityStateGraph> {
         self.graphs.get_mut(&(entity_type, id))
     }
 
-    pub fn get_timed_events(&self, _after: DateTime<Utc>) -> Vec<AnyEvent> {
-        vec![]
+    pub fn remove_entity_graph(&mut self, entity_type: EntityType, id: Uuid) -> bool {
+        let Some(graph) = self.graphs.remove(&(entity_type, id)) else { return false };
+        if let Some(ids) = self.ids_for_type.get_mut(&entity_type) {
+            ids.retain(|&other_id| other_id != id);
+        }
+        self.removed_graphs.insert((entity_type, id), graph);
+        true
+    }
+
+    pub fn restore_entity_graph(&mut self, entity_type: EntityType, id: Uuid) -> bool {
+        let Some(graph) = self.removed_graphs.remove(&(entity_type, id)) else { return false };
+        self.graphs.insert((entity_type, id), graph);
+        self.ids_for_type.entry(entity_type).or_default().push(id);
+        true
+    }
+
+    // Finished games never change again, so once every version of a game is finalized and older
+    // than `cutoff`, its graph is set aside with remove_entity_graph. It comes back if the game is
+    // ever observed or targeted by an event again. Returns the number of games removed.
+    pub fn remove_finished_games(&mut self, cutoff: DateTime<Utc>) -> usize {
+        let finished = self.entity_ids_of_type(EntityType::Game).into_iter()
+            .filter(|&id| {
+                let Some(graph) = self.entity_graph(EntityType::Game, id) else { return false };
+                graph.leafs.iter().all(|&leaf| {
+                    graph.get_version(leaf).map_or(false, |node| {
+                        node.valid_from < cutoff &&
+                            matches!(&node.entity, AnyEntity::Game(game) if game.finalized == Some(true))
+                    })
+                })
+            })
+            .collect_vec();
+
+        finished.into_iter()
+            .filter(|&id| self.remove_entity_graph(EntityType::Game, id))
+            .count()
+    }
+
+    // See EntityStateGraph::prune_before
+    pub fn prune_before(&mut self, cutoff: DateTime<Utc>) -> usize {
+        self.graphs.values_mut()
+            .map(|graph| graph.prune_before(cutoff))
+            .sum()
+    }
+
+    // Timed events implied by the current state of every entity that happen in [start, end). Events
+    // that are scheduled by other events aren't included, since those events' successors already
+    // scheduled them. Different leafs of the same entity can imply the same event, so there can be
+    // duplicates.
+    pub fn get_timed_events_between(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<AnyEvent> {
+        self.graphs.values()
+            .flat_map(|graph| graph.leafs().iter()
+                .flat_map(move |&idx| graph.timed_events_from(idx)))
+            .filter(|event| start <= event.time() && event.time() < end)
+            .collect()
         // // This function is not intended to be generic. I need to see the natural usage pattern in
         // // the normal case before deciding what the general API will look like.
         // let sim_graph = self.entity_graph(EntityType::Sim, Uuid::nil())
@@ -404,26 +1082,98 @@ impl StateGraph {
         result.expect("Leafs array for entity is empty")
     }
 
+    // Like query_entity_unique, but instead of requiring every leaf to agree this returns each
+    // distinct result once, in the order of the leafs they first came from. An entity that isn't
+    // in the graph has no results.
+    fn query_entity_all<EntityT: Entity, F, T>(&self, leaf_id: &(EntityType, Uuid), accessor: F) -> Vec<T>
+        where F: Fn(&EntityT) -> T,
+              T: Debug + Eq,
+              for<'a> &'a AnyEntity: TryInto<&'a EntityT>,
+              for<'a> <&'a AnyEntity as TryInto<&'a EntityT>>::Error: Debug {
+        let Some(graph) = self.entity_graph(leaf_id.0, leaf_id.1) else { return Vec::new() };
+        let mut results = Vec::new();
+        for &leaf in &graph.leafs {
+            let entity = &graph.get_version(leaf)
+                .expect("Leafs should never have an invalid index")
+                .entity;
+            let entity: &EntityT = entity.try_into()
+                .expect("Corrupt graph: Leaf was not the expected type");
+            let new_result = accessor(entity);
+            if !results.contains(&new_result) {
+                results.push(new_result);
+            }
+        }
+
+        results
+    }
+
     pub fn query_sim_unique<F, T>(&self, accessor: F) -> T
         where F: Fn(&entity::Sim) -> T, T: Debug + Eq {
         self.query_entity_unique::<entity::Sim, _, _>(&(EntityType::Sim, Uuid::nil()), accessor)
     }
 
+    pub fn query_sim_all<F, T>(&self, accessor: F) -> Vec<T>
+        where F: Fn(&entity::Sim) -> T, T: Debug + Eq {
+        self.query_entity_all::<entity::Sim, _, _>(&(EntityType::Sim, Uuid::nil()), accessor)
+    }
+
     pub fn query_game_unique<F, T>(&self, id: Uuid, accessor: F) -> T
         where F: Fn(&entity::Game) -> T, T: Debug + Eq {
         self.query_entity_unique::<entity::Game, _, _>(&(EntityType::Game, id), accessor)
     }
 
+    pub fn query_game_all<F, T>(&self, id: Uuid, accessor: F) -> Vec<T>
+        where F: Fn(&entity::Game) -> T, T: Debug + Eq {
+        self.query_entity_all::<entity::Game, _, _>(&(EntityType::Game, id), accessor)
+    }
+
     pub fn query_team_unique<F, T>(&self, id: Uuid, accessor: F) -> T
         where F: Fn(&entity::Team) -> T, T: Debug + Eq {
         self.query_entity_unique::<entity::Team, _, _>(&(EntityType::Team, id), accessor)
     }
 
+    pub fn query_team_all<F, T>(&self, id: Uuid, accessor: F) -> Vec<T>
+        where F: Fn(&entity::Team) -> T, T: Debug + Eq {
+        self.query_entity_all::<entity::Team, _, _>(&(EntityType::Team, id), accessor)
+    }
+
     pub fn query_player_unique<F, T>(&self, id: Uuid, accessor: F) -> T
         where F: Fn(&entity::Player) -> T, T: Debug + Eq {
         self.query_entity_unique::<entity::Player, _, _>(&(EntityType::Player, id), accessor)
     }
+
+    pub fn query_player_all<F, T>(&self, id: Uuid, accessor: F) -> Vec<T>
+        where F: Fn(&entity::Player) -> T, T: Debug + Eq {
+        self.query_entity_all::<entity::Player, _, _>(&(EntityType::Player, id), accessor)
+    }
     
+    pub fn game_stadium_reports(&self) -> Vec<GameStadiumReport> {
+        let Some(game_ids) = self.ids_for_type.get(&EntityType::Game) else { return Vec::new() };
+
+        game_ids.iter()
+            .flat_map(|&game_id| {
+                // An ambiguous game gets one report for each possible weather and stadium
+                let candidates = self.query_game_all(game_id, |game| {
+                    (game.weather.known(), game.stadium_id)
+                });
+                let ambiguous = candidates.len() > 1;
+
+                candidates.into_iter()
+                    .map(move |(weather, stadium_id)| {
+                        // Stadium attributes aren't Eq so this can't use query_entity_all. Any leaf
+                        // will do for a debug view.
+                        let stadium = stadium_id
+                            .and_then(|id| self.entity_graph(EntityType::Stadium, id))
+                            .and_then(|graph| graph.leafs().first().and_then(|&idx| graph.get_version(idx)))
+                            .and_then(|node| node.entity.as_stadium())
+                            .map(StadiumReport::new);
+
+                        GameStadiumReport { game_id, ambiguous, weather, stadium_id, stadium }
+                    })
+            })
+            .collect()
+    }
+
     pub fn games_for_day(&self, season: i32, day: i32) -> impl Iterator<Item=Uuid> + '_ {
         self.ids_for_type.get(&EntityType::Game)
             .expect("Game entity type must exist here")
@@ -435,4 +1185,340 @@ impl StateGraph {
             })
             .cloned()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use chrono::Utc;
+    use partial_information::MaybeKnown;
+    use uuid::Uuid;
+    use crate::entity::{test_game, test_player, test_team, AnyEntity, AnyEntityRaw, Entity, Player, Weather};
+    use crate::events::{GameUpcomingEffectVariant, PlayerTeamChangedEffect};
+    use crate::ingest::{GraphDebugHistory, IngestError, Observation};
+    use crate::ingest::chron::tests::{game_observation, player_observation};
+    use crate::state::EntityType;
+    use super::{check_identity_unchanged, AddedReason, EntityStateGraph, GraphInvariantViolation, StateGraph, StateGraphNode, StateSnapshot};
+
+    // A state where each entity has a single version, as if they'd all just been observed
+    pub(crate) fn test_state(entities: impl IntoIterator<Item=AnyEntity>) -> StateGraph {
+        let time = Utc::now();
+        let mut state = StateGraph::new();
+        for entity in entities {
+            let (entity_type, entity_id) = (entity.entity_type(), entity.id());
+            let raw_json = entity.to_raw_approximation_json()
+                .expect("Test entity should serialize");
+            let obs = Observation {
+                perceived_at: time,
+                entity_type,
+                entity_id,
+                entity_raw: AnyEntityRaw::from_json(entity_type, raw_json)
+                    .expect("Test entity's raw JSON should parse"),
+            };
+            let node = StateGraphNode::new_observed(entity, time, Arc::new(obs), AddedReason::Start);
+            state.graphs.insert((entity_type, entity_id), EntityStateGraph::new(node));
+            state.ids_for_type.entry(entity_type).or_default().push(entity_id);
+        }
+        state
+    }
+
+    #[test]
+    fn ambiguous_games_get_a_stadium_report_per_candidate() {
+        let mut game = test_game();
+        game.weather = Weather(MaybeKnown::Known(1));
+        let mut state = test_state([game.clone().into()]);
+
+        let time = Utc::now();
+        let graph = state.entity_graph_mut(EntityType::Game, game.id).unwrap();
+        let root = graph.roots()[0];
+        let mut other = game.clone();
+        other.weather = Weather(MaybeKnown::Known(7));
+        let left = graph.add_child_version(root, game.clone().into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        let right = graph.add_child_version(root, other.into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        graph.set_leafs(vec![left, right]);
+
+        let reports = state.game_stadium_reports();
+        assert_eq!(reports.len(), 2);
+        assert!(reports.iter().all(|report| report.ambiguous && report.game_id == game.id));
+        let weathers = reports.iter().map(|report| report.weather).collect::<Vec<_>>();
+        assert!(weathers.contains(&Some(1)));
+        assert!(weathers.contains(&Some(7)));
+    }
+
+    #[test]
+    fn snapshot_round_trips_whole_graphs() {
+        let game = test_game();
+        let key = (EntityType::Game, game.id());
+        let mut state = test_state([game.clone().into()]);
+
+        // A root with two branches, one of which goes through an observed interior node. There's
+        // also a removed node so the saved indices have a hole in them.
+        let time = Utc::now();
+        let graph = state.graphs.get_mut(&key).unwrap();
+        let root = graph.roots()[0];
+        let removed = graph.add_child_version(root, game.clone().into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        graph.graph.remove_node(removed);
+        let observed = graph.add_child_version(root, game.clone().into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        graph.get_version_mut(observed).unwrap().observed = graph.latest_ingested_observation().cloned().map(Arc::new);
+        let left = graph.add_child_version(observed, game.clone().into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        let right = graph.add_child_version(root, game.clone().into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        graph.set_leafs(vec![left, right]);
+
+        let json = serde_json::to_string(&state.snapshot()).unwrap();
+        let snapshot: StateSnapshot = serde_json::from_str(&json).unwrap();
+        let restored = StateGraph::from_snapshot(snapshot).unwrap();
+
+        let graph = restored.graphs.get(&key).unwrap();
+        assert!(graph.validate().is_empty());
+        assert_eq!(graph.roots().len(), 1);
+        assert_eq!(graph.leafs().len(), 2);
+        assert_eq!(graph.graph.graph().node_count(), 4);
+        assert_eq!(graph.graph.graph().edge_count(), 3);
+        let observed_count = graph.graph.graph().node_indices()
+            .filter(|&idx| graph.get_version(idx).unwrap().observed.is_some())
+            .count();
+        assert_eq!(observed_count, 2);
+        assert!(graph.latest_ingested_observation().is_some());
+    }
+
+    #[test]
+    fn finished_games_are_removed_and_restored() {
+        let mut finished = test_game();
+        finished.finalized = Some(true);
+        let mut ongoing = test_game();
+        ongoing.id = uuid::Uuid::new_v4();
+        let mut state = test_state([finished.clone().into(), ongoing.clone().into()]);
+
+        let cutoff = Utc::now() + chrono::Duration::minutes(1);
+        assert_eq!(state.remove_finished_games(cutoff), 1);
+        assert!(state.entity_graph(EntityType::Game, finished.id).is_none());
+        assert_eq!(state.entity_ids_of_type(EntityType::Game), vec![ongoing.id]);
+
+        // Its history comes back intact when it's needed again
+        assert!(state.restore_entity_graph(EntityType::Game, finished.id));
+        let graph = state.entity_graph(EntityType::Game, finished.id).unwrap();
+        assert!(graph.latest_ingested_observation().is_some());
+        assert!(graph.validate().is_empty());
+    }
+
+    #[test]
+    fn scores_are_from_feed_only_if_an_event_changed_them() {
+        let game = test_game();
+        let key = (EntityType::Game, game.id());
+        let mut state = test_state([game.clone().into()]);
+        let graph = state.graphs.get_mut(&key).unwrap();
+
+        // observed root -> same score -> changed score -> same score
+        let time = Utc::now();
+        let root = graph.roots()[0];
+        let unchanged = graph.add_child_version(root, game.clone().into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        let mut scored = game.clone();
+        scored.home.score = Some(scored.home.score.unwrap_or_default() + 1.0);
+        let changed = graph.add_child_version(unchanged, scored.clone().into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        let after = graph.add_child_version(changed, scored.into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+
+        assert!(!graph.score_from_feed(root));
+        assert!(!graph.score_from_feed(unchanged));
+        assert!(graph.score_from_feed(changed));
+        assert!(graph.score_from_feed(after));
+    }
+
+    #[test]
+    fn prune_only_cuts_at_observed_nodes() {
+        let game = test_game();
+        let key = (EntityType::Game, game.id());
+        let mut state = test_state([game.clone().into()]);
+
+        // root (observed) -> a -> b (observed) -> c, all before the cutoff
+        let time = Utc::now();
+        let graph = state.graphs.get_mut(&key).unwrap();
+        let root = graph.roots()[0];
+        let obs = graph.latest_ingested_observation().cloned().map(Arc::new);
+        let a = graph.add_child_version(root, game.clone().into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        let b = graph.add_child_version(a, game.clone().into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        graph.get_version_mut(b).unwrap().observed = obs;
+        let c = graph.add_child_version(b, game.clone().into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        graph.set_leafs(vec![c]);
+
+        assert_eq!(state.prune_before(time + chrono::Duration::minutes(1)), 2);
+        let graph = state.graphs.get(&key).unwrap();
+        assert_eq!(graph.roots(), &vec![b]);
+        assert!(graph.validate().is_empty());
+    }
+
+    #[test]
+    fn candidate_placements_are_the_versions_that_overlap_the_range() {
+        let game = test_game();
+        let key = (EntityType::Game, game.id());
+        let mut state = test_state([game.clone().into()]);
+
+        // root -> a -> (left, right), each 10 seconds after the last
+        let graph = state.graphs.get_mut(&key).unwrap();
+        let root = graph.roots()[0];
+        let start = graph.get_version(root).unwrap().valid_from;
+        let at = |seconds| start + chrono::Duration::seconds(seconds);
+        let a = graph.add_child_version(root, game.clone().into(), at(10), GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        let left = graph.add_child_version(a, game.clone().into(), at(20), GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        let right = graph.add_child_version(a, game.clone().into(), at(20), GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        graph.set_leafs(vec![right, left]);
+
+        let candidates = |earliest, latest| graph.get_candidate_placements(earliest, latest).into_iter().collect::<Vec<_>>();
+        assert_eq!(candidates(at(1), at(5)), vec![root]);
+        // Versions that end exactly at the start of the range or start exactly at its end count
+        assert_eq!(candidates(at(10), at(10)), vec![root, a]);
+        assert_eq!(candidates(at(15), at(20)), vec![a, left, right]);
+        // Leafs are valid forever
+        assert_eq!(candidates(at(1000), at(2000)), vec![left, right]);
+        assert!(candidates(at(-10), at(-5)).is_empty());
+    }
+
+    #[test]
+    fn populated_entities_are_listed_by_type() {
+        let (first, second, game) = (test_player(None), test_player(None), test_game());
+        let time = Utc::now();
+        let mut state = StateGraph::new();
+        state.populate(vec![
+            player_observation(&first, time),
+            game_observation(&game, time),
+            player_observation(&second, time),
+        ], time, &mut GraphDebugHistory::new(true, 0));
+
+        // In the order they were populated
+        assert_eq!(state.entity_ids_of_type(EntityType::Player), vec![first.id, second.id]);
+        assert_eq!(state.entity_ids_of_type(EntityType::Game), vec![game.id]);
+        assert!(state.entity_ids_of_type(EntityType::Team).is_empty());
+
+        let all = state.all_entities().collect::<HashSet<_>>();
+        assert_eq!(all, HashSet::from([
+            (EntityType::Player, first.id),
+            (EntityType::Player, second.id),
+            (EntityType::Game, game.id),
+        ]));
+    }
+
+    #[test]
+    fn valid_graphs_have_no_violations() {
+        let game = test_game();
+        let key = (EntityType::Game, game.id());
+        let mut state = test_state([game.clone().into()]);
+
+        let graph = state.graphs.get_mut(&key).unwrap();
+        let root = graph.roots()[0];
+        let time = graph.get_version(root).unwrap().valid_from;
+        let child = graph.add_child_version(root, game.into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        graph.set_leafs(vec![child]);
+
+        assert!(graph.validate().is_empty());
+    }
+
+    #[test]
+    fn broken_graphs_report_each_violation() {
+        let game = test_game();
+        let key = (EntityType::Game, game.id());
+        let mut state = test_state([game.clone().into()]);
+
+        // root (observed, but contradicts its observation) -> mismatched child, which is also
+        // wrongly listed as the parent of a leaf. There's also an orphan.
+        let graph = state.graphs.get_mut(&key).unwrap();
+        let root = graph.roots()[0];
+        let time = graph.get_version(root).unwrap().valid_from;
+        let mut changed = game.clone();
+        changed.day += 1;
+        graph.get_version_mut(root).unwrap().entity = changed.clone().into();
+        let mismatched = graph.add_child_version(root, game.clone().into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        let leaf = graph.add_child_version(mismatched, game.clone().into(), time, GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        let orphan = graph.add_child_disconnected(game.into(), time, AddedReason::RefinedFromObservation);
+        graph.set_leafs(vec![mismatched, leaf, orphan]);
+
+        let violations = graph.validate();
+        assert_eq!(violations.len(), 4, "{violations:?}");
+        assert!(violations.iter().any(|violation| matches!(violation,
+            GraphInvariantViolation::ObservationConflicts { node, fields } if *node == root && fields == &vec!["day".to_string()])));
+        assert!(violations.iter().any(|violation| matches!(violation,
+            GraphInvariantViolation::EdgeMismatch { parent, child, fields } if *parent == root && *child == mismatched && fields == &vec!["day".to_string()])));
+        assert!(violations.iter().any(|violation| matches!(violation, GraphInvariantViolation::LeafHasChildren(node) if *node == mismatched)));
+        assert!(violations.iter().any(|violation| matches!(violation, GraphInvariantViolation::Orphan(node) if *node == orphan)));
+    }
+
+    #[test]
+    fn game_end_events_are_timed_from_when_the_game_ended() {
+        let mut game = test_game();
+        game.end_phase = Some(3);
+        let key = (EntityType::Game, game.id());
+        let mut state = test_state([game.clone().into()]);
+
+        // An unrelated change to the game after it ended shouldn't push its end events later
+        let graph = state.graphs.get_mut(&key).unwrap();
+        let root = graph.roots()[0];
+        let ended_at = graph.get_version(root).unwrap().valid_from;
+        let later = graph.add_child_version(root, game.clone().into(), ended_at + chrono::Duration::seconds(2), GameUpcomingEffectVariant::default().into(), AddedReason::NewFromEvent);
+        graph.set_leafs(vec![later]);
+
+        let times = graph.timed_events_from(later).iter().map(|event| event.time()).collect::<Vec<_>>();
+        assert_eq!(times, vec![ended_at + chrono::Duration::seconds(5), ended_at + chrono::Duration::seconds(10)]);
+    }
+
+    #[test]
+    fn entities_created_by_events_get_a_graph_and_are_expected() {
+        let (player_id, team_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut state = StateGraph::new();
+        let mut history = GraphDebugHistory::new(true, 0);
+        state.add_new_entity(Player::new_from_event(player_id, Some(team_id)).into(), Utc::now(), "Player hatched", &mut history);
+
+        let graph = state.entity_graph(EntityType::Player, player_id)
+            .expect("Hatched player should have a graph");
+        let node = graph.get_version(graph.leafs()[0]).unwrap();
+        assert!(node.observed.is_none());
+        assert_eq!(node.entity.as_player().unwrap().league_team_id, Some(team_id));
+        assert_eq!(state.entity_ids_of_type(EntityType::Player), vec![player_id]);
+
+        assert!(state.take_expected_entity(EntityType::Player, player_id));
+        assert!(!state.take_expected_entity(EntityType::Player, player_id));
+    }
+
+    #[test]
+    fn undoing_an_effect_leaves_the_graph_as_it_was() {
+        let player = test_player(None);
+        let mut state = test_state([player.clone().into()]);
+        let new_team = Uuid::new_v4();
+
+        let graph = state.entity_graph_mut(EntityType::Player, player.id).unwrap();
+        let original_leafs = graph.leafs().clone();
+        let previous_leafs = graph.apply_effect(&PlayerTeamChangedEffect::new(player.id, new_team).into(), Utc::now())
+            .expect("Changing a player's team should apply");
+        assert_eq!(previous_leafs, original_leafs);
+        assert_ne!(graph.leafs(), &original_leafs);
+
+        graph.undo_effect(previous_leafs);
+        assert_eq!(graph.leafs(), &original_leafs);
+        assert!(graph.validate().is_empty());
+        assert_eq!(state.query_player_all(player.id, |p| p.league_team_id), vec![None]);
+    }
+
+    #[test]
+    fn effects_that_change_an_entitys_id_are_rejected() {
+        let player = test_player(None);
+        let mut impostor = player.clone();
+        impostor.id = Uuid::new_v4();
+        let effect = PlayerTeamChangedEffect::new(player.id, Uuid::new_v4()).into();
+
+        let result = check_identity_unchanged(&effect, &player.clone().into(), &impostor.clone().into());
+        assert!(matches!(result, Err(IngestError::EffectChangedIdentity { ty: EntityType::Player, id, new_ty: EntityType::Player, new_id, .. })
+            if id == player.id && new_id == impostor.id));
+        assert!(check_identity_unchanged(&effect, &player.clone().into(), &player.into()).is_ok());
+    }
+
+    #[test]
+    fn effects_for_another_type_of_entity_are_rejected_without_changing_the_graph() {
+        let team = test_team(vec![], vec![]);
+        let mut state = test_state([team.clone().into()]);
+
+        let graph = state.entity_graph_mut(EntityType::Team, team.id).unwrap();
+        let original_leafs = graph.leafs().clone();
+        let result = graph.apply_effect(&PlayerTeamChangedEffect::new(team.id, Uuid::new_v4()).into(), Utc::now());
+
+        assert!(matches!(result, Err(IngestError::EffectChangedIdentity { ty: EntityType::Team, new_ty: EntityType::Player, .. })));
+        assert_eq!(graph.leafs(), &original_leafs);
+        assert!(graph.validate().is_empty());
+    }
+}
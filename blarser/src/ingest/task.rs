@@ -1,45 +1,143 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
 use std::sync::{Arc, Mutex as StdMutex};
-use chrono::{DateTime, Utc};
-use diesel::{ExpressionMethods, OptionalExtension, QueryDsl, QueryResult, RunQueryDsl};
+use chrono::{DateTime, Duration, Utc};
+use diesel::{ExpressionMethods, QueryResult, RunQueryDsl};
 use rocket::info;
+use log::warn;
 use core::default::Default;
 use petgraph::stable_graph::NodeIndex;
-use serde::Serialize;
-use tokio::sync::{oneshot, mpsc, Mutex as TokioMutex};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::{oneshot, mpsc, watch, Mutex as TokioMutex};
+use tokio::task::JoinHandle;
 use uuid::Uuid;
-
-use crate::db::BlarserDbConn;
-use crate::ingest::run_ingest;
-use crate::ingest::state::{AddedReason, StateGraph};
+use partial_information::Severity;
+
+use crate::db::{BlarserDbConn, get_approvals_by_id, get_unfinished_backfill};
+use crate::events::AnyEvent;
+use crate::ingest::{run_ingest, run_replay, Observation};
+use crate::ingest::fed::TimedEventQueue;
+use crate::ingest::approval_policy::{ApprovalPolicies, QueuedApproval};
+use crate::ingest::dedup_window::DedupWindows;
+use crate::ingest::ambiguity::{AmbiguityStrategy, FieldWeights};
+use crate::ingest::backfill::BackfillConfig;
+use crate::ingest::config::IngestConfig;
+use crate::ingest::state::{AddedReason, StateGraph, StateSnapshot};
 use crate::schema;
-use crate::state::{ApprovalState, EntityType, StateInterface};
+use crate::state::{ApprovalState, EntityType, NewConflict, StateInterface};
 
 // Doing 15:31 to skip a trivial change that just changes the milliseconds of every date in `sim`,
 // I'm guessing due to a sim restart or something
 const BLARSER_START: &str = "2021-03-01T15:31:00Z";
+// How long before the earliest approval a focused ingest starts, to give the state time to settle
+const FOCUS_LEAD_MINUTES: i64 = 10;
+// How many debug history versions to keep for each entity. Keeping all of them runs out of memory
+// after a few sim-days.
+pub const DEFAULT_DEBUG_HISTORY_CAP: usize = 500;
+// Where the live ingest saves its state, so a restart can pick up from there instead of loading
+// everything from Chronicler again
+const SNAPSHOT_PATH: &str = "ingest_snapshot.json";
 
 pub struct IngestTaskHolder {
     pub latest_ingest: Arc<StdMutex<Option<IngestTask>>>,
+    // Focused ingests and backfills, which run alongside the live ingest instead of replacing it.
+    // Keyed by ingest id.
+    pub side_ingests: Arc<StdMutex<HashMap<i32, IngestTask>>>,
 }
 
 impl IngestTaskHolder {
     pub fn new() -> Self {
         Self {
-            latest_ingest: Arc::new(StdMutex::new(None))
+            latest_ingest: Arc::new(StdMutex::new(None)),
+            side_ingests: Arc::new(StdMutex::new(HashMap::new())),
         }
     }
 
+    // Also forgets any side ingests that have finished, since nothing else cleans them up
+    pub fn add_side_ingest(&self, ingest_task: IngestTask) {
+        let mut side_ingests = self.side_ingests.lock().unwrap();
+        side_ingests.retain(|_, task| !task.is_finished());
+        side_ingests.insert(ingest_task.ingest_id, ingest_task);
+    }
+
     pub fn latest_ingest_id(&self) -> Option<i32> {
         let lock = self.latest_ingest.lock().unwrap();
         lock.as_ref().map(|ingest| ingest.ingest_id)
     }
 
+    // Side ingests can be waiting on approvals too, and whichever one asked for this approval will
+    // pick it up
     pub fn notify_approval(&self, id: i32, result: bool) {
         let lock = self.latest_ingest.lock().unwrap();
         if let Some(task) = &*lock {
             task.notify_approval(id, result)
         }
+        for task in self.side_ingests.lock().unwrap().values() {
+            task.notify_approval(id, result)
+        }
+    }
+
+    pub fn latest_ingest_time(&self) -> Option<DateTime<Utc>> {
+        let lock = self.latest_ingest.lock().unwrap();
+        lock.as_ref().map(|ingest| ingest.latest_ingest_time())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        let lock = self.latest_ingest.lock().unwrap();
+        lock.as_ref().map_or(false, |ingest| ingest.resumer.is_some())
+    }
+
+    // The resume handle is kept on the task so a later request can resume it
+    pub fn pause(&self) -> Result<(), PauseError> {
+        let mut lock = self.latest_ingest.lock().unwrap();
+        let task = lock.as_mut().ok_or(PauseError::NoIngest)?;
+        if task.resumer.is_some() {
+            return Err(PauseError::AlreadyPaused);
+        }
+
+        task.resumer = Some(task.pause()?);
+        Ok(())
+    }
+
+    pub fn resume(&self) -> Result<(), PauseError> {
+        let mut lock = self.latest_ingest.lock().unwrap();
+        let task = lock.as_mut().ok_or(PauseError::NoIngest)?;
+        task.resumer.take()
+            .ok_or(PauseError::NotPaused)?
+            .resume();
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PauseError {
+    #[error("No ingest")]
+    NoIngest,
+
+    #[error("Already paused")]
+    AlreadyPaused,
+
+    #[error("Not paused")]
+    NotPaused,
+
+    #[error("Ingest is no longer running")]
+    Stopped,
+
+    #[error("Too many pause requests are waiting for the ingest to pick them up")]
+    TooManyPendingPauses,
+}
+
+// Dropping this without calling resume also resumes the ingest, because the ingest treats a closed
+// channel the same as a resume
+pub struct ResumeHandle {
+    resumer: oneshot::Sender<()>,
+}
+
+impl ResumeHandle {
+    pub fn resume(self) {
+        // If this fails the ingest has stopped, and there's nothing to resume
+        let _ = self.resumer.send(());
     }
 }
 
@@ -53,54 +151,203 @@ pub struct IngestTask {
     ingest_id: i32,
     pending_approvals: Arc<StdMutex<HashMap<i32, oneshot::Sender<bool>>>>,
     pub debug_history: GraphDebugHistorySync,
-    pub pause_requester: Arc<TokioMutex<mpsc::Sender<oneshot::Receiver<()>>>>,
-    pub resumer: Option<oneshot::Sender<()>>,
+    pub state: Arc<StdMutex<StateGraph>>,
+    pub timed_events: Arc<StdMutex<TimedEventQueue>>,
+    pub applied_events: AppliedEvents,
+    pub event_log: EventLog,
+    start_time: DateTime<Utc>,
+    pause_requester: mpsc::Sender<oneshot::Receiver<()>>,
+    resumer: Option<ResumeHandle>,
+    latest_ingest_time: watch::Receiver<DateTime<Utc>>,
+    handle: JoinHandle<()>,
+}
+
+// The entities a focused ingest replays, and when it starts. None if there are no approvals.
+fn approval_focus(approvals: &[crate::db::Approval]) -> Option<(HashSet<(EntityType, Uuid)>, DateTime<Utc>)> {
+    let focus = approvals.iter()
+        .map(|approval| (approval.entity_type, approval.entity_id))
+        .collect();
+    let start_time = approvals.iter()
+        .map(|approval| approval.perceived_at)
+        .min()?
+        - Duration::minutes(FOCUS_LEAD_MINUTES);
+    Some((focus, start_time))
+}
+
+// Where an interrupted backfill picks up, and the config that takes it the rest of the way
+fn backfill_resume_point(unfinished: &crate::db::Ingest, chunk_size: Option<Duration>) -> Option<(DateTime<Utc>, BackfillConfig)> {
+    let (Some(until), Some(backfilled_to)) = (unfinished.backfill_until, unfinished.backfilled_to) else {
+        return None;
+    };
+    let mut config = BackfillConfig::new(until);
+    if let Some(chunk_size) = chunk_size {
+        config = config.with_chunk_size(chunk_size);
+    }
+    Some((backfilled_to, config))
 }
 
 impl IngestTask {
-    pub async fn new(conn: BlarserDbConn) -> IngestTask {
+    // Resumes from the saved snapshot if there is one, otherwise starts over from BLARSER_START
+    pub async fn new(conn: BlarserDbConn, config: IngestConfig) -> IngestTask {
+        let debug_history = GraphDebugHistory::new(false, config.debug_history_cap);
+        let snapshot = load_snapshot();
+        let start_time = match &snapshot {
+            Some(snapshot) => {
+                info!("Resuming from the snapshot saved at {}", snapshot.time);
+                snapshot.time
+            }
+            None => {
+                DateTime::parse_from_rfc3339(BLARSER_START)
+                    .expect("Couldn't parse hard-coded Blarser start time")
+                    .with_timezone(&Utc)
+            }
+        };
+
+        Self::spawn(conn, start_time, None, debug_history, move |mut ingest| {
+            ingest.save_snapshots = true;
+            ingest.pause_on_rejected_observations = config.pause_on_rejected_observations;
+            ingest.approval_policies = ApprovalPolicies::default().with_score_policy(config.score_policy);
+            ingest.ambiguity_strategy = config.ambiguity_strategy;
+            ingest.field_weights = config.field_weights;
+            run_ingest(ingest, start_time, config.replay_speed, None, snapshot)
+        }).await
+    }
+
+    // Replays only the observations of the entities involved in the given approvals, starting just
+    // before the earliest of them. Events are still all applied because they're needed to get the
+    // entities into the right state. The resulting conflicts are recorded under the new ingest.
+    pub async fn new_focused(conn: BlarserDbConn, approval_ids: Vec<i32>) -> QueryResult<IngestTask> {
+        let approvals = conn.run(move |c| get_approvals_by_id(c, &approval_ids)).await?;
+        let (focus, start_time) = approval_focus(&approvals)
+            .ok_or(diesel::result::Error::NotFound)?;
+
+        info!("Starting focused ingest of {} entities from {}", focus.len(), start_time);
+        Ok(Self::start(conn, start_time, Some(focus), None, GraphDebugHistory::new(false, DEFAULT_DEBUG_HISTORY_CAP)).await)
+    }
+
+    pub async fn new_backfill(conn: BlarserDbConn, start_time: DateTime<Utc>, config: BackfillConfig) -> IngestTask {
+        info!("Starting backfill from {} to {}", start_time, config.until);
+        let debug_history = GraphDebugHistory::new(!config.record_debug_history, DEFAULT_DEBUG_HISTORY_CAP);
+        Self::start(conn, start_time, None, Some(config), debug_history).await
+    }
+
+    // Starts a new backfill covering whatever the last interrupted one didn't get to. The state is
+    // loaded fresh from Chronicler at the resume point, the same way any other ingest starts.
+    pub async fn resume_backfill(conn: BlarserDbConn, chunk_size: Option<Duration>) -> QueryResult<Option<IngestTask>> {
+        let Some(unfinished) = conn.run(|c| get_unfinished_backfill(c)).await? else {
+            return Ok(None);
+        };
+        let Some((backfilled_to, config)) = backfill_resume_point(&unfinished, chunk_size) else {
+            warn!("Backfill ingest {} never recorded where it started, so it can't be resumed", unfinished.id);
+            return Ok(None);
+        };
+
+        info!("Resuming backfill from ingest {} at {}", unfinished.id, backfilled_to);
+        let debug_history = GraphDebugHistory::new(!config.record_debug_history, DEFAULT_DEBUG_HISTORY_CAP);
+        Ok(Some(Self::start(conn, backfilled_to, None, Some(config), debug_history).await))
+    }
+
+    // Ingests the events and observations from an exported event log, in order, on top of the
+    // initial state recorded in the log. Nothing is fetched, so the result only depends on the log.
+    pub async fn new_replay(conn: BlarserDbConn, start_time: DateTime<Utc>, initial_state: Vec<Observation>, items: Vec<ReplayItem>) -> IngestTask {
+        info!("Starting replay of {} items from {}", items.len(), start_time);
+        Self::spawn(conn, start_time, None, GraphDebugHistory::new(false, DEFAULT_DEBUG_HISTORY_CAP), move |ingest| run_replay(ingest, start_time, initial_state, items)).await
+    }
+
+    async fn start(conn: BlarserDbConn, start_time: DateTime<Utc>, focus: Option<HashSet<(EntityType, Uuid)>>, backfill: Option<BackfillConfig>, debug_history: GraphDebugHistory) -> IngestTask {
+        let backfill_end = backfill.map(|config| config.until);
+        Self::spawn(conn, start_time, backfill_end, debug_history, move |mut ingest| {
+            ingest.focus = focus;
+            // Focused ingests and backfills are never watched live, so they always go full speed
+            run_ingest(ingest, start_time, 0., backfill, None)
+        }).await
+    }
+
+    async fn spawn<F, Fut>(conn: BlarserDbConn, start_time: DateTime<Utc>, backfill_end: Option<DateTime<Utc>>, debug_history: GraphDebugHistory, run: F) -> IngestTask
+        where F: FnOnce(Ingest) -> Fut,
+              Fut: Future<Output=()> + Send + 'static {
         info!("Starting ingest");
 
-        let ingest_id: i32 = conn.run(|c| {
+        let ingest_id: i32 = conn.run(move |c| {
             use diesel::dsl::*;
             use schema::ingests::dsl::*;
 
-            // Delete all except latest ingest
-            let latest_ingest = ingests
-                .select(id)
-                .order(started_at.desc())
-                .limit(1)
-                .get_result::<i32>(c)
-                .optional()?;
-
-            if let Some(latest_ingest) = latest_ingest {
-                delete(ingests.filter(id.ne(latest_ingest))).execute(c)?;
-            }
-
-            insert_into(ingests).default_values().returning(id).get_result(c)
+            // backfilled_to starts at the start time so a backfill that's interrupted before its
+            // first checkpoint can still be resumed
+            insert_into(ingests)
+                .values((
+                    backfill_until.eq(backfill_end),
+                    backfilled_to.eq(backfill_end.map(|_| start_time)),
+                ))
+                .returning(id)
+                .get_result(c)
         }).await
             .expect("Failed to create new ingest record");
 
-        let start_time_parsed = DateTime::parse_from_rfc3339(BLARSER_START)
-            .expect("Couldn't parse hard-coded Blarser start time")
-            .with_timezone(&Utc);
-
         let approvals = Arc::new(StdMutex::new(HashMap::new()));
         let (pause_requester, pause_requests) = mpsc::channel(10);
-        let ingest = Ingest::new(ingest_id, conn, pause_requests);
+        let (latest_ingest_time_sender, latest_ingest_time) = watch::channel(start_time);
+        let ingest = Ingest::new(ingest_id, conn, pause_requests, latest_ingest_time_sender, debug_history);
         let debug_history = ingest.debug_history.clone();
+        let state = ingest.state.clone();
+        let timed_events = ingest.timed_events.clone();
+        let applied_events = ingest.applied_events.clone();
+        let event_log = ingest.event_log.clone();
 
-        tokio::spawn(run_ingest(ingest, start_time_parsed));
+        let handle = tokio::spawn(run(ingest));
 
         IngestTask {
             ingest_id,
             pending_approvals: approvals,
             debug_history,
-            pause_requester: Arc::new(TokioMutex::new(pause_requester)),
+            state,
+            timed_events,
+            applied_events,
+            event_log,
+            start_time,
+            pause_requester,
             resumer: None,
+            latest_ingest_time,
+            handle,
         }
     }
 
+    pub fn ingest_id(&self) -> i32 {
+        self.ingest_id
+    }
+
+    // The time the ingest has caught up with the Feed through. This advances even when the Feed
+    // has no new events.
+    pub fn latest_ingest_time(&self) -> DateTime<Utc> {
+        *self.latest_ingest_time.borrow()
+    }
+
+    pub fn export_event_log(&self) -> EventLogExport {
+        self.event_log.lock().unwrap().export(self.start_time)
+    }
+
+    // The ingest pauses before it processes its next item, and stays paused until the returned
+    // handle is resumed
+    pub fn pause(&self) -> Result<ResumeHandle, PauseError> {
+        let (resumer, resume_receiver) = oneshot::channel();
+        self.pause_requester.try_send(resume_receiver)
+            .map_err(|e| match e {
+                mpsc::error::TrySendError::Full(_) => { PauseError::TooManyPendingPauses }
+                mpsc::error::TrySendError::Closed(_) => { PauseError::Stopped }
+            })?;
+        Ok(ResumeHandle { resumer })
+    }
+
+    // Used when replacing the current ingest, since two ingests running at once would fight over
+    // approvals
+    pub fn stop(&self) {
+        self.handle.abort();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
     pub fn notify_approval(&self, id: i32, result: bool) {
         let mut pending_approvals = self.pending_approvals.lock().unwrap();
         if let Some(sender) = pending_approvals.remove(&id) {
@@ -114,6 +361,7 @@ impl IngestTask {
 pub struct DebugTreeNode {
     pub description: String,
     pub is_ambiguous: bool,
+    pub confidence: f32,
     pub created_at: DateTime<Utc>,
     pub observed_at: Option<DateTime<Utc>>,
     pub added_reason: AddedReason,
@@ -142,31 +390,74 @@ pub struct DebugHistoryVersion {
 
 pub struct DebugHistoryItem {
     pub entity_human_name: String,
-    pub versions: Vec<DebugHistoryVersion>,
+    pub versions: VecDeque<DebugHistoryVersion>,
+    // How many versions have been dropped off the front to stay under the cap. Indices into the
+    // history count these, so the index of a version doesn't change when older ones are dropped.
+    pub dropped_versions: usize,
+}
+
+impl DebugHistoryItem {
+    pub fn new(entity_human_name: String, first_version: DebugHistoryVersion) -> Self {
+        Self {
+            entity_human_name,
+            versions: VecDeque::from([first_version]),
+            dropped_versions: 0,
+        }
+    }
+
+    pub fn latest_version(&self) -> Option<&DebugHistoryVersion> {
+        self.versions.back()
+    }
+
+    pub fn get_version(&self, index: usize) -> Option<&DebugHistoryVersion> {
+        self.versions.get(index.checked_sub(self.dropped_versions)?)
+    }
+
+    pub fn iter_versions(&self) -> impl DoubleEndedIterator<Item=(usize, &DebugHistoryVersion)> {
+        let dropped_versions = self.dropped_versions;
+        self.versions.iter()
+            .enumerate()
+            .map(move |(i, version)| (i + dropped_versions, version))
+    }
+
+    fn truncate_to(&mut self, max_versions: usize) {
+        while self.versions.len() > max_versions {
+            self.versions.pop_front();
+            self.dropped_versions += 1;
+        }
+    }
 }
 
 pub struct GraphDebugHistory {
     disabled: bool,
+    max_versions: usize,
     inner: HashMap<(EntityType, Uuid), DebugHistoryItem>,
 }
 
 impl GraphDebugHistory {
-    pub fn new(disabled: bool) -> Self {
+    pub fn new(disabled: bool, max_versions: usize) -> Self {
         Self {
             disabled,
+            // Every recorded entity keeps at least its latest version, because the debug view needs
+            // something to show. Turn the history off entirely with `disabled` instead.
+            max_versions: max_versions.max(1),
             inner: Default::default(),
         }
     }
 
-    pub fn push_item(&mut self, key: (EntityType, Uuid), item: DebugHistoryItem) {
+    pub fn push_item(&mut self, key: (EntityType, Uuid), mut item: DebugHistoryItem) {
         if self.disabled { return }
+        item.truncate_to(self.max_versions);
         self.inner.insert(key, item);
     }
 
-    // Shortcut for push_version
-    pub fn push(&mut self, key: &(EntityType, Uuid), version: DebugHistoryVersion) {
+    // Shortcut for push_version. Takes a closure because building the debug tree is expensive, and
+    // there's no need to do it when recording is disabled.
+    pub fn push(&mut self, key: &(EntityType, Uuid), make_version: impl FnOnce() -> DebugHistoryVersion) {
         if self.disabled { return }
-        self.inner.get_mut(key).unwrap().versions.push(version);
+        let item = self.inner.get_mut(key).unwrap();
+        item.versions.push_back(make_version());
+        item.truncate_to(self.max_versions);
     }
 
     pub fn iter(&self) -> impl Iterator<Item=(&(EntityType, Uuid), &DebugHistoryItem)> {
@@ -180,24 +471,304 @@ impl GraphDebugHistory {
 
 pub type GraphDebugHistorySync = Arc<TokioMutex<GraphDebugHistory>>;
 
+// Feed events that have been applied, keyed by (game id, play number)
+pub type AppliedEvents = Arc<StdMutex<AppliedEventsRecorder>>;
+
+// How many plays' worth of applied events to keep. This is only for the debug view, so once it's
+// full the oldest plays are forgotten.
+pub const APPLIED_EVENTS_CAP: usize = 50_000;
+
+#[derive(Debug)]
+pub struct AppliedEventsRecorder {
+    events: HashMap<(Uuid, i64), Vec<serde_json::Value>>,
+    // Keys in the order they were first recorded, for eviction
+    order: VecDeque<(Uuid, i64)>,
+    cap: usize,
+}
+
+impl Default for AppliedEventsRecorder {
+    fn default() -> Self {
+        Self::new(APPLIED_EVENTS_CAP)
+    }
+}
+
+impl AppliedEventsRecorder {
+    pub fn new(cap: usize) -> Self {
+        Self {
+            events: HashMap::new(),
+            order: VecDeque::new(),
+            cap,
+        }
+    }
+
+    pub fn record(&mut self, key: (Uuid, i64), event: serde_json::Value) {
+        if !self.events.contains_key(&key) {
+            while self.order.len() >= self.cap {
+                let Some(oldest) = self.order.pop_front() else { break };
+                self.events.remove(&oldest);
+            }
+            self.order.push_back(key);
+        }
+        self.events.entry(key).or_default().push(event);
+    }
+
+    pub fn get(&self, key: &(Uuid, i64)) -> Option<&Vec<serde_json::Value>> {
+        self.events.get(key)
+    }
+}
+
+// Every event and observation that was ingested, in the order they were ingested, plus the state
+// the ingest started from. Events include timed events and the ones Blarser makes up, so together
+// this is enough to reproduce the ingest without going to Chronicler or the Feed.
+pub type EventLog = Arc<StdMutex<EventLogRecorder>>;
+
+// The log stops recording once it has this many entries, so it can't grow without bound. What's
+// kept is still a complete record of the start of the ingest, which can be replayed as-is.
+pub const EVENT_LOG_CAP: usize = 200_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum EventLogEntry {
+    // Stored as JSON so exporting doesn't need the events themselves to be Clone
+    Event { event: serde_json::Value },
+    // `approved` is for observations that were ingested again after a human approved them
+    Observation { observation: Observation, approved: bool },
+}
+
+impl EventLogEntry {
+    pub fn event(event: &AnyEvent) -> Self {
+        EventLogEntry::Event {
+            event: serde_json::to_value(event).expect("Serializing an event should never fail"),
+        }
+    }
+
+    pub fn into_replay_item(self) -> serde_json::Result<ReplayItem> {
+        match self {
+            EventLogEntry::Event { event } => {
+                serde_json::from_value::<AnyEvent>(event).map(ReplayItem::Event)
+            }
+            EventLogEntry::Observation { observation, approved } => {
+                Ok(ReplayItem::Observation { observation, approved })
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct EventLogRecorder {
+    // None if the ingest didn't start from Chronicler, e.g. if it was restored from a snapshot
+    initial_state: Option<Vec<Observation>>,
+    entries: Vec<EventLogEntry>,
+    cap: usize,
+    truncated: bool,
+}
+
+impl Default for EventLogRecorder {
+    fn default() -> Self {
+        Self::new(EVENT_LOG_CAP)
+    }
+}
+
+impl EventLogRecorder {
+    pub fn new(cap: usize) -> Self {
+        Self {
+            initial_state: None,
+            entries: Vec::new(),
+            cap,
+            truncated: false,
+        }
+    }
+
+    pub fn set_initial_state(&mut self, initial_state: Vec<Observation>) {
+        self.initial_state = Some(initial_state);
+    }
+
+    pub fn push(&mut self, entry: EventLogEntry) {
+        if self.entries.len() >= self.cap {
+            if !self.truncated {
+                warn!("Event log reached {} entries; not recording any more", self.cap);
+                self.truncated = true;
+            }
+            return;
+        }
+        self.entries.push(entry);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    pub fn export(&self, start_time: DateTime<Utc>) -> EventLogExport {
+        EventLogExport {
+            start_time,
+            initial_state: self.initial_state.clone(),
+            entries: self.entries.clone(),
+            truncated: self.truncated,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventLogExport {
+    pub start_time: DateTime<Utc>,
+    pub initial_state: Option<Vec<Observation>>,
+    pub entries: Vec<EventLogEntry>,
+    // If this is set the log stopped recording partway through the ingest. Replaying it still
+    // works, it just stops early.
+    pub truncated: bool,
+}
+
+// An EventLogEntry with its event parsed, ready to be replayed
+pub enum ReplayItem {
+    Event(AnyEvent),
+    Observation { observation: Observation, approved: bool },
+}
+
+// Everything needed to resume an ingest from `time`. Timed events are stored as JSON for the same
+// reason as in EventLogExport.
+#[derive(Serialize, Deserialize)]
+pub struct IngestSnapshot {
+    pub time: DateTime<Utc>,
+    pub state: StateSnapshot,
+    pub timed_events: Vec<serde_json::Value>,
+}
+
+// A snapshot that's been fully parsed and turned back into ingest state. Everything that can fail
+// happens in load_snapshot, so a bad snapshot falls back to a full reload instead of crashing the
+// ingest halfway through restoring it.
+pub struct RestoredSnapshot {
+    pub time: DateTime<Utc>,
+    pub state: StateGraph,
+    pub timed_events: TimedEventQueue,
+}
+
+fn load_snapshot() -> Option<RestoredSnapshot> {
+    let bytes = match std::fs::read(SNAPSHOT_PATH) {
+        Ok(bytes) => { bytes }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => { return None }
+        Err(e) => {
+            warn!("Couldn't read the ingest snapshot: {e}");
+            return None;
+        }
+    };
+
+    let snapshot: IngestSnapshot = match serde_json::from_slice(&bytes) {
+        Ok(snapshot) => { snapshot }
+        Err(e) => {
+            warn!("Couldn't parse the ingest snapshot: {e}");
+            return None;
+        }
+    };
+
+    let timed_events = match snapshot.timed_events.into_iter()
+        .map(serde_json::from_value::<AnyEvent>)
+        .collect::<Result<Vec<_>, _>>() {
+        Ok(timed_events) => { timed_events }
+        Err(e) => {
+            warn!("Ingest snapshot contained an invalid timed event: {e}");
+            return None;
+        }
+    };
+
+    let state = match StateGraph::from_snapshot(snapshot.state) {
+        Ok(state) => { state }
+        Err(e) => {
+            warn!("Couldn't restore the ingest snapshot: {e}");
+            return None;
+        }
+    };
+
+    Some(RestoredSnapshot {
+        time: snapshot.time,
+        state,
+        timed_events: TimedEventQueue::from(timed_events),
+    })
+}
+
+// Call with the time of the next item, before it's ingested
+pub(crate) async fn save_snapshot(ingest: &Ingest, time: DateTime<Utc>) {
+    let snapshot = IngestSnapshot {
+        time,
+        state: ingest.state.lock().unwrap().snapshot(),
+        timed_events: ingest.timed_events.lock().unwrap().sorted().into_iter()
+            .map(|event| serde_json::to_value(event).expect("Timed events should always be serializable"))
+            .collect(),
+    };
+    let bytes = serde_json::to_vec(&snapshot)
+        .expect("Snapshots should always be serializable");
+
+    // Written to the side and then renamed over the old one, so a crash mid-write can't leave a
+    // truncated snapshot behind
+    let temp_path = format!("{SNAPSHOT_PATH}.tmp");
+    let result = match tokio::fs::write(&temp_path, bytes).await {
+        Ok(()) => { tokio::fs::rename(&temp_path, SNAPSHOT_PATH).await }
+        Err(e) => { Err(e) }
+    };
+    match result {
+        Ok(()) => { info!("Saved ingest snapshot at {time}") }
+        Err(e) => { warn!("Couldn't save the ingest snapshot: {e}") }
+    }
+}
+
 pub struct Ingest {
     pub ingest_id: i32,
     pub db: BlarserDbConn,
     pub pending_approvals: Arc<StdMutex<HashMap<i32, oneshot::Sender<bool>>>>,
     pub state: Arc<StdMutex<StateGraph>>,
+    // Shared so the debug routes can see what's coming up
+    pub timed_events: Arc<StdMutex<TimedEventQueue>>,
+    pub applied_events: AppliedEvents,
+    pub event_log: EventLog,
     pub debug_history: GraphDebugHistorySync,
     pub pause_request: mpsc::Receiver<oneshot::Receiver<()>>,
+    pub approval_policies: ApprovalPolicies,
+    pub dedup_windows: DedupWindows,
+    pub ambiguity_strategy: AmbiguityStrategy,
+    pub field_weights: FieldWeights,
+    // Conflicts that need a human to look at them. Observation ingest can't wait for approvals
+    // itself because it holds the state lock, so they get queued here.
+    pub queued_approvals: Vec<QueuedApproval>,
+    // Same deal for conflicts, which get written to the db after each observation
+    pub recorded_conflicts: Vec<NewConflict>,
+    // If this is set, observations of any other entity are skipped
+    pub focus: Option<HashSet<(EntityType, Uuid)>>,
+    // If this is set, an observation that can't be placed anywhere pauses ingest for approval.
+    // Otherwise it's logged and skipped.
+    pub pause_on_rejected_observations: bool,
+    pub latest_ingest_time: watch::Sender<DateTime<Utc>>,
+    // Only the live ingest saves snapshots. Focused ingests and backfills would overwrite its
+    // snapshot with a state that it can't resume from.
+    pub save_snapshots: bool,
 }
 
 impl Ingest {
-    pub fn new(ingest_id: i32, db: BlarserDbConn, pause_request: mpsc::Receiver<oneshot::Receiver<()>>) -> Self {
+    pub fn new(ingest_id: i32, db: BlarserDbConn, pause_request: mpsc::Receiver<oneshot::Receiver<()>>,
+               latest_ingest_time: watch::Sender<DateTime<Utc>>, debug_history: GraphDebugHistory) -> Self {
         Self {
             ingest_id,
             db,
             pending_approvals: Arc::new(StdMutex::new(Default::default())),
             state: Arc::new(StdMutex::new(StateGraph::new())),
-            debug_history: Arc::new(TokioMutex::new(GraphDebugHistory::new(false))),
+            timed_events: Arc::new(StdMutex::new(TimedEventQueue::new())),
+            applied_events: Arc::new(StdMutex::new(AppliedEventsRecorder::default())),
+            event_log: Arc::new(StdMutex::new(EventLogRecorder::default())),
+            debug_history: Arc::new(TokioMutex::new(debug_history)),
             pause_request,
+            approval_policies: Default::default(),
+            dedup_windows: Default::default(),
+            ambiguity_strategy: Default::default(),
+            field_weights: Default::default(),
+            queued_approvals: Vec::new(),
+            recorded_conflicts: Vec::new(),
+            focus: None,
+            pause_on_rejected_observations: false,
+            latest_ingest_time,
+            save_snapshots: false,
         }
     }
 
@@ -210,9 +781,9 @@ impl Ingest {
         }).await
     }
 
-    pub async fn get_approval(&mut self, entity_type: EntityType, entity_id: Uuid, perceived_at: DateTime<Utc>, message: String) -> QueryResult<bool> {
+    pub async fn get_approval(&mut self, entity_type: EntityType, entity_id: Uuid, perceived_at: DateTime<Utc>, message: String, severity: Severity) -> QueryResult<bool> {
         let result = self.run(move |mut state| {
-            state.upsert_approval(entity_type, entity_id, perceived_at, &message)
+            state.upsert_approval(entity_type, entity_id, perceived_at, &message, severity)
         }).await?;
 
         match result {
@@ -232,4 +803,143 @@ impl Ingest {
             ApprovalState::Rejected => { Ok(false) }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use uuid::Uuid;
+    use chrono::{DateTime, Duration, Utc};
+    use tokio::sync::{mpsc, watch};
+    use crate::db::tests::{insert_test_ingest, test_db_conn};
+    use crate::state::EntityType;
+    use super::{approval_focus, backfill_resume_point, AppliedEventsRecorder, FOCUS_LEAD_MINUTES, DebugHistoryItem, DebugHistoryVersion, DebugTree, EventLogEntry, EventLogRecorder, GraphDebugHistory, Ingest};
+
+    // An ingest with its own row in the test database and nothing in its state
+    pub(crate) async fn test_ingest() -> Ingest {
+        let conn = test_db_conn().await;
+        let ingest_id = conn.run(insert_test_ingest).await;
+        // Nothing can pause it, which is fine because nothing in a test wants to
+        let (_, pause_requests) = mpsc::channel(1);
+        let (latest_ingest_time, _) = watch::channel(Utc::now());
+        Ingest::new(ingest_id, conn, pause_requests, latest_ingest_time, GraphDebugHistory::new(true, 0))
+    }
+
+    #[test]
+    fn applied_events_forget_the_oldest_plays_at_their_cap() {
+        let game_id = Uuid::new_v4();
+        let mut applied_events = AppliedEventsRecorder::new(2);
+        applied_events.record((game_id, 1), serde_json::Value::Null);
+        applied_events.record((game_id, 2), serde_json::Value::Null);
+        // Another event for a play that's already recorded doesn't evict anything
+        applied_events.record((game_id, 2), serde_json::Value::Null);
+        assert!(applied_events.get(&(game_id, 1)).is_some());
+        assert_eq!(applied_events.get(&(game_id, 2)).map(Vec::len), Some(2));
+
+        applied_events.record((game_id, 3), serde_json::Value::Null);
+        assert!(applied_events.get(&(game_id, 1)).is_none());
+        assert!(applied_events.get(&(game_id, 2)).is_some());
+        assert!(applied_events.get(&(game_id, 3)).is_some());
+    }
+
+    #[test]
+    fn event_log_stops_recording_at_its_cap() {
+        let mut event_log = EventLogRecorder::new(2);
+        for _ in 0..3 {
+            event_log.push(EventLogEntry::Event { event: serde_json::Value::Null });
+        }
+
+        assert_eq!(event_log.len(), 2);
+        assert!(event_log.is_truncated());
+    }
+
+    fn empty_version() -> DebugHistoryVersion {
+        DebugHistoryVersion {
+            event_human_name: String::new(),
+            time: Utc::now(),
+            tree: DebugTree {
+                generations: Vec::new(),
+                edges: Default::default(),
+                data: Default::default(),
+                roots: Vec::new(),
+                leafs: Vec::new(),
+            },
+            queued_for_update: None,
+            currently_updating: None,
+            queued_for_delete: None,
+        }
+    }
+
+    #[test]
+    fn a_debug_history_cap_of_zero_still_keeps_the_latest_version() {
+        let key = (EntityType::Player, Uuid::new_v4());
+        let mut history = GraphDebugHistory::new(false, 0);
+        history.push_item(key, DebugHistoryItem::new(String::new(), empty_version()));
+        history.push(&key, empty_version);
+
+        let item = history.get(&key).expect("The item was just pushed");
+        assert!(item.latest_version().is_some());
+        assert_eq!(item.versions.len(), 1);
+        assert_eq!(item.dropped_versions, 1);
+    }
+
+    fn unfinished_backfill(backfilled_to: Option<DateTime<Utc>>) -> crate::db::Ingest {
+        let now = Utc::now();
+        crate::db::Ingest {
+            id: 1,
+            started_at: now,
+            archived: false,
+            backfill_until: Some(now),
+            backfilled_to,
+        }
+    }
+
+    #[test]
+    fn backfill_resumes_from_its_progress() {
+        let unfinished = unfinished_backfill(Some(Utc::now() - Duration::hours(3)));
+        let (start_time, config) = backfill_resume_point(&unfinished, Some(Duration::minutes(10)))
+            .expect("A backfill with progress should be resumable");
+        assert_eq!(Some(start_time), unfinished.backfilled_to);
+        assert_eq!(Some(config.until), unfinished.backfill_until);
+        assert_eq!(config.chunk_size, Duration::minutes(10));
+    }
+
+    #[test]
+    fn backfill_without_progress_cant_be_resumed() {
+        assert!(backfill_resume_point(&unfinished_backfill(None), None).is_none());
+    }
+
+    fn approval(entity_type: EntityType, entity_id: Uuid, perceived_at: DateTime<Utc>) -> crate::db::Approval {
+        crate::db::Approval {
+            id: 1,
+            entity_type,
+            entity_id,
+            perceived_at,
+            message: String::new(),
+            approved: None,
+            explanation: None,
+            severity: 1,
+        }
+    }
+
+    #[test]
+    fn focused_ingest_covers_only_the_approvals_entities() {
+        let (player_id, team_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let earliest = Utc::now() - Duration::hours(1);
+        let approvals = [
+            approval(EntityType::Player, player_id, earliest + Duration::minutes(30)),
+            approval(EntityType::Team, team_id, earliest),
+            // A second approval for the same entity doesn't add anything
+            approval(EntityType::Player, player_id, earliest + Duration::minutes(45)),
+        ];
+
+        let (focus, start_time) = approval_focus(&approvals)
+            .expect("There are approvals to focus on");
+        assert_eq!(focus, [(EntityType::Player, player_id), (EntityType::Team, team_id)].into_iter().collect());
+        assert_eq!(start_time, earliest - Duration::minutes(FOCUS_LEAD_MINUTES));
+    }
+
+    #[test]
+    fn no_approvals_means_nothing_to_focus_on() {
+        assert!(approval_focus(&[]).is_none());
+    }
+}
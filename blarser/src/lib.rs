@@ -1,6 +1,7 @@
 #![feature(let_chains)]
 #![feature(trivial_bounds)] // Necessary for partial_information
 #![feature(min_specialization)] // Used for Event/Entity interaction
+#![cfg_attr(test, feature(test))] // For benchmarks
 #![recursion_limit = "256"]
 
 #[macro_use]
@@ -8,7 +9,7 @@ extern crate diesel;
 extern crate core;
 
 pub mod ingest;
-mod api;
+pub mod api;
 pub mod db;
 #[allow(unused_imports)]
 pub mod schema;
@@ -2,12 +2,12 @@
 
 use rocket::fairing::{AdHoc, Fairing, Info, Kind};
 use rocket::fs::{FileServer, relative};
-use rocket::{Error, Request, Response};
+use rocket::{Build, Error, Request, Response, Rocket};
 use rocket::http::Header;
 use rocket_dyn_templates::Template;
-use blarser::ingest::{IngestTaskHolder, IngestTask};
+use blarser::ingest::{IngestTaskHolder, IngestTask, IngestConfig};
 use blarser::db::{BlarserDbConn};
-use routes::{index, approvals, approve, debug, entity_debug_json, /*entities*/};
+use routes::{index, approvals, approve, debug, entity_debug_json, export_conflicts, export_approvals, ingests, ingest, archive_ingest, restore_ingest, entity_ids, create_bookmark, bookmarks, entities};
 
 mod routes;
 mod debug_routes;
@@ -32,12 +32,11 @@ impl Fairing for CORS {
 }
 
 
-// Using main as an entry point instead of rocket::launch because CLion doesn't understand launch
-#[rocket::main]
-async fn main() -> Result<(), Error> {
-    let _ = rocket::build()
+// Split out of main so tests can build the same app
+fn rocket() -> Rocket<Build> {
+    rocket::build()
         .mount("/public", FileServer::from(relative!("static")))
-        .mount("/", rocket::routes![index, approvals, approve, debug, entity_debug_json, /*entities*/])
+        .mount("/", rocket::routes![index, approvals, approve, debug, entity_debug_json, export_conflicts, export_approvals, ingests, ingest, archive_ingest, restore_ingest, entity_ids, create_bookmark, bookmarks, entities])
         .mount("/api/debug", debug_routes::routes())
         .attach(BlarserDbConn::fairing())
         .attach(Template::fairing())
@@ -47,10 +46,15 @@ async fn main() -> Result<(), Error> {
             let conn = BlarserDbConn::get_one(rocket).await.unwrap();
             let task_holder: &IngestTaskHolder = rocket.state().unwrap();
 
-            let ingest_task = IngestTask::new(conn).await;
+            let ingest_task = IngestTask::new(conn, IngestConfig::from_env()).await;
             let mut task_mut = task_holder.latest_ingest.lock().unwrap();
             *task_mut = Some(ingest_task);
         })))
-        .launch().await?;
+}
+
+// Using main as an entry point instead of rocket::launch because CLion doesn't understand launch
+#[rocket::main]
+async fn main() -> Result<(), Error> {
+    let _ = rocket().launch().await?;
     Ok(())
 }
@@ -1,15 +1,15 @@
 macro_rules! polymorphic_enum {
-    ($(#[$meta:meta])* $name:ident: $macro:ident { $($variant:ident($type:path),)* }) => {
+    ($(#[$meta:meta])* $name:ident: $macro:ident { $($variant:ident($type:ty),)* }) => {
         polymorphic_enum! { () $(#[$meta])* $name: $macro { $($variant($type),)* } }
     };
-    ($(#[$meta:meta])* pub $name:ident: $macro:ident { $($variant:ident($type:path),)* }) => {
+    ($(#[$meta:meta])* pub $name:ident: $macro:ident { $($variant:ident($type:ty),)* }) => {
         polymorphic_enum! { (pub) $(#[$meta])* $name: $macro { $($variant($type),)* } }
     };
-    ($(#[$meta:meta])* pub(crate) $name:ident: $macro:ident { $($variant:ident($type:path),)* }) => {
+    ($(#[$meta:meta])* pub(crate) $name:ident: $macro:ident { $($variant:ident($type:ty),)* }) => {
         polymorphic_enum! { (pub(crate)) $(#[$meta])* $name: $macro { $($variant($type),)* } }
     };
 
-    (($($vis:tt)*) $(#[$meta:meta])* $name:ident: $macro:ident { $($variant:ident($type:path),)* }) => {
+    (($($vis:tt)*) $(#[$meta:meta])* $name:ident: $macro:ident { $($variant:ident($type:ty),)* }) => {
         $(#[$meta])*
         $($vis)* enum $name { $($variant($type)),* }
         #[allow(unused_macros)]
@@ -7,25 +7,34 @@ use rocket::{
 use diesel::result::Error as DieselError;
 use rocket_dyn_templates::Template;
 use serde::Serialize;
+use partial_information::Severity;
 
 use blarser::ingest::IngestTaskHolder;
 use blarser::db::{BlarserDbConn, get_pending_approvals, Approval, set_approval};
 use crate::routes::{ApiError, rocket_uri_macro_index};
 
-#[rocket::get("/approvals")]
-pub async fn approvals(conn: BlarserDbConn) -> Result<Template, ApiError> {
-    let approvals = conn.run(|c| {
-        get_pending_approvals(c)
+// min_severity is one of "info", "warn", or "error". Leaving it out shows everything.
+#[rocket::get("/approvals?<min_severity>")]
+pub async fn approvals(conn: BlarserDbConn, min_severity: Option<String>) -> Result<Template, ApiError> {
+    let min_severity = match min_severity {
+        Some(min_severity) => min_severity.parse().map_err(ApiError::ParseError)?,
+        None => Severity::Info,
+    };
+
+    let approvals = conn.run(move |c| {
+        get_pending_approvals(c, min_severity)
     }).await
         .map_err(|err: DieselError| ApiError::InternalError(err.to_string()))?;
 
     #[derive(Serialize)]
     struct ApprovalTemplateParams {
         approvals: Vec<Approval>,
+        min_severity: Severity,
     }
 
     Ok(Template::render("approvals", ApprovalTemplateParams {
         approvals,
+        min_severity,
     }))
 }
 
@@ -42,7 +51,7 @@ pub async fn approve(task: &State<IngestTaskHolder>, conn: BlarserDbConn, approv
     let redirect_to = if approval.from_route == "index" {
         Ok(uri!(index))
     } else if approval.from_route == "approvals" {
-        Ok(uri!(approvals))
+        Ok(uri!(approvals(_)))
     } else {
         Err(ApiError::InternalError(format!("Unexpected value in from_route: {}", approval.from_route)))
     }?;
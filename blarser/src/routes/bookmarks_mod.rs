@@ -0,0 +1,57 @@
+use chrono::{DateTime, Utc};
+use rocket::State;
+use rocket::serde::json::Json;
+use diesel::result::Error as DieselError;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use blarser::ingest::IngestTaskHolder;
+use blarser::db::{BlarserDbConn, Bookmark, NewBookmark, get_bookmarks_for_ingest, insert_bookmark};
+use blarser::state::EntityType;
+use crate::routes::ApiError;
+
+#[derive(Deserialize)]
+pub struct BookmarkRequest {
+    // Defaults to the running ingest
+    ingest_id: Option<i32>,
+    time: DateTime<Utc>,
+    entity_type: EntityType,
+    entity_id: Uuid,
+    note: String,
+}
+
+#[rocket::post("/bookmarks", data = "<bookmark>")]
+pub async fn create_bookmark(task: &State<IngestTaskHolder>, conn: BlarserDbConn, bookmark: Json<BookmarkRequest>) -> Result<Json<Bookmark>, ApiError> {
+    let bookmark = bookmark.into_inner();
+    let ingest_id = bookmark.ingest_id
+        .or_else(|| task.latest_ingest_id())
+        .ok_or_else(|| ApiError::InternalError("No ingest to bookmark".to_string()))?;
+
+    let new_bookmark = NewBookmark {
+        ingest_id,
+        time: bookmark.time,
+        entity_type: bookmark.entity_type,
+        entity_id: bookmark.entity_id,
+        note: bookmark.note,
+    };
+    let bookmark = conn.run(move |c| {
+        insert_bookmark(c, new_bookmark)
+    }).await
+        .map_err(|err: DieselError| ApiError::InternalError(err.to_string()))?;
+
+    Ok(Json(bookmark))
+}
+
+#[rocket::get("/bookmarks?<ingest_id>")]
+pub async fn bookmarks(task: &State<IngestTaskHolder>, conn: BlarserDbConn, ingest_id: Option<i32>) -> Result<Json<Vec<Bookmark>>, ApiError> {
+    let ingest_id = ingest_id
+        .or_else(|| task.latest_ingest_id())
+        .ok_or_else(|| ApiError::InternalError("No ingest yet".to_string()))?;
+
+    let bookmarks = conn.run(move |c| {
+        get_bookmarks_for_ingest(c, ingest_id)
+    }).await
+        .map_err(|err: DieselError| ApiError::InternalError(err.to_string()))?;
+
+    Ok(Json(bookmarks))
+}
@@ -1,232 +1,367 @@
-// use std::io::{Cursor, Read, Write};
-// use std::ops::Deref;
-// use byteorder::{NativeEndian, ReadBytesExt, WriteBytesExt};
-// use chrono::{DateTime, Duration, TimeZone, Utc};
-// use rocket::{State, FromForm, form::{self, FromFormField}};
-// use serde::{Serialize, Serializer};
-// use serde::ser::Error;
-// use serde_json::{Value, json, Map};
-// use uuid::Uuid;
-// use diesel::prelude::*;
-// use diesel::Queryable;
-//
-// use blarser::db::BlarserDbConn;
-// use blarser::ingest::IngestTaskHolder;
-// use crate::routes::ApiError;
-//
-// pub struct ParseableDateTime(DateTime<Utc>);
-//
-// #[rocket::async_trait]
-// impl<'r> FromFormField<'r> for ParseableDateTime {
-//     fn from_value(field: form::ValueField<'r>) -> form::Result<'r, Self> {
-//         DateTime::parse_from_rfc3339(field.value)
-//             .map(|d| Self(d.with_timezone(&Utc)))
-//             .map_err(|e| form::Error::validation(e.to_string()).into())
-//     }
-// }
-//
-// impl Deref for ParseableDateTime {
-//     type Target = DateTime<Utc>;
-//
-//     fn deref(&self) -> &Self::Target {
-//         &self.0
-//     }
-// }
-//
-// pub struct UuidList(Vec<Uuid>);
-//
-// impl UuidList {
-//     pub fn into_inner(self) -> Vec<Uuid> { self.0 }
-// }
-//
-// #[rocket::async_trait]
-// impl<'r> FromFormField<'r> for UuidList {
-//     fn from_value(field: form::ValueField<'r>) -> form::Result<'r, Self> {
-//         let uuids = field.value.split(',')
-//             .map(|id_str| Uuid::parse_str(id_str)
-//                 .map_err(|e| form::Error::validation(e.to_string()).into()))
-//             .collect::<form::Result<_>>()?;
-//         Ok(Self(uuids))
-//     }
-// }
-//
-// impl Deref for UuidList {
-//     type Target = Vec<Uuid>;
-//
-//     fn deref(&self) -> &Self::Target {
-//         &self.0
-//     }
-// }
-//
-// pub struct PageToken {
-//     id: Uuid,
-//     time: DateTime<Utc>,
-// }
-//
-// fn blaseball_epoch() -> DateTime<Utc> {
-//     Utc.ymd(2020, 7, 1).and_hms(0, 0, 0)
-// }
-//
-// #[rocket::async_trait]
-// impl<'r> FromFormField<'r> for PageToken {
-//     fn from_value(field: form::ValueField<'r>) -> form::Result<'r, Self> {
-//         let bytes = base64::decode(field.value.replace('-', "+").replace('_', "/"))
-//             .map_err(|e| form::Error::validation(e.to_string()))?;
-//
-//         if bytes.len() != 24 {
-//             return Err(form::Error::validation("must be 24 bytes long".to_string()).into());
-//         }
-//
-//         let mut bytes = Cursor::new(bytes);
-//
-//         let mut uuid_bytes: [u8; 16] = Default::default();
-//         bytes.read_exact(&mut uuid_bytes)
-//             .map_err(|e| form::Error::validation(e.to_string()))?;
-//         let uuid = Uuid::from_bytes(uuid_bytes);
-//
-//         let time_offset = bytes.read_i64::<NativeEndian>()
-//             .map_err(|e| form::Error::validation(e.to_string()))?;
-//         let time = blaseball_epoch() + Duration::nanoseconds(time_offset * 100);
-//
-//         Ok(Self { id: uuid, time })
-//     }
-// }
-//
-// impl Serialize for PageToken {
-//     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
-//         let mut bytes = Cursor::new(Vec::<u8>::new());
-//         bytes.write(self.id.as_bytes())
-//             .map_err(|e| S::Error::custom(e.to_string()))?;
-//
-//         // TODO Implement a num_ticks in the style of num_nanoseconds and that won't require checked
-//         //  multiplication
-//         let time_offset = (self.time - blaseball_epoch()).num_nanoseconds().map(|n| n / 100)
-//             .expect("time_offset overflowed");
-//         bytes.write_i64::<NativeEndian>(time_offset)
-//             .map_err(|e| S::Error::custom(e.to_string()))?;
-//
-//         // TODO Use a Config object to get the proper character set instead of hacky string replace
-//         let str = base64::encode(bytes.into_inner()).replace('+', "-").replace('/', "_");
-//         serializer.serialize_str(&str)
-//     }
-// }
-//
-// #[derive(FromForm)]
-// pub struct EntitiesParams {
-//     r#type: String,
-//     at: Option<ParseableDateTime>,
-//     count: Option<i64>,
-//     id: Option<UuidList>,
-//     page: Option<PageToken>,
-//     all: Option<bool>,
-// }
-//
-// #[derive(Serialize, Queryable)]
-// #[serde(rename = "camelCase")]
-// pub struct EntityVersions {
-//     pub entity_id: Uuid,
-//     pub valid_from: DateTime<Utc>,
-//     pub valid_to: Option<DateTime<Utc>>,
-//     pub data: Vec<Value>,
-// }
-//
-// #[derive(Serialize)]
-// #[serde(rename = "camelCase")]
-// pub struct EntityVersion {
-//     pub entity_id: Uuid,
-//     pub valid_from: DateTime<Utc>,
-//     pub valid_to: Option<DateTime<Utc>>,
-//     pub data: Value,
-// }
-//
-// impl EntityVersion {
-//     fn from_versions(entity_type: &str, versions: EntityVersions) -> Self {
-//         Self {
-//             entity_id: versions.entity_id,
-//             valid_from: versions.valid_from,
-//             valid_to: versions.valid_to,
-//             data: versions.data.into_iter().next()
-//                 .map(|value| {
-//                     entity_to_raw_approximation(entity_type, value)
-//                 })
-//                 .unwrap_or_else(|| Value::Object(Map::new())),
-//         }
-//     }
-// }
-//
-// #[rocket::get("/entities?<params..>")]
-// pub async fn entities(conn: BlarserDbConn, ingest: &State<IngestTaskHolder>, params: Result<EntitiesParams, form::Errors<'_>>) -> Result<Value, ApiError> {
-//     let params = params.map_err(|e| ApiError::ParseError(e.to_string()))?;
-//     let ingest_id = ingest.latest_ingest_id()
-//         .ok_or_else(|| ApiError::InternalError("No ingest yet".to_string()))?;
-//
-//     let wants_all = params.all.unwrap_or(false);
-//     let entity_type = params.r#type.clone();
-//     let results = conn.run(move |c| {
-//         use blarser::schema::versions_with_end::dsl as versions;
-//         use diesel::dsl::sql;
-//         use diesel::sql_types::{Array, Jsonb};
-//
-//         // Need to repeat this part because distinct_on fails to compile on a boxed query
-//         let query = versions::versions_with_end
-//             // Group results for each entity (end_time should never create a separate group)
-//             .group_by((versions::entity_id, versions::start_time, versions::end_time))
-//             .select((versions::entity_id, versions::start_time, versions::end_time, sql::<Array<Jsonb>>("array_agg(data) AS data")))
-//             // Is from the right ingest
-//             .filter(versions::ingest_id.eq(ingest_id))
-//             // Has the right entity type
-//             .filter(versions::entity_type.eq(params.r#type))
-//             // Has not been terminated
-//             .filter(versions::terminated.is_null())
-//             // Order by id, necessary for page_token
-//             .order(versions::entity_id)
-//             .limit(params.count.unwrap_or(100))
-//             .into_boxed();
-//
-//         let query = if let Some(time) = params.at {
-//             query
-//                 // Was created before the requested time
-//                 // This needs to be lt, rather than le, to work correctly in FeedStateInterface::read_entity
-//                 .filter(versions::start_time.lt(*time))
-//                 // Has no children, or at least one child is after the requested time
-//                 // This needs to be ge, rather than gt, to work correctly in FeedStateInterface::read_entity
-//                 .filter(versions::end_time.is_null().or(versions::end_time.ge(*time)))
-//         } else {
-//             // No time specified = latest version only
-//             query.filter(versions::end_time.is_null())
-//         };
-//
-//         let query = if let Some(ids) = params.id {
-//             query.filter(versions::entity_id.eq_any(ids.into_inner()))
-//         } else {
-//             query
-//         };
-//
-//         let query = if let Some(page) = params.page {
-//             query.filter(versions::entity_id.gt(page.id))
-//         } else {
-//             query
-//         };
-//
-//         query.load::<EntityVersions>(c)
-//     }).await
-//         .map_err(|e| ApiError::InternalError(e.to_string()))?;
-//
-//     let next_page = results.last()
-//         .map(|v| PageToken { id: v.entity_id, time: v.valid_from });
-//
-//     if wants_all {
-//         Ok(json!({
-//             "nextPage": next_page,
-//             "items": results
-//         }))
-//     } else {
-//         let results: Vec<_> = results.into_iter()
-//             .map(|v| EntityVersion::from_versions(&entity_type, v))
-//             .collect();
-//         Ok(json!({
-//             "nextPage": next_page,
-//             "items": results
-//         }))
-//     }
-// }
\ No newline at end of file
+use std::io::{Cursor, Read, Write};
+use std::ops::Deref;
+use byteorder::{NativeEndian, ReadBytesExt, WriteBytesExt};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use rocket::{State, FromForm, form::{self, FromFormField}};
+use rocket::serde::json::Json;
+use serde::{Serialize, Serializer};
+use serde::ser::Error;
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+use diesel::prelude::*;
+use diesel::Queryable;
+use diesel::result::Error as DieselError;
+
+use blarser::db::{BlarserDbConn, get_entity_ids_page};
+use blarser::entity::{AnyEntity, UntaggedRaw};
+use blarser::ingest::IngestTaskHolder;
+use blarser::state::EntityType;
+use crate::routes::ApiError;
+
+#[derive(FromForm)]
+pub struct EntityIdsParams {
+    r#type: String,
+    at: Option<String>,
+    count: Option<i64>,
+    after: Option<Uuid>,
+}
+
+#[rocket::get("/api/entities/ids?<params..>")]
+pub async fn entity_ids(conn: BlarserDbConn, ingest: &State<IngestTaskHolder>, params: EntityIdsParams) -> Result<Json<Value>, ApiError> {
+    let ingest_id = ingest.latest_ingest_id()
+        .ok_or_else(|| ApiError::InternalError("No ingest yet".to_string()))?;
+    let entity_type = EntityType::try_from(params.r#type.as_str())
+        .map_err(|()| ApiError::ParseError(format!("Unknown entity type {}", params.r#type)))?;
+    let at = params.at
+        .map(|at| DateTime::parse_from_rfc3339(&at)
+            .map(|at| at.with_timezone(&Utc))
+            .map_err(|err| ApiError::ParseError(format!("Invalid time {at}: {err}"))))
+        .transpose()?;
+    let count = params.count.unwrap_or(1000);
+    let after = params.after;
+
+    let ids = conn.run(move |c| {
+        get_entity_ids_page(c, ingest_id, entity_type, at, after, count)
+    }).await
+        .map_err(|err: DieselError| ApiError::InternalError(err.to_string()))?;
+
+    // A short page means there's nothing after it
+    let next_page = if ids.len() as i64 == count { ids.last().copied() } else { None };
+    Ok(Json(json!({
+        "nextPage": next_page,
+        "items": ids,
+    })))
+}
+
+pub struct ParseableDateTime(DateTime<Utc>);
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for ParseableDateTime {
+    fn from_value(field: form::ValueField<'r>) -> form::Result<'r, Self> {
+        DateTime::parse_from_rfc3339(field.value)
+            .map(|d| Self(d.with_timezone(&Utc)))
+            .map_err(|e| form::Error::validation(e.to_string()).into())
+    }
+}
+
+impl Deref for ParseableDateTime {
+    type Target = DateTime<Utc>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub struct UuidList(Vec<Uuid>);
+
+impl UuidList {
+    pub fn into_inner(self) -> Vec<Uuid> { self.0 }
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for UuidList {
+    fn from_value(field: form::ValueField<'r>) -> form::Result<'r, Self> {
+        let uuids = field.value.split(',')
+            .map(|id_str| Uuid::parse_str(id_str)
+                .map_err(|e| form::Error::validation(e.to_string()).into()))
+            .collect::<form::Result<_>>()?;
+        Ok(Self(uuids))
+    }
+}
+
+impl Deref for UuidList {
+    type Target = Vec<Uuid>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub struct PageToken {
+    id: Uuid,
+    time: DateTime<Utc>,
+}
+
+fn blaseball_epoch() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2020, 7, 1, 0, 0, 0).unwrap()
+}
+
+#[rocket::async_trait]
+impl<'r> FromFormField<'r> for PageToken {
+    fn from_value(field: form::ValueField<'r>) -> form::Result<'r, Self> {
+        let bytes = base64::decode(field.value.replace('-', "+").replace('_', "/"))
+            .map_err(|e| form::Error::validation(e.to_string()))?;
+
+        if bytes.len() != 24 {
+            return Err(form::Error::validation("must be 24 bytes long".to_string()).into());
+        }
+
+        let mut bytes = Cursor::new(bytes);
+
+        let mut uuid_bytes: [u8; 16] = Default::default();
+        bytes.read_exact(&mut uuid_bytes)
+            .map_err(|e| form::Error::validation(e.to_string()))?;
+        let uuid = Uuid::from_bytes(uuid_bytes);
+
+        let time_offset = bytes.read_i64::<NativeEndian>()
+            .map_err(|e| form::Error::validation(e.to_string()))?;
+        let time = blaseball_epoch() + Duration::nanoseconds(time_offset * 100);
+
+        Ok(Self { id: uuid, time })
+    }
+}
+
+impl Serialize for PageToken {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut bytes = Cursor::new(Vec::<u8>::new());
+        bytes.write_all(self.id.as_bytes())
+            .map_err(|e| S::Error::custom(e.to_string()))?;
+
+        // TODO Implement a num_ticks in the style of num_nanoseconds and that won't require checked
+        //  multiplication
+        let time_offset = (self.time - blaseball_epoch()).num_nanoseconds().map(|n| n / 100)
+            .expect("time_offset overflowed");
+        bytes.write_i64::<NativeEndian>(time_offset)
+            .map_err(|e| S::Error::custom(e.to_string()))?;
+
+        // TODO Use a Config object to get the proper character set instead of hacky string replace
+        let str = base64::encode(bytes.into_inner()).replace('+', "-").replace('/', "_");
+        serializer.serialize_str(&str)
+    }
+}
+
+#[derive(FromForm)]
+pub struct EntitiesParams {
+    r#type: String,
+    at: Option<ParseableDateTime>,
+    count: Option<i64>,
+    id: Option<UuidList>,
+    page: Option<PageToken>,
+    all: Option<bool>,
+}
+
+#[derive(Serialize, Queryable)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityVersions {
+    pub entity_id: Uuid,
+    pub valid_from: DateTime<Utc>,
+    pub valid_to: Option<DateTime<Utc>>,
+    pub data: Vec<Value>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityVersion {
+    pub entity_id: Uuid,
+    pub valid_from: DateTime<Utc>,
+    pub valid_to: Option<DateTime<Utc>>,
+    pub data: EntityData,
+}
+
+// The raw approximation is serialized straight into the response rather than going through a Value
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum EntityData {
+    Raw(UntaggedRaw),
+    Stored(Value),
+}
+
+impl EntityVersion {
+    fn from_versions(entity_type: EntityType, versions: EntityVersions) -> Self {
+        Self {
+            entity_id: versions.entity_id,
+            valid_from: versions.valid_from,
+            valid_to: versions.valid_to,
+            data: match versions.data.into_iter().next() {
+                Some(value) => {
+                    match AnyEntity::from_json_ref(entity_type, &value) {
+                        Ok(entity) => { EntityData::Raw(UntaggedRaw(entity.into_raw_approximation())) }
+                        // Fall back to the stored JSON rather than failing the whole page
+                        Err(_) => { EntityData::Stored(value) }
+                    }
+                }
+                None => { EntityData::Stored(Value::Object(Map::new())) }
+            },
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntitiesPage {
+    next_page: Option<PageToken>,
+    items: EntitiesPageItems,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum EntitiesPageItems {
+    All(Vec<EntityVersions>),
+    Latest(Vec<EntityVersion>),
+}
+
+impl EntitiesPage {
+    // The results are ordered by id, so the next page starts after the last one
+    fn new(entity_type: EntityType, results: Vec<EntityVersions>, wants_all: bool) -> Self {
+        let next_page = results.last()
+            .map(|v| PageToken { id: v.entity_id, time: v.valid_from });
+
+        let items = if wants_all {
+            EntitiesPageItems::All(results)
+        } else {
+            EntitiesPageItems::Latest(results.into_iter()
+                .map(|v| EntityVersion::from_versions(entity_type, v))
+                .collect())
+        };
+        Self { next_page, items }
+    }
+}
+
+#[rocket::get("/entities?<params..>")]
+pub async fn entities(conn: BlarserDbConn, ingest: &State<IngestTaskHolder>, params: Result<EntitiesParams, form::Errors<'_>>) -> Result<Json<EntitiesPage>, ApiError> {
+    let params = params.map_err(|e| ApiError::ParseError(e.to_string()))?;
+    let ingest_id = ingest.latest_ingest_id()
+        .ok_or_else(|| ApiError::InternalError("No ingest yet".to_string()))?;
+
+    let wants_all = params.all.unwrap_or(false);
+    let entity_type = EntityType::try_from(params.r#type.as_str())
+        .map_err(|()| ApiError::ParseError(format!("Unknown entity type {}", params.r#type)))?;
+    let results = conn.run(move |c| {
+        use blarser::schema::versions_with_end::dsl as versions;
+        use diesel::dsl::sql;
+        use diesel::sql_types::{Array, Jsonb};
+
+        // Need to repeat this part because distinct_on fails to compile on a boxed query
+        let query = versions::versions_with_end
+            // Group results for each entity (end_time should never create a separate group)
+            .group_by((versions::entity_id, versions::start_time, versions::end_time))
+            .select((versions::entity_id, versions::start_time, versions::end_time, sql::<Array<Jsonb>>("array_agg(entity) AS data")))
+            // Is from the right ingest
+            .filter(versions::ingest_id.eq(ingest_id))
+            // Has the right entity type
+            .filter(versions::entity_type.eq(entity_type))
+            // Has not been terminated
+            .filter(versions::terminated.is_null())
+            // Order by id, necessary for page_token
+            .order(versions::entity_id)
+            .limit(params.count.unwrap_or(100))
+            .into_boxed();
+
+        let query = if let Some(time) = params.at {
+            query
+                // Was created before the requested time
+                // This needs to be lt, rather than le, to work correctly in FeedStateInterface::read_entity
+                .filter(versions::start_time.lt(*time))
+                // Has no children, or at least one child is after the requested time
+                // This needs to be ge, rather than gt, to work correctly in FeedStateInterface::read_entity
+                .filter(versions::end_time.is_null().or(versions::end_time.ge(*time)))
+        } else {
+            // No time specified = latest version only
+            query.filter(versions::end_time.is_null())
+        };
+
+        let query = if let Some(ids) = params.id {
+            query.filter(versions::entity_id.eq_any(ids.into_inner()))
+        } else {
+            query
+        };
+
+        let query = if let Some(page) = params.page {
+            query.filter(versions::entity_id.gt(page.id))
+        } else {
+            query
+        };
+
+        query.load::<EntityVersions>(c)
+    }).await
+        .map_err(|e| ApiError::InternalError(e.to_string()))?;
+
+    Ok(Json(EntitiesPage::new(entity_type, results, wants_all)))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, TimeZone, Utc};
+    use rocket::form::{FromFormField, ValueField};
+    use serde_json::{json, Value};
+    use uuid::Uuid;
+    use blarser::state::EntityType;
+    use super::{EntitiesPage, EntityVersions, PageToken};
+
+    fn versions(entity_id: Uuid, data: Value) -> EntityVersions {
+        EntityVersions {
+            entity_id,
+            valid_from: Utc.with_ymd_and_hms(2021, 3, 1, 15, 31, 0).unwrap(),
+            valid_to: None,
+            data: vec![data],
+        }
+    }
+
+    fn parse_page_token(token: &str) -> PageToken {
+        PageToken::from_value(ValueField::from_value(token))
+            .expect("Page token should parse")
+    }
+
+    #[test]
+    fn page_tokens_round_trip() {
+        // Tokens only keep the time to the nearest 100ns
+        let token = PageToken {
+            id: Uuid::new_v4(),
+            time: Utc.with_ymd_and_hms(2021, 3, 1, 15, 31, 0).unwrap() + Duration::nanoseconds(1_234_500),
+        };
+        let serialized = serde_json::to_value(&token).unwrap();
+        let parsed = parse_page_token(serialized.as_str().expect("Page tokens should serialize to a string"));
+        assert_eq!(parsed.id, token.id);
+        assert_eq!(parsed.time, token.time);
+    }
+
+    #[test]
+    fn page_tokens_of_the_wrong_length_are_rejected() {
+        assert!(PageToken::from_value(ValueField::from_value("AAAA")).is_err());
+    }
+
+    #[test]
+    fn page_continues_after_its_last_entity() {
+        let ids = [Uuid::from_u128(1), Uuid::from_u128(2)];
+        let page = EntitiesPage::new(EntityType::Player, ids.iter().map(|&id| versions(id, json!({}))).collect(), true);
+        let page = serde_json::to_value(&page).unwrap();
+
+        assert_eq!(page["items"].as_array().map(Vec::len), Some(2));
+        let next_page = parse_page_token(page["nextPage"].as_str().expect("A non-empty page should have a next page"));
+        assert_eq!(next_page.id, ids[1]);
+    }
+
+    #[test]
+    fn empty_page_has_no_next_page() {
+        let page = serde_json::to_value(&EntitiesPage::new(EntityType::Player, Vec::new(), false)).unwrap();
+        assert_eq!(page, json!({ "nextPage": null, "items": [] }));
+    }
+
+    #[test]
+    fn latest_items_fall_back_to_the_stored_json() {
+        let stored = json!({ "not": "a player" });
+        let page = EntitiesPage::new(EntityType::Player, vec![versions(Uuid::new_v4(), stored.clone())], false);
+        let page = serde_json::to_value(&page).unwrap();
+        assert_eq!(page["items"][0]["data"], stored);
+    }
+}
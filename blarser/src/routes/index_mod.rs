@@ -1,12 +1,14 @@
+use rocket::State;
 use rocket_dyn_templates::Template;
 use diesel::result::Error as DieselError;
 use serde::Serialize;
 
 use blarser::db::{BlarserDbConn, get_latest_ingest};
+use blarser::ingest::IngestTaskHolder;
 use crate::routes::ApiError;
 
 #[rocket::get("/")]
-pub async fn index(conn: BlarserDbConn) -> Result<Template, ApiError> {
+pub async fn index(conn: BlarserDbConn, task: &State<IngestTaskHolder>) -> Result<Template, ApiError> {
     let ingest = conn.run(|c| {
         get_latest_ingest(c)
     }).await
@@ -15,6 +17,7 @@ pub async fn index(conn: BlarserDbConn) -> Result<Template, ApiError> {
     #[derive(Serialize)]
     struct IndexTemplateParams {
         ingest_started_at: String,
+        caught_up_through: Option<String>,
     }
 
     match ingest {
@@ -22,6 +25,7 @@ pub async fn index(conn: BlarserDbConn) -> Result<Template, ApiError> {
         Some(ingest) => {
             Ok(Template::render("index", IndexTemplateParams {
                 ingest_started_at: ingest.started_at.format("%c").to_string(),
+                caught_up_through: task.latest_ingest_time().map(|time| time.format("%c").to_string()),
             }))
         }
     }
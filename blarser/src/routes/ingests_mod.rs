@@ -0,0 +1,60 @@
+use rocket::State;
+use rocket::serde::json::Json;
+use diesel::result::Error as DieselError;
+
+use blarser::ingest::IngestTaskHolder;
+use blarser::db::{BlarserDbConn, Ingest, get_ingest, get_ingests, set_ingest_archived};
+use crate::routes::ApiError;
+
+#[rocket::get("/ingests?<include_archived>")]
+pub async fn ingests(conn: BlarserDbConn, include_archived: Option<bool>) -> Result<Json<Vec<Ingest>>, ApiError> {
+    let include_archived = include_archived.unwrap_or(false);
+    let ingests = conn.run(move |c| {
+        get_ingests(c, include_archived)
+    }).await
+        .map_err(|err: DieselError| ApiError::InternalError(err.to_string()))?;
+
+    Ok(Json(ingests))
+}
+
+#[rocket::get("/ingests/<ingest_id>")]
+pub async fn ingest(conn: BlarserDbConn, ingest_id: i32) -> Result<Json<Ingest>, ApiError> {
+    let ingest = conn.run(move |c| {
+        get_ingest(c, ingest_id)
+    }).await
+        .map_err(|err: DieselError| ApiError::InternalError(err.to_string()))?;
+
+    ingest
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("Ingest {ingest_id} does not exist")))
+}
+
+#[rocket::post("/ingests/<ingest_id>/archive")]
+pub async fn archive_ingest(task: &State<IngestTaskHolder>, conn: BlarserDbConn, ingest_id: i32) -> Result<Json<Ingest>, ApiError> {
+    // Archiving the running ingest would leave it writing to a hidden ingest
+    if task.latest_ingest_id() == Some(ingest_id) {
+        return Err(ApiError::InternalError("Can't archive the running ingest".to_string()));
+    }
+
+    set_archived(conn, ingest_id, true).await
+}
+
+#[rocket::post("/ingests/<ingest_id>/restore")]
+pub async fn restore_ingest(conn: BlarserDbConn, ingest_id: i32) -> Result<Json<Ingest>, ApiError> {
+    set_archived(conn, ingest_id, false).await
+}
+
+async fn set_archived(conn: BlarserDbConn, ingest_id: i32, archived: bool) -> Result<Json<Ingest>, ApiError> {
+    let ingest = conn.run(move |c| {
+        if set_ingest_archived(c, ingest_id, archived)? {
+            get_ingest(c, ingest_id)
+        } else {
+            Ok(None)
+        }
+    }).await
+        .map_err(|err: DieselError| ApiError::InternalError(err.to_string()))?;
+
+    ingest
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("Ingest {ingest_id} does not exist")))
+}
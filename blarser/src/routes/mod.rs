@@ -5,16 +5,25 @@ mod debug_mod;
 mod approvals_mod;
 mod index_mod;
 mod entities_mod;
+mod export_mod;
+mod ingests_mod;
+mod bookmarks_mod;
 
 pub use index_mod::*;
 pub use debug_mod::*;
 pub use approvals_mod::*;
 pub use entities_mod::*;
+pub use export_mod::*;
+pub use ingests_mod::*;
+pub use bookmarks_mod::*;
 
 #[derive(rocket::Responder)]
 pub enum ApiError {
-    // #[response(status = 400)]
-    // ParseError(String),
+    #[response(status = 400)]
+    ParseError(String),
+
+    #[response(status = 404)]
+    NotFound(String),
 
     #[response(status = 500)]
     InternalError(String)
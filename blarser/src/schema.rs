@@ -22,6 +22,39 @@ diesel::table! {
         message -> Text,
         approved -> Nullable<Bool>,
         explanation -> Nullable<Text>,
+        severity -> Int4,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EntityType;
+
+    bookmarks (id) {
+        id -> Int4,
+        ingest_id -> Int4,
+        time -> Timestamptz,
+        entity_type -> EntityType,
+        entity_id -> Uuid,
+        note -> Text,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::EntityType;
+
+    conflicts (id) {
+        id -> Int4,
+        ingest_id -> Int4,
+        entity_type -> EntityType,
+        entity_id -> Uuid,
+        perceived_at -> Timestamptz,
+        property -> Text,
+        message -> Text,
+        resolved -> Bool,
+        severity -> Int4,
     }
 }
 
@@ -55,6 +88,9 @@ diesel::table! {
     ingests (id) {
         id -> Int4,
         started_at -> Timestamptz,
+        archived -> Bool,
+        backfill_until -> Nullable<Timestamptz>,
+        backfilled_to -> Nullable<Timestamptz>,
     }
 }
 
@@ -103,12 +139,16 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(bookmarks -> ingests (ingest_id));
+diesel::joinable!(conflicts -> ingests (ingest_id));
 diesel::joinable!(event_effects -> events (event_id));
 diesel::joinable!(versions -> events (from_event));
 diesel::joinable!(versions_with_end -> events (from_event));
 
 diesel::allow_tables_to_appear_in_same_query!(
     approvals,
+    bookmarks,
+    conflicts,
     event_effects,
     events,
     ingests,
@@ -11,6 +11,7 @@ pub struct NewApproval<'a> {
     pub entity_id: Uuid,
     pub perceived_at: DateTime<Utc>,
     pub message: &'a str,
+    pub severity: i32,
 }
 
 pub enum ApprovalState {
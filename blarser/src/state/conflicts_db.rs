@@ -0,0 +1,18 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::schema::*;
+use crate::state::EntityType;
+
+#[derive(Insertable, Debug)]
+#[diesel(table_name = conflicts)]
+pub struct NewConflict {
+    pub ingest_id: i32,
+    pub entity_type: EntityType,
+    pub entity_id: Uuid,
+    pub perceived_at: DateTime<Utc>,
+    pub property: String,
+    pub message: String,
+    // See partial_information::Severity::level
+    pub severity: i32,
+}
@@ -1,11 +1,13 @@
 mod merged_successors;
 mod state_interface;
 mod approvals_db;
+mod conflicts_db;
 mod versions_db;
 // mod events_db;
 
 pub use merged_successors::MergedSuccessors;
 pub use approvals_db::{ApprovalState};
+pub use conflicts_db::NewConflict;
 // pub use events_db::EventEffect;
 pub use versions_db::{
     // get_entity_debug,
@@ -14,4 +16,4 @@ pub use versions_db::{
     // Version,
     // NewVersion,
 };
-pub use state_interface::{StateInterface, EntityDescription, Effects};
\ No newline at end of file
+pub use state_interface::{StateInterface, EntityDescription, Effects, VersionLineageDebug};
\ No newline at end of file
@@ -3,9 +3,11 @@ use chrono::{DateTime, Utc};
 use diesel::{prelude::*, dsl, PgConnection, QueryResult};
 use serde::Serialize;
 use uuid::Uuid;
+use partial_information::Severity;
 
 use diesel::sql_types;
-use crate::state::{EntityType, ApprovalState};
+use crate::entity::{AnyEntity, Entity, Season};
+use crate::state::{EntityType, ApprovalState, NewConflict};
 use crate::state::approvals_db::NewApproval;
 
 use crate::schema::versions_with_end::dsl as versions_dsl;
@@ -41,6 +43,17 @@ pub struct VersionLinkDebug {
     pub child_id: i32,
 }
 
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionLineageDebug {
+    pub entity_type: EntityType,
+    pub entity_id: Uuid,
+    #[serde(flatten)]
+    pub version: VersionDebug,
+    pub parents: Vec<i32>,
+    pub children: Vec<i32>,
+}
+
 #[derive(Serialize)]
 pub struct EntityVersionsDebug {
     pub edges: Vec<VersionLinkDebug>,
@@ -396,14 +409,15 @@ impl<'conn> StateInterface<'conn> {
     //     })
     // }
 
-    pub fn upsert_approval(&mut self, entity_type: EntityType, entity_id: Uuid, perceived_at: DateTime<Utc>, message: &str) -> QueryResult<ApprovalState> {
+    pub fn upsert_approval(&mut self, entity_type: EntityType, entity_id: Uuid, perceived_at: DateTime<Utc>, message: &str, severity: Severity) -> QueryResult<ApprovalState> {
         use crate::schema::approvals::dsl as approvals;
 
+        let severity = severity.level();
         let (id, approved) = diesel::insert_into(approvals::approvals)
-            .values(NewApproval { entity_type, entity_id, perceived_at, message })
+            .values(NewApproval { entity_type, entity_id, perceived_at, message, severity })
             .on_conflict((approvals::entity_type, approvals::entity_id, approvals::perceived_at))
             .do_update()
-            .set(approvals::message.eq(message))
+            .set((approvals::message.eq(message), approvals::severity.eq(severity)))
             .returning((approvals::id, approvals::approved))
             .get_result::<(i32, Option<bool>)>(self.conn)?;
 
@@ -418,6 +432,40 @@ impl<'conn> StateInterface<'conn> {
         }
     }
 
+    pub fn insert_conflicts(&mut self, new_conflicts: Vec<NewConflict>) -> QueryResult<()> {
+        use crate::schema::conflicts::dsl as conflicts;
+
+        diesel::insert_into(conflicts::conflicts)
+            .values(new_conflicts)
+            .execute(self.conn)?;
+
+        Ok(())
+    }
+
+    pub fn resolve_conflicts(&mut self, entity_type: EntityType, entity_id: Uuid, perceived_at: DateTime<Utc>) -> QueryResult<()> {
+        use crate::schema::conflicts::dsl as conflicts;
+
+        diesel::update(conflicts::conflicts)
+            .filter(conflicts::ingest_id.eq(self.ingest_id))
+            .filter(conflicts::entity_type.eq(entity_type))
+            .filter(conflicts::entity_id.eq(entity_id))
+            .filter(conflicts::perceived_at.eq(perceived_at))
+            .set(conflicts::resolved.eq(true))
+            .execute(self.conn)?;
+
+        Ok(())
+    }
+
+    pub fn set_backfill_progress(&mut self, backfilled_to: DateTime<Utc>) -> QueryResult<()> {
+        use crate::schema::ingests::dsl as ingests;
+
+        diesel::update(ingests::ingests.find(self.ingest_id))
+            .set(ingests::backfilled_to.eq(backfilled_to))
+            .execute(self.conn)?;
+
+        Ok(())
+    }
+
     pub fn terminate_versions(&mut self, mut to_update: Vec<i32>, reason: String) -> QueryResult<()> {
         use crate::schema::versions::dsl as versions;
 
@@ -451,6 +499,7 @@ impl<'conn> StateInterface<'conn> {
 
     pub fn get_recently_updated_entity_descriptions(&mut self, limit: i64) -> QueryResult<Vec<EntityDescription>> {
         use crate::schema::versions_with_end::dsl as versions;
+        let standings_seasons = self.standings_season_numbers()?;
         let result = versions::versions_with_end
             .select((versions::entity_type, versions::entity_id, versions::entity))
             // Is from the right ingest
@@ -462,9 +511,17 @@ impl<'conn> StateInterface<'conn> {
             .limit(limit)
             .get_results::<(EntityType, Uuid, serde_json::Value)>(self.conn)?
             .into_iter()
-            .map(|(entity_type, entity_id, _)| {
-                // let description = entity_description(&entity_type, entity_json);
-                let description = "TODO".to_string();
+            .map(|(entity_type, entity_id, entity_json)| {
+                let description = match AnyEntity::from_json(entity_type, entity_json) {
+                    Ok(AnyEntity::Standings(standings)) => {
+                        match standings_seasons.get(&standings.id) {
+                            Some(&season_number) => { standings.description_for_season(season_number) }
+                            None => { standings.description() }
+                        }
+                    }
+                    Ok(entity) => { entity.description() }
+                    Err(err) => { format!("Couldn't parse {}: {}", entity_type, err) }
+                };
 
                 EntityDescription {
                     entity_type,
@@ -477,6 +534,22 @@ impl<'conn> StateInterface<'conn> {
         Ok(result)
     }
 
+    // Standings don't say which season they're for. Only the season knows which standings are its.
+    fn standings_season_numbers(&mut self) -> QueryResult<HashMap<Uuid, i32>> {
+        use crate::schema::versions_with_end::dsl as versions;
+        let seasons = versions::versions_with_end
+            .select(versions::entity)
+            .filter(versions::ingest_id.eq(self.ingest_id))
+            .filter(versions::entity_type.eq(EntityType::Season))
+            .filter(versions::end_time.is_null())
+            .get_results::<serde_json::Value>(self.conn)?;
+
+        Ok(seasons.into_iter()
+            .filter_map(|json| serde_json::from_value::<Season>(json).ok())
+            .map(|season| (season.standings, season.season_number))
+            .collect())
+    }
+
     fn query_versions_with_end(&self, entity_type: EntityType, entity_id: Uuid) ->
     dsl::FindBy<dsl::FindBy<dsl::FindBy<versions_dsl::versions_with_end, versions_dsl::ingest_id, i32>, versions_dsl::entity_type, EntityType>, versions_dsl::entity_id, Uuid> {
         use crate::schema::versions_with_end::dsl as versions;
@@ -519,4 +592,98 @@ impl<'conn> StateInterface<'conn> {
 
         Ok(EntityVersionsDebug { edges, nodes })
     }
-}
\ No newline at end of file
+
+    // Returns None if there's no version with that id in this ingest
+    pub fn get_version_lineage_debug(&mut self, version_id: i32) -> QueryResult<Option<VersionLineageDebug>> {
+        use crate::schema::versions_with_end::dsl as versions;
+        use crate::schema::events::dsl as events;
+        use crate::schema::version_links::dsl as version_links;
+
+        let version = versions::versions_with_end
+            .filter(versions::ingest_id.eq(self.ingest_id))
+            .filter(versions::id.eq(version_id))
+            .inner_join(events::events.on(events::id.eq(versions::from_event)))
+            .select((
+                (versions::entity_type, versions::entity_id),
+                (
+                    versions::id,
+                    versions::start_time,
+                    events::data,
+                    versions::event_aux_data,
+                    versions::entity,
+                    versions::terminated,
+                    versions::observations
+                ),
+            ))
+            .get_result::<((EntityType, Uuid), VersionDebug)>(self.conn)
+            .optional()?;
+
+        let Some(((entity_type, entity_id), version)) = version else {
+            return Ok(None);
+        };
+
+        let parents = version_links::version_links
+            .filter(version_links::child_id.eq(version_id))
+            .select(version_links::parent_id)
+            .get_results::<i32>(self.conn)?;
+
+        let children = version_links::version_links
+            .filter(version_links::parent_id.eq(version_id))
+            .select(version_links::child_id)
+            .get_results::<i32>(self.conn)?;
+
+        Ok(Some(VersionLineageDebug { entity_type, entity_id, version, parents, children }))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl};
+    use serde_json::json;
+    use uuid::Uuid;
+    use crate::db::tests::{insert_test_event, insert_test_ingest, insert_test_version, test_connection};
+    use crate::state::EntityType;
+    use super::StateInterface;
+
+    #[test]
+    fn version_is_found_by_id_with_its_lineage() {
+        test_connection().test_transaction::<_, diesel::result::Error, _>(|conn| {
+            let ingest_id = insert_test_ingest(conn);
+            let event_id = insert_test_event(conn, ingest_id);
+            let player_id = Uuid::new_v4();
+            let start = Utc::now();
+            let parent = insert_test_version(conn, ingest_id, event_id, EntityType::Player, player_id, start, None);
+            let version = insert_test_version(conn, ingest_id, event_id, EntityType::Player, player_id, start + Duration::minutes(1), Some(parent));
+            let child = insert_test_version(conn, ingest_id, event_id, EntityType::Player, player_id, start + Duration::minutes(2), Some(version));
+            {
+                use crate::schema::versions::dsl::*;
+                diesel::update(versions.find(version))
+                    .set(entity.eq(json!({ "name": "Test Player" })))
+                    .execute(conn)?;
+            }
+
+            let lineage = StateInterface::new(conn, ingest_id).get_version_lineage_debug(version)?
+                .expect("The version should be found by its id");
+            assert_eq!(lineage.entity_type, EntityType::Player);
+            assert_eq!(lineage.entity_id, player_id);
+            assert_eq!(lineage.version.id, version);
+            assert_eq!(lineage.version.entity, json!({ "name": "Test Player" }));
+            assert_eq!(lineage.parents, vec![parent]);
+            assert_eq!(lineage.children, vec![child]);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn version_from_another_ingest_is_not_found() {
+        test_connection().test_transaction::<_, diesel::result::Error, _>(|conn| {
+            let ingest_id = insert_test_ingest(conn);
+            let other_ingest_id = insert_test_ingest(conn);
+            let event_id = insert_test_event(conn, ingest_id);
+            let version = insert_test_version(conn, ingest_id, event_id, EntityType::Player, Uuid::new_v4(), Utc::now(), None);
+
+            assert!(StateInterface::new(conn, other_ingest_id).get_version_lineage_debug(version)?.is_none());
+            Ok(())
+        });
+    }
+}
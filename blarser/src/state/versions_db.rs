@@ -13,6 +13,10 @@ pub enum EntityType {
     Game,
     Standings,
     Season,
+    Stadium,
+    League,
+    Subleague,
+    Division,
 }
 
 impl TryFrom<&str> for EntityType {
@@ -26,6 +30,10 @@ impl TryFrom<&str> for EntityType {
             "game" => { Self::Game }
             "standings" => { Self::Standings }
             "season" => { Self::Season }
+            "stadium" => { Self::Stadium }
+            "league" => { Self::League }
+            "subleague" => { Self::Subleague }
+            "division" => { Self::Division }
             _ => { return Err(()); }
         })
     }
@@ -40,6 +48,10 @@ impl Display for EntityType {
             EntityType::Game => { write!(f, "game") }
             EntityType::Standings => { write!(f, "standings") }
             EntityType::Season => { write!(f, "season") }
+            EntityType::Stadium => { write!(f, "stadium") }
+            EntityType::League => { write!(f, "league") }
+            EntityType::Subleague => { write!(f, "subleague") }
+            EntityType::Division => { write!(f, "division") }
         }
     }
 }
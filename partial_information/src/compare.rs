@@ -4,6 +4,7 @@ use std::hash::Hash;
 use std::iter;
 use uuid::Uuid;
 use std::iter::Iterator;
+use std::str::FromStr;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
@@ -11,28 +12,171 @@ pub trait PartialInformationDiff<'d>: Debug {
     fn is_empty(&self) -> bool;
 }
 
-#[derive(Debug)]
+// How much a conflict matters, so people can deal with the important ones first. Each type picks
+// the severity of the conflicts it creates. Anything that doesn't pick is an Error.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[repr(i32)]
+pub enum Severity {
+    Info = 0,
+    Warn = 1,
+    Error = 2,
+}
+
+impl Severity {
+    // For storage
+    pub fn level(self) -> i32 {
+        self as i32
+    }
+
+    pub fn from_level(level: i32) -> Option<Severity> {
+        match level {
+            0 => { Some(Severity::Info) }
+            1 => { Some(Severity::Warn) }
+            2 => { Some(Severity::Error) }
+            _ => { None }
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Info => { write!(f, "info") }
+            Severity::Warn => { write!(f, "warn") }
+            Severity::Error => { write!(f, "error") }
+        }
+    }
+}
+
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "info" => { Ok(Severity::Info) }
+            "warn" => { Ok(Severity::Warn) }
+            "error" => { Ok(Severity::Error) }
+            _ => { Err(format!("Unknown severity {}", s)) }
+        }
+    }
+}
+
+// The path is the chain of field names (or map keys, or Vec indices) from the entity down to the
+// value that conflicted, outermost first. Leaf values create conflicts with an empty path and each
+// container pushes its own name on the front as the conflict bubbles up.
+#[derive(Debug, Clone)]
 pub struct Conflict {
-    property: String,
+    path: Vec<String>,
+    expected: Option<String>,
+    observed: Option<String>,
     message: String,
+    severity: Severity,
 }
 
 impl Conflict {
     pub fn new(property: String, message: String) -> Conflict {
-        Conflict { property, message }
+        Conflict {
+            path: if property.is_empty() { Vec::new() } else { vec![property] },
+            expected: None,
+            observed: None,
+            message,
+            severity: Severity::Error,
+        }
     }
 
-    pub fn with_prefix(self, prefix: &str) -> Conflict {
+    // For the common case of a value that was simply different from what was expected. Takes the
+    // values already rendered so callers can choose how to display them (e.g. "null").
+    pub fn mismatch(expected: String, observed: String) -> Conflict {
         Conflict {
-            property: format!("{}/{}", prefix, self.property),
-            message: self.message,
+            path: Vec::new(),
+            message: format!("Expected {}, but observed {}", expected, observed),
+            expected: Some(expected),
+            observed: Some(observed),
+            severity: Severity::Error,
         }
     }
+
+    pub fn with_severity(mut self, severity: Severity) -> Conflict {
+        self.severity = severity;
+        self
+    }
+
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+
+    pub fn property(&self) -> String {
+        self.path.join("/")
+    }
+
+    pub fn expected(&self) -> Option<&str> {
+        self.expected.as_deref()
+    }
+
+    pub fn observed(&self) -> Option<&str> {
+        self.observed.as_deref()
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    // For callers that know more about what a conflict means than the field that produced it
+    pub fn with_message(mut self, message: String) -> Conflict {
+        self.message = message;
+        self
+    }
+
+    pub fn with_prefix(mut self, prefix: &str) -> Conflict {
+        self.path.insert(0, prefix.to_string());
+        self
+    }
 }
 
 impl Display for Conflict {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}: {}", self.property, self.message)
+        write!(f, "{}: {}", self.property(), self.message)
+    }
+}
+
+// How many leaf values in an entity are fully known, out of how many there are in total
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Serialize)]
+pub struct KnownCount {
+    pub known: usize,
+    pub total: usize,
+}
+
+impl KnownCount {
+    pub fn leaf(known: bool) -> Self {
+        Self { known: if known { 1 } else { 0 }, total: 1 }
+    }
+
+    // An entity with no leaves has nothing left to be unsure about
+    pub fn confidence(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.known as f32 / self.total as f32
+        }
+    }
+}
+
+impl std::ops::Add for KnownCount {
+    type Output = KnownCount;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        KnownCount { known: self.known + rhs.known, total: self.total + rhs.total }
+    }
+}
+
+impl iter::Sum for KnownCount {
+    fn sum<I: Iterator<Item=Self>>(iter: I) -> Self {
+        iter.fold(KnownCount::default(), |a, b| a + b)
     }
 }
 
@@ -44,6 +188,18 @@ pub trait PartialInformationCompare: Sized + Debug {
     fn observe(&mut self, observed: &Self::Raw) -> Vec<Conflict>;
     fn is_ambiguous(&self) -> bool;
 
+    // Like observe, but floats within epsilon of the expected value count as equal. This is what
+    // the derive calls for fields marked #[partial_information(epsilon = ...)]. Only floats and
+    // the wrappers that can hold them need to override it.
+    fn observe_within(&mut self, observed: &Self::Raw, _epsilon: f64) -> Vec<Conflict> {
+        self.observe(observed)
+    }
+
+    // Leaf types only need is_ambiguous. Containers should override this to add up their members.
+    fn count_known(&self) -> KnownCount {
+        KnownCount::leaf(!self.is_ambiguous())
+    }
+
     fn from_raw(raw: Self::Raw) -> Self;
     fn raw_approximation(self) -> Self::Raw;
 }
@@ -57,7 +213,7 @@ pub struct HashMapDiff<'d, KeyT, ValT: PartialInformationCompare> {
 }
 
 impl<K, V> PartialInformationCompare for HashMap<K, V>
-    where K: 'static + Debug + Eq + Hash + Clone + for<'de> Deserialize<'de> + Serialize + Send + Sync,
+    where K: 'static + Debug + Display + Eq + Hash + Clone + for<'de> Deserialize<'de> + Serialize + Send + Sync,
           V: 'static + PartialInformationCompare {
     type Raw = HashMap<K, V::Raw>;
     type Diff<'d> = HashMapDiff<'d, K, V>;
@@ -81,6 +237,8 @@ impl<K, V> PartialInformationCompare for HashMap<K, V>
         }
     }
 
+    // Keys use Display rather than Debug so the conflict paths match how approval policies are
+    // written (no quotes around string keys)
     fn observe(&mut self, observed: &Self::Raw) -> Vec<Conflict> {
         let mut conflicts = Vec::new();
 
@@ -89,8 +247,8 @@ impl<K, V> PartialInformationCompare for HashMap<K, V>
                 Some(_) => {}
                 None => {
                     conflicts.push(
-                        Conflict::new(format!("{:?}", key),
-                                      format!("Expected no value in HashMap, but observed {:?}", val))
+                        Conflict::new(key.to_string(),
+                                      format!("Expected no value for key {} in HashMap, but observed {:?}", key, val))
                     );
                 }
             }
@@ -100,14 +258,14 @@ impl<K, V> PartialInformationCompare for HashMap<K, V>
             match observed.get(&key) {
                 None => {
                     conflicts.push(
-                        Conflict::new(format!("{:?}", key),
-                                      format!("Expected value {:?} in HashMap, but observed none", expected_val))
+                        Conflict::new(key.to_string(),
+                                      format!("Expected value {:?} for key {} in HashMap, but observed none", expected_val, key))
                     );
                 }
                 Some(observed_val) => {
                     conflicts.extend(
                         expected_val.observe(observed_val).into_iter()
-                            .map(move |conflict| conflict.with_prefix(&format!("{:?}", key)))
+                            .map(move |conflict| conflict.with_prefix(&key.to_string()))
                     )
                 }
             }
@@ -120,6 +278,10 @@ impl<K, V> PartialInformationCompare for HashMap<K, V>
         self.iter().any(|(_, v)| v.is_ambiguous())
     }
 
+    fn count_known(&self) -> KnownCount {
+        self.values().map(|v| v.count_known()).sum()
+    }
+
     fn from_raw(raw: Self::Raw) -> Self {
         raw.into_iter()
             .map(|(key, raw_value)| (key, V::from_raw(raw_value)))
@@ -163,25 +325,37 @@ impl<T> PartialInformationCompare for Option<T>
         }
     }
 
+    // Appearing or disappearing is always a conflict, even when the inner value is only partially
+    // known. Some(MaybeKnown::Unknown) says there *is* a value, just not which one, so observing
+    // null against it is as wrong as observing null against a known value.
     fn observe(&mut self, observed: &Self::Raw) -> Vec<Conflict> {
         match (self, observed) {
             (None, None) => vec![],
             (None, Some(val)) => {
-                vec![Conflict::new(String::new(),
-                                   format!("Expected no value in Option, but observed {:?}", val))]
+                vec![Conflict::mismatch("null".to_string(), format!("{:?}", val))]
             }
             (Some(val), None) => {
-                vec![Conflict::new(String::new(),
-                                   format!("Expected value {:?} in Option, but observed none", val))]
+                vec![Conflict::mismatch(format!("{:?}", val), "null".to_string())]
             }
             (Some(a), Some(b)) => a.observe(b)
         }
     }
 
+    fn observe_within(&mut self, observed: &Self::Raw, epsilon: f64) -> Vec<Conflict> {
+        match (self, observed) {
+            (Some(a), Some(b)) => a.observe_within(b, epsilon),
+            (this, observed) => this.observe(observed),
+        }
+    }
+
     fn is_ambiguous(&self) -> bool {
         self.as_ref().map_or(false, |v| v.is_ambiguous())
     }
 
+    fn count_known(&self) -> KnownCount {
+        self.as_ref().map_or(KnownCount::leaf(true), |v| v.count_known())
+    }
+
     fn from_raw(raw: Self::Raw) -> Self {
         raw.map(|v| T::from_raw(v))
     }
@@ -203,6 +377,39 @@ impl<'d, T> PartialInformationDiff<'d> for OptionDiff<'d, T>
     }
 }
 
+// Borrowed values are what MaybeKnown::as_ref and friends hold. They're only for reading, so
+// observing checks for conflicts without narrowing anything, and there's no way to make one from
+// raw data.
+impl<'a, T> PartialInformationCompare for &'a T
+    where T: PartialInformationCompare + Clone {
+    type Raw = T::Raw;
+    type Diff<'d> = T::Diff<'d> where Self: 'd;
+
+    fn diff<'d>(&'d self, observed: &'d Self::Raw, time: DateTime<Utc>) -> Self::Diff<'d> {
+        (**self).diff(observed, time)
+    }
+
+    fn observe(&mut self, observed: &Self::Raw) -> Vec<Conflict> {
+        (**self).clone().observe(observed)
+    }
+
+    fn is_ambiguous(&self) -> bool {
+        (**self).is_ambiguous()
+    }
+
+    fn count_known(&self) -> KnownCount {
+        (**self).count_known()
+    }
+
+    fn from_raw(_: Self::Raw) -> Self {
+        panic!("Can't borrow a value that was made from raw data. Observe into the owned value instead.")
+    }
+
+    fn raw_approximation(self) -> Self::Raw {
+        self.clone().raw_approximation()
+    }
+}
+
 #[derive(Debug)]
 pub struct VecDiff<'d, T: PartialInformationCompare> {
     missing: &'d [T],
@@ -264,6 +471,10 @@ impl<ItemT> PartialInformationCompare for Vec<ItemT>
         self.iter().any(|v| v.is_ambiguous())
     }
 
+    fn count_known(&self) -> KnownCount {
+        self.iter().map(|v| v.count_known()).sum()
+    }
+
     fn from_raw(raw: Self::Raw) -> Self {
         raw.into_iter()
             .map(|v| ItemT::from_raw(v))
@@ -302,7 +513,10 @@ impl<'d, T> PartialInformationDiff<'d> for PrimitiveDiff<'d, T>
 
 macro_rules! trivial_compare {
     ($($t:ty),+) => {
-        $(impl PartialInformationCompare for $t {
+        $(trivial_compare!(@impl $t, {});)+
+    };
+    (@impl $t:ty, { $($extra:item)* }) => {
+        impl PartialInformationCompare for $t {
             type Raw = Self;
             type Diff<'d> = PrimitiveDiff<'d, $t>;
 
@@ -318,16 +532,135 @@ macro_rules! trivial_compare {
                 if self == observed {
                     vec![]
                 } else {
-                    vec![Conflict::new(String::new(),
-                                       format!("Expected {:?}, but observed {:?}", self, observed))]
+                    vec![Conflict::mismatch(format!("{:?}", self), format!("{:?}", observed))]
                 }
             }
 
             fn from_raw(raw: Self::Raw) -> Self { raw }
             fn raw_approximation(self) -> Self::Raw { self }
             fn is_ambiguous(&self) -> bool { false }
-        })+
+
+            $($extra)*
+        }
+    };
+}
+
+macro_rules! float_compare {
+    ($($t:ty),+) => {
+        $(trivial_compare!(@impl $t, {
+            fn observe_within(&mut self, observed: &Self::Raw, epsilon: f64) -> Vec<Conflict> {
+                if (*self as f64 - *observed as f64).abs() <= epsilon {
+                    // Take the observed value so it's not lost, and so the error can't build up
+                    // over many observations
+                    *self = *observed;
+                    vec![]
+                } else {
+                    self.observe(observed)
+                }
+            }
+        });)+
     }
 }
 
-trivial_compare!(bool, f64, f32, i64, i32, i16, i8, isize, u64, u32, u16, u8, usize, Uuid, String, DateTime<Utc>);
\ No newline at end of file
+float_compare!(f64, f32);
+trivial_compare!(bool, i64, i32, i16, i8, isize, u64, u32, u16, u8, usize, Uuid, String, DateTime<Utc>);
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use chrono::Utc;
+    use crate::{MaybeKnown, PartialInformationCompare, PartialInformationDiff};
+
+    fn reno_log(entries: &[(&str, i32)]) -> HashMap<String, i32> {
+        entries.iter().map(|(key, count)| (key.to_string(), *count)).collect()
+    }
+
+    #[test]
+    fn hash_map_conflicts_are_named_by_their_display_key() {
+        let mut expected = reno_log(&[("hoops_mod", 1), ("grandiosity_up", 2)]);
+        let observed = reno_log(&[("hoops_mod", 2), ("grandiosity_up", 2)]);
+
+        let conflicts = expected.observe(&observed);
+        assert_eq!(conflicts.len(), 1);
+        // Not "\"hoops_mod\"", which is what Debug would give
+        assert_eq!(conflicts[0].property(), "hoops_mod");
+        assert_eq!(conflicts[0].expected(), Some("1"));
+        assert_eq!(conflicts[0].observed(), Some("2"));
+    }
+
+    #[test]
+    fn hash_map_keys_that_appear_or_disappear_are_conflicts() {
+        let mut expected = reno_log(&[("hoops_mod", 1)]);
+        let observed = reno_log(&[("grandiosity_up", 1)]);
+
+        let mut properties = expected.observe(&observed).iter()
+            .map(|conflict| conflict.property())
+            .collect::<Vec<_>>();
+        properties.sort();
+        assert_eq!(properties, vec!["grandiosity_up".to_string(), "hoops_mod".to_string()]);
+    }
+
+    #[test]
+    fn hash_map_diff_agrees_with_observe() {
+        let expected = reno_log(&[("hoops_mod", 1), ("grandiosity_up", 2)]);
+        let time = Utc::now();
+
+        assert!(expected.diff(&expected.clone(), time).is_empty());
+        assert!(!expected.diff(&reno_log(&[("hoops_mod", 1), ("grandiosity_up", 3)]), time).is_empty());
+        assert!(!expected.diff(&reno_log(&[("hoops_mod", 1)]), time).is_empty());
+        assert!(!expected.diff(&reno_log(&[("hoops_mod", 1), ("grandiosity_up", 2), ("fax_machine", 1)]), time).is_empty());
+    }
+
+    #[test]
+    fn option_appearing_or_disappearing_is_a_conflict_against_null() {
+        let mut expected: Option<i32> = None;
+        let conflicts = expected.observe(&Some(3));
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].expected(), Some("null"));
+        assert_eq!(conflicts[0].observed(), Some("3"));
+
+        let mut expected = Some(3);
+        let conflicts = expected.observe(&None);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].expected(), Some("3"));
+        assert_eq!(conflicts[0].observed(), Some("null"));
+    }
+
+    #[test]
+    fn option_disappearing_conflicts_even_when_the_value_is_unknown() {
+        let mut expected = Some(MaybeKnown::<i32>::Unknown);
+        assert_eq!(expected.observe(&None).len(), 1);
+        // But any value at all is fine
+        assert!(expected.observe(&Some(7)).is_empty());
+    }
+
+    #[test]
+    fn option_values_on_both_sides_are_compared_as_values() {
+        let mut expected = Some(3);
+        assert!(expected.observe(&Some(3)).is_empty());
+        assert_eq!(expected.observe(&Some(4)).len(), 1);
+        let mut expected: Option<i32> = None;
+        assert!(expected.observe(&None).is_empty());
+
+        let time = Utc::now();
+        assert!(None::<i32>.diff(&None, time).is_empty());
+        assert!(!None::<i32>.diff(&Some(3), time).is_empty());
+        assert!(!Some(3).diff(&None, time).is_empty());
+    }
+
+    #[test]
+    fn floats_within_epsilon_are_equal_and_take_the_observed_value() {
+        let mut expected = 0.5_f64;
+        assert!(expected.observe_within(&(0.5 + 1e-12), 1e-9).is_empty());
+        // The observed value is kept, so it's not lost downstream
+        assert_eq!(expected, 0.5 + 1e-12);
+
+        assert_eq!(expected.observe_within(&0.6, 1e-9).len(), 1);
+    }
+
+    #[test]
+    fn zero_epsilon_is_an_exact_comparison() {
+        let mut expected = 0.5_f64;
+        assert_eq!(expected.observe_within(&(0.5 + 1e-12), 0.0).len(), 1);
+        assert!(expected.observe_within(&0.5, 0.0).is_empty());
+    }
+}
@@ -0,0 +1,19 @@
+use serde::Serialize;
+
+// Field values are passed around as json because the fields of an entity don't have any common
+// type other than Serialize
+pub type FieldValue = serde_json::Value;
+
+pub trait FieldAccess {
+    // Uses the Rust field names, same as Conflict::property
+    fn fields() -> &'static [&'static str];
+    fn get_field(&self, name: &str) -> Option<FieldValue>;
+    // Replaces one field with the same field from `other`, leaving the rest alone. Returns false
+    // if there's no field with that name.
+    fn take_field(&mut self, other: &Self, name: &str) -> bool;
+}
+
+pub fn to_field_value<T: Serialize>(value: &T) -> FieldValue {
+    serde_json::to_value(value)
+        .expect("Field value should always be serializable to json")
+}
@@ -0,0 +1,185 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::compare::{Conflict, PartialInformationDiff};
+use crate::{PartialInformationCompare, RangeInclusive};
+
+// An integer that's known to be somewhere in [lower, upper]. This is what blaseball_state's
+// PrimitiveValue::IntRange used to be, and it's meant for things like which base a runner is on.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntRange {
+    lower: i64,
+    upper: i64,
+}
+
+impl IntRange {
+    pub fn new(lower: i64, upper: i64) -> Self {
+        assert!(lower <= upper, "IntRange lower bound must not be greater than its upper bound");
+        Self { lower, upper }
+    }
+
+    pub fn exactly(value: i64) -> Self {
+        Self { lower: value, upper: value }
+    }
+
+    pub fn lower(&self) -> i64 {
+        self.lower
+    }
+
+    pub fn upper(&self) -> i64 {
+        self.upper
+    }
+
+    pub fn known(&self) -> Option<i64> {
+        if self.lower == self.upper {
+            Some(self.lower)
+        } else {
+            None
+        }
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        self.lower <= value && value <= self.upper
+    }
+
+    pub fn advance(&mut self, by: i64) {
+        self.lower += by;
+        self.upper += by;
+    }
+
+    // For when something other than an observation proves what the value is
+    pub fn narrow_to(&mut self, value: i64) {
+        assert!(self.contains(value),
+                "Tried to narrow IntRange [{}, {}] to {}, which is outside it", self.lower, self.upper, value);
+        self.lower = value;
+        self.upper = value;
+    }
+}
+
+impl From<RangeInclusive<i64>> for IntRange {
+    fn from(range: RangeInclusive<i64>) -> Self {
+        Self::new(range.lower, range.upper)
+    }
+}
+
+impl From<IntRange> for RangeInclusive<i64> {
+    fn from(range: IntRange) -> Self {
+        RangeInclusive { lower: range.lower, upper: range.upper }
+    }
+}
+
+#[derive(Debug)]
+pub enum IntRangeDiff<'d> {
+    NoDiff,
+    OutsideRange(&'d IntRange, i64),
+}
+
+impl PartialInformationCompare for IntRange {
+    type Raw = i64;
+    type Diff<'d> = IntRangeDiff<'d>;
+
+    fn diff<'d>(&'d self, observed: &'d Self::Raw, _: DateTime<Utc>) -> Self::Diff<'d> {
+        if self.contains(*observed) {
+            IntRangeDiff::NoDiff
+        } else {
+            IntRangeDiff::OutsideRange(self, *observed)
+        }
+    }
+
+    fn observe(&mut self, observed: &Self::Raw) -> Vec<Conflict> {
+        if self.contains(*observed) {
+            self.narrow_to(*observed);
+            Vec::new()
+        } else if let Some(expected) = self.known() {
+            vec![Conflict::mismatch(expected.to_string(), observed.to_string())]
+        } else {
+            vec![Conflict::new(String::new(),
+                               format!("Expected value between {} and {}, but observed {}",
+                                       self.lower, self.upper, observed))]
+        }
+    }
+
+    fn is_ambiguous(&self) -> bool {
+        self.lower < self.upper
+    }
+
+    fn from_raw(raw: Self::Raw) -> Self {
+        Self::exactly(raw)
+    }
+
+    fn raw_approximation(self) -> Self::Raw {
+        self.lower
+    }
+}
+
+impl<'d> PartialInformationDiff<'d> for IntRangeDiff<'d> {
+    fn is_empty(&self) -> bool {
+        match self {
+            IntRangeDiff::NoDiff => { true }
+            IntRangeDiff::OutsideRange(_, _) => { false }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use crate::{PartialInformationCompare, PartialInformationDiff};
+    use super::IntRange;
+
+    #[test]
+    fn observation_inside_the_range_narrows_it() {
+        let mut value = IntRange::new(1, 3);
+        assert!(value.is_ambiguous());
+        assert!(value.diff(&2, Utc::now()).is_empty());
+
+        assert!(value.observe(&2).is_empty());
+        assert_eq!(value.known(), Some(2));
+        assert!(!value.is_ambiguous());
+    }
+
+    #[test]
+    fn observation_outside_the_range_conflicts_and_leaves_it_alone() {
+        let mut value = IntRange::new(1, 3);
+        assert!(!value.diff(&4, Utc::now()).is_empty());
+
+        assert_eq!(value.observe(&4).len(), 1);
+        assert_eq!((value.lower(), value.upper()), (1, 3));
+    }
+
+    #[test]
+    fn known_value_conflicts_as_a_mismatch() {
+        let mut value = IntRange::exactly(2);
+        let conflicts = value.observe(&3);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].expected(), Some("2"));
+        assert_eq!(conflicts[0].observed(), Some("3"));
+    }
+
+    #[test]
+    fn advance_moves_both_bounds() {
+        let mut value = IntRange::new(0, 2);
+        value.advance(1);
+        assert_eq!((value.lower(), value.upper()), (1, 3));
+    }
+
+    #[test]
+    fn narrow_to_picks_a_value_inside_the_range() {
+        let mut value = IntRange::new(0, 2);
+        value.narrow_to(1);
+        assert_eq!(value.known(), Some(1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn narrow_to_outside_the_range_panics() {
+        IntRange::new(0, 2).narrow_to(3);
+    }
+
+    #[test]
+    fn raw_round_trips() {
+        let value = IntRange::from_raw(2);
+        assert_eq!(value, IntRange::exactly(2));
+        assert_eq!(value.raw_approximation(), 2);
+    }
+}
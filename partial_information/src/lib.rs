@@ -4,10 +4,16 @@ mod compare;
 mod spurious;
 mod resets_ms;
 mod range;
+mod int_range;
+mod fields;
+mod nullable;
 
 pub use rerollable::Rerollable;
-pub use maybe_known::MaybeKnown;
-pub use compare::{PartialInformationCompare, PartialInformationDiff, Conflict};
+pub use maybe_known::{MaybeKnown, MaybeKnownDiff};
+pub use compare::{PartialInformationCompare, PartialInformationDiff, Conflict, KnownCount, Severity};
 pub use spurious::Spurious;
 pub use resets_ms::DatetimeWithResettingMs;
-pub use range::RangeInclusive;
\ No newline at end of file
+pub use range::RangeInclusive;
+pub use int_range::IntRange;
+pub use nullable::Nullable;
+pub use fields::{FieldAccess, FieldValue, to_field_value};
\ No newline at end of file
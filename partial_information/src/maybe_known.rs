@@ -1,4 +1,5 @@
 use std::fmt::Debug;
+use std::ops::{Add, AddAssign, Sub, SubAssign};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use crate::compare::{Conflict, PartialInformationDiff};
@@ -23,6 +24,40 @@ impl<UnderlyingType: PartialInformationCompare> MaybeKnown<UnderlyingType>
             MaybeKnown::UnknownExcluding(_) => { None }
         }
     }
+
+    // Borrows the value so it can go through map and friends without giving up the original
+    pub fn as_ref(&self) -> MaybeKnown<&UnderlyingType> {
+        match self {
+            MaybeKnown::Unknown => { MaybeKnown::Unknown }
+            MaybeKnown::Known(val) => { MaybeKnown::Known(val) }
+            MaybeKnown::UnknownExcluding(excluded) => { MaybeKnown::UnknownExcluding(excluded.clone()) }
+        }
+    }
+
+    // The excluded value of UnknownExcluding can't be carried through an arbitrary function, so
+    // it becomes plain Unknown
+    pub fn map<U, F>(self, f: F) -> MaybeKnown<U>
+        where U: PartialInformationCompare, F: FnOnce(UnderlyingType) -> U {
+        match self {
+            MaybeKnown::Known(val) => { MaybeKnown::Known(f(val)) }
+            MaybeKnown::Unknown | MaybeKnown::UnknownExcluding(_) => { MaybeKnown::Unknown }
+        }
+    }
+
+    pub fn and_then<U, F>(self, f: F) -> MaybeKnown<U>
+        where U: PartialInformationCompare, F: FnOnce(UnderlyingType) -> MaybeKnown<U> {
+        match self {
+            MaybeKnown::Known(val) => { f(val) }
+            MaybeKnown::Unknown | MaybeKnown::UnknownExcluding(_) => { MaybeKnown::Unknown }
+        }
+    }
+
+    pub fn unwrap_or(self, default: UnderlyingType) -> UnderlyingType {
+        match self {
+            MaybeKnown::Known(val) => { val }
+            MaybeKnown::Unknown | MaybeKnown::UnknownExcluding(_) => { default }
+        }
+    }
 }
 
 impl<UnderlyingType: PartialInformationCompare> From<UnderlyingType> for MaybeKnown<UnderlyingType>
@@ -32,6 +67,96 @@ impl<UnderlyingType: PartialInformationCompare> From<UnderlyingType> for MaybeKn
     }
 }
 
+// Arithmetic only stays known if every operand is known. Like map, the excluded value of
+// UnknownExcluding doesn't survive, so it becomes plain Unknown.
+impl<T> Add for MaybeKnown<T>
+    where T: PartialInformationCompare + Add<Output = T> {
+    type Output = MaybeKnown<T>;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        match (self, rhs) {
+            (MaybeKnown::Known(a), MaybeKnown::Known(b)) => { MaybeKnown::Known(a + b) }
+            _ => { MaybeKnown::Unknown }
+        }
+    }
+}
+
+impl<T> Add<T> for MaybeKnown<T>
+    where T: PartialInformationCompare + Add<Output = T> {
+    type Output = MaybeKnown<T>;
+
+    fn add(self, rhs: T) -> Self::Output {
+        match self {
+            MaybeKnown::Known(a) => { MaybeKnown::Known(a + rhs) }
+            _ => { MaybeKnown::Unknown }
+        }
+    }
+}
+
+impl<T> Sub for MaybeKnown<T>
+    where T: PartialInformationCompare + Sub<Output = T> {
+    type Output = MaybeKnown<T>;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        match (self, rhs) {
+            (MaybeKnown::Known(a), MaybeKnown::Known(b)) => { MaybeKnown::Known(a - b) }
+            _ => { MaybeKnown::Unknown }
+        }
+    }
+}
+
+impl<T> Sub<T> for MaybeKnown<T>
+    where T: PartialInformationCompare + Sub<Output = T> {
+    type Output = MaybeKnown<T>;
+
+    fn sub(self, rhs: T) -> Self::Output {
+        match self {
+            MaybeKnown::Known(a) => { MaybeKnown::Known(a - rhs) }
+            _ => { MaybeKnown::Unknown }
+        }
+    }
+}
+
+impl<T> AddAssign for MaybeKnown<T>
+    where T: PartialInformationCompare + AddAssign {
+    fn add_assign(&mut self, rhs: Self) {
+        match rhs {
+            MaybeKnown::Known(rhs) => { *self += rhs; }
+            _ => { *self = MaybeKnown::Unknown; }
+        }
+    }
+}
+
+impl<T> AddAssign<T> for MaybeKnown<T>
+    where T: PartialInformationCompare + AddAssign {
+    fn add_assign(&mut self, rhs: T) {
+        match self {
+            MaybeKnown::Known(val) => { *val += rhs; }
+            _ => { *self = MaybeKnown::Unknown; }
+        }
+    }
+}
+
+impl<T> SubAssign for MaybeKnown<T>
+    where T: PartialInformationCompare + SubAssign {
+    fn sub_assign(&mut self, rhs: Self) {
+        match rhs {
+            MaybeKnown::Known(rhs) => { *self -= rhs; }
+            _ => { *self = MaybeKnown::Unknown; }
+        }
+    }
+}
+
+impl<T> SubAssign<T> for MaybeKnown<T>
+    where T: PartialInformationCompare + SubAssign {
+    fn sub_assign(&mut self, rhs: T) {
+        match self {
+            MaybeKnown::Known(val) => { *val -= rhs; }
+            _ => { *self = MaybeKnown::Unknown; }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum MaybeKnownDiff<'d, T: 'd + PartialInformationCompare> {
     NoDiff,
@@ -75,6 +200,13 @@ impl<T> PartialInformationCompare for MaybeKnown<T>
         }
     }
 
+    fn observe_within(&mut self, observed: &Self::Raw, epsilon: f64) -> Vec<Conflict> {
+        match self {
+            MaybeKnown::Known(expected) => { expected.observe_within(observed, epsilon) }
+            _ => { self.observe(observed) }
+        }
+    }
+
     fn is_ambiguous(&self) -> bool {
         match self {
             MaybeKnown::Unknown => { true }
@@ -86,8 +218,12 @@ impl<T> PartialInformationCompare for MaybeKnown<T>
     fn from_raw(raw: Self::Raw) -> Self {
         MaybeKnown::Known(T::from_raw(raw))
     }
+    // Only unknown values have to make something up
     fn raw_approximation(self) -> Self::Raw {
-        Self::Raw::default()
+        match self {
+            MaybeKnown::Known(val) => { val.raw_approximation() }
+            MaybeKnown::Unknown | MaybeKnown::UnknownExcluding(_) => { Self::Raw::default() }
+        }
     }
 }
 
@@ -99,4 +235,70 @@ impl<'d, T> PartialInformationDiff<'d> for MaybeKnownDiff<'d, T>
             MaybeKnownDiff::Diff(nested) => { nested.is_empty() }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::PartialInformationCompare;
+    use super::MaybeKnown;
+
+    #[test]
+    fn unknown_flag_is_narrowed_by_observation() {
+        let mut flag: MaybeKnown<bool> = MaybeKnown::Unknown;
+        assert!(flag.is_ambiguous());
+        assert!(flag.observe(&true).is_empty());
+        assert_eq!(flag, MaybeKnown::Known(true));
+        assert!(!flag.is_ambiguous());
+    }
+
+    #[test]
+    fn known_flag_conflicts_with_a_different_observation() {
+        let mut flag = MaybeKnown::Known(false);
+        assert!(!flag.observe(&true).is_empty());
+    }
+
+    #[test]
+    fn map_chains_apply_to_known_values() {
+        let value = MaybeKnown::Known(3)
+            .map(|v| v * 2)
+            .and_then(|v| MaybeKnown::Known(v + 1))
+            .map(|v| v > 5);
+        assert_eq!(value, MaybeKnown::Known(true));
+        assert!(value.unwrap_or(false));
+    }
+
+    #[test]
+    fn map_chains_propagate_unknown_values() {
+        let value = MaybeKnown::<i32>::Unknown
+            .map(|v| v * 2)
+            .and_then(|v| MaybeKnown::Known(v + 1))
+            .map(|v| v > 5);
+        assert_eq!(value, MaybeKnown::Unknown);
+        assert!(!value.unwrap_or(false));
+
+        // Known values can still turn unknown partway through
+        let value = MaybeKnown::Known(3).and_then(|_| MaybeKnown::<i32>::Unknown).map(|v| v + 1);
+        assert_eq!(value, MaybeKnown::Unknown);
+    }
+
+    #[test]
+    fn as_ref_borrows_without_consuming() {
+        let name = MaybeKnown::Known("Test".to_string());
+        assert_eq!(name.as_ref().map(|name| name.len()), MaybeKnown::Known(4));
+        assert_eq!(name.as_ref().known(), Some(&&"Test".to_string()));
+        assert_eq!(name, MaybeKnown::Known("Test".to_string()));
+
+        let unknown = MaybeKnown::<String>::Unknown;
+        assert_eq!(unknown.as_ref().map(|name| name.len()), MaybeKnown::Unknown);
+
+        let excluding = MaybeKnown::<String>::UnknownExcluding("Test".to_string());
+        assert_eq!(excluding.as_ref(), MaybeKnown::UnknownExcluding("Test".to_string()));
+    }
+
+    #[test]
+    fn known_values_approximate_as_themselves() {
+        assert!(MaybeKnown::Known(true).raw_approximation());
+        assert_eq!(MaybeKnown::Known(3).raw_approximation(), 3);
+        assert_eq!(MaybeKnown::<i32>::Unknown.raw_approximation(), 0);
+    }
+}
@@ -0,0 +1,145 @@
+use std::fmt::Debug;
+use std::ops::{Deref, DerefMut};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::compare::{Conflict, KnownCount, PartialInformationDiff};
+use crate::PartialInformationCompare;
+
+// For fields that start out null and get set at some point we might not have an event for, like a
+// game's winner. Observing a value where null was expected narrows it down instead of conflicting,
+// but a value can't change to a different value or back to null. Plain Option treats all of those
+// as conflicts.
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct Nullable<UnderlyingType>(Option<UnderlyingType>);
+
+// Derived Default would require the underlying type to be Default
+impl<UnderlyingType> Default for Nullable<UnderlyingType> {
+    fn default() -> Self {
+        Nullable(None)
+    }
+}
+
+impl<UnderlyingType> Nullable<UnderlyingType> {
+    // Null is also how a Nullable says the value isn't known: the first observed value is taken
+    // without a conflict. This is the same as Default, but says so at the call site.
+    pub fn unknown() -> Self {
+        Nullable(None)
+    }
+}
+
+// Nullable is Clone if underlying type is Clone
+impl<UnderlyingType> Clone for Nullable<UnderlyingType>
+    where UnderlyingType: Clone {
+    fn clone(&self) -> Self {
+        Nullable(self.0.clone())
+    }
+}
+
+// Nullable is Copy if underlying type is Copy
+impl<UnderlyingType> Copy for Nullable<UnderlyingType>
+    where UnderlyingType: Copy + Clone {}
+
+impl<UnderlyingType> From<Option<UnderlyingType>> for Nullable<UnderlyingType> {
+    fn from(value: Option<UnderlyingType>) -> Self {
+        Nullable(value)
+    }
+}
+
+impl<UnderlyingType> Deref for Nullable<UnderlyingType> {
+    type Target = Option<UnderlyingType>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<UnderlyingType> DerefMut for Nullable<UnderlyingType> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[derive(Debug)]
+pub enum NullableDiff<'d, T: 'd + PartialInformationCompare> {
+    NoDiff,
+    // Observed a value where null was expected, which is allowed
+    Narrowed(&'d T::Raw),
+    ExpectedSomeGotNone(&'d T),
+    Underlying(T::Diff<'d>),
+}
+
+impl<T> PartialInformationCompare for Nullable<T>
+    where T: 'static + PartialInformationCompare {
+    type Raw = Option<T::Raw>;
+    type Diff<'d> = NullableDiff<'d, T>;
+
+    fn diff<'d>(&'d self, observed: &'d Self::Raw, time: DateTime<Utc>) -> Self::Diff<'d> {
+        match (&self.0, observed) {
+            (None, None) => NullableDiff::NoDiff,
+            (None, Some(val)) => NullableDiff::Narrowed(val),
+            (Some(val), None) => NullableDiff::ExpectedSomeGotNone(val),
+            (Some(a), Some(b)) => NullableDiff::Underlying(a.diff(b, time)),
+        }
+    }
+
+    fn observe(&mut self, observed: &Self::Raw) -> Vec<Conflict> {
+        match (&mut self.0, observed) {
+            (None, None) => vec![],
+            (None, Some(val)) => {
+                self.0 = Some(T::from_raw(val.clone()));
+                vec![]
+            }
+            (Some(val), None) => {
+                vec![Conflict::mismatch(format!("{:?}", val), "null".to_string())]
+            }
+            (Some(a), Some(b)) => a.observe(b)
+        }
+    }
+
+    fn is_ambiguous(&self) -> bool {
+        self.0.as_ref().is_some_and(|v| v.is_ambiguous())
+    }
+
+    fn count_known(&self) -> KnownCount {
+        self.0.as_ref().map_or(KnownCount::leaf(true), |v| v.count_known())
+    }
+
+    fn from_raw(raw: Self::Raw) -> Self {
+        Self(raw.map(|v| T::from_raw(v)))
+    }
+
+    fn raw_approximation(self) -> Self::Raw {
+        self.0.map(|val| val.raw_approximation())
+    }
+}
+
+impl<'d, T> PartialInformationDiff<'d> for NullableDiff<'d, T>
+    where T: PartialInformationCompare {
+    fn is_empty(&self) -> bool {
+        match self {
+            NullableDiff::NoDiff => { true }
+            NullableDiff::Narrowed(_) => { true }
+            NullableDiff::ExpectedSomeGotNone(_) => { false }
+            NullableDiff::Underlying(diff) => { diff.is_empty() }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::PartialInformationCompare;
+    use super::Nullable;
+
+    #[test]
+    fn unknown_takes_the_observed_value() {
+        let mut value: Nullable<Vec<String>> = Nullable::unknown();
+        assert!(value.observe(&Some(vec!["Play ball!".to_string()])).is_empty());
+        assert_eq!(*value, Some(vec!["Play ball!".to_string()]));
+    }
+
+    #[test]
+    fn computed_value_conflicts_with_a_different_observation() {
+        let mut value: Nullable<Vec<String>> = Some(Vec::new()).into();
+        assert!(!value.observe(&Some(vec!["Play ball!".to_string()])).is_empty());
+    }
+}
@@ -12,9 +12,30 @@ pub struct RangeInclusive<UnderlyingType> {
     pub upper: UnderlyingType,
 }
 
+impl<T> RangeInclusive<T> where T: PartialOrd + Clone {
+    pub fn contains(&self, value: &T) -> bool {
+        !(value < &self.lower || value > &self.upper)
+    }
+
+    pub fn is_point(&self) -> bool {
+        self.lower == self.upper
+    }
+
+    // The values that are possible according to both ranges, or None if there aren't any
+    pub fn intersect(&self, other: &Self) -> Option<Self> {
+        let lower = if self.lower > other.lower { &self.lower } else { &other.lower };
+        let upper = if self.upper < other.upper { &self.upper } else { &other.upper };
+        if lower > upper {
+            None
+        } else {
+            Some(RangeInclusive { lower: lower.clone(), upper: upper.clone() })
+        }
+    }
+}
+
 impl<T> RangeInclusive<T> where T: for<'a> AddAssign<&'a T> {
-    pub fn could_be(&self, observed: &T) -> bool where T: PartialOrd {
-        !(observed < &self.lower || observed > &self.upper)
+    pub fn could_be(&self, observed: &T) -> bool where T: PartialOrd + Clone {
+        self.contains(observed)
     }
 
     pub fn update(&mut self, raw: T) where T: Clone {
@@ -79,20 +100,20 @@ impl<T> PartialInformationCompare for RangeInclusive<T>
     }
 
     fn observe(&mut self, observed: &Self::Raw) -> Vec<Conflict> {
-        if observed < &self.lower || observed > &self.upper {
-            if self.lower == self.upper {
-                vec![Conflict::new(String::new(),
-                                   format!("Expected {:?}, but observed {:?}",
-                                           self.lower, observed))]
-            } else {
+        // Observing a value narrows the range down to just that value
+        match self.intersect(&Self::from_raw(observed.clone())) {
+            Some(narrowed) => {
+                *self = narrowed;
+                Vec::new()
+            }
+            None if self.is_point() => {
+                vec![Conflict::mismatch(format!("{:?}", self.lower), format!("{:?}", observed))]
+            }
+            None => {
                 vec![Conflict::new(String::new(),
                                    format!("Expected value between {:?} and {:?}, but observed {:?}",
                                            self.lower, self.upper, observed))]
             }
-        } else {
-            self.upper = observed.clone();
-            self.lower = observed.clone();
-            Vec::new()
         }
     }
 
@@ -116,4 +137,51 @@ impl<'d> PartialInformationDiff<'d> for RangeDiff {
             _ => { false }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::PartialInformationCompare;
+    use super::RangeInclusive;
+
+    #[test]
+    fn overlapping_ranges_intersect_to_the_overlap() {
+        let a = RangeInclusive { lower: 1, upper: 5 };
+        let b = RangeInclusive { lower: 3, upper: 8 };
+        assert_eq!(a.intersect(&b), Some(RangeInclusive { lower: 3, upper: 5 }));
+        assert_eq!(b.intersect(&a), Some(RangeInclusive { lower: 3, upper: 5 }));
+    }
+
+    #[test]
+    fn touching_ranges_intersect_to_a_point() {
+        let a = RangeInclusive { lower: 1, upper: 3 };
+        let b = RangeInclusive { lower: 3, upper: 8 };
+        let intersection = a.intersect(&b).expect("Ranges that share an endpoint should intersect");
+        assert!(intersection.is_point());
+        assert_eq!(intersection.lower, 3);
+    }
+
+    #[test]
+    fn disjoint_ranges_dont_intersect() {
+        let a = RangeInclusive { lower: 1, upper: 2 };
+        let b = RangeInclusive { lower: 4, upper: 8 };
+        assert_eq!(a.intersect(&b), None);
+        assert_eq!(b.intersect(&a), None);
+    }
+
+    #[test]
+    fn contains_includes_both_ends() {
+        let range = RangeInclusive { lower: 1, upper: 3 };
+        assert!(range.contains(&1));
+        assert!(range.contains(&2));
+        assert!(range.contains(&3));
+        assert!(!range.contains(&0));
+        assert!(!range.contains(&4));
+    }
+
+    #[test]
+    fn only_ranges_with_equal_ends_are_points() {
+        assert!(RangeInclusive::<i32>::from_raw(2).is_point());
+        assert!(!RangeInclusive { lower: 2, upper: 3 }.is_point());
+    }
+}
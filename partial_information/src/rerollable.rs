@@ -45,8 +45,15 @@ impl PartialInformationCompare for Rerollable {
     type Raw = f32;
     type Diff<'d> = RerollableDiff;
 
-    fn diff<'d>(&'d self, _observed: &'d Self::Raw, _: DateTime<Utc>) -> Self::Diff<'d> {
-        todo!()
+    fn diff<'d>(&'d self, observed: &'d Self::Raw, _: DateTime<Utc>) -> Self::Diff<'d> {
+        // This has to agree with observe, or a boost that observe would accept shows up as a diff
+        if self.could_be(*observed) {
+            RerollableDiff::NoDiff
+        } else if let Some((lower, upper)) = &self.range {
+            RerollableDiff::RerollOutsideRange(self.raw + lower, self.raw + upper, *observed)
+        } else {
+            RerollableDiff::UnexpectedChange(self.raw, *observed)
+        }
     }
 
     fn observe(&mut self, observed: &Self::Raw) -> Vec<Conflict> {
@@ -64,9 +71,7 @@ impl PartialInformationCompare for Rerollable {
         } else if &self.raw == observed {
             Vec::new()
         } else {
-            vec![Conflict::new(String::new(),
-                               format!("Expected value {:?}, but observed {:?}",
-                                       self.raw, observed))]
+            vec![Conflict::mismatch(format!("{:?}", self.raw), format!("{:?}", observed))]
         }
     }
 
@@ -90,4 +95,33 @@ impl<'d> PartialInformationDiff<'d> for RerollableDiff {
             _ => { false }
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use crate::{PartialInformationCompare, PartialInformationDiff};
+    use super::Rerollable;
+
+    #[test]
+    fn observation_inside_the_range_narrows_it() {
+        let mut value = Rerollable::from_raw(0.5);
+        value.add_range(0.04, 0.08);
+        assert!(value.is_ambiguous());
+        assert!(value.diff(&0.55, Utc::now()).is_empty());
+
+        assert!(value.observe(&0.55).is_empty());
+        assert!(!value.is_ambiguous());
+        assert_eq!(value.raw_approximation(), 0.55);
+    }
+
+    #[test]
+    fn observation_outside_the_range_conflicts() {
+        let mut value = Rerollable::from_raw(0.5);
+        value.add_range(0.04, 0.08);
+        assert!(!value.diff(&0.5, Utc::now()).is_empty());
+
+        assert_eq!(value.observe(&0.5).len(), 1);
+        // The range is kept so a later observation still has something to check against
+        assert!(value.is_ambiguous());
+    }
+}
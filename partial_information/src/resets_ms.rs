@@ -34,6 +34,12 @@ impl DatetimeWithResettingMs {
         self.ms_known = false;
     }
 
+    // For marking a value as "ms was reset here" while building it, e.g. in an effect
+    pub fn with_reset_ms(mut self) -> Self {
+        self.forget_ms();
+        self
+    }
+
     pub fn ns(&self) -> MaybeKnown<u32> {
         if self.ms_known {
             MaybeKnown::Known(self.date.nanosecond())
@@ -87,7 +93,9 @@ impl PartialInformationCompare for DatetimeWithResettingMs {
 
     fn observe(&mut self, observed: &Self::Raw) -> Vec<Conflict> {
         if self.ms_known {
-            if &self.date == observed {
+            // Blaseball strips trailing zeros from the fractional seconds when it formats dates, so
+            // anything more precise than a millisecond can't be relied on to round-trip
+            if self.date.timestamp_millis() == observed.timestamp_millis() {
                 Vec::new()
             } else {
                 vec![Conflict::new(String::new(),
@@ -130,4 +138,46 @@ impl<'d> PartialInformationDiff<'d> for ResetsMsDiff {
             ResetsMsDiff::Diff(_) => { false }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Duration, Utc};
+    use crate::PartialInformationCompare;
+    use super::DatetimeWithResettingMs;
+
+    fn parse(date: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(date).unwrap().into()
+    }
+
+    #[test]
+    fn trailing_zero_milliseconds_match() {
+        // Blaseball would write this one as ".12Z"
+        let mut expected = DatetimeWithResettingMs::from(parse("2021-03-01T00:00:00.120Z"));
+        assert!(expected.observe(&parse("2021-03-01T00:00:00.12Z")).is_empty());
+
+        let mut expected = DatetimeWithResettingMs::from(parse("2021-03-01T00:00:00.100Z"));
+        assert!(expected.observe(&parse("2021-03-01T00:00:00.1Z")).is_empty());
+    }
+
+    #[test]
+    fn only_milliseconds_are_compared() {
+        let date = parse("2021-03-01T00:00:00.120Z");
+        let mut expected = DatetimeWithResettingMs::from(date + Duration::microseconds(400));
+        assert!(expected.observe(&date).is_empty());
+
+        assert_eq!(expected.observe(&(date + Duration::milliseconds(1))).len(), 1);
+    }
+
+    #[test]
+    fn reset_ms_takes_the_observed_ms() {
+        let date = parse("2021-03-01T00:00:00Z");
+        let mut expected = DatetimeWithResettingMs::from(date).with_reset_ms();
+        assert!(expected.is_ambiguous());
+
+        let observed = parse("2021-03-01T00:00:00.12Z");
+        assert!(expected.observe(&observed).is_empty());
+        assert!(!expected.is_ambiguous());
+        assert_eq!(expected.known_date(), Some(observed));
+    }
+}
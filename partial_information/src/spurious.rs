@@ -2,7 +2,7 @@ use std::fmt::Debug;
 use std::ops::{Deref, DerefMut};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use crate::compare::{Conflict, PartialInformationDiff};
+use crate::compare::{Conflict, KnownCount, PartialInformationDiff};
 use crate::PartialInformationCompare;
 
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
@@ -63,10 +63,22 @@ impl<T> PartialInformationCompare for Spurious<T>
         }
     }
 
+    fn observe_within(&mut self, observed: &Self::Raw, epsilon: f64) -> Vec<Conflict> {
+        if observed == &Self::Raw::default() {
+            vec![]
+        } else {
+            self.0.observe_within(observed, epsilon)
+        }
+    }
+
     fn is_ambiguous(&self) -> bool {
         self.0.is_ambiguous()
     }
 
+    fn count_known(&self) -> KnownCount {
+        self.0.count_known()
+    }
+
     fn from_raw(raw: Self::Raw) -> Self {
         Self(T::from_raw(raw))
     }
@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use partial_information::{PartialInformationCompare, PartialInformationDiff};
+use partial_information_derive::PartialInformationCompare;
+
+#[derive(Clone, Debug, PartialEq, Serialize, PartialInformationCompare)]
+struct Tolerant {
+    #[partial_information(epsilon = 1e-9)]
+    tolerant: f64,
+    exact: f64,
+}
+
+// Doesn't implement PartialInformationCompare, which skipped fields don't have to
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct Cache(u32);
+
+#[derive(Clone, Debug, PartialEq, Serialize, PartialInformationCompare)]
+struct WithSkipped {
+    value: i64,
+    #[partial_information(skip)]
+    cache: Cache,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, PartialInformationCompare)]
+struct Tuple(i64, #[partial_information(skip)] Cache, String);
+
+#[derive(Clone, Debug, PartialEq, Serialize, PartialInformationCompare)]
+struct Unit;
+
+#[test]
+fn epsilon_fields_ignore_tiny_float_differences() {
+    let mut expected = Tolerant { tolerant: 0.5, exact: 0.5 };
+    let conflicts = expected.observe(&TolerantRaw { tolerant: 0.5 + 1e-12, exact: 0.5 });
+    assert!(conflicts.is_empty(), "{conflicts:?}");
+    // The observed value is taken, not the expected one
+    assert_eq!(expected.tolerant, 0.5 + 1e-12);
+}
+
+#[test]
+fn fields_without_epsilon_are_still_exact() {
+    let mut expected = Tolerant { tolerant: 0.5, exact: 0.5 };
+    let conflicts = expected.observe(&TolerantRaw { tolerant: 0.5, exact: 0.5 + 1e-12 });
+    assert_eq!(conflicts.iter().map(|conflict| conflict.property()).collect::<Vec<_>>(), vec!["exact".to_string()]);
+
+    let conflicts = expected.observe(&TolerantRaw { tolerant: 0.6, exact: 0.5 });
+    assert_eq!(conflicts.iter().map(|conflict| conflict.property()).collect::<Vec<_>>(), vec!["tolerant".to_string()]);
+}
+
+#[test]
+fn diff_still_sees_differences_within_epsilon() {
+    // Only observe is tolerant. The diff compares the raw values so nothing is hidden from it.
+    let expected = Tolerant { tolerant: 0.5, exact: 0.5 };
+    let time = chrono::Utc::now();
+    assert!(expected.diff(&TolerantRaw { tolerant: 0.5, exact: 0.5 }, time).is_empty());
+    assert!(!expected.diff(&TolerantRaw { tolerant: 0.5 + 1e-12, exact: 0.5 }, time).is_empty());
+}
+
+#[test]
+fn skipped_fields_are_never_compared() {
+    let mut expected = WithSkipped { value: 1, cache: Cache(1) };
+    let observed = WithSkippedRaw { value: 1, cache: Cache(2) };
+    assert!(expected.observe(&observed).is_empty());
+    assert!(expected.diff(&observed, chrono::Utc::now()).is_empty());
+    // Observing doesn't touch the skipped field either
+    assert_eq!(expected.cache, Cache(1));
+
+    let conflicts = expected.observe(&WithSkippedRaw { value: 2, cache: Cache(1) });
+    assert_eq!(conflicts.iter().map(|conflict| conflict.property()).collect::<Vec<_>>(), vec!["value".to_string()]);
+}
+
+#[test]
+fn skipped_fields_are_defaulted_from_raw() {
+    let from_raw = WithSkipped::from_raw(WithSkippedRaw { value: 3, cache: Cache(5) });
+    assert_eq!(from_raw, WithSkipped { value: 3, cache: Cache::default() });
+
+    let raw = WithSkipped { value: 3, cache: Cache(5) }.raw_approximation();
+    assert_eq!(raw.value, 3);
+    assert_eq!(raw.cache, Cache::default());
+}
+
+#[test]
+fn tuple_structs_round_trip_through_raw() {
+    let raw = TupleRaw(1, Cache(2), "three".to_string());
+    let tuple = Tuple::from_raw(raw.clone());
+    assert_eq!(tuple, Tuple(1, Cache::default(), "three".to_string()));
+
+    let approximation = tuple.clone().raw_approximation();
+    assert_eq!((approximation.0, approximation.2), (1, "three".to_string()));
+    assert!(tuple.diff(&raw, chrono::Utc::now()).is_empty());
+}
+
+#[test]
+fn tuple_struct_conflicts_are_named_by_index() {
+    let mut expected = Tuple(1, Cache::default(), "three".to_string());
+    // The skipped field in the middle doesn't shift the index of the one after it
+    let conflicts = expected.observe(&TupleRaw(1, Cache(2), "four".to_string()));
+    assert_eq!(conflicts.iter().map(|conflict| conflict.property()).collect::<Vec<_>>(), vec!["2".to_string()]);
+
+    let conflicts = expected.observe(&TupleRaw(5, Cache::default(), "three".to_string()));
+    assert_eq!(conflicts.iter().map(|conflict| conflict.property()).collect::<Vec<_>>(), vec!["0".to_string()]);
+}
+
+#[test]
+fn unit_structs_never_conflict() {
+    let mut unit = Unit;
+    assert!(unit.observe(&UnitRaw).is_empty());
+    assert!(unit.diff(&UnitRaw, chrono::Utc::now()).is_empty());
+    assert_eq!(Unit::from_raw(Unit.raw_approximation()), Unit);
+}
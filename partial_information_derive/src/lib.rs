@@ -5,7 +5,7 @@ extern crate proc_macro;
 use ::proc_macro::TokenStream;
 use std::iter;
 use ::proc_macro2::{Span, TokenStream as TokenStream2};
-use ::quote::{quote, ToTokens};
+use ::quote::{quote, quote_spanned, ToTokens};
 use ::syn::{*, parse::{Parse, Parser, ParseStream}, punctuated::Punctuated, spanned::Spanned, Result};
 
 #[proc_macro_derive(PartialInformationCompare, attributes(partial_information))]
@@ -32,35 +32,64 @@ fn impl_partial_information_compare(ast: DeriveInput) -> Result<TokenStream2> {
                 ));
             }
 
-            | Data::Struct(DataStruct { fields: Fields::Named(it), .. })
-            => it,
-
-            | Data::Struct(_)
-            => {
-                return Err(Error::new(
-                    Span::call_site(),
-                    "Expected a `struct` with named fields",
-                ));
-            }
+            | Data::Struct(DataStruct { fields, .. })
+            => fields,
         };
+        let is_named = matches!(fields, Fields::Named(_));
+
+        // Tuple struct fields are referred to by index. Since `Self { 0: a, 1: b }` is valid syntax
+        // for tuple structs (and `Self {}` for unit structs), most of the generated code doesn't
+        // have to care which kind of struct it is.
+        let members = fields.iter().enumerate()
+            .map(|(i, field)| match &field.ident {
+                Some(ident) => Member::Named(ident.clone()),
+                None => Member::Unnamed(Index { index: i as u32, span: field.span() }),
+            })
+            .collect::<Vec<_>>();
+        let member_names = members.iter()
+            .map(|member| match member {
+                Member::Named(ident) => LitStr::new(&ident.to_string(), ident.span()),
+                Member::Unnamed(index) => LitStr::new(&index.index.to_string(), index.span),
+            })
+            .collect::<Vec<_>>();
+
+        // Skipped fields are still deserialized into the Raw struct, but otherwise they're invisible
+        // to comparisons
+        let mut options = Vec::new();
+        for field in fields.iter() {
+            options.push(field_options(field)?);
+        }
+        let all_fields = fields.iter()
+            .zip(&members)
+            .zip(&member_names)
+            .zip(&options)
+            .map(|(((field, member), member_name), options)| (field, member, member_name, options))
+            .collect::<Vec<_>>();
+        let compared_fields = all_fields.iter()
+            .filter(|(_, _, _, options)| !options.skip)
+            .collect::<Vec<_>>();
 
-        let diff_method_items = fields.named.iter()
-            .map(|field| {
-                let field_name = field.ident.as_ref().expect("Unreachable");
-                // let field_name_stringified = LitStr::new(&field_name.to_string(), span);
+        let diff_method_items = compared_fields.iter()
+            .map(|(_, member, _, _)| {
                 quote! {
-                    #field_name: self.#field_name.diff(&other.#field_name, time)
+                    self.#member.diff(&other.#member, time)
                 }
-            });
+            })
+            .collect::<Vec<_>>();
 
-        let observe_method_items = fields.named.iter()
-            .map(|field| {
-                let field_name = field.ident.as_ref().expect("Unreachable");
-                let field_name_stringified = LitStr::new(&field_name.to_string(), field_name.span());
+        let observe_method_items = compared_fields.iter()
+            .map(|(_, member, member_name, options)| {
+                let observe_call = match &options.epsilon {
+                    Some(epsilon) => quote! { self.#member.observe_within(&observed.#member, #epsilon) },
+                    None => quote! { self.#member.observe(&observed.#member) },
+                };
+                let set_severity = options.severity.as_ref().map(|severity| {
+                    quote! { .with_severity(::partial_information::Severity::#severity) }
+                });
                 quote! {
                     conflicts.extend(
-                        self.#field_name.observe(&observed.#field_name).into_iter()
-                            .map(|conflict| conflict.with_prefix(#field_name_stringified))
+                        #observe_call.into_iter()
+                            .map(|conflict| conflict.with_prefix(#member_name)#set_severity)
                     );
                 }
             });
@@ -70,17 +99,37 @@ fn impl_partial_information_compare(ast: DeriveInput) -> Result<TokenStream2> {
                 attr.style == AttrStyle::Outer && attr.path.is_ident("serde")
             });
         let raw_name = Ident::new(&format!("{}Raw", name), name.span());
-        let raw_members = fields.named.iter()
-            .map(|field| {
-                let field_attrs = &field.attrs;
+        let raw_members = all_fields.iter()
+            .map(|(field, _, _, options)| {
+                // Our own attributes don't mean anything to serde
+                let field_attrs = field.attrs.iter()
+                    .filter(|attr| !attr.path.is_ident("partial_information"));
                 let field_vis = &field.vis;
-                let field_name = field.ident.as_ref().expect("Unreachable");
                 let field_type = &field.ty;
-                quote! {
-                    #(#field_attrs)*
-                    #field_vis #field_name: <#field_type as ::partial_information::PartialInformationCompare>::Raw
+                // Skipped fields don't have to implement PartialInformationCompare, so they don't
+                // necessarily have a Raw type
+                let raw_type = if options.skip {
+                    quote! { #field_type }
+                } else {
+                    quote! { <#field_type as ::partial_information::PartialInformationCompare>::Raw }
+                };
+                match &field.ident {
+                    Some(field_name) => quote! {
+                        #(#field_attrs)*
+                        #field_vis #field_name: #raw_type
+                    },
+                    None => quote! {
+                        #(#field_attrs)*
+                        #field_vis #raw_type
+                    },
                 }
-            });
+            })
+            .collect::<Vec<_>>();
+        let raw_body = match &fields {
+            Fields::Named(_) => quote! { { #(#raw_members),* } },
+            Fields::Unnamed(_) => quote! { ( #(#raw_members),* ); },
+            Fields::Unit => quote! { ; },
+        };
 
         let mut raw_implements_default = false;
         for attr in ast.attrs.iter() {
@@ -116,49 +165,113 @@ fn impl_partial_information_compare(ast: DeriveInput) -> Result<TokenStream2> {
             quote! {}
         };
 
+        // Diffs only have members for the compared fields, so a tuple struct's diff can have
+        // different indices from the struct itself. The phantom goes last so it doesn't shift them.
         let diff_name = Ident::new(&format!("{}Diff", name), name.span());
-        let diff_members = fields.named.iter()
-            .map(|field| {
+        let diff_members = compared_fields.iter()
+            .map(|(field, _, _, _)| {
                 let field_vis = &field.vis;
-                let field_name = field.ident.as_ref().expect("Unreachable");
                 let field_type = &field.ty;
-                quote! {
-                    #field_vis #field_name: <#field_type as ::partial_information::PartialInformationCompare>::Diff<'d>
+                let diff_type = quote! { <#field_type as ::partial_information::PartialInformationCompare>::Diff<'d> };
+                match &field.ident {
+                    Some(field_name) => quote! { #field_vis #field_name: #diff_type },
+                    None => quote! { #field_vis #diff_type },
                 }
-            });
-        let is_empty_members = fields.named.iter()
-            .map(|field| {
-                let field_name = field.ident.as_ref().expect("Unreachable");
+            })
+            .collect::<Vec<_>>();
+        let diff_accessors = compared_fields.iter().enumerate()
+            .map(|(i, (_, member, _, _))| {
+                if is_named {
+                    quote! { self.#member }
+                } else {
+                    let index = Index::from(i);
+                    quote! { self.#index }
+                }
+            })
+            .collect::<Vec<_>>();
+        let (diff_body, diff_construction) = if is_named {
+            let compared_members = compared_fields.iter().map(|(_, member, _, _)| member);
+            (
+                quote! {
+                    {
+                        _phantom: ::std::marker::PhantomData<&'d ()>,
+                        #(#diff_members),*
+                    }
+                },
+                quote! {
+                    #diff_name {
+                        _phantom: ::std::default::Default::default(),
+                        #(#compared_members: #diff_method_items),*
+                    }
+                },
+            )
+        } else {
+            (
+                quote! {
+                    ( #(#diff_members,)* ::std::marker::PhantomData<&'d ()> );
+                },
                 quote! {
-                    self.#field_name.is_empty()
+                    #diff_name(#(#diff_method_items,)* ::std::default::Default::default())
+                },
+            )
+        };
+        let is_empty_members = diff_accessors.iter()
+            .map(|accessor| {
+                quote! {
+                    #accessor.is_empty()
                 }
             })
             .chain(iter::once(quote! { true }));
 
-        let from_raw_members = fields.named.iter()
-            .map(|field| {
-                let field_name = field.ident.as_ref().expect("Unreachable");
+        let from_raw_members = all_fields.iter()
+            .map(|(field, member, _, options)| {
                 let field_type = &field.ty;
-                quote! {
-                    #field_name: <#field_type as ::partial_information::PartialInformationCompare>::from_raw(raw.#field_name)
+                if options.skip {
+                    let default = skipped_field_default(field_type);
+                    quote! { #member: #default }
+                } else {
+                    quote! {
+                        #member: <#field_type as ::partial_information::PartialInformationCompare>::from_raw(raw.#member)
+                    }
                 }
             });
 
-        let raw_approximation_members = fields.named.iter()
-            .map(|field| {
-                let field_name = field.ident.as_ref().expect("Unreachable");
+        let raw_approximation_members = all_fields.iter()
+            .map(|(field, member, _, options)| {
                 let field_type = &field.ty;
+                if options.skip {
+                    let default = skipped_field_default(field_type);
+                    quote! { #member: #default }
+                } else {
+                    quote! {
+                        #member: <#field_type as ::partial_information::PartialInformationCompare>::raw_approximation(self.#member)
+                    }
+                }
+            });
+
+        let get_field_arms = iter::zip(members.iter(), member_names.iter())
+            .map(|(member, member_name)| {
                 quote! {
-                    #field_name: <#field_type as ::partial_information::PartialInformationCompare>::raw_approximation(self.#field_name)
+                    #member_name => Some(::partial_information::to_field_value(&self.#member))
                 }
             });
 
-        let accessors = fields.named.iter()
-            .map(|field| {
-                let field_name = field.ident.as_ref().expect("Unreachable");
-                quote! { self.#field_name }
+        let take_field_arms = iter::zip(members.iter(), member_names.iter())
+            .map(|(member, member_name)| {
+                quote! {
+                    #member_name => {
+                        self.#member = ::core::clone::Clone::clone(&other.#member);
+                        true
+                    }
+                }
             });
 
+        let accessors = compared_fields.iter()
+            .map(|(_, member, _, _)| {
+                quote! { self.#member }
+            })
+            .collect::<Vec<_>>();
+
 
         quote! {
             impl ::partial_information::PartialInformationCompare for #name {
@@ -166,10 +279,7 @@ fn impl_partial_information_compare(ast: DeriveInput) -> Result<TokenStream2> {
                 type Diff<'d> = #diff_name<'d>;
 
                 fn diff<'d>(&'d self, other: &'d Self::Raw, time: ::chrono::DateTime<::chrono::Utc>) -> Self::Diff<'d> {
-                    #diff_name {
-                        _phantom: ::std::default::Default::default(),
-                        #(#diff_method_items),*
-                    }
+                    #diff_construction
                 }
 
                 fn observe(&mut self, observed: &Self::Raw) -> Vec<::partial_information::Conflict> {
@@ -183,6 +293,10 @@ fn impl_partial_information_compare(ast: DeriveInput) -> Result<TokenStream2> {
                     false #(|| #accessors.is_ambiguous())*
                 }
 
+                fn count_known(&self) -> ::partial_information::KnownCount {
+                    ::partial_information::KnownCount::default() #(+ #accessors.count_known())*
+                }
+
                 fn from_raw(raw: Self::Raw) -> Self {
                     Self {
                         #(#from_raw_members),*
@@ -196,18 +310,33 @@ fn impl_partial_information_compare(ast: DeriveInput) -> Result<TokenStream2> {
                 }
             }
 
+            impl ::partial_information::FieldAccess for #name {
+                fn fields() -> &'static [&'static str] {
+                    &[#(#member_names),*]
+                }
+
+                fn get_field(&self, name: &str) -> Option<::partial_information::FieldValue> {
+                    match name {
+                        #(#get_field_arms,)*
+                        _ => None,
+                    }
+                }
+
+                fn take_field(&mut self, other: &Self, name: &str) -> bool {
+                    match name {
+                        #(#take_field_arms,)*
+                        _ => false,
+                    }
+                }
+            }
+
             #[derive(Clone, ::core::fmt::Debug, ::serde::Deserialize, ::serde::Serialize)]
             #raw_default
             #(#raw_attrs)*
-            #item_vis struct #raw_name {
-                #(#raw_members),*
-            }
+            #item_vis struct #raw_name #raw_body
 
             #[derive(::core::fmt::Debug)]
-            #item_vis struct #diff_name<'d> {
-                _phantom: ::std::marker::PhantomData<&'d ()>,
-                #(#diff_members),*
-            }
+            #item_vis struct #diff_name<'d> #diff_body
 
             impl<'d> ::partial_information::PartialInformationDiff<'d> for #diff_name<'d> {
                 fn is_empty(&self) -> bool {
@@ -216,4 +345,63 @@ fn impl_partial_information_compare(ast: DeriveInput) -> Result<TokenStream2> {
             }
         }
     })
-}
\ No newline at end of file
+}
+
+#[derive(Default)]
+struct FieldOptions {
+    // From #[partial_information(epsilon = ...)]. Fields without it are compared exactly.
+    epsilon: Option<LitFloat>,
+    // From #[partial_information(skip)]
+    skip: bool,
+    // From #[partial_information(severity = "...")]. Overrides whatever the field's type chose.
+    severity: Option<Ident>,
+}
+
+fn field_options(field: &Field) -> Result<FieldOptions> {
+    let mut options = FieldOptions::default();
+    for attr in field.attrs.iter() {
+        if attr.style != AttrStyle::Outer || !attr.path.is_ident("partial_information") {
+            continue;
+        }
+
+        let Meta::List(list) = attr.parse_meta()? else {
+            return Err(Error::new(attr.span(), "Expected #[partial_information(...)]"));
+        };
+
+        for item in list.nested.iter() {
+            match item {
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit: Lit::Float(value), .. }))
+                if path.is_ident("epsilon") => {
+                    options.epsilon = Some(value.clone());
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                    options.skip = true;
+                }
+                NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, lit: Lit::Str(value), .. }))
+                if path.is_ident("severity") => {
+                    let variant = match value.value().as_str() {
+                        "info" => { "Info" }
+                        "warn" => { "Warn" }
+                        "error" => { "Error" }
+                        _ => {
+                            return Err(Error::new(value.span(), "Expected \"info\", \"warn\", or \"error\""));
+                        }
+                    };
+                    options.severity = Some(Ident::new(variant, value.span()));
+                }
+                _ => {
+                    return Err(Error::new(item.span(), "Expected `epsilon = <float>`, `severity = \"...\"`, or `skip`"));
+                }
+            }
+        }
+    }
+
+    Ok(options)
+}
+
+// Spanned to the field's type so a type without Default gets an error pointing at the field
+fn skipped_field_default(field_type: &Type) -> TokenStream2 {
+    quote_spanned! {field_type.span()=>
+        <#field_type as ::std::default::Default>::default()
+    }
+}